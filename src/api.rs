@@ -3,6 +3,14 @@
 use {std, vk, env_logger, libc, alloc};
 
 use {PhysicalDevice, Device, Instance, Queue, Swapchain, CommandBuffer, Dispatched};
+use {ShaderModule, DescriptorSetLayout, DescriptorPool, DescriptorSet, PipelineLayout, Pipeline, GraphicsPipeline, ComputePipeline};
+use {DeviceMemory, Buffer, BufferView, Image, ImageView, Sampler, RenderPass, Framebuffer, validation_enabled};
+use {format, memory, raster, image, multisample, descriptor, sampler, compute};
+use command::{Command, VertexBufferBinding, IndexBufferBinding, MAX_VERTEX_BINDINGS};
+use pipeline::{Viewport, Rect, slice, MAX_PUSH_CONSTANTS_SIZE};
+use image::{Region, BufferImageCopy};
+use memory::BufferCopy;
+use depth::DepthBias;
 
 macro_rules! entrypoints {
     (
@@ -116,6 +124,9 @@ entrypoints! {
     GetPhysicalDeviceFormatProperties(physical_device: vk::PhysicalDevice, format: vk::Format,
                                       format_properties: *mut vk::FormatProperties) -> () =>
     {
+        unsafe {
+            *format_properties = format::properties(format);
+        }
     }
 
     GetPhysicalDeviceImageFormatProperties(physical_device: vk::PhysicalDevice, format: vk::Format,
@@ -123,7 +134,13 @@ entrypoints! {
                                            usage: vk::ImageUsageFlags, flags: vk::ImageCreateFlags,
                                            image_format_props: *mut vk::ImageFormatProperties)
     -> vk::Result => {
-        vk::ERROR_FORMAT_NOT_SUPPORTED
+        match image::format_properties(format, type_, tiling, usage, flags) {
+            Some(props) => {
+                unsafe { *image_format_props = props; }
+                vk::SUCCESS
+            }
+            None => vk::ERROR_FORMAT_NOT_SUPPORTED
+        }
     }
 
     GetPhysicalDeviceProperties(physical_device: vk::PhysicalDevice,
@@ -136,6 +153,43 @@ entrypoints! {
             (*properties).deviceID = 0;
             (*properties).deviceType = vk::PHYSICAL_DEVICE_TYPE_CPU;
 
+            (*properties).limits.maxVertexInputAttributes = 16;
+            (*properties).limits.maxVertexInputBindings = MAX_VERTEX_BINDINGS;
+            (*properties).limits.maxVertexInputAttributeOffset = 2047;
+            (*properties).limits.maxVertexInputBindingStride = 2048;
+            (*properties).limits.subPixelPrecisionBits = raster::SUB_PIXEL_BITS;
+            (*properties).limits.maxViewports = 1;
+            (*properties).limits.maxViewportDimensions = [raster::MAX_VIEWPORT_DIMENSION; 2];
+            (*properties).limits.viewportBoundsRange = raster::VIEWPORT_BOUNDS;
+            (*properties).limits.pointSizeRange = raster::POINT_SIZE_RANGE;
+            (*properties).limits.lineWidthRange = raster::LINE_WIDTH_RANGE;
+            (*properties).limits.pointSizeGranularity = raster::GRANULARITY;
+            (*properties).limits.lineWidthGranularity = raster::GRANULARITY;
+            (*properties).limits.strictLines = vk::FALSE;
+            (*properties).limits.maxFragmentDualSrcAttachments = 1;
+            (*properties).limits.maxPushConstantsSize = MAX_PUSH_CONSTANTS_SIZE;
+            (*properties).limits.maxTexelBufferElements = memory::MAX_TEXEL_BUFFER_ELEMENTS;
+            (*properties).limits.minTexelBufferOffsetAlignment = memory::STORAGE_ALIGNMENT;
+            (*properties).limits.minStorageBufferOffsetAlignment = memory::STORAGE_ALIGNMENT;
+            (*properties).limits.maxImageDimension1D = image::MAX_IMAGE_DIMENSION;
+            (*properties).limits.maxImageDimension2D = image::MAX_IMAGE_DIMENSION;
+            (*properties).limits.maxImageDimension3D = image::MAX_IMAGE_DIMENSION_3D;
+            (*properties).limits.maxImageDimensionCube = image::MAX_IMAGE_DIMENSION;
+            (*properties).limits.maxImageArrayLayers = image::MAX_IMAGE_ARRAY_LAYERS;
+            (*properties).limits.maxSamplerLodBias = sampler::MAX_LOD_BIAS;
+            (*properties).limits.maxSamplerAnisotropy = sampler::MAX_ANISOTROPY;
+            (*properties).limits.framebufferColorSampleCounts = image::SAMPLE_COUNTS;
+            (*properties).limits.framebufferDepthSampleCounts = image::SAMPLE_COUNTS;
+            (*properties).limits.framebufferStencilSampleCounts = image::SAMPLE_COUNTS;
+            (*properties).limits.framebufferNoAttachmentsSampleCounts = image::SAMPLE_COUNTS;
+            (*properties).limits.maxSampleMaskWords = (multisample::MAX_SAMPLES + 31) / 32;
+            (*properties).limits.standardSampleLocations = vk::TRUE;
+            (*properties).limits.maxDrawIndirectCount = std::u32::MAX;
+            (*properties).limits.maxComputeSharedMemorySize = compute::MAX_SHARED_MEMORY_SIZE;
+            (*properties).limits.maxComputeWorkGroupCount = compute::MAX_WORKGROUP_COUNT;
+            (*properties).limits.maxComputeWorkGroupInvocations = compute::MAX_WORKGROUP_INVOCATIONS;
+            (*properties).limits.maxComputeWorkGroupSize = compute::MAX_WORKGROUP_SIZE;
+
             copy_slice(b"SoftVK Renderer\0", &mut (*properties).deviceName);
         }
     }
@@ -156,17 +210,34 @@ entrypoints! {
                                       memory_props: *mut vk::PhysicalDeviceMemoryProperties)
     -> () => {
         unsafe {
-            (*memory_props).memoryTypeCount = 0;
-            (*memory_props).memoryHeapCount = 0;
+            *memory_props = memory::properties();
         }
     }
 
     GetPhysicalDeviceFeatures(physical_device: vk::PhysicalDevice,
                               features: *mut vk::PhysicalDeviceFeatures) -> () =>
     {
-        // Disable all features
         unsafe {
             *features = std::mem::zeroed();
+            (*features).independentBlend = vk::TRUE;
+            (*features).multiDrawIndirect = vk::TRUE;
+            (*features).drawIndirectFirstInstance = vk::TRUE;
+            (*features).dualSrcBlend = vk::TRUE;
+            (*features).logicOp = vk::TRUE;
+            (*features).depthClamp = vk::TRUE;
+            (*features).depthBiasClamp = vk::TRUE;
+            (*features).depthBounds = vk::TRUE;
+            (*features).fillModeNonSolid = vk::TRUE;
+            (*features).wideLines = vk::TRUE;
+            (*features).largePoints = vk::TRUE;
+            (*features).sampleRateShading = vk::TRUE;
+            (*features).alphaToOne = vk::TRUE;
+            (*features).samplerAnisotropy = vk::TRUE;
+            (*features).vertexPipelineStoresAndAtomics = vk::TRUE;
+            (*features).fragmentStoresAndAtomics = vk::TRUE;
+            (*features).shaderStorageImageExtendedFormats = vk::TRUE;
+            (*features).shaderStorageImageReadWithoutFormat = vk::TRUE;
+            (*features).shaderStorageImageWriteWithoutFormat = vk::TRUE;
         }
     }
 
@@ -368,9 +439,521 @@ entrypoints! {
 
     BeginCommandBuffer(vk_buf: vk::CommandBuffer, info: *const vk::CommandBufferBeginInfo)
     -> vk::Result => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.begin();
+        }
+
+        vk::SUCCESS
+    }
+
+    EndCommandBuffer(vk_buf: vk::CommandBuffer) -> vk::Result => {
         vk::SUCCESS
     }
 
+    QueueSubmit(vk_queue: vk::Queue, submit_count: u32, submits: *const vk::SubmitInfo, fence: vk::Fence)
+    -> vk::Result => {
+        unsafe {
+            let queue: &'static Dispatched<Queue> = std::mem::transmute(vk_queue);
+            let submits = std::slice::from_raw_parts(submits, submit_count as usize);
+
+            for submit in submits {
+                let handles = std::slice::from_raw_parts(submit.pCommandBuffers,
+                                                         submit.commandBufferCount as usize);
+                let buffers: Vec<&CommandBuffer> = handles.iter()
+                    .map(|&h| &**(h as *const Dispatched<CommandBuffer>))
+                    .collect();
+                queue.submit(&buffers);
+            }
+        }
+
+        vk::SUCCESS
+    }
+
+    CmdBindPipeline(vk_buf: vk::CommandBuffer, bind_point: vk::PipelineBindPoint, pipeline: vk::Pipeline)
+    -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::BindPipeline {
+                bind_point: bind_point,
+                pipeline: pipeline as *const Pipeline,
+            });
+        }
+    }
+
+    CmdBindVertexBuffers(vk_buf: vk::CommandBuffer, first_binding: u32, binding_count: u32,
+                         buffers: *const vk::Buffer, offsets: *const vk::DeviceSize) -> () => {
+        if first_binding + binding_count > MAX_VERTEX_BINDINGS {
+            error!("CmdBindVertexBuffers: bindings {}..{} out of range", first_binding,
+                   first_binding + binding_count);
+            return;
+        }
+
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            let buffers = std::slice::from_raw_parts(buffers, binding_count as usize);
+            let offsets = std::slice::from_raw_parts(offsets, binding_count as usize);
+
+            buf.record(Command::BindVertexBuffers {
+                first: first_binding,
+                buffers: buffers.iter().zip(offsets).map(|(&b, &offset)| VertexBufferBinding {
+                    buffer: b as *const Buffer,
+                    offset: offset,
+                }).collect(),
+            });
+        }
+    }
+
+    CmdBindDescriptorSets(vk_buf: vk::CommandBuffer, bind_point: vk::PipelineBindPoint,
+                          layout: vk::PipelineLayout, first_set: u32, set_count: u32,
+                          sets: *const vk::DescriptorSet, dynamic_offset_count: u32,
+                          dynamic_offsets: *const u32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            let sets = std::slice::from_raw_parts(sets, set_count as usize);
+
+            buf.record(Command::BindDescriptorSets {
+                bind_point: bind_point,
+                layout: (*(layout as *const PipelineLayout)).clone(),
+                first_set: first_set,
+                sets: sets.iter().map(|&s| s as *const DescriptorSet).collect(),
+                dynamic_offsets: slice(dynamic_offsets, dynamic_offset_count).to_vec(),
+            });
+        }
+    }
+
+    CmdPushConstants(vk_buf: vk::CommandBuffer, layout: vk::PipelineLayout, stages: vk::ShaderStageFlags,
+                     offset: u32, size: u32, values: *const libc::c_void) -> () => {
+        unsafe {
+            let layout = layout as *const PipelineLayout;
+            if layout.is_null() {
+                error!("CmdPushConstants: null pipeline layout");
+                return;
+            }
+            if !(*layout).check_push_constants(stages, offset, size) {
+                error!("CmdPushConstants: bytes {}..{} are not in the layout's ranges for stages {:#x}",
+                       offset, offset as u64 + size as u64, stages);
+                return;
+            }
+
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::PushConstants {
+                offset: offset,
+                values: slice(values as *const u8, size).to_vec(),
+            });
+        }
+    }
+
+    CmdBindIndexBuffer(vk_buf: vk::CommandBuffer, buffer: vk::Buffer, offset: vk::DeviceSize,
+                       index_type: vk::IndexType) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::BindIndexBuffer(IndexBufferBinding {
+                buffer: buffer as *const Buffer,
+                offset: offset,
+                index_type: index_type,
+            }));
+        }
+    }
+
+    CmdSetViewport(vk_buf: vk::CommandBuffer, first_viewport: u32, viewport_count: u32,
+                   viewports: *const vk::Viewport) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            let viewports = std::slice::from_raw_parts(viewports, viewport_count as usize);
+            buf.record(Command::SetViewport {
+                first: first_viewport,
+                viewports: viewports.iter().map(Viewport::from_vk).collect(),
+            });
+        }
+    }
+
+    CmdSetScissor(vk_buf: vk::CommandBuffer, first_scissor: u32, scissor_count: u32,
+                  scissors: *const vk::Rect2D) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            let scissors = std::slice::from_raw_parts(scissors, scissor_count as usize);
+            buf.record(Command::SetScissor {
+                first: first_scissor,
+                scissors: scissors.iter().map(Rect::from_vk).collect(),
+            });
+        }
+    }
+
+    CmdSetLineWidth(vk_buf: vk::CommandBuffer, line_width: f32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::SetLineWidth(line_width));
+        }
+    }
+
+    CmdSetDepthBias(vk_buf: vk::CommandBuffer, constant_factor: f32, clamp: f32, slope_factor: f32)
+    -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::SetDepthBias(DepthBias {
+                constant_factor: constant_factor,
+                clamp: clamp,
+                slope_factor: slope_factor,
+            }));
+        }
+    }
+
+    CmdSetDepthBounds(vk_buf: vk::CommandBuffer, min_depth_bounds: f32, max_depth_bounds: f32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::SetDepthBounds {
+                min: min_depth_bounds,
+                max: max_depth_bounds,
+            });
+        }
+    }
+
+    CmdSetStencilCompareMask(vk_buf: vk::CommandBuffer, face_mask: vk::StencilFaceFlags, compare_mask: u32)
+    -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::SetStencilCompareMask {
+                faces: face_mask,
+                mask: compare_mask,
+            });
+        }
+    }
+
+    CmdSetStencilWriteMask(vk_buf: vk::CommandBuffer, face_mask: vk::StencilFaceFlags, write_mask: u32)
+    -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::SetStencilWriteMask {
+                faces: face_mask,
+                mask: write_mask,
+            });
+        }
+    }
+
+    CmdSetStencilReference(vk_buf: vk::CommandBuffer, face_mask: vk::StencilFaceFlags, reference: u32)
+    -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::SetStencilReference {
+                faces: face_mask,
+                reference: reference,
+            });
+        }
+    }
+
+    CmdSetBlendConstants(vk_buf: vk::CommandBuffer, blend_constants: *const f32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            let c = std::slice::from_raw_parts(blend_constants, 4);
+            buf.record(Command::SetBlendConstants([c[0], c[1], c[2], c[3]]));
+        }
+    }
+
+    CmdBeginRenderPass(vk_buf: vk::CommandBuffer, info: *const vk::RenderPassBeginInfo,
+                       contents: vk::SubpassContents) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            let info = &*info;
+            let clear_values = slice(info.pClearValues, info.clearValueCount);
+            buf.record(Command::BeginRenderPass {
+                render_pass: info.renderPass as *const RenderPass,
+                framebuffer: info.framebuffer as *const Framebuffer,
+                area: Rect::from_vk(&info.renderArea),
+                clear_values: clear_values.iter().map(|v| *v.as_color().as_uint32()).collect(),
+            });
+        }
+    }
+
+    CmdNextSubpass(vk_buf: vk::CommandBuffer, contents: vk::SubpassContents) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::NextSubpass);
+        }
+    }
+
+    CmdEndRenderPass(vk_buf: vk::CommandBuffer) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::EndRenderPass);
+        }
+    }
+
+    CmdResolveImage(vk_buf: vk::CommandBuffer, src_image: vk::Image, src_image_layout: vk::ImageLayout,
+                    dst_image: vk::Image, dst_image_layout: vk::ImageLayout, region_count: u32,
+                    regions: *const vk::ImageResolve) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            let regions = std::slice::from_raw_parts(regions, region_count as usize);
+            buf.record(Command::ResolveImage {
+                src: src_image as *const Image,
+                dst: dst_image as *const Image,
+                regions: regions.iter().map(Region::from_resolve).collect(),
+            });
+        }
+    }
+
+    CmdCopyBuffer(vk_buf: vk::CommandBuffer, src_buffer: vk::Buffer, dst_buffer: vk::Buffer, region_count: u32,
+                  regions: *const vk::BufferCopy) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            let regions: Vec<BufferCopy> = slice(regions, region_count).iter().map(BufferCopy::from_vk).collect();
+            let src = src_buffer as *const Buffer;
+            let dst = dst_buffer as *const Buffer;
+            if validation_enabled() && memory::regions_overlap(&*src, &*dst, &regions) {
+                error!("CmdCopyBuffer: source and destination regions overlap in memory");
+            }
+
+            buf.record(Command::CopyBuffer {
+                src: src,
+                dst: dst,
+                regions: regions,
+            });
+        }
+    }
+
+    CmdUpdateBuffer(vk_buf: vk::CommandBuffer, dst_buffer: vk::Buffer, dst_offset: vk::DeviceSize,
+                    data_size: vk::DeviceSize, data: *const libc::c_void) -> () => {
+        unsafe {
+            if data_size > memory::MAX_UPDATE_SIZE || data_size % 4 != 0 || dst_offset % 4 != 0 {
+                error!("CmdUpdateBuffer: invalid size {} or offset {}", data_size, dst_offset);
+                return;
+            }
+
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::UpdateBuffer {
+                dst: dst_buffer as *const Buffer,
+                offset: dst_offset,
+                data: std::slice::from_raw_parts(data as *const u8, data_size as usize).to_vec(),
+            });
+        }
+    }
+
+    CmdFillBuffer(vk_buf: vk::CommandBuffer, dst_buffer: vk::Buffer, dst_offset: vk::DeviceSize,
+                  size: vk::DeviceSize, data: u32) -> () => {
+        unsafe {
+            if dst_offset % 4 != 0 || (size != vk::WHOLE_SIZE && size % 4 != 0) {
+                error!("CmdFillBuffer: invalid size {} or offset {}", size, dst_offset);
+                return;
+            }
+
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::FillBuffer {
+                dst: dst_buffer as *const Buffer,
+                offset: dst_offset,
+                size: size,
+                data: data,
+            });
+        }
+    }
+
+    CmdCopyBufferToImage(vk_buf: vk::CommandBuffer, src_buffer: vk::Buffer, dst_image: vk::Image,
+                         dst_image_layout: vk::ImageLayout, region_count: u32,
+                         regions: *const vk::BufferImageCopy) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::CopyBufferToImage {
+                src: src_buffer as *const Buffer,
+                dst: dst_image as *const Image,
+                regions: slice(regions, region_count).iter().map(BufferImageCopy::from_vk).collect(),
+            });
+        }
+    }
+
+    CmdCopyImageToBuffer(vk_buf: vk::CommandBuffer, src_image: vk::Image, src_image_layout: vk::ImageLayout,
+                         dst_buffer: vk::Buffer, region_count: u32, regions: *const vk::BufferImageCopy) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::CopyImageToBuffer {
+                src: src_image as *const Image,
+                dst: dst_buffer as *const Buffer,
+                regions: slice(regions, region_count).iter().map(BufferImageCopy::from_vk).collect(),
+            });
+        }
+    }
+
+    CmdDraw(vk_buf: vk::CommandBuffer, vertex_count: u32, instance_count: u32, first_vertex: u32,
+            first_instance: u32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::Draw {
+                vertex_count: vertex_count,
+                instance_count: instance_count,
+                first_vertex: first_vertex,
+                first_instance: first_instance,
+            });
+        }
+    }
+
+    CmdDrawIndexed(vk_buf: vk::CommandBuffer, index_count: u32, instance_count: u32, first_index: u32,
+                   vertex_offset: i32, first_instance: u32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::DrawIndexed {
+                index_count: index_count,
+                instance_count: instance_count,
+                first_index: first_index,
+                vertex_offset: vertex_offset,
+                first_instance: first_instance,
+            });
+        }
+    }
+
+    CmdDrawIndirect(vk_buf: vk::CommandBuffer, buffer: vk::Buffer, offset: vk::DeviceSize, draw_count: u32,
+                    stride: u32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::DrawIndirect {
+                buffer: buffer as *const Buffer,
+                offset: offset,
+                draw_count: draw_count,
+                stride: stride,
+            });
+        }
+    }
+
+    CmdDrawIndexedIndirect(vk_buf: vk::CommandBuffer, buffer: vk::Buffer, offset: vk::DeviceSize, draw_count: u32,
+                           stride: u32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::DrawIndexedIndirect {
+                buffer: buffer as *const Buffer,
+                offset: offset,
+                draw_count: draw_count,
+                stride: stride,
+            });
+        }
+    }
+
+    CmdDispatch(vk_buf: vk::CommandBuffer, x: u32, y: u32, z: u32) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::Dispatch([x, y, z]));
+        }
+    }
+
+    CmdDispatchIndirect(vk_buf: vk::CommandBuffer, buffer: vk::Buffer, offset: vk::DeviceSize) -> () => {
+        unsafe {
+            let buf: &mut Dispatched<CommandBuffer> = std::mem::transmute(vk_buf);
+            buf.record(Command::DispatchIndirect { buffer: buffer as *const Buffer, offset: offset });
+        }
+    }
+
+    // Memory
+
+    AllocateMemory(vk_dev: vk::Device, info: *const vk::MemoryAllocateInfo,
+                   allocator: *const vk::AllocationCallbacks, ptr: *mut vk::DeviceMemory)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("AllocateMemory: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            match DeviceMemory::new(&*info) {
+                Ok(memory) => {
+                    *ptr = Box::into_raw(Box::new(memory)) as u64;
+                    vk::SUCCESS
+                }
+                Err(e) => e
+            }
+        }
+    }
+
+    FreeMemory(vk_dev: vk::Device, memory: vk::DeviceMemory, allocator: *const vk::AllocationCallbacks)
+    -> () => {
+        if memory != 0 {
+            unsafe { Box::<DeviceMemory>::from_raw(memory as *mut DeviceMemory); }
+        }
+    }
+
+    MapMemory(vk_dev: vk::Device, memory: vk::DeviceMemory, offset: vk::DeviceSize, size: vk::DeviceSize,
+              flags: vk::MemoryMapFlags, data: *mut *mut libc::c_void) -> vk::Result => {
+        unsafe {
+            let memory = &*(memory as *const DeviceMemory);
+            *data = memory.ptr().offset(offset as isize) as *mut libc::c_void;
+        }
+
+        vk::SUCCESS
+    }
+
+    UnmapMemory(vk_dev: vk::Device, memory: vk::DeviceMemory) -> () => {
+    }
+
+    FlushMappedMemoryRanges(vk_dev: vk::Device, count: u32, ranges: *const vk::MappedMemoryRange)
+    -> vk::Result => {
+        // Memory is coherent.
+        vk::SUCCESS
+    }
+
+    InvalidateMappedMemoryRanges(vk_dev: vk::Device, count: u32, ranges: *const vk::MappedMemoryRange)
+    -> vk::Result => {
+        vk::SUCCESS
+    }
+
+    // Buffers
+
+    CreateBuffer(vk_dev: vk::Device, info: *const vk::BufferCreateInfo,
+                 allocator: *const vk::AllocationCallbacks, ptr: *mut vk::Buffer)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateBuffer: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            *ptr = Box::into_raw(Box::new(Buffer::new(&*info))) as u64;
+        }
+
+        vk::SUCCESS
+    }
+
+    DestroyBuffer(vk_dev: vk::Device, buffer: vk::Buffer, allocator: *const vk::AllocationCallbacks)
+    -> () => {
+        if buffer != 0 {
+            unsafe { Box::<Buffer>::from_raw(buffer as *mut Buffer); }
+        }
+    }
+
+    GetBufferMemoryRequirements(vk_dev: vk::Device, buffer: vk::Buffer,
+                                requirements: *mut vk::MemoryRequirements) -> () => {
+        unsafe {
+            *requirements = (*(buffer as *const Buffer)).memory_requirements();
+        }
+    }
+
+    BindBufferMemory(vk_dev: vk::Device, buffer: vk::Buffer, memory: vk::DeviceMemory,
+                     offset: vk::DeviceSize) -> vk::Result => {
+        unsafe {
+            (*(buffer as *mut Buffer)).bind(&*(memory as *const DeviceMemory), offset);
+        }
+
+        vk::SUCCESS
+    }
+
+    CreateBufferView(vk_dev: vk::Device, info: *const vk::BufferViewCreateInfo,
+                     allocator: *const vk::AllocationCallbacks, ptr: *mut vk::BufferView)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateBufferView: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            match BufferView::new(&*info) {
+                Ok(view) => {
+                    *ptr = Box::into_raw(Box::new(view)) as u64;
+                    vk::SUCCESS
+                }
+                Err(e) => e
+            }
+        }
+    }
+
+    DestroyBufferView(vk_dev: vk::Device, view: vk::BufferView, allocator: *const vk::AllocationCallbacks)
+    -> () => {
+        if view != 0 {
+            unsafe { Box::<BufferView>::from_raw(view as *mut BufferView); }
+        }
+    }
+
     // Images
 
     CreateImage(vk_dev: vk::Device, info: *const vk::ImageCreateInfo,
@@ -381,12 +964,47 @@ entrypoints! {
         }
 
         unsafe {
-            *ptr = 43;
+            match Image::new(&*info) {
+                Ok(image) => {
+                    *ptr = Box::into_raw(Box::new(image)) as u64;
+                    vk::SUCCESS
+                }
+                Err(e) => e
+            }
+        }
+    }
+
+    DestroyImage(vk_dev: vk::Device, image: vk::Image, allocator: *const vk::AllocationCallbacks)
+    -> () => {
+        if image != 0 {
+            unsafe { Box::<Image>::from_raw(image as *mut Image); }
+        }
+    }
+
+    GetImageMemoryRequirements(vk_dev: vk::Device, image: vk::Image,
+                               requirements: *mut vk::MemoryRequirements) -> () => {
+        unsafe {
+            *requirements = (*(image as *const Image)).memory_requirements();
+        }
+    }
+
+    BindImageMemory(vk_dev: vk::Device, image: vk::Image, memory: vk::DeviceMemory,
+                    offset: vk::DeviceSize) -> vk::Result => {
+        unsafe {
+            (*(image as *mut Image)).bind(&*(memory as *const DeviceMemory), offset);
         }
 
         vk::SUCCESS
     }
 
+    GetImageSubresourceLayout(vk_dev: vk::Device, image: vk::Image, subresource: *const vk::ImageSubresource,
+                              layout: *mut vk::SubresourceLayout) -> () => {
+        unsafe {
+            let subresource = &*subresource;
+            *layout = (*(image as *const Image)).subresource_layout(subresource.mipLevel, subresource.arrayLayer);
+        }
+    }
+
     CreateImageView(vk_dev: vk::Device, info: *const vk::ImageViewCreateInfo,
                     allocator: *const vk::AllocationCallbacks, ptr: *mut vk::ImageView)
     -> vk::Result => {
@@ -395,29 +1013,317 @@ entrypoints! {
         }
 
         unsafe {
-            *ptr = 42;
+            match ImageView::new(&*info) {
+                Ok(view) => {
+                    *ptr = Box::into_raw(Box::new(view)) as u64;
+                    vk::SUCCESS
+                }
+                Err(e) => e
+            }
+        }
+    }
+
+    DestroyImageView(vk_dev: vk::Device, view: vk::ImageView, allocator: *const vk::AllocationCallbacks)
+    -> () => {
+        if view != 0 {
+            unsafe { Box::<ImageView>::from_raw(view as *mut ImageView); }
+        }
+    }
+
+    // Samplers
+
+    CreateSampler(vk_dev: vk::Device, info: *const vk::SamplerCreateInfo,
+                  allocator: *const vk::AllocationCallbacks, ptr: *mut vk::Sampler)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateSampler: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            *ptr = Box::into_raw(Box::new(Sampler::new(&*info))) as u64;
+        }
+
+        vk::SUCCESS
+    }
+
+    DestroySampler(vk_dev: vk::Device, sampler: vk::Sampler, allocator: *const vk::AllocationCallbacks)
+    -> () => {
+        if sampler != 0 {
+            unsafe { Box::<Sampler>::from_raw(sampler as *mut Sampler); }
+        }
+    }
+
+    // Render passes
+
+    CreateRenderPass(vk_dev: vk::Device, info: *const vk::RenderPassCreateInfo,
+                     allocator: *const vk::AllocationCallbacks, ptr: *mut vk::RenderPass)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateRenderPass: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            *ptr = Box::into_raw(Box::new(RenderPass::new(&*info))) as u64;
+        }
+
+        vk::SUCCESS
+    }
+
+    DestroyRenderPass(vk_dev: vk::Device, render_pass: vk::RenderPass,
+                      allocator: *const vk::AllocationCallbacks) -> () => {
+        if render_pass != 0 {
+            unsafe { Box::<RenderPass>::from_raw(render_pass as *mut RenderPass); }
+        }
+    }
+
+    GetRenderAreaGranularity(vk_dev: vk::Device, render_pass: vk::RenderPass, granularity: *mut vk::Extent2D)
+    -> () => {
+        unsafe {
+            *granularity = vk::Extent2D {
+                width: 1,
+                height: 1,
+            };
+        }
+    }
+
+    CreateFramebuffer(vk_dev: vk::Device, info: *const vk::FramebufferCreateInfo,
+                      allocator: *const vk::AllocationCallbacks, ptr: *mut vk::Framebuffer)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateFramebuffer: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            *ptr = Box::into_raw(Box::new(Framebuffer::new(&*info))) as u64;
+        }
+
+        vk::SUCCESS
+    }
+
+    DestroyFramebuffer(vk_dev: vk::Device, framebuffer: vk::Framebuffer,
+                       allocator: *const vk::AllocationCallbacks) -> () => {
+        if framebuffer != 0 {
+            unsafe { Box::<Framebuffer>::from_raw(framebuffer as *mut Framebuffer); }
+        }
+    }
+
+    // Shaders
+
+    CreateShaderModule(vk_dev: vk::Device, info: *const vk::ShaderModuleCreateInfo,
+                       allocator: *const vk::AllocationCallbacks, ptr: *mut vk::ShaderModule)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateShaderModule: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            let dev: &'static Dispatched<Device> = std::mem::transmute(vk_dev);
+            let code = std::slice::from_raw_parts((*info).pCode, (*info).codeSize / 4);
+
+            match dev.create_shader_module(code) {
+                Ok(module) => {
+                    *ptr = Box::into_raw(module) as u64;
+                    vk::SUCCESS
+                }
+                Err(e) => e
+            }
+        }
+    }
+
+    DestroyShaderModule(vk_dev: vk::Device, module: vk::ShaderModule,
+                        allocator: *const vk::AllocationCallbacks) -> () => {
+        if module != 0 {
+            unsafe { Box::<ShaderModule>::from_raw(module as *mut ShaderModule); }
+        }
+    }
+
+    // Pipelines
+
+    CreateDescriptorSetLayout(vk_dev: vk::Device, info: *const vk::DescriptorSetLayoutCreateInfo,
+                              allocator: *const vk::AllocationCallbacks,
+                              ptr: *mut vk::DescriptorSetLayout)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateDescriptorSetLayout: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            let layout = Box::new(DescriptorSetLayout::new(&*info));
+            *ptr = Box::into_raw(layout) as u64;
+        }
+
+        vk::SUCCESS
+    }
+
+    DestroyDescriptorSetLayout(vk_dev: vk::Device, layout: vk::DescriptorSetLayout,
+                               allocator: *const vk::AllocationCallbacks) -> () => {
+        if layout != 0 {
+            unsafe { Box::<DescriptorSetLayout>::from_raw(layout as *mut DescriptorSetLayout); }
+        }
+    }
+
+    CreateDescriptorPool(vk_dev: vk::Device, info: *const vk::DescriptorPoolCreateInfo,
+                         allocator: *const vk::AllocationCallbacks, ptr: *mut vk::DescriptorPool)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateDescriptorPool: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            let pool = Box::new(DescriptorPool::new(&*info));
+            *ptr = Box::into_raw(pool) as u64;
         }
 
         vk::SUCCESS
     }
 
+    DestroyDescriptorPool(vk_dev: vk::Device, pool: vk::DescriptorPool,
+                          allocator: *const vk::AllocationCallbacks) -> () => {
+        if pool != 0 {
+            unsafe { Box::<DescriptorPool>::from_raw(pool as *mut DescriptorPool); }
+        }
+    }
+
+    ResetDescriptorPool(vk_dev: vk::Device, pool: vk::DescriptorPool, flags: vk::DescriptorPoolResetFlags)
+    -> vk::Result => {
+        unsafe { (*(pool as *mut DescriptorPool)).reset(); }
+        vk::SUCCESS
+    }
+
+    AllocateDescriptorSets(vk_dev: vk::Device, info: *const vk::DescriptorSetAllocateInfo,
+                           sets: *mut vk::DescriptorSet)
+    -> vk::Result => {
+        unsafe {
+            let pool = &mut *((*info).descriptorPool as *mut DescriptorPool);
+            let layouts = std::slice::from_raw_parts((*info).pSetLayouts, (*info).descriptorSetCount as usize);
+
+            for (i, &layout) in layouts.iter().enumerate() {
+                match pool.allocate(&*(layout as *const DescriptorSetLayout)) {
+                    Ok(set) => *sets.offset(i as isize) = set as u64,
+                    Err(e) => {
+                        // Nothing is allocated when any set fails.
+                        for j in 0..i {
+                            pool.free(*sets.offset(j as isize) as *mut DescriptorSet);
+                        }
+                        for j in 0..layouts.len() {
+                            *sets.offset(j as isize) = 0;
+                        }
+                        return e;
+                    }
+                }
+            }
+        }
+
+        vk::SUCCESS
+    }
+
+    FreeDescriptorSets(vk_dev: vk::Device, pool: vk::DescriptorPool, count: u32,
+                       sets: *const vk::DescriptorSet) -> vk::Result => {
+        unsafe {
+            let pool = &mut *(pool as *mut DescriptorPool);
+            for &set in std::slice::from_raw_parts(sets, count as usize) {
+                if set != 0 {
+                    pool.free(set as *mut DescriptorSet);
+                }
+            }
+        }
+
+        vk::SUCCESS
+    }
+
+    UpdateDescriptorSets(vk_dev: vk::Device, write_count: u32, writes: *const vk::WriteDescriptorSet,
+                         copy_count: u32, copies: *const vk::CopyDescriptorSet) -> () => {
+        unsafe {
+            for write in slice(writes, write_count) {
+                (*(write.dstSet as *mut DescriptorSet)).write(write);
+            }
+            for copy in slice(copies, copy_count) {
+                descriptor::copy(copy);
+            }
+        }
+    }
+
+    CreatePipelineLayout(vk_dev: vk::Device, info: *const vk::PipelineLayoutCreateInfo,
+                         allocator: *const vk::AllocationCallbacks, ptr: *mut vk::PipelineLayout)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreatePipelineLayout: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            let layout = Box::new(PipelineLayout::new(&*info));
+            *ptr = Box::into_raw(layout) as u64;
+        }
+
+        vk::SUCCESS
+    }
+
+    DestroyPipelineLayout(vk_dev: vk::Device, layout: vk::PipelineLayout,
+                          allocator: *const vk::AllocationCallbacks) -> () => {
+        if layout != 0 {
+            unsafe { Box::<PipelineLayout>::from_raw(layout as *mut PipelineLayout); }
+        }
+    }
+
+    CreateGraphicsPipelines(vk_dev: vk::Device, cache: vk::PipelineCache, count: u32,
+                            infos: *const vk::GraphicsPipelineCreateInfo,
+                            allocator: *const vk::AllocationCallbacks, pipelines: *mut vk::Pipeline)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateGraphicsPipelines: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            let infos = std::slice::from_raw_parts(infos, count as usize);
+            let mut result = vk::SUCCESS;
+
+            for (i, info) in infos.iter().enumerate() {
+                *pipelines.offset(i as isize) = match GraphicsPipeline::new(info) {
+                    Ok(pipeline) => Box::into_raw(Box::new(Pipeline::Graphics(pipeline))) as u64,
+                    Err(e) => { result = e; 0 }
+                };
+            }
+
+            result
+        }
+    }
+
+    CreateComputePipelines(vk_dev: vk::Device, cache: vk::PipelineCache, count: u32,
+                           infos: *const vk::ComputePipelineCreateInfo,
+                           allocator: *const vk::AllocationCallbacks, pipelines: *mut vk::Pipeline)
+    -> vk::Result => {
+        if !allocator.is_null() {
+            warn!("CreateComputePipelines: ignoring request for custom allocator");
+        }
+
+        unsafe {
+            let infos = std::slice::from_raw_parts(infos, count as usize);
+            let mut result = vk::SUCCESS;
+
+            for (i, info) in infos.iter().enumerate() {
+                *pipelines.offset(i as isize) = match ComputePipeline::new(info) {
+                    Ok(pipeline) => Box::into_raw(Box::new(Pipeline::Compute(pipeline))) as u64,
+                    Err(e) => { result = e; 0 }
+                };
+            }
+
+            result
+        }
+    }
+
+    DestroyPipeline(vk_dev: vk::Device, pipeline: vk::Pipeline,
+                    allocator: *const vk::AllocationCallbacks) -> () => {
+        if pipeline != 0 {
+            unsafe { Box::<Pipeline>::from_raw(pipeline as *mut Pipeline); }
+        }
+    }
+
     // Stubs
 
-    QueueSubmit() -> () => { }
     QueueWaitIdle() -> () => { }
     DeviceWaitIdle() -> () => { }
-    AllocateMemory() -> () => { }
-    FreeMemory() -> () => { }
-    MapMemory() -> () => { }
-    UnmapMemory() -> () => { }
-    FlushMappedMemoryRanges() -> () => { }
-    InvalidateMappedMemoryRanges() -> () => { }
     GetDeviceMemoryCommitment() -> () => { }
     GetImageSparseMemoryRequirements() -> () => { }
-    GetBufferMemoryRequirements() -> () => { }
-    GetImageMemoryRequirements() -> () => { }
-    BindBufferMemory() -> () => { }
-    BindImageMemory() -> () => { }
     QueueBindSparse() -> () => { }
     CreateFence() -> () => { }
     DestroyFence() -> () => { }
@@ -434,74 +1340,19 @@ entrypoints! {
     CreateQueryPool() -> () => { }
     DestroyQueryPool() -> () => { }
     GetQueryPoolResults() -> () => { }
-    CreateBuffer() -> () => { }
-    DestroyBuffer() -> () => { }
-    CreateBufferView() -> () => { }
-    DestroyBufferView() -> () => { }
-    DestroyImage() -> () => { }
-    GetImageSubresourceLayout() -> () => { }
-    DestroyImageView() -> () => { }
-    CreateShaderModule() -> () => { }
-    DestroyShaderModule() -> () => { }
     CreatePipelineCache() -> () => { }
     DestroyPipelineCache() -> () => { }
     GetPipelineCacheData() -> () => { }
     MergePipelineCaches() -> () => { }
-    CreateGraphicsPipelines() -> () => { }
-    CreateComputePipelines() -> () => { }
-    DestroyPipeline() -> () => { }
-    CreatePipelineLayout() -> () => { }
-    DestroyPipelineLayout() -> () => { }
-    CreateSampler() -> () => { }
-    DestroySampler() -> () => { }
-    CreateDescriptorSetLayout() -> () => { }
-    DestroyDescriptorSetLayout() -> () => { }
-    CreateDescriptorPool() -> () => { }
-    DestroyDescriptorPool() -> () => { }
-    ResetDescriptorPool() -> () => { }
-    AllocateDescriptorSets() -> () => { }
-    FreeDescriptorSets() -> () => { }
-    UpdateDescriptorSets() -> () => { }
-    CreateFramebuffer() -> () => { }
-    DestroyFramebuffer() -> () => { }
-    CreateRenderPass() -> () => { }
-    DestroyRenderPass() -> () => { }
-    GetRenderAreaGranularity() -> () => { }
     DestroyCommandPool() -> () => { }
     ResetCommandPool() -> () => { }
     FreeCommandBuffers() -> () => { }
-    EndCommandBuffer() -> () => { }
     ResetCommandBuffer() -> () => { }
-    CmdBindPipeline() -> () => { }
-    CmdSetViewport() -> () => { }
-    CmdSetScissor() -> () => { }
-    CmdSetLineWidth() -> () => { }
-    CmdSetDepthBias() -> () => { }
-    CmdSetBlendConstants() -> () => { }
-    CmdSetDepthBounds() -> () => { }
-    CmdSetStencilCompareMask() -> () => { }
-    CmdSetStencilWriteMask() -> () => { }
-    CmdSetStencilReference() -> () => { }
-    CmdBindDescriptorSets() -> () => { }
-    CmdBindVertexBuffers() -> () => { }
-    CmdBindIndexBuffer() -> () => { }
-    CmdDraw() -> () => { }
-    CmdDrawIndexed() -> () => { }
-    CmdDrawIndirect() -> () => { }
-    CmdDrawIndexedIndirect() -> () => { }
-    CmdDispatch() -> () => { }
-    CmdDispatchIndirect() -> () => { }
-    CmdCopyBuffer() -> () => { }
     CmdCopyImage() -> () => { }
     CmdBlitImage() -> () => { }
-    CmdCopyBufferToImage() -> () => { }
-    CmdCopyImageToBuffer() -> () => { }
-    CmdUpdateBuffer() -> () => { }
-    CmdFillBuffer() -> () => { }
     CmdClearColorImage() -> () => { }
     CmdClearDepthStencilImage() -> () => { }
     CmdClearAttachments() -> () => { }
-    CmdResolveImage() -> () => { }
     CmdSetEvent() -> () => { }
     CmdResetEvent() -> () => { }
     CmdWaitEvents() -> () => { }
@@ -511,10 +1362,6 @@ entrypoints! {
     CmdResetQueryPool() -> () => { }
     CmdWriteTimestamp() -> () => { }
     CmdCopyQueryPoolResults() -> () => { }
-    CmdPushConstants() -> () => { }
-    CmdBeginRenderPass() -> () => { }
-    CmdNextSubpass() -> () => { }
-    CmdEndRenderPass() -> () => { }
     CmdExecuteCommands() -> () => { }
 //     AcquireNextImageKHR() -> () => { }
 //     CreateSwapchainKHR() -> () => { }
@@ -0,0 +1,257 @@
+use {std, vk};
+
+use command::IndexBufferBinding;
+
+/// A primitive as a list of vertex indices. The provoking vertex is always
+/// the first vertex; triangles keep the winding order of the topology.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Primitive {
+    Point([u32; 1]),
+    Line([u32; 2]),
+    Triangle([u32; 3]),
+    /// A line and the vertices adjacent to its first and second vertex.
+    LineAdjacency([u32; 2], [u32; 2]),
+    /// A triangle and the vertices adjacent to its edges v0-v1, v1-v2 and
+    /// v2-v0.
+    TriangleAdjacency([u32; 3], [u32; 3]),
+    Patch(Vec<u32>),
+}
+
+impl Primitive {
+    /// The vertices that are rasterized, without adjacent vertices.
+    pub fn vertices(&self) -> &[u32] {
+        match *self {
+            Primitive::Point(ref v) => v,
+            Primitive::Line(ref v) => v,
+            Primitive::Triangle(ref v) => v,
+            Primitive::LineAdjacency(ref v, _) => v,
+            Primitive::TriangleAdjacency(ref v, _) => v,
+            Primitive::Patch(ref v) => v,
+        }
+    }
+
+    pub fn provoking_vertex(&self) -> u32 {
+        self.vertices()[0]
+    }
+}
+
+/// The index that restarts a strip or fan for the given index type.
+pub fn restart_index(index_type: vk::IndexType) -> u32 {
+    match index_type {
+        vk::INDEX_TYPE_UINT16 => 0xFFFF,
+        _ => 0xFFFFFFFF,
+    }
+}
+
+/// Reads `count` indices starting at `first` from the bound index buffer.
+/// Indices past the end of the buffer are dropped.
+pub unsafe fn read_indices(binding: &IndexBufferBinding, first: u32, count: u32) -> Vec<u32> {
+    let size = match binding.index_type {
+        vk::INDEX_TYPE_UINT16 => 2,
+        _ => 4,
+    };
+
+    let data = (*binding.buffer).data();
+    let start = binding.offset as usize + first as usize * size;
+    let available = if start < data.len() { (data.len() - start) / size } else { 0 };
+    if available < count as usize {
+        warn!("read_indices: {} indices out of bounds of the index buffer", count as usize - available);
+    }
+
+    let mut indices = Vec::with_capacity(std::cmp::min(count as usize, available));
+    for i in 0..std::cmp::min(count as usize, available) {
+        let b = &data[start + i * size..start + (i + 1) * size];
+        indices.push(if size == 2 {
+            b[0] as u32 | (b[1] as u32) << 8
+        } else {
+            b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24
+        });
+    }
+
+    indices
+}
+
+/// Splits a sequence of vertex indices into primitives. With `restart` set,
+/// that index ends the current strip or fan and starts a new one. Incomplete
+/// primitives at the end of a sequence are discarded.
+pub fn assemble(topology: vk::PrimitiveTopology, patch_control_points: u32, indices: &[u32],
+                restart: Option<u32>) -> Vec<Primitive> {
+    let mut primitives = Vec::new();
+
+    match restart {
+        Some(restart) => {
+            for run in indices.split(|&i| i == restart) {
+                assemble_run(topology, patch_control_points, run, &mut primitives);
+            }
+        }
+        None => assemble_run(topology, patch_control_points, indices, &mut primitives),
+    }
+
+    primitives
+}
+
+fn assemble_run(topology: vk::PrimitiveTopology, patch_control_points: u32, v: &[u32],
+                primitives: &mut Vec<Primitive>) {
+    let n = v.len();
+
+    match topology {
+        vk::PRIMITIVE_TOPOLOGY_POINT_LIST => {
+            for i in 0..n {
+                primitives.push(Primitive::Point([v[i]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_LINE_LIST => {
+            for i in 0..n / 2 {
+                primitives.push(Primitive::Line([v[2 * i], v[2 * i + 1]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_LINE_STRIP => {
+            for i in 1..n {
+                primitives.push(Primitive::Line([v[i - 1], v[i]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_TRIANGLE_LIST => {
+            for i in 0..n / 3 {
+                primitives.push(Primitive::Triangle([v[3 * i], v[3 * i + 1], v[3 * i + 2]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP => {
+            for i in 2..n {
+                let i = i - 2;
+                // Every other triangle swaps its last two vertices to keep
+                // the winding order.
+                primitives.push(Primitive::Triangle([v[i], v[i + 1 + i % 2], v[i + 2 - i % 2]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_TRIANGLE_FAN => {
+            for i in 2..n {
+                let i = i - 2;
+                primitives.push(Primitive::Triangle([v[i + 1], v[i + 2], v[0]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_LINE_LIST_WITH_ADJACENCY => {
+            for i in 0..n / 4 {
+                let v = &v[4 * i..];
+                primitives.push(Primitive::LineAdjacency([v[1], v[2]], [v[0], v[3]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_LINE_STRIP_WITH_ADJACENCY => {
+            for i in 3..n {
+                let v = &v[i - 3..];
+                primitives.push(Primitive::LineAdjacency([v[1], v[2]], [v[0], v[3]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_TRIANGLE_LIST_WITH_ADJACENCY => {
+            for i in 0..n / 6 {
+                let v = &v[6 * i..];
+                primitives.push(Primitive::TriangleAdjacency([v[0], v[2], v[4]], [v[1], v[3], v[5]]));
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP_WITH_ADJACENCY => {
+            if n < 6 {
+                return;
+            }
+            let count = (n - 4) / 2;
+            for i in 0..count {
+                let j = 2 * i;
+                // The edge shared with the previous triangle, or the
+                // adjacent vertex of the first one.
+                let previous = if i == 0 { v[1] } else { v[j - 2] };
+                // The edge shared with the next triangle, or the adjacent
+                // vertex of the last one.
+                let next = if i == count - 1 { v[j + 5] } else { v[j + 6] };
+                let opposite = v[j + 3];
+
+                primitives.push(if i % 2 == 0 {
+                    Primitive::TriangleAdjacency([v[j], v[j + 2], v[j + 4]], [previous, next, opposite])
+                } else {
+                    Primitive::TriangleAdjacency([v[j], v[j + 4], v[j + 2]], [opposite, next, previous])
+                });
+            }
+        }
+        vk::PRIMITIVE_TOPOLOGY_PATCH_LIST => {
+            let size = patch_control_points as usize;
+            if size == 0 {
+                return;
+            }
+            for i in 0..n / size {
+                primitives.push(Primitive::Patch(v[i * size..(i + 1) * size].to_vec()));
+            }
+        }
+        _ => error!("assemble: unknown primitive topology {}", topology)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vk;
+    use super::*;
+    use super::Primitive::*;
+
+    fn indices(count: u32) -> Vec<u32> {
+        (0..count).collect()
+    }
+
+    #[test]
+    fn lists_drop_incomplete_primitives() {
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_POINT_LIST, 0, &indices(2), None),
+                   vec![Point([0]), Point([1])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_LINE_LIST, 0, &indices(5), None),
+                   vec![Line([0, 1]), Line([2, 3])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_TRIANGLE_LIST, 0, &indices(8), None),
+                   vec![Triangle([0, 1, 2]), Triangle([3, 4, 5])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_PATCH_LIST, 3, &indices(7), None),
+                   vec![Patch(vec![0, 1, 2]), Patch(vec![3, 4, 5])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_PATCH_LIST, 0, &indices(7), None), vec![]);
+    }
+
+    #[test]
+    fn strips_keep_winding_order() {
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_LINE_STRIP, 0, &indices(3), None),
+                   vec![Line([0, 1]), Line([1, 2])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP, 0, &indices(5), None),
+                   vec![Triangle([0, 1, 2]), Triangle([1, 3, 2]), Triangle([2, 3, 4])]);
+    }
+
+    #[test]
+    fn fans_put_the_center_last() {
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_TRIANGLE_FAN, 0, &indices(4), None),
+                   vec![Triangle([1, 2, 0]), Triangle([2, 3, 0])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_TRIANGLE_FAN, 0, &indices(2), None), vec![]);
+    }
+
+    #[test]
+    fn adjacency() {
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_LINE_LIST_WITH_ADJACENCY, 0, &indices(9), None),
+                   vec![LineAdjacency([1, 2], [0, 3]), LineAdjacency([5, 6], [4, 7])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_LINE_STRIP_WITH_ADJACENCY, 0, &indices(5), None),
+                   vec![LineAdjacency([1, 2], [0, 3]), LineAdjacency([2, 3], [1, 4])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_TRIANGLE_LIST_WITH_ADJACENCY, 0, &indices(12), None),
+                   vec![TriangleAdjacency([0, 2, 4], [1, 3, 5]), TriangleAdjacency([6, 8, 10], [7, 9, 11])]);
+    }
+
+    #[test]
+    fn triangle_strip_with_adjacency() {
+        let strip = vk::PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP_WITH_ADJACENCY;
+        assert_eq!(assemble(strip, 0, &indices(5), None), vec![]);
+        assert_eq!(assemble(strip, 0, &indices(6), None), vec![TriangleAdjacency([0, 2, 4], [1, 5, 3])]);
+        assert_eq!(assemble(strip, 0, &indices(10), None), vec![
+            TriangleAdjacency([0, 2, 4], [1, 6, 3]),
+            TriangleAdjacency([2, 6, 4], [5, 8, 0]),
+            TriangleAdjacency([4, 6, 8], [2, 9, 7]),
+        ]);
+    }
+
+    #[test]
+    fn primitive_restart() {
+        let restart = restart_index(vk::INDEX_TYPE_UINT16);
+        let indices = [0, 1, 2, restart, 3, 4, restart, 5, 6, 7, 8];
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP, 0, &indices, Some(restart)),
+                   vec![Triangle([0, 1, 2]), Triangle([5, 6, 7]), Triangle([6, 8, 7])]);
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_TRIANGLE_FAN, 0, &indices, Some(restart)),
+                   vec![Triangle([1, 2, 0]), Triangle([6, 7, 5]), Triangle([7, 8, 5])]);
+        // Without restart the index is an ordinary vertex.
+        assert_eq!(assemble(vk::PRIMITIVE_TOPOLOGY_LINE_STRIP, 0, &[0, restart, 1], None),
+                   vec![Line([0, restart]), Line([restart, 1])]);
+    }
+}
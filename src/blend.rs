@@ -0,0 +1,250 @@
+use vk;
+
+use format::{self, FormatInfo, NumericFormat};
+use pipeline::ColorBlendAttachment;
+use spirv::{bits_to_f32, f32_to_bits};
+
+/// Value of a blend factor for channel `i`.
+fn factor(factor: vk::BlendFactor, i: usize, src: &[f32; 4], src1: &[f32; 4], dst: &[f32; 4],
+          constants: &[f32; 4]) -> f32 {
+    match factor {
+        vk::BLEND_FACTOR_ZERO => 0.0,
+        vk::BLEND_FACTOR_ONE => 1.0,
+        vk::BLEND_FACTOR_SRC_COLOR => src[i],
+        vk::BLEND_FACTOR_ONE_MINUS_SRC_COLOR => 1.0 - src[i],
+        vk::BLEND_FACTOR_DST_COLOR => dst[i],
+        vk::BLEND_FACTOR_ONE_MINUS_DST_COLOR => 1.0 - dst[i],
+        vk::BLEND_FACTOR_SRC_ALPHA => src[3],
+        vk::BLEND_FACTOR_ONE_MINUS_SRC_ALPHA => 1.0 - src[3],
+        vk::BLEND_FACTOR_DST_ALPHA => dst[3],
+        vk::BLEND_FACTOR_ONE_MINUS_DST_ALPHA => 1.0 - dst[3],
+        vk::BLEND_FACTOR_CONSTANT_COLOR => constants[i],
+        vk::BLEND_FACTOR_ONE_MINUS_CONSTANT_COLOR => 1.0 - constants[i],
+        vk::BLEND_FACTOR_CONSTANT_ALPHA => constants[3],
+        vk::BLEND_FACTOR_ONE_MINUS_CONSTANT_ALPHA => 1.0 - constants[3],
+        vk::BLEND_FACTOR_SRC_ALPHA_SATURATE => if i == 3 { 1.0 } else { src[3].min(1.0 - dst[3]) },
+        vk::BLEND_FACTOR_SRC1_COLOR => src1[i],
+        vk::BLEND_FACTOR_ONE_MINUS_SRC1_COLOR => 1.0 - src1[i],
+        vk::BLEND_FACTOR_SRC1_ALPHA => src1[3],
+        vk::BLEND_FACTOR_ONE_MINUS_SRC1_ALPHA => 1.0 - src1[3],
+        _ => 0.0,
+    }
+}
+
+fn operation(op: vk::BlendOp, src: f32, src_factor: f32, dst: f32, dst_factor: f32) -> f32 {
+    match op {
+        vk::BLEND_OP_SUBTRACT => src * src_factor - dst * dst_factor,
+        vk::BLEND_OP_REVERSE_SUBTRACT => dst * dst_factor - src * src_factor,
+        vk::BLEND_OP_MIN => src.min(dst),
+        vk::BLEND_OP_MAX => src.max(dst),
+        _ => src * src_factor + dst * dst_factor,
+    }
+}
+
+pub fn apply_logic_op(op: vk::LogicOp, s: u8, d: u8) -> u8 {
+    match op {
+        vk::LOGIC_OP_CLEAR => 0,
+        vk::LOGIC_OP_AND => s & d,
+        vk::LOGIC_OP_AND_REVERSE => s & !d,
+        vk::LOGIC_OP_AND_INVERTED => !s & d,
+        vk::LOGIC_OP_NO_OP => d,
+        vk::LOGIC_OP_XOR => s ^ d,
+        vk::LOGIC_OP_OR => s | d,
+        vk::LOGIC_OP_NOR => !(s | d),
+        vk::LOGIC_OP_EQUIVALENT => !(s ^ d),
+        vk::LOGIC_OP_INVERT => !d,
+        vk::LOGIC_OP_OR_REVERSE => s | !d,
+        vk::LOGIC_OP_COPY_INVERTED => !s,
+        vk::LOGIC_OP_OR_INVERTED => !s | d,
+        vk::LOGIC_OP_NAND => !(s & d),
+        vk::LOGIC_OP_SET => 0xff,
+        _ => s,
+    }
+}
+
+/// Converts shader values to floats, clamped to the range of fixed-point
+/// formats.
+fn to_floats(info: &FormatInfo, value: &[u32; 4]) -> [f32; 4] {
+    let mut out = [0.0; 4];
+    for i in 0..4 {
+        let v = bits_to_f32(value[i]);
+        out[i] = match info.numeric {
+            NumericFormat::Unorm | NumericFormat::Srgb => v.max(0.0).min(1.0),
+            NumericFormat::Snorm => v.max(-1.0).min(1.0),
+            _ => v
+        };
+    }
+    out
+}
+
+/// Writes a fragment shader output to a texel of a color attachment,
+/// applying the logic op or blending and the write mask. `src1` is the
+/// second output for dual-source blending. Blending happens in linear space
+/// for sRGB formats and is skipped for integer formats.
+pub fn write(attachment: &ColorBlendAttachment, logic_op: Option<vk::LogicOp>, constants: &[f32; 4],
+             info: &FormatInfo, src: &[u32; 4], src1: &[u32; 4], texel: &mut [u8]) {
+    let mask = attachment.write_mask;
+    let float = match info.numeric {
+        NumericFormat::Ufloat | NumericFormat::Sfloat | NumericFormat::Srgb => true,
+        _ => false
+    };
+
+    match logic_op {
+        // Logic ops work on the bits stored in the attachment, so the
+        // source is encoded first.
+        Some(op) if !float => {
+            let mut result = texel.to_vec();
+            format::encode(info, src, !0, &mut result);
+            for (r, &d) in result.iter_mut().zip(texel.iter()) {
+                *r = apply_logic_op(op, *r, d);
+            }
+            let value = format::decode(info, &result);
+            format::encode(info, &value, mask, texel);
+        }
+        _ if attachment.blend && !info.numeric.is_integer() => {
+            let dst = to_floats(info, &format::decode(info, texel));
+            let s = to_floats(info, src);
+            let s1 = to_floats(info, src1);
+            let c = to_floats(info, &[f32_to_bits(constants[0]), f32_to_bits(constants[1]),
+                                      f32_to_bits(constants[2]), f32_to_bits(constants[3])]);
+
+            let mut value = [0; 4];
+            for i in 0..4 {
+                let (src_factor, dst_factor, op) = if i < 3 {
+                    (attachment.src_color_factor, attachment.dst_color_factor, attachment.color_op)
+                } else {
+                    (attachment.src_alpha_factor, attachment.dst_alpha_factor, attachment.alpha_op)
+                };
+                let sf = factor(src_factor, i, &s, &s1, &dst, &c);
+                let df = factor(dst_factor, i, &s, &s1, &dst, &c);
+                value[i] = f32_to_bits(operation(op, s[i], sf, dst[i], df));
+            }
+            format::encode(info, &value, mask, texel);
+        }
+        _ => format::encode(info, src, mask, texel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vk;
+    use format;
+    use pipeline::ColorBlendAttachment;
+    use spirv::f32_to_bits;
+    use super::*;
+
+    fn attachment(src: vk::BlendFactor, dst: vk::BlendFactor, op: vk::BlendOp) -> ColorBlendAttachment {
+        ColorBlendAttachment {
+            blend: true,
+            src_color_factor: src,
+            dst_color_factor: dst,
+            color_op: op,
+            src_alpha_factor: vk::BLEND_FACTOR_ONE,
+            dst_alpha_factor: vk::BLEND_FACTOR_ZERO,
+            alpha_op: vk::BLEND_OP_ADD,
+            write_mask: 0xF,
+        }
+    }
+
+    fn floats(value: [f32; 4]) -> [u32; 4] {
+        [f32_to_bits(value[0]), f32_to_bits(value[1]), f32_to_bits(value[2]), f32_to_bits(value[3])]
+    }
+
+    /// Writes `src` over an RGBA8 texel of `format` holding `dst`.
+    fn blend(format: vk::Format, attachment: &ColorBlendAttachment, logic_op: Option<vk::LogicOp>, src: [u32; 4],
+             dst: [u8; 4]) -> [u8; 4] {
+        let info = format::info(format).unwrap();
+        let mut texel = dst;
+        write(attachment, logic_op, &[0.25, 0.5, 0.75, 1.0], &info, &src, &floats([0.0, 0.0, 0.0, 0.5]),
+              &mut texel);
+        texel
+    }
+
+    #[test]
+    fn factors() {
+        let src = [0.5, 0.25, 1.0, 0.75];
+        let src1 = [0.1, 0.2, 0.3, 0.4];
+        let dst = [1.0, 0.0, 0.5, 0.5];
+        let constants = [0.25, 0.5, 0.75, 0.125];
+        let f = |blend_factor: vk::BlendFactor, i: usize| factor(blend_factor, i, &src, &src1, &dst, &constants);
+
+        assert_eq!(f(vk::BLEND_FACTOR_ONE_MINUS_SRC_COLOR, 1), 0.75);
+        assert_eq!(f(vk::BLEND_FACTOR_DST_ALPHA, 0), 0.5);
+        assert_eq!(f(vk::BLEND_FACTOR_ONE_MINUS_CONSTANT_COLOR, 2), 0.25);
+        assert_eq!(f(vk::BLEND_FACTOR_CONSTANT_ALPHA, 0), 0.125);
+        assert_eq!(f(vk::BLEND_FACTOR_SRC1_COLOR, 2), 0.3);
+        assert_eq!(f(vk::BLEND_FACTOR_ONE_MINUS_SRC1_ALPHA, 0), 0.6);
+        // min(As, 1 - Ad) for color, one for alpha.
+        assert_eq!(f(vk::BLEND_FACTOR_SRC_ALPHA_SATURATE, 0), 0.5);
+        assert_eq!(f(vk::BLEND_FACTOR_SRC_ALPHA_SATURATE, 3), 1.0);
+    }
+
+    #[test]
+    fn operations() {
+        assert_eq!(operation(vk::BLEND_OP_ADD, 0.5, 0.5, 1.0, 0.25), 0.5);
+        assert_eq!(operation(vk::BLEND_OP_SUBTRACT, 0.5, 1.0, 1.0, 0.25), 0.25);
+        assert_eq!(operation(vk::BLEND_OP_REVERSE_SUBTRACT, 0.5, 1.0, 1.0, 0.25), -0.25);
+        // Min and max ignore the factors.
+        assert_eq!(operation(vk::BLEND_OP_MIN, 0.5, 0.0, 1.0, 0.0), 0.5);
+        assert_eq!(operation(vk::BLEND_OP_MAX, 0.5, 0.0, 1.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn logic_ops() {
+        let (s, d) = (0b1100, 0b1010);
+        let expected = [
+            (vk::LOGIC_OP_CLEAR, 0b0000), (vk::LOGIC_OP_AND, 0b1000), (vk::LOGIC_OP_AND_REVERSE, 0b0100),
+            (vk::LOGIC_OP_COPY, 0b1100), (vk::LOGIC_OP_AND_INVERTED, 0b0010), (vk::LOGIC_OP_NO_OP, 0b1010),
+            (vk::LOGIC_OP_XOR, 0b0110), (vk::LOGIC_OP_OR, 0b1110), (vk::LOGIC_OP_NOR, 0b0001),
+            (vk::LOGIC_OP_EQUIVALENT, 0b1001), (vk::LOGIC_OP_INVERT, 0b0101), (vk::LOGIC_OP_OR_REVERSE, 0b1101),
+            (vk::LOGIC_OP_COPY_INVERTED, 0b0011), (vk::LOGIC_OP_OR_INVERTED, 0b1011), (vk::LOGIC_OP_NAND, 0b0111),
+            (vk::LOGIC_OP_SET, 0b1111),
+        ];
+        for &(op, result) in &expected {
+            assert_eq!(apply_logic_op(op, s, d) & 0xF, result, "logic op {}", op);
+        }
+    }
+
+    #[test]
+    fn alpha_blending() {
+        let over = attachment(vk::BLEND_FACTOR_SRC_ALPHA, vk::BLEND_FACTOR_ONE_MINUS_SRC_ALPHA, vk::BLEND_OP_ADD);
+        assert_eq!(blend(vk::FORMAT_R8G8B8A8_UNORM, &over, None, floats([1.0, 0.0, 0.0, 0.5]), [0, 0, 255, 255]),
+                   [128, 0, 128, 128]);
+
+        // Sources are clamped to the range of normalized formats first.
+        let add = attachment(vk::BLEND_FACTOR_ONE, vk::BLEND_FACTOR_ONE, vk::BLEND_OP_ADD);
+        assert_eq!(blend(vk::FORMAT_R8G8B8A8_UNORM, &add, None, floats([2.0, -1.0, 0.5, 1.0]), [0, 0, 0, 0]),
+                   [255, 0, 128, 255]);
+
+        let constant = attachment(vk::BLEND_FACTOR_CONSTANT_COLOR, vk::BLEND_FACTOR_ZERO, vk::BLEND_OP_ADD);
+        assert_eq!(blend(vk::FORMAT_R8G8B8A8_UNORM, &constant, None, floats([1.0; 4]), [9; 4]),
+                   [64, 128, 191, 255]);
+
+        let dual = attachment(vk::BLEND_FACTOR_SRC1_ALPHA, vk::BLEND_FACTOR_ZERO, vk::BLEND_OP_ADD);
+        assert_eq!(blend(vk::FORMAT_R8G8B8A8_UNORM, &dual, None, floats([1.0; 4]), [0; 4]), [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn srgb_blends_in_linear_space() {
+        // Half of linear white is 188 in sRGB, not 128.
+        let half = attachment(vk::BLEND_FACTOR_SRC_ALPHA, vk::BLEND_FACTOR_ONE_MINUS_SRC_ALPHA, vk::BLEND_OP_ADD);
+        assert_eq!(blend(vk::FORMAT_R8G8B8A8_SRGB, &half, None, floats([1.0, 1.0, 1.0, 0.5]), [0, 0, 0, 0]),
+                   [188, 188, 188, 128]);
+    }
+
+    #[test]
+    fn write_mask_and_logic_op() {
+        let mut replace = attachment(vk::BLEND_FACTOR_ONE, vk::BLEND_FACTOR_ZERO, vk::BLEND_OP_ADD);
+        replace.blend = false;
+        replace.write_mask = vk::COLOR_COMPONENT_R_BIT | vk::COLOR_COMPONENT_A_BIT;
+        assert_eq!(blend(vk::FORMAT_R8G8B8A8_UINT, &replace, None, [1, 2, 3, 4], [9, 9, 9, 9]), [1, 9, 9, 4]);
+
+        replace.write_mask = 0xF;
+        assert_eq!(blend(vk::FORMAT_R8G8B8A8_UINT, &replace, Some(vk::LOGIC_OP_XOR), [0xF0, 0x0F, 0xFF, 0],
+                         [0xFF, 0xFF, 0xFF, 0xFF]), [0x0F, 0xF0, 0x00, 0xFF]);
+
+        // Logic ops also apply to normalized formats, on the stored bits.
+        assert_eq!(blend(vk::FORMAT_R8G8B8A8_UNORM, &replace, Some(vk::LOGIC_OP_INVERT), floats([1.0; 4]),
+                         [0x0F; 4]), [0xF0; 4]);
+    }
+}
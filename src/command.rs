@@ -0,0 +1,592 @@
+use std::collections::HashMap;
+
+use {std, vk};
+
+use {assembly, compute, tile};
+use depth::DepthBias;
+use descriptor::{DescriptorSet, BoundDescriptorSet};
+use image::{self, Image, ImageView, Region, BufferImageCopy};
+use memory::{self, Buffer, BufferCopy};
+use pipeline::{Pipeline, GraphicsPipeline, ComputePipeline, PipelineLayout, Viewport, Rect, DepthStencilState,
+               MAX_PUSH_CONSTANTS_SIZE};
+use renderpass::{RenderPass, Framebuffer, RenderPassState};
+use spirv::Resources;
+
+/// Maximum number of vertex input bindings, as reported in the device limits.
+pub const MAX_VERTEX_BINDINGS: u32 = 16;
+
+/// A recorded command. Objects are referenced by pointer; the specification
+/// requires them to stay alive until the command buffer has executed.
+pub enum Command {
+    BindPipeline { bind_point: vk::PipelineBindPoint, pipeline: *const Pipeline },
+    BindVertexBuffers { first: u32, buffers: Vec<VertexBufferBinding> },
+    BindIndexBuffer(IndexBufferBinding),
+    BindDescriptorSets {
+        bind_point: vk::PipelineBindPoint,
+        layout: PipelineLayout,
+        first_set: u32,
+        sets: Vec<*const DescriptorSet>,
+        dynamic_offsets: Vec<u32>,
+    },
+    PushConstants { offset: u32, values: Vec<u8> },
+    SetViewport { first: u32, viewports: Vec<Viewport> },
+    SetScissor { first: u32, scissors: Vec<Rect> },
+    SetLineWidth(f32),
+    SetDepthBias(DepthBias),
+    SetDepthBounds { min: f32, max: f32 },
+    SetStencilCompareMask { faces: vk::StencilFaceFlags, mask: u32 },
+    SetStencilWriteMask { faces: vk::StencilFaceFlags, mask: u32 },
+    SetStencilReference { faces: vk::StencilFaceFlags, reference: u32 },
+    SetBlendConstants([f32; 4]),
+    ResolveImage { src: *const Image, dst: *const Image, regions: Vec<Region> },
+    CopyBuffer { src: *const Buffer, dst: *const Buffer, regions: Vec<BufferCopy> },
+    /// Writes data that was copied when the command was recorded.
+    UpdateBuffer { dst: *const Buffer, offset: u64, data: Vec<u8> },
+    FillBuffer { dst: *const Buffer, offset: u64, size: u64, data: u32 },
+    CopyBufferToImage { src: *const Buffer, dst: *const Image, regions: Vec<BufferImageCopy> },
+    CopyImageToBuffer { src: *const Image, dst: *const Buffer, regions: Vec<BufferImageCopy> },
+    BeginRenderPass {
+        render_pass: *const RenderPass,
+        framebuffer: *const Framebuffer,
+        area: Rect,
+        clear_values: Vec<[u32; 4]>,
+    },
+    NextSubpass,
+    EndRenderPass,
+    Draw { vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32 },
+    DrawIndexed { index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32 },
+    /// Draws whose parameters are read from `draw_count` records of
+    /// `VkDrawIndirectCommand`, `stride` bytes apart, when the command
+    /// executes.
+    DrawIndirect { buffer: *const Buffer, offset: u64, draw_count: u32, stride: u32 },
+    /// Like `DrawIndirect` with `VkDrawIndexedIndirectCommand` records.
+    DrawIndexedIndirect { buffer: *const Buffer, offset: u64, draw_count: u32, stride: u32 },
+    /// Runs the bound compute pipeline over a grid of workgroups.
+    Dispatch([u32; 3]),
+    /// A dispatch whose size is read from a `VkDispatchIndirectCommand` when
+    /// the command executes.
+    DispatchIndirect { buffer: *const Buffer, offset: u64 },
+}
+
+#[derive(Clone, Copy)]
+pub struct VertexBufferBinding {
+    pub buffer: *const Buffer,
+    pub offset: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct IndexBufferBinding {
+    pub buffer: *const Buffer,
+    pub offset: u64,
+    pub index_type: vk::IndexType,
+}
+
+/// A descriptor set bound to a bind point, with the pipeline layout it was
+/// bound with.
+#[derive(Clone)]
+pub struct DescriptorSetBinding {
+    pub set: BoundDescriptorSet,
+    pub layout: PipelineLayout,
+}
+
+/// State set by earlier commands of a command buffer that later commands
+/// depend on. Every command buffer starts with empty state.
+pub struct State {
+    pub graphics_pipeline: *const GraphicsPipeline,
+    pub compute_pipeline: *const ComputePipeline,
+    pub vertex_buffers: Vec<Option<VertexBufferBinding>>,
+    pub index_buffer: Option<IndexBufferBinding>,
+    /// Bound descriptor sets by set number, for the graphics and compute
+    /// bind points.
+    pub descriptor_sets: [Vec<Option<DescriptorSetBinding>>; 2],
+    /// Push constant storage, shared by all stages and bind points.
+    pub push_constants: [u8; MAX_PUSH_CONSTANTS_SIZE as usize],
+    pub viewports: Vec<Viewport>,
+    pub scissors: Vec<Rect>,
+    pub line_width: f32,
+    pub depth_bias: DepthBias,
+    pub depth_bounds: [f32; 2],
+    /// Stencil compare masks, write masks and references for front and back
+    /// faces.
+    pub stencil_compare_mask: [u32; 2],
+    pub stencil_write_mask: [u32; 2],
+    pub stencil_reference: [u32; 2],
+    pub blend_constants: [f32; 4],
+    pub render_pass: Option<RenderPassState>,
+    /// Draws of the render pass instance that are waiting to be rendered.
+    pub bins: Option<tile::Bins>,
+}
+
+impl State {
+    pub fn new() -> State {
+        State {
+            graphics_pipeline: 0 as *const GraphicsPipeline,
+            compute_pipeline: 0 as *const ComputePipeline,
+            vertex_buffers: vec![None; MAX_VERTEX_BINDINGS as usize],
+            index_buffer: None,
+            descriptor_sets: [Vec::new(), Vec::new()],
+            push_constants: [0; MAX_PUSH_CONSTANTS_SIZE as usize],
+            viewports: Vec::new(),
+            scissors: Vec::new(),
+            line_width: 1.0,
+            depth_bias: DepthBias::default(),
+            depth_bounds: [0.0, 1.0],
+            stencil_compare_mask: [0; 2],
+            stencil_write_mask: [0; 2],
+            stencil_reference: [0; 2],
+            blend_constants: [0.0; 4],
+            render_pass: None,
+            bins: None,
+        }
+    }
+
+    /// The depth/stencil state of the bound graphics pipeline with the
+    /// current dynamic values.
+    pub unsafe fn depth_stencil(&self) -> Option<DepthStencilState> {
+        let pipeline = match self.graphics_pipeline.as_ref() {
+            Some(p) => p,
+            None => return None
+        };
+
+        pipeline.depth_stencil.map(|mut state| {
+            state.min_depth_bounds = self.depth_bounds[0];
+            state.max_depth_bounds = self.depth_bounds[1];
+            for (i, face) in [&mut state.front, &mut state.back].iter_mut().enumerate() {
+                face.compare_mask = self.stencil_compare_mask[i];
+                face.write_mask = self.stencil_write_mask[i];
+                face.reference = self.stencil_reference[i];
+            }
+            state
+        })
+    }
+
+    /// Resources for shaders of a pipeline with the given layout. Sets are
+    /// only used if they were bound with a layout compatible with the
+    /// pipeline's for their set number.
+    pub unsafe fn resources(&self, bind_point: vk::PipelineBindPoint, layout: &PipelineLayout) -> Resources {
+        let descriptor_sets = self.descriptor_sets[bind_point as usize].iter().enumerate()
+            .map(|(i, binding)| match *binding {
+                Some(ref binding) if binding.layout.is_compatible(layout, i) => Some(binding.set.clone()),
+                _ => None
+            })
+            .collect();
+
+        let input_attachments = match self.render_pass {
+            Some(ref state) if bind_point == vk::PIPELINE_BIND_POINT_GRAPHICS => state.input_attachments(),
+            _ => Vec::new()
+        };
+
+        Resources {
+            input_attachments: input_attachments,
+            descriptor_sets: descriptor_sets,
+            push_constants: self.push_constants.to_vec(),
+        }
+    }
+
+    /// Adds `instance_count` instances of the vertices at `indices` to the
+    /// bins of the render pass instance, with the bound graphics pipeline and
+    /// the current state. Indexed draws pass the indices read from the index
+    /// buffer, to which `vertex_offset` is added, and the restart index if
+    /// primitive restart is enabled.
+    ///
+    /// Vertices are shaded and primitives rendered when the bins are flushed.
+    unsafe fn draw(&mut self, indices: &[u32], vertex_offset: i32, restart: Option<u32>, instance_count: u32,
+                   first_instance: u32) {
+        let pipeline = match self.graphics_pipeline.as_ref() {
+            Some(p) => p,
+            None => {
+                error!("Draw: no graphics pipeline bound");
+                return;
+            }
+        };
+        let render_pass = match self.render_pass {
+            Some(ref state) => state,
+            None => {
+                error!("Draw: no render pass instance is active");
+                return;
+            }
+        };
+        if pipeline.stages.iter().any(|s| s.stage != vk::SHADER_STAGE_VERTEX_BIT &&
+                                          s.stage != vk::SHADER_STAGE_FRAGMENT_BIT) {
+            error!("Draw: tessellation and geometry shaders are not supported");
+            return;
+        }
+        let viewport = match self.viewports.get(0) {
+            Some(&viewport) => viewport,
+            None => {
+                error!("Draw: no viewport set");
+                return;
+            }
+        };
+
+        let mut bounds = render_pass.bounds();
+        if let Some(scissor) = self.scissors.get(0) {
+            bounds = bounds.intersection(scissor);
+        }
+
+        let patch_control_points = pipeline.tessellation.map(|t| t.patch_control_points).unwrap_or(0);
+        let mut primitives = assembly::assemble(pipeline.input_assembly.topology, patch_control_points,
+                                                indices, restart);
+
+        // Primitives refer to the shaded vertices by their position in the
+        // list of distinct indices.
+        let mut used: Vec<u32> = indices.iter().cloned().filter(|&i| Some(i) != restart).collect();
+        used.sort();
+        used.dedup();
+        let slots: HashMap<u32, u32> = used.iter().enumerate().map(|(slot, &i)| (i, slot as u32)).collect();
+        for primitive in primitives.iter_mut() {
+            *primitive = match *primitive {
+                assembly::Primitive::Point(v) => assembly::Primitive::Point([slots[&v[0]]]),
+                assembly::Primitive::Line(v) => assembly::Primitive::Line([slots[&v[0]], slots[&v[1]]]),
+                assembly::Primitive::Triangle(v) =>
+                    assembly::Primitive::Triangle([slots[&v[0]], slots[&v[1]], slots[&v[2]]]),
+                assembly::Primitive::LineAdjacency(v, _) =>
+                    assembly::Primitive::Line([slots[&v[0]], slots[&v[1]]]),
+                assembly::Primitive::TriangleAdjacency(v, _) =>
+                    assembly::Primitive::Triangle([slots[&v[0]], slots[&v[1]], slots[&v[2]]]),
+                assembly::Primitive::Patch(ref v) =>
+                    assembly::Primitive::Patch(v.iter().map(|i| slots[i]).collect()),
+            };
+        }
+
+        let draw = tile::Draw {
+            pipeline: pipeline,
+            resources: self.resources(vk::PIPELINE_BIND_POINT_GRAPHICS, &pipeline.layout),
+            vertex_buffers: self.vertex_buffers.clone(),
+            indices: used.iter().map(|&i| (i as i32).wrapping_add(vertex_offset) as u32).collect(),
+            primitives: primitives,
+            first_instance: first_instance,
+            instance_count: instance_count,
+            viewport: viewport,
+            bounds: bounds,
+            line_width: self.line_width,
+            depth_bias: if pipeline.rasterization.depth_bias { Some(self.depth_bias) } else { None },
+            depth_stencil: self.depth_stencil(),
+            blend_constants: self.blend_constants,
+            color_attachments: render_pass.color_attachments().into_iter()
+                .map(|v| v.map(|v| v as *const ImageView))
+                .collect(),
+            depth_stencil_attachment: render_pass.depth_stencil_attachment().map(|v| v as *const ImageView),
+        };
+        if let Some(ref mut bins) = self.bins {
+            bins.push(draw);
+        }
+    }
+
+    /// Renders the draws waiting in the bins of the render pass instance.
+    unsafe fn flush(&mut self) {
+        if let Some(ref mut bins) = self.bins {
+            bins.flush();
+        }
+    }
+
+    unsafe fn draw_indexed(&mut self, index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32,
+                           first_instance: u32) {
+        let binding = match self.index_buffer {
+            Some(ref binding) => binding,
+            None => {
+                error!("DrawIndexed: no index buffer bound");
+                return;
+            }
+        };
+        let restart = match self.graphics_pipeline.as_ref() {
+            Some(p) if p.input_assembly.primitive_restart => Some(assembly::restart_index(binding.index_type)),
+            _ => None
+        };
+
+        let indices = assembly::read_indices(binding, first_index, index_count);
+        self.draw(&indices, vertex_offset, restart, instance_count, first_instance);
+    }
+
+    /// Runs the bound compute pipeline over a grid of workgroups.
+    unsafe fn dispatch(&self, groups: [u32; 3]) {
+        let pipeline = match self.compute_pipeline.as_ref() {
+            Some(p) => p,
+            None => {
+                error!("Dispatch: no compute pipeline bound");
+                return;
+            }
+        };
+
+        if groups.iter().zip(compute::MAX_WORKGROUP_COUNT.iter()).any(|(&g, &max)| g > max) {
+            error!("Dispatch: workgroup count {:?} exceeds maxComputeWorkGroupCount", groups);
+            return;
+        }
+        let size = compute::local_size(&pipeline.stage);
+        if size.iter().zip(compute::MAX_WORKGROUP_SIZE.iter()).any(|(&s, &max)| s > max) ||
+           size[0] * size[1] * size[2] > compute::MAX_WORKGROUP_INVOCATIONS {
+            error!("Dispatch: workgroup size {:?} exceeds the compute limits", size);
+            return;
+        }
+
+        let resources = self.resources(vk::PIPELINE_BIND_POINT_COMPUTE, &pipeline.layout);
+        compute::dispatch(&pipeline.stage, &resources, groups);
+    }
+
+    pub unsafe fn execute(&mut self, command: &Command) {
+        // Draws wait in the bins with the state they were recorded with, so
+        // only commands that may touch their attachments or depend on their
+        // results need them to be rendered first.
+        match *command {
+            Command::BindPipeline { .. } | Command::BindVertexBuffers { .. } | Command::BindIndexBuffer(_) |
+            Command::BindDescriptorSets { .. } | Command::PushConstants { .. } | Command::SetViewport { .. } |
+            Command::SetScissor { .. } | Command::SetLineWidth(_) | Command::SetDepthBias(_) |
+            Command::SetDepthBounds { .. } | Command::SetStencilCompareMask { .. } |
+            Command::SetStencilWriteMask { .. } | Command::SetStencilReference { .. } |
+            Command::SetBlendConstants(_) | Command::Draw { .. } | Command::DrawIndexed { .. } |
+            Command::DrawIndirect { .. } | Command::DrawIndexedIndirect { .. } => (),
+            _ => self.flush()
+        }
+
+        match *command {
+            Command::BindPipeline { bind_point, pipeline } => match (bind_point, &*pipeline) {
+                (vk::PIPELINE_BIND_POINT_GRAPHICS, &Pipeline::Graphics(ref p)) => {
+                    self.graphics_pipeline = p;
+
+                    // Static state replaces whatever was set dynamically.
+                    if let Some(ref viewport) = p.viewport {
+                        if !p.dynamic.contains(vk::DYNAMIC_STATE_VIEWPORT) {
+                            self.viewports = viewport.viewports.clone();
+                        }
+                        if !p.dynamic.contains(vk::DYNAMIC_STATE_SCISSOR) {
+                            self.scissors = viewport.scissors.clone();
+                        }
+                    }
+                    if !p.dynamic.contains(vk::DYNAMIC_STATE_LINE_WIDTH) {
+                        self.line_width = p.rasterization.line_width;
+                    }
+                    if !p.dynamic.contains(vk::DYNAMIC_STATE_DEPTH_BIAS) {
+                        self.depth_bias = DepthBias {
+                            constant_factor: p.rasterization.depth_bias_constant_factor,
+                            clamp: p.rasterization.depth_bias_clamp,
+                            slope_factor: p.rasterization.depth_bias_slope_factor,
+                        };
+                    }
+                    if let Some(ref cb) = p.color_blend {
+                        if !p.dynamic.contains(vk::DYNAMIC_STATE_BLEND_CONSTANTS) {
+                            self.blend_constants = cb.blend_constants;
+                        }
+                    }
+                    if let Some(ref ds) = p.depth_stencil {
+                        if !p.dynamic.contains(vk::DYNAMIC_STATE_DEPTH_BOUNDS) {
+                            self.depth_bounds = [ds.min_depth_bounds, ds.max_depth_bounds];
+                        }
+                        if !p.dynamic.contains(vk::DYNAMIC_STATE_STENCIL_COMPARE_MASK) {
+                            self.stencil_compare_mask = [ds.front.compare_mask, ds.back.compare_mask];
+                        }
+                        if !p.dynamic.contains(vk::DYNAMIC_STATE_STENCIL_WRITE_MASK) {
+                            self.stencil_write_mask = [ds.front.write_mask, ds.back.write_mask];
+                        }
+                        if !p.dynamic.contains(vk::DYNAMIC_STATE_STENCIL_REFERENCE) {
+                            self.stencil_reference = [ds.front.reference, ds.back.reference];
+                        }
+                    }
+                }
+                (vk::PIPELINE_BIND_POINT_COMPUTE, &Pipeline::Compute(ref p)) => self.compute_pipeline = p,
+                _ => error!("BindPipeline: pipeline does not match bind point {}", bind_point)
+            },
+            Command::BindVertexBuffers { first, ref buffers } => {
+                for (i, &binding) in buffers.iter().enumerate() {
+                    self.vertex_buffers[first as usize + i] = Some(binding);
+                }
+            }
+            Command::BindIndexBuffer(binding) => self.index_buffer = Some(binding),
+            Command::BindDescriptorSets { bind_point, ref layout, first_set, ref sets, ref dynamic_offsets } => {
+                let bound = &mut self.descriptor_sets[bind_point as usize];
+                let first = first_set as usize;
+                if bound.len() < first + sets.len() {
+                    bound.resize(first + sets.len(), None);
+                }
+
+                // Dynamic offsets are consumed in set order.
+                let mut offsets = &dynamic_offsets[..];
+                for (i, &set) in sets.iter().enumerate() {
+                    let count = std::cmp::min((*set).layout.dynamic_count(), offsets.len());
+                    bound[first + i] = Some(DescriptorSetBinding {
+                        set: BoundDescriptorSet { set: set, dynamic_offsets: offsets[..count].to_vec() },
+                        layout: layout.clone(),
+                    });
+                    offsets = &offsets[count..];
+                }
+
+                // Other sets are disturbed unless their layout is compatible
+                // with the new one for their set number.
+                for (i, binding) in bound.iter_mut().enumerate() {
+                    let compatible = match *binding {
+                        Some(ref binding) => binding.layout.is_compatible(layout, i),
+                        None => true
+                    };
+                    if !compatible {
+                        *binding = None;
+                    }
+                }
+            }
+            Command::PushConstants { offset, ref values } => {
+                let offset = offset as usize;
+                match self.push_constants.get_mut(offset..offset.saturating_add(values.len())) {
+                    Some(range) => range.copy_from_slice(values),
+                    None => error!("PushConstants: {} bytes at offset {} out of bounds", values.len(), offset)
+                }
+            }
+            Command::SetViewport { first, ref viewports } => set_range(&mut self.viewports, first, viewports),
+            Command::SetScissor { first, ref scissors } => set_range(&mut self.scissors, first, scissors),
+            Command::SetLineWidth(width) => self.line_width = width,
+            Command::SetDepthBias(bias) => self.depth_bias = bias,
+            Command::SetDepthBounds { min, max } => self.depth_bounds = [min, max],
+            Command::SetStencilCompareMask { faces, mask } => set_faces(&mut self.stencil_compare_mask, faces, mask),
+            Command::SetStencilWriteMask { faces, mask } => set_faces(&mut self.stencil_write_mask, faces, mask),
+            Command::SetStencilReference { faces, reference } =>
+                set_faces(&mut self.stencil_reference, faces, reference),
+            Command::SetBlendConstants(constants) => self.blend_constants = constants,
+            Command::ResolveImage { src, dst, ref regions } => {
+                for region in regions {
+                    image::resolve(&*src, &*dst, region);
+                }
+            }
+            Command::CopyBuffer { src, dst, ref regions } => {
+                for region in regions {
+                    memory::copy_buffer(&*src, &*dst, region);
+                }
+            }
+            Command::UpdateBuffer { dst, offset, ref data } => memory::update_buffer(&*dst, offset, data),
+            Command::FillBuffer { dst, offset, size, data } => memory::fill_buffer(&*dst, offset, size, data),
+            Command::CopyBufferToImage { src, dst, ref regions } => {
+                for region in regions {
+                    image::copy_buffer_to_image(&*src, &*dst, region);
+                }
+            }
+            Command::CopyImageToBuffer { src, dst, ref regions } => {
+                for region in regions {
+                    image::copy_image_to_buffer(&*src, &*dst, region);
+                }
+            }
+            Command::BeginRenderPass { render_pass, framebuffer, area, ref clear_values } => {
+                let state = RenderPassState {
+                    render_pass: render_pass,
+                    framebuffer: framebuffer,
+                    area: area,
+                    clear_values: clear_values.clone(),
+                    subpass: 0,
+                };
+                state.begin();
+                self.bins = Some(tile::Bins::new(state.bounds()));
+                self.render_pass = Some(state);
+            }
+            Command::NextSubpass => match self.render_pass {
+                Some(ref mut state) => state.next_subpass(),
+                None => error!("NextSubpass: no render pass instance is active")
+            },
+            Command::EndRenderPass => match self.render_pass.take() {
+                Some(state) => {
+                    self.bins = None;
+                    state.end();
+                }
+                None => error!("EndRenderPass: no render pass instance is active")
+            },
+            Command::Draw { vertex_count, instance_count, first_vertex, first_instance } => {
+                let indices: Vec<u32> = (0..vertex_count).map(|i| first_vertex.wrapping_add(i)).collect();
+                self.draw(&indices, 0, None, instance_count, first_instance);
+            }
+            Command::DrawIndexed { index_count, instance_count, first_index, vertex_offset, first_instance } =>
+                self.draw_indexed(index_count, instance_count, first_index, vertex_offset, first_instance),
+            Command::DrawIndirect { buffer, offset, draw_count, stride } => {
+                for i in 0..draw_count as u64 {
+                    let p = match read_words(buffer, offset + i * stride as u64, 4) {
+                        Some(p) => p,
+                        None => {
+                            error!("DrawIndirect: parameters out of bounds of the buffer");
+                            return;
+                        }
+                    };
+                    let indices: Vec<u32> = (0..p[0]).map(|i| p[2].wrapping_add(i)).collect();
+                    self.draw(&indices, 0, None, p[1], p[3]);
+                }
+            }
+            Command::DrawIndexedIndirect { buffer, offset, draw_count, stride } => {
+                for i in 0..draw_count as u64 {
+                    let p = match read_words(buffer, offset + i * stride as u64, 5) {
+                        Some(p) => p,
+                        None => {
+                            error!("DrawIndexedIndirect: parameters out of bounds of the buffer");
+                            return;
+                        }
+                    };
+                    self.draw_indexed(p[0], p[1], p[2], p[3] as i32, p[4]);
+                }
+            }
+            Command::Dispatch(groups) => self.dispatch(groups),
+            Command::DispatchIndirect { buffer, offset } => match read_words(buffer, offset, 3) {
+                Some(groups) => self.dispatch([groups[0], groups[1], groups[2]]),
+                None => error!("DispatchIndirect: parameters out of bounds of the buffer")
+            },
+        }
+    }
+}
+
+/// Reads `count` little-endian words of indirect command parameters from a
+/// buffer, or `None` if they don't fit in it.
+unsafe fn read_words(buffer: *const Buffer, offset: u64, count: usize) -> Option<Vec<u32>> {
+    let data = (*buffer).data();
+    let start = offset as usize;
+    if offset > data.len() as u64 || data.len() - start < count * 4 {
+        return None;
+    }
+
+    Some(data[start..start + count * 4].chunks(4)
+        .map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+        .collect())
+}
+
+/// Replaces the elements of `list` starting at `first`, growing it as needed.
+fn set_range<T: Copy>(list: &mut Vec<T>, first: u32, values: &[T]) {
+    if values.is_empty() {
+        return;
+    }
+
+    let first = first as usize;
+    if list.len() < first + values.len() {
+        let fill = values[0];
+        list.resize(first + values.len(), fill);
+    }
+    list[first..first + values.len()].copy_from_slice(values);
+}
+
+/// Sets the front and/or back value of a per-face stencil state.
+fn set_faces(values: &mut [u32; 2], faces: vk::StencilFaceFlags, value: u32) {
+    if faces & vk::STENCIL_FACE_FRONT_BIT != 0 {
+        values[0] = value;
+    }
+    if faces & vk::STENCIL_FACE_BACK_BIT != 0 {
+        values[1] = value;
+    }
+}
+
+pub struct CommandBuffer {
+    commands: Vec<Command>,
+}
+
+impl CommandBuffer {
+    pub fn new() -> CommandBuffer {
+        CommandBuffer {
+            commands: Vec::new(),
+        }
+    }
+
+    /// Starts recording, discarding any previously recorded commands.
+    pub fn begin(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn record(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+
+    pub unsafe fn execute(&self) {
+        let mut state = State::new();
+
+        for command in &self.commands {
+            state.execute(command);
+        }
+        state.flush();
+    }
+}
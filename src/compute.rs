@@ -0,0 +1,117 @@
+use spirv;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pipeline::ShaderStage;
+use pool;
+
+/// Largest number of workgroups of a dispatch in each dimension.
+pub const MAX_WORKGROUP_COUNT: [u32; 3] = [65535; 3];
+/// Largest workgroup size in each dimension.
+pub const MAX_WORKGROUP_SIZE: [u32; 3] = [1024, 1024, 64];
+/// Largest number of invocations in a workgroup.
+pub const MAX_WORKGROUP_INVOCATIONS: u32 = 1024;
+/// Largest total size of the `Workgroup` variables of a compute shader.
+pub const MAX_SHARED_MEMORY_SIZE: u32 = 32768;
+
+/// Size of a workgroup of a compute shader, from its `LocalSize` execution
+/// mode or `WorkgroupSize` built-in.
+pub fn local_size(stage: &ShaderStage) -> [u32; 3] {
+    stage.program.entry_point.local_size.unwrap_or([1, 1, 1])
+}
+
+/// Runs one workgroup of a compute shader. The invocations of the workgroup
+/// take turns running until they reach a barrier, and all of them continue
+/// once every invocation has reached it or finished.
+fn run_workgroup(stage: &ShaderStage, resources: &spirv::Resources, workgroup: [u32; 3], groups: [u32; 3]) {
+    let program = &stage.program;
+    let size = local_size(stage);
+    let memory = spirv::WorkgroupMemory::new(program);
+
+    let mut invocations = Vec::with_capacity((size[0] * size[1] * size[2]) as usize);
+    for z in 0..size[2] {
+        for y in 0..size[1] {
+            for x in 0..size[0] {
+                let mut invocation = spirv::Invocation::new(program);
+                invocation.set_resources(resources);
+                invocation.set_workgroup(&memory);
+
+                let local = [x, y, z];
+                let global = [workgroup[0] * size[0] + x, workgroup[1] * size[1] + y, workgroup[2] * size[2] + z];
+                invocation.set_builtin(spirv::BUILT_IN_NUM_WORKGROUPS, &groups);
+                invocation.set_builtin(spirv::BUILT_IN_WORKGROUP_SIZE, &size);
+                invocation.set_builtin(spirv::BUILT_IN_WORKGROUP_ID, &workgroup);
+                invocation.set_builtin(spirv::BUILT_IN_LOCAL_INVOCATION_ID, &local);
+                invocation.set_builtin(spirv::BUILT_IN_GLOBAL_INVOCATION_ID, &global);
+                invocation.set_builtin(spirv::BUILT_IN_LOCAL_INVOCATION_INDEX,
+                                       &[(z * size[1] + y) * size[0] + x]);
+                invocations.push(invocation);
+            }
+        }
+    }
+
+    loop {
+        let mut waiting = false;
+        for invocation in invocations.iter_mut() {
+            waiting |= invocation.run() == spirv::Status::Barrier;
+        }
+        if !waiting {
+            break;
+        }
+
+        for invocation in invocations.iter_mut() {
+            invocation.pass_barrier();
+        }
+    }
+}
+
+/// A dispatch shared by the worker threads, which take workgroups from it
+/// in order until none are left.
+struct Dispatch<'a> {
+    stage: &'a ShaderStage,
+    resources: &'a spirv::Resources,
+    groups: [u32; 3],
+    next: AtomicUsize,
+}
+
+// Resources are only read, except for the memory that shaders write, which
+// the application keeps workgroups from racing on or accesses atomically.
+unsafe impl<'a> Sync for Dispatch<'a> {}
+
+impl<'a> Dispatch<'a> {
+    fn count(&self) -> usize {
+        self.groups.iter().fold(1, |n, &g| n * g as usize)
+    }
+
+    fn run(&self) {
+        let count = self.count();
+        loop {
+            let index = self.next.fetch_add(1, Ordering::Relaxed);
+            if index >= count {
+                break;
+            }
+
+            let x = index % self.groups[0] as usize;
+            let y = index / self.groups[0] as usize % self.groups[1] as usize;
+            let z = index / self.groups[0] as usize / self.groups[1] as usize;
+            run_workgroup(self.stage, self.resources, [x as u32, y as u32, z as u32], self.groups);
+        }
+    }
+}
+
+/// Runs a compute shader over a grid of `groups` workgroups. Workgroups are
+/// spread over the threads of the pool, and each runs on a single thread.
+pub fn dispatch(stage: &ShaderStage, resources: &spirv::Resources, groups: [u32; 3]) {
+    let dispatch = Dispatch {
+        stage: stage,
+        resources: resources,
+        groups: groups,
+        next: AtomicUsize::new(0),
+    };
+
+    match dispatch.count() {
+        0 => (),
+        1 => dispatch.run(),
+        _ => pool::run(&|| dispatch.run()),
+    }
+}
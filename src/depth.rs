@@ -0,0 +1,257 @@
+use vk;
+
+use pipeline::{DepthStencilState, StencilState};
+use spirv::f32_to_bits;
+
+/// Depth bias factors, from the pipeline or `CmdSetDepthBias`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DepthBias {
+    pub constant_factor: f32,
+    pub clamp: f32,
+    pub slope_factor: f32,
+}
+
+impl DepthBias {
+    /// Offset added to the depth of every fragment of a polygon. `slope` is
+    /// the larger of the depth gradients along x and y, and `max_depth` the
+    /// largest depth of the polygon.
+    pub fn offset(&self, format: vk::Format, slope: f32, max_depth: f32) -> f32 {
+        let offset = slope * self.slope_factor + minimum_resolvable_difference(format, max_depth) * self.constant_factor;
+
+        if self.clamp > 0.0 {
+            offset.min(self.clamp)
+        } else if self.clamp < 0.0 {
+            offset.max(self.clamp)
+        } else {
+            offset
+        }
+    }
+}
+
+/// The smallest difference in depth that stays distinct in an attachment of
+/// the given format.
+pub fn minimum_resolvable_difference(format: vk::Format, max_depth: f32) -> f32 {
+    match format {
+        vk::FORMAT_D16_UNORM | vk::FORMAT_D16_UNORM_S8_UINT => 1.0 / 65536.0,
+        vk::FORMAT_X8_D24_UNORM_PACK32 | vk::FORMAT_D24_UNORM_S8_UINT => 1.0 / 16777216.0,
+        _ => {
+            // 2^(e - 23), where e is the exponent of the largest depth.
+            let e = ((f32_to_bits(max_depth.abs()) >> 23) & 0xFF) as i32 - 127;
+            2f32.powi(e - 23)
+        }
+    }
+}
+
+/// Converts a fragment depth to the precision of the attachment format.
+pub fn quantize(format: vk::Format, depth: f32) -> f32 {
+    let max = match format {
+        vk::FORMAT_D16_UNORM | vk::FORMAT_D16_UNORM_S8_UINT => 65535.0,
+        vk::FORMAT_X8_D24_UNORM_PACK32 | vk::FORMAT_D24_UNORM_S8_UINT => 16777215.0,
+        _ => return depth
+    };
+
+    (depth.max(0.0).min(1.0) * max).round() / max
+}
+
+/// Evaluates `a op b`.
+pub fn compare<T: PartialOrd>(op: vk::CompareOp, a: T, b: T) -> bool {
+    match op {
+        vk::COMPARE_OP_NEVER => false,
+        vk::COMPARE_OP_LESS => a < b,
+        vk::COMPARE_OP_EQUAL => a == b,
+        vk::COMPARE_OP_LESS_OR_EQUAL => a <= b,
+        vk::COMPARE_OP_GREATER => a > b,
+        vk::COMPARE_OP_NOT_EQUAL => a != b,
+        vk::COMPARE_OP_GREATER_OR_EQUAL => a >= b,
+        _ => true,
+    }
+}
+
+/// The stencil value after applying `op`, before the write mask.
+pub fn stencil_op(op: vk::StencilOp, value: u8, reference: u8) -> u8 {
+    match op {
+        vk::STENCIL_OP_ZERO => 0,
+        vk::STENCIL_OP_REPLACE => reference,
+        vk::STENCIL_OP_INCREMENT_AND_CLAMP => value.saturating_add(1),
+        vk::STENCIL_OP_DECREMENT_AND_CLAMP => value.saturating_sub(1),
+        vk::STENCIL_OP_INVERT => !value,
+        vk::STENCIL_OP_INCREMENT_AND_WRAP => value.wrapping_add(1),
+        vk::STENCIL_OP_DECREMENT_AND_WRAP => value.wrapping_sub(1),
+        _ => value,
+    }
+}
+
+fn update_stencil(face: &StencilState, op: vk::StencilOp, stencil: &mut u8) {
+    let value = stencil_op(op, *stencil, face.reference as u8);
+    let mask = face.write_mask as u8;
+    *stencil = (*stencil & !mask) | (value & mask);
+}
+
+/// Runs the depth bounds, stencil and depth tests for one sample of a
+/// fragment, updating the stored depth and stencil values. Aspects the
+/// attachment doesn't have always pass. Returns whether the sample passed.
+pub fn test(state: &DepthStencilState, format: vk::Format, front_facing: bool, depth: f32,
+            stored_depth: Option<&mut f32>, stored_stencil: Option<&mut u8>) -> bool {
+    if state.depth_bounds_test {
+        if let Some(ref d) = stored_depth {
+            if **d < state.min_depth_bounds || **d > state.max_depth_bounds {
+                return false;
+            }
+        }
+    }
+
+    let face = if front_facing { &state.front } else { &state.back };
+
+    let stencil_passed = match stored_stencil {
+        Some(ref s) if state.stencil_test => {
+            let mask = face.compare_mask as u8;
+            compare(face.compare_op, face.reference as u8 & mask, **s & mask)
+        }
+        _ => true
+    };
+
+    if !stencil_passed {
+        if let Some(s) = stored_stencil {
+            update_stencil(face, face.fail_op, s);
+        }
+        return false;
+    }
+
+    let depth = quantize(format, depth);
+    let depth_passed = match stored_depth {
+        Some(d) => {
+            let passed = !state.depth_test || compare(state.depth_compare_op, depth, *d);
+            if passed && state.depth_test && state.depth_write {
+                *d = depth;
+            }
+            passed
+        }
+        None => true
+    };
+
+    if state.stencil_test {
+        if let Some(s) = stored_stencil {
+            update_stencil(face, if depth_passed { face.pass_op } else { face.depth_fail_op }, s);
+        }
+    }
+
+    depth_passed
+}
+
+#[cfg(test)]
+mod tests {
+    use vk;
+    use pipeline::{DepthStencilState, StencilState};
+    use super::*;
+
+    fn stencil(compare_op: vk::CompareOp, reference: u32) -> StencilState {
+        StencilState {
+            fail_op: vk::STENCIL_OP_ZERO,
+            pass_op: vk::STENCIL_OP_INCREMENT_AND_CLAMP,
+            depth_fail_op: vk::STENCIL_OP_INVERT,
+            compare_op: compare_op,
+            compare_mask: 0xFF,
+            write_mask: 0xFF,
+            reference: reference,
+        }
+    }
+
+    fn state(depth_compare_op: vk::CompareOp, stencil_test: bool) -> DepthStencilState {
+        DepthStencilState {
+            depth_test: true,
+            depth_write: true,
+            depth_compare_op: depth_compare_op,
+            depth_bounds_test: false,
+            stencil_test: stencil_test,
+            front: stencil(vk::COMPARE_OP_EQUAL, 5),
+            back: stencil(vk::COMPARE_OP_NEVER, 5),
+            min_depth_bounds: 0.0,
+            max_depth_bounds: 1.0,
+        }
+    }
+
+    #[test]
+    fn quantization() {
+        assert_eq!(quantize(vk::FORMAT_D16_UNORM, 0.5), 32768.0 / 65535.0);
+        assert_eq!(quantize(vk::FORMAT_D16_UNORM, 1.0 / 65535.0 * 0.4), 0.0);
+        assert_eq!(quantize(vk::FORMAT_D16_UNORM, -1.0), 0.0);
+        assert_eq!(quantize(vk::FORMAT_D16_UNORM, 2.0), 1.0);
+        assert_eq!(quantize(vk::FORMAT_D24_UNORM_S8_UINT, 0.25), (0.25 * 16777215.0f32).round() / 16777215.0);
+        assert_eq!(quantize(vk::FORMAT_D32_SFLOAT, 0.3), 0.3);
+        assert_eq!(quantize(vk::FORMAT_D32_SFLOAT, 2.0), 2.0);
+    }
+
+    #[test]
+    fn quantized_depths_compare_equal() {
+        // Two depths closer than the precision of the format are equal once
+        // quantized, so an EQUAL test against the stored value passes.
+        let state = state(vk::COMPARE_OP_EQUAL, false);
+        let mut stored = quantize(vk::FORMAT_D16_UNORM, 0.5);
+        assert!(test(&state, vk::FORMAT_D16_UNORM, true, 0.5 + 1.0 / 262144.0, Some(&mut stored), None));
+        assert!(!test(&state, vk::FORMAT_D32_SFLOAT, true, 0.5 + 1.0 / 262144.0, Some(&mut stored), None));
+    }
+
+    #[test]
+    fn minimum_resolvable_differences() {
+        assert_eq!(minimum_resolvable_difference(vk::FORMAT_D16_UNORM, 0.5), 1.0 / 65536.0);
+        assert_eq!(minimum_resolvable_difference(vk::FORMAT_D24_UNORM_S8_UINT, 0.5), 1.0 / 16777216.0);
+        assert_eq!(minimum_resolvable_difference(vk::FORMAT_D32_SFLOAT, 0.75), 2f32.powi(-24));
+        assert_eq!(minimum_resolvable_difference(vk::FORMAT_D32_SFLOAT, 1.0), 2f32.powi(-23));
+    }
+
+    #[test]
+    fn stencil_ops() {
+        assert_eq!(stencil_op(vk::STENCIL_OP_KEEP, 7, 3), 7);
+        assert_eq!(stencil_op(vk::STENCIL_OP_ZERO, 7, 3), 0);
+        assert_eq!(stencil_op(vk::STENCIL_OP_REPLACE, 7, 3), 3);
+        assert_eq!(stencil_op(vk::STENCIL_OP_INCREMENT_AND_CLAMP, 255, 3), 255);
+        assert_eq!(stencil_op(vk::STENCIL_OP_DECREMENT_AND_CLAMP, 0, 3), 0);
+        assert_eq!(stencil_op(vk::STENCIL_OP_INVERT, 0x0F, 3), 0xF0);
+        assert_eq!(stencil_op(vk::STENCIL_OP_INCREMENT_AND_WRAP, 255, 3), 0);
+        assert_eq!(stencil_op(vk::STENCIL_OP_DECREMENT_AND_WRAP, 0, 3), 255);
+    }
+
+    #[test]
+    fn stencil_test_updates_stored_value() {
+        let state = state(vk::COMPARE_OP_LESS, true);
+
+        // Stencil and depth pass: incremented, depth written.
+        let (mut depth, mut stencil) = (0.5, 5);
+        assert!(test(&state, vk::FORMAT_D32_SFLOAT_S8_UINT, true, 0.25, Some(&mut depth), Some(&mut stencil)));
+        assert_eq!((depth, stencil), (0.25, 6));
+
+        // Depth fails: inverted, depth kept.
+        let (mut depth, mut stencil) = (0.5, 5);
+        assert!(!test(&state, vk::FORMAT_D32_SFLOAT_S8_UINT, true, 0.75, Some(&mut depth), Some(&mut stencil)));
+        assert_eq!((depth, stencil), (0.5, !5));
+
+        // Stencil fails: zeroed, depth kept.
+        let (mut depth, mut stencil) = (0.5, 4);
+        assert!(!test(&state, vk::FORMAT_D32_SFLOAT_S8_UINT, true, 0.25, Some(&mut depth), Some(&mut stencil)));
+        assert_eq!((depth, stencil), (0.5, 0));
+
+        // Back faces use their own state.
+        let (mut depth, mut stencil) = (0.5, 5);
+        assert!(!test(&state, vk::FORMAT_D32_SFLOAT_S8_UINT, false, 0.25, Some(&mut depth), Some(&mut stencil)));
+        assert_eq!((depth, stencil), (0.5, 0));
+    }
+
+    #[test]
+    fn stencil_masks() {
+        let mut state = state(vk::COMPARE_OP_ALWAYS, true);
+        state.front.compare_mask = 0x0F;
+        state.front.write_mask = 0xF0;
+        state.front.pass_op = vk::STENCIL_OP_REPLACE;
+        state.front.reference = 0x35;
+
+        // Only the low bits are compared, and only the high bits written.
+        state.front.compare_op = vk::COMPARE_OP_EQUAL;
+        let mut stencil = 0xA5;
+        assert!(test(&state, vk::FORMAT_S8_UINT, true, 0.0, None, Some(&mut stencil)));
+        assert_eq!(stencil, 0x35);
+
+        let mut stencil = 0xA4;
+        assert!(!test(&state, vk::FORMAT_S8_UINT, true, 0.0, None, Some(&mut stencil)));
+        assert_eq!(stencil, 0x04);
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use {std, vk};
+
+use image::ImageView;
+use memory::Buffer;
+use pipeline::slice;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DescriptorSetLayoutBinding {
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    /// Samplers that sampler and combined image sampler descriptors of the
+    /// binding are fixed to, one per descriptor, or empty if there are none.
+    pub immutable_samplers: Vec<vk::Sampler>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DescriptorSetLayout {
+    /// Sorted by binding number, which is the order descriptor updates
+    /// continue across bindings in.
+    pub bindings: Vec<DescriptorSetLayoutBinding>,
+}
+
+impl DescriptorSetLayout {
+    pub unsafe fn new(info: &vk::DescriptorSetLayoutCreateInfo) -> DescriptorSetLayout {
+        let bindings = slice(info.pBindings, info.bindingCount);
+
+        let mut bindings: Vec<DescriptorSetLayoutBinding> = bindings.iter().map(|b| {
+            let samplers = match b.descriptorType {
+                vk::DESCRIPTOR_TYPE_SAMPLER | vk::DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER =>
+                    slice(b.pImmutableSamplers, b.descriptorCount).to_vec(),
+                _ => Vec::new()
+            };
+
+            DescriptorSetLayoutBinding {
+                binding: b.binding,
+                descriptor_type: b.descriptorType,
+                count: b.descriptorCount,
+                stages: b.stageFlags,
+                immutable_samplers: samplers,
+            }
+        }).collect();
+        bindings.sort_by_key(|b| b.binding);
+
+        DescriptorSetLayout {
+            bindings: bindings
+        }
+    }
+
+    pub fn binding(&self, binding: u32) -> Option<&DescriptorSetLayoutBinding> {
+        self.bindings.iter().find(|b| b.binding == binding)
+    }
+
+    /// Index of a dynamic uniform or storage buffer descriptor among the
+    /// dynamic descriptors of the layout. This is the index of its offset
+    /// among the dynamic offsets the set is bound with.
+    pub fn dynamic_index(&self, binding: u32, element: u32) -> Option<usize> {
+        let mut index = 0;
+        for b in &self.bindings {
+            let dynamic = is_dynamic(b.descriptor_type);
+            if b.binding == binding {
+                return if dynamic && element < b.count { Some(index + element as usize) } else { None };
+            }
+            if dynamic {
+                index += b.count as usize;
+            }
+        }
+        None
+    }
+
+    /// Number of dynamic offsets a set with this layout is bound with.
+    pub fn dynamic_count(&self) -> usize {
+        self.bindings.iter()
+            .filter(|b| is_dynamic(b.descriptor_type))
+            .fold(0, |count, b| count + b.count as usize)
+    }
+
+    /// Number of descriptors of each type in a set with this layout.
+    fn descriptor_counts(&self) -> HashMap<vk::DescriptorType, u32> {
+        let mut counts = HashMap::new();
+        for b in &self.bindings {
+            *counts.entry(b.descriptor_type).or_insert(0) += b.count;
+        }
+        counts
+    }
+}
+
+fn is_dynamic(descriptor_type: vk::DescriptorType) -> bool {
+    descriptor_type == vk::DESCRIPTOR_TYPE_UNIFORM_BUFFER_DYNAMIC ||
+        descriptor_type == vk::DESCRIPTOR_TYPE_STORAGE_BUFFER_DYNAMIC
+}
+
+/// Contents of a descriptor. Which variant a descriptor holds follows from
+/// the descriptor type of its binding.
+#[derive(Clone, Copy, Debug)]
+pub enum Descriptor {
+    /// Not written yet.
+    Empty,
+    Sampler(vk::Sampler),
+    /// A sampled image, storage image or input attachment, or a combined
+    /// image sampler when `sampler` isn't null.
+    Image { view: *const ImageView, layout: vk::ImageLayout, sampler: vk::Sampler },
+    TexelBuffer(vk::BufferView),
+    /// A uniform or storage buffer. `range` has `WHOLE_SIZE` resolved.
+    Buffer { buffer: *const Buffer, offset: u64, range: u64 },
+}
+
+pub struct DescriptorSet {
+    pub layout: DescriptorSetLayout,
+    /// Descriptors by binding, in the order of the layout's bindings.
+    descriptors: Vec<Vec<Descriptor>>,
+}
+
+impl DescriptorSet {
+    pub fn new(layout: &DescriptorSetLayout) -> DescriptorSet {
+        DescriptorSet {
+            layout: layout.clone(),
+            descriptors: layout.bindings.iter().map(|b| {
+                if b.immutable_samplers.is_empty() {
+                    vec![Descriptor::Empty; b.count as usize]
+                } else {
+                    b.immutable_samplers.iter().map(|&s| match b.descriptor_type {
+                        vk::DESCRIPTOR_TYPE_SAMPLER => Descriptor::Sampler(s),
+                        _ => Descriptor::Image { view: std::ptr::null(), layout: 0, sampler: s }
+                    }).collect()
+                }
+            }).collect(),
+        }
+    }
+
+    /// Finds a descriptor, continuing into the following bindings when
+    /// `element` is past the end of `binding`, as descriptor updates do.
+    /// Returns the indices of the binding in the layout and of the element.
+    fn locate(&self, binding: u32, mut element: u32) -> Option<(usize, usize)> {
+        let mut index = match self.layout.bindings.iter().position(|b| b.binding == binding) {
+            Some(index) => index,
+            None => return None
+        };
+
+        while element >= self.layout.bindings[index].count {
+            element -= self.layout.bindings[index].count;
+            index += 1;
+            if index >= self.layout.bindings.len() {
+                return None;
+            }
+        }
+
+        Some((index, element as usize))
+    }
+
+    /// Descriptors of a binding, or `None` if the layout doesn't have it.
+    pub fn binding(&self, binding: u32) -> Option<&[Descriptor]> {
+        self.layout.bindings.iter().position(|b| b.binding == binding).map(|i| &self.descriptors[i][..])
+    }
+
+    pub fn descriptor(&self, binding: u32, element: u32) -> Option<Descriptor> {
+        self.locate(binding, element).map(|(b, e)| self.descriptors[b][e])
+    }
+
+    pub unsafe fn write(&mut self, write: &vk::WriteDescriptorSet) {
+        for i in 0..write.descriptorCount {
+            let (b, e) = match self.locate(write.dstBinding, write.dstArrayElement + i) {
+                Some(position) => position,
+                None => {
+                    error!("UpdateDescriptorSets: write past the last binding");
+                    return;
+                }
+            };
+            let immutable_sampler = self.layout.bindings[b].immutable_samplers.get(e).cloned();
+            let i = i as isize;
+
+            let descriptor = match write.descriptorType {
+                vk::DESCRIPTOR_TYPE_SAMPLER => match immutable_sampler {
+                    Some(_) => continue,
+                    None => Descriptor::Sampler((*write.pImageInfo.offset(i)).sampler)
+                },
+                vk::DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER |
+                vk::DESCRIPTOR_TYPE_SAMPLED_IMAGE |
+                vk::DESCRIPTOR_TYPE_STORAGE_IMAGE |
+                vk::DESCRIPTOR_TYPE_INPUT_ATTACHMENT => {
+                    let info = &*write.pImageInfo.offset(i);
+                    let sampler = if write.descriptorType == vk::DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER {
+                        immutable_sampler.unwrap_or(info.sampler)
+                    } else {
+                        0
+                    };
+                    Descriptor::Image {
+                        view: info.imageView as *const ImageView,
+                        layout: info.imageLayout,
+                        sampler: sampler,
+                    }
+                }
+                vk::DESCRIPTOR_TYPE_UNIFORM_TEXEL_BUFFER |
+                vk::DESCRIPTOR_TYPE_STORAGE_TEXEL_BUFFER => {
+                    Descriptor::TexelBuffer(*write.pTexelBufferView.offset(i))
+                }
+                vk::DESCRIPTOR_TYPE_UNIFORM_BUFFER |
+                vk::DESCRIPTOR_TYPE_STORAGE_BUFFER |
+                vk::DESCRIPTOR_TYPE_UNIFORM_BUFFER_DYNAMIC |
+                vk::DESCRIPTOR_TYPE_STORAGE_BUFFER_DYNAMIC => {
+                    let info = &*write.pBufferInfo.offset(i);
+                    let buffer = info.buffer as *const Buffer;
+                    let range = if info.range == vk::WHOLE_SIZE {
+                        (*buffer).size - info.offset
+                    } else {
+                        info.range
+                    };
+                    Descriptor::Buffer { buffer: buffer, offset: info.offset, range: range }
+                }
+                t => {
+                    error!("UpdateDescriptorSets: unknown descriptor type {}", t);
+                    return;
+                }
+            };
+
+            self.descriptors[b][e] = descriptor;
+        }
+    }
+}
+
+/// A descriptor set as bound by `CmdBindDescriptorSets`, with the offsets
+/// of its dynamic buffer descriptors.
+#[derive(Clone, Debug)]
+pub struct BoundDescriptorSet {
+    pub set: *const DescriptorSet,
+    pub dynamic_offsets: Vec<u32>,
+}
+
+impl BoundDescriptorSet {
+    /// Start and size of the memory a uniform or storage buffer descriptor
+    /// refers to, with the dynamic offset applied and clipped to the buffer.
+    pub unsafe fn buffer(&self, binding: u32, element: u32) -> Option<(*mut u8, usize)> {
+        let set = &*self.set;
+        let (buffer, offset, range) = match set.descriptor(binding, element) {
+            Some(Descriptor::Buffer { buffer, offset, range }) => (buffer, offset, range),
+            _ => return None
+        };
+
+        let offset = offset + match set.layout.dynamic_index(binding, element) {
+            Some(i) => self.dynamic_offsets.get(i).cloned().unwrap_or(0) as u64,
+            None => 0
+        };
+        let buffer = &*buffer;
+        if !buffer.is_bound() || offset >= buffer.size {
+            return Some((buffer.ptr(), 0));
+        }
+
+        let size = std::cmp::min(range, buffer.size - offset);
+        Some((buffer.ptr().offset(offset as isize), size as usize))
+    }
+}
+
+/// Copies descriptors between sets, which may be the same set.
+pub unsafe fn copy(copy: &vk::CopyDescriptorSet) {
+    let src = copy.srcSet as *const DescriptorSet;
+    let dst = copy.dstSet as *mut DescriptorSet;
+
+    for i in 0..copy.descriptorCount {
+        let descriptor = match (*src).descriptor(copy.srcBinding, copy.srcArrayElement + i) {
+            Some(descriptor) => descriptor,
+            None => {
+                error!("UpdateDescriptorSets: copy from past the last binding");
+                return;
+            }
+        };
+        let dst = &mut *dst;
+        match dst.locate(copy.dstBinding, copy.dstArrayElement + i) {
+            Some((b, e)) => dst.descriptors[b][e] = descriptor,
+            None => {
+                error!("UpdateDescriptorSets: copy to past the last binding");
+                return;
+            }
+        }
+    }
+}
+
+/// A descriptor pool. Sets are only allocated from the pool's budget of sets
+/// and descriptors of each type; their storage comes from the heap.
+pub struct DescriptorPool {
+    pub flags: vk::DescriptorPoolCreateFlags,
+    pub max_sets: u32,
+    /// Descriptors of each type the pool was created with.
+    pub sizes: HashMap<vk::DescriptorType, u32>,
+    /// Sets allocated and not freed, which the pool owns.
+    sets: Vec<*mut DescriptorSet>,
+    /// Descriptors of each type in allocated sets.
+    used: HashMap<vk::DescriptorType, u32>,
+}
+
+impl DescriptorPool {
+    pub unsafe fn new(info: &vk::DescriptorPoolCreateInfo) -> DescriptorPool {
+        let mut sizes = HashMap::new();
+        for size in slice(info.pPoolSizes, info.poolSizeCount) {
+            *sizes.entry(size.ty).or_insert(0) += size.descriptorCount;
+        }
+
+        DescriptorPool {
+            flags: info.flags,
+            max_sets: info.maxSets,
+            sizes: sizes,
+            sets: Vec::new(),
+            used: HashMap::new(),
+        }
+    }
+
+    /// Allocates a set, failing with `ERROR_OUT_OF_POOL_MEMORY_KHR` if the
+    /// pool has run out of sets or descriptors of a type the layout needs.
+    pub fn allocate(&mut self, layout: &DescriptorSetLayout) -> Result<*mut DescriptorSet, vk::Result> {
+        if self.sets.len() as u32 >= self.max_sets {
+            return Err(vk::ERROR_OUT_OF_POOL_MEMORY_KHR);
+        }
+
+        let counts = layout.descriptor_counts();
+        for (ty, &count) in &counts {
+            let used = self.used.get(ty).cloned().unwrap_or(0);
+            if used + count > self.sizes.get(ty).cloned().unwrap_or(0) {
+                return Err(vk::ERROR_OUT_OF_POOL_MEMORY_KHR);
+            }
+        }
+
+        for (ty, count) in counts {
+            *self.used.entry(ty).or_insert(0) += count;
+        }
+        let set = Box::into_raw(Box::new(DescriptorSet::new(layout)));
+        self.sets.push(set);
+        Ok(set)
+    }
+
+    /// Frees a set and returns its descriptors to the pool. Only valid for
+    /// pools created with `DESCRIPTOR_POOL_CREATE_FREE_DESCRIPTOR_SET_BIT`.
+    pub unsafe fn free(&mut self, set: *mut DescriptorSet) {
+        let index = match self.sets.iter().position(|&s| s == set) {
+            Some(index) => index,
+            None => {
+                error!("FreeDescriptorSets: set wasn't allocated from this pool");
+                return;
+            }
+        };
+        self.sets.swap_remove(index);
+
+        let set = Box::from_raw(set);
+        for (ty, count) in set.layout.descriptor_counts() {
+            *self.used.entry(ty).or_insert(0) -= count;
+        }
+    }
+
+    /// Frees every set allocated from the pool.
+    pub fn reset(&mut self) {
+        for set in self.sets.drain(..) {
+            unsafe { Box::from_raw(set); }
+        }
+        self.used.clear();
+    }
+}
+
+impl Drop for DescriptorPool {
+    fn drop(&mut self) {
+        self.reset();
+    }
+}
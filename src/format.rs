@@ -0,0 +1,711 @@
+use {std, vk};
+
+use half::{f16_to_f32, f32_to_f16};
+use spirv::{bits_to_f32, f32_to_bits};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumericFormat {
+    Unorm,
+    Snorm,
+    Uscaled,
+    Sscaled,
+    Uint,
+    Sint,
+    Ufloat,
+    Sfloat,
+    Srgb,
+}
+
+impl NumericFormat {
+    /// Whether values of this format are seen by shaders as integers.
+    pub fn is_integer(&self) -> bool {
+        match *self {
+            NumericFormat::Uint | NumericFormat::Sint => true,
+            _ => false
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    A,
+    D,
+    S,
+    /// Unused bits.
+    X,
+}
+
+pub type Components = &'static [(Channel, u32)];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Layout {
+    /// Components in order starting from the least significant bit of the
+    /// texel read as a little endian integer.
+    Components(Components),
+    /// B10G11R11_UFLOAT_PACK32.
+    Ufloat11_11_10,
+    /// E5B9G9R9_UFLOAT_PACK32.
+    SharedExponent,
+    Compressed,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FormatInfo {
+    /// Bytes per texel, or per block for compressed formats.
+    pub size: u32,
+    pub block_width: u32,
+    pub block_height: u32,
+    pub numeric: NumericFormat,
+    pub layout: Layout,
+}
+
+impl FormatInfo {
+    pub fn has_channel(&self, channel: Channel) -> bool {
+        match self.layout {
+            Layout::Components(components) => components.iter().any(|c| c.0 == channel),
+            Layout::Ufloat11_11_10 | Layout::SharedExponent => channel != Channel::A && channel != Channel::D && channel != Channel::S,
+            Layout::Compressed => false
+        }
+    }
+
+    pub fn is_color(&self) -> bool {
+        !self.has_channel(Channel::D) && !self.has_channel(Channel::S)
+    }
+}
+
+use self::Channel::*;
+use self::NumericFormat::*;
+
+const EIGHT_BIT: [NumericFormat; 7] = [Unorm, Snorm, Uscaled, Sscaled, Uint, Sint, Srgb];
+const SIXTEEN_BIT: [NumericFormat; 7] = [Unorm, Snorm, Uscaled, Sscaled, Uint, Sint, Sfloat];
+const PACKED_10_BIT: [NumericFormat; 6] = [Unorm, Snorm, Uscaled, Sscaled, Uint, Sint];
+const WIDE: [NumericFormat; 3] = [Uint, Sint, Sfloat];
+
+const R4G4: Components = &[(G, 4), (R, 4)];
+const R4G4B4A4: Components = &[(A, 4), (B, 4), (G, 4), (R, 4)];
+const B4G4R4A4: Components = &[(A, 4), (R, 4), (G, 4), (B, 4)];
+const R5G6B5: Components = &[(B, 5), (G, 6), (R, 5)];
+const B5G6R5: Components = &[(R, 5), (G, 6), (B, 5)];
+const R5G5B5A1: Components = &[(A, 1), (B, 5), (G, 5), (R, 5)];
+const B5G5R5A1: Components = &[(A, 1), (R, 5), (G, 5), (B, 5)];
+const A1R5G5B5: Components = &[(B, 5), (G, 5), (R, 5), (A, 1)];
+const R8: Components = &[(R, 8)];
+const R8G8: Components = &[(R, 8), (G, 8)];
+const R8G8B8: Components = &[(R, 8), (G, 8), (B, 8)];
+const B8G8R8: Components = &[(B, 8), (G, 8), (R, 8)];
+const R8G8B8A8: Components = &[(R, 8), (G, 8), (B, 8), (A, 8)];
+const B8G8R8A8: Components = &[(B, 8), (G, 8), (R, 8), (A, 8)];
+const A2R10G10B10: Components = &[(B, 10), (G, 10), (R, 10), (A, 2)];
+const A2B10G10R10: Components = &[(R, 10), (G, 10), (B, 10), (A, 2)];
+const R16: Components = &[(R, 16)];
+const R16G16: Components = &[(R, 16), (G, 16)];
+const R16G16B16: Components = &[(R, 16), (G, 16), (B, 16)];
+const R16G16B16A16: Components = &[(R, 16), (G, 16), (B, 16), (A, 16)];
+const R32: Components = &[(R, 32)];
+const R32G32: Components = &[(R, 32), (G, 32)];
+const R32G32B32: Components = &[(R, 32), (G, 32), (B, 32)];
+const R32G32B32A32: Components = &[(R, 32), (G, 32), (B, 32), (A, 32)];
+const R64: Components = &[(R, 64)];
+const R64G64: Components = &[(R, 64), (G, 64)];
+const R64G64B64: Components = &[(R, 64), (G, 64), (B, 64)];
+const R64G64B64A64: Components = &[(R, 64), (G, 64), (B, 64), (A, 64)];
+const D16: Components = &[(D, 16)];
+const X8D24: Components = &[(D, 24), (X, 8)];
+const D32: Components = &[(D, 32)];
+const S8: Components = &[(S, 8)];
+const D16S8: Components = &[(D, 16), (S, 8), (X, 8)];
+const D24S8: Components = &[(D, 24), (S, 8)];
+const D32S8: Components = &[(D, 32), (S, 8), (X, 24)];
+
+fn plain(size: u32, numeric: NumericFormat, components: Components) -> FormatInfo {
+    FormatInfo {
+        size: size,
+        block_width: 1,
+        block_height: 1,
+        numeric: numeric,
+        layout: Layout::Components(components),
+    }
+}
+
+fn compressed(size: u32, width: u32, height: u32, numeric: NumericFormat) -> FormatInfo {
+    FormatInfo {
+        size: size,
+        block_width: width,
+        block_height: height,
+        numeric: numeric,
+        layout: Layout::Compressed,
+    }
+}
+
+/// Returns the memory layout of a format, or None for FORMAT_UNDEFINED and
+/// unknown values.
+pub fn info(format: vk::Format) -> Option<FormatInfo> {
+    let f = format;
+
+    Some(match f {
+        vk::FORMAT_R4G4_UNORM_PACK8 => plain(1, Unorm, R4G4),
+        vk::FORMAT_R4G4B4A4_UNORM_PACK16 => plain(2, Unorm, R4G4B4A4),
+        vk::FORMAT_B4G4R4A4_UNORM_PACK16 => plain(2, Unorm, B4G4R4A4),
+        vk::FORMAT_R5G6B5_UNORM_PACK16 => plain(2, Unorm, R5G6B5),
+        vk::FORMAT_B5G6R5_UNORM_PACK16 => plain(2, Unorm, B5G6R5),
+        vk::FORMAT_R5G5B5A1_UNORM_PACK16 => plain(2, Unorm, R5G5B5A1),
+        vk::FORMAT_B5G5R5A1_UNORM_PACK16 => plain(2, Unorm, B5G5R5A1),
+        vk::FORMAT_A1R5G5B5_UNORM_PACK16 => plain(2, Unorm, A1R5G5B5),
+        vk::FORMAT_R8_UNORM...vk::FORMAT_R8_SRGB =>
+            plain(1, EIGHT_BIT[(f - vk::FORMAT_R8_UNORM) as usize], R8),
+        vk::FORMAT_R8G8_UNORM...vk::FORMAT_R8G8_SRGB =>
+            plain(2, EIGHT_BIT[(f - vk::FORMAT_R8G8_UNORM) as usize], R8G8),
+        vk::FORMAT_R8G8B8_UNORM...vk::FORMAT_R8G8B8_SRGB =>
+            plain(3, EIGHT_BIT[(f - vk::FORMAT_R8G8B8_UNORM) as usize], R8G8B8),
+        vk::FORMAT_B8G8R8_UNORM...vk::FORMAT_B8G8R8_SRGB =>
+            plain(3, EIGHT_BIT[(f - vk::FORMAT_B8G8R8_UNORM) as usize], B8G8R8),
+        vk::FORMAT_R8G8B8A8_UNORM...vk::FORMAT_R8G8B8A8_SRGB =>
+            plain(4, EIGHT_BIT[(f - vk::FORMAT_R8G8B8A8_UNORM) as usize], R8G8B8A8),
+        vk::FORMAT_B8G8R8A8_UNORM...vk::FORMAT_B8G8R8A8_SRGB =>
+            plain(4, EIGHT_BIT[(f - vk::FORMAT_B8G8R8A8_UNORM) as usize], B8G8R8A8),
+        // Packed ABGR in a little endian word is the same as RGBA bytes.
+        vk::FORMAT_A8B8G8R8_UNORM_PACK32...vk::FORMAT_A8B8G8R8_SRGB_PACK32 =>
+            plain(4, EIGHT_BIT[(f - vk::FORMAT_A8B8G8R8_UNORM_PACK32) as usize], R8G8B8A8),
+        vk::FORMAT_A2R10G10B10_UNORM_PACK32...vk::FORMAT_A2R10G10B10_SINT_PACK32 =>
+            plain(4, PACKED_10_BIT[(f - vk::FORMAT_A2R10G10B10_UNORM_PACK32) as usize], A2R10G10B10),
+        vk::FORMAT_A2B10G10R10_UNORM_PACK32...vk::FORMAT_A2B10G10R10_SINT_PACK32 =>
+            plain(4, PACKED_10_BIT[(f - vk::FORMAT_A2B10G10R10_UNORM_PACK32) as usize], A2B10G10R10),
+        vk::FORMAT_R16_UNORM...vk::FORMAT_R16_SFLOAT =>
+            plain(2, SIXTEEN_BIT[(f - vk::FORMAT_R16_UNORM) as usize], R16),
+        vk::FORMAT_R16G16_UNORM...vk::FORMAT_R16G16_SFLOAT =>
+            plain(4, SIXTEEN_BIT[(f - vk::FORMAT_R16G16_UNORM) as usize], R16G16),
+        vk::FORMAT_R16G16B16_UNORM...vk::FORMAT_R16G16B16_SFLOAT =>
+            plain(6, SIXTEEN_BIT[(f - vk::FORMAT_R16G16B16_UNORM) as usize], R16G16B16),
+        vk::FORMAT_R16G16B16A16_UNORM...vk::FORMAT_R16G16B16A16_SFLOAT =>
+            plain(8, SIXTEEN_BIT[(f - vk::FORMAT_R16G16B16A16_UNORM) as usize], R16G16B16A16),
+        vk::FORMAT_R32_UINT...vk::FORMAT_R32_SFLOAT =>
+            plain(4, WIDE[(f - vk::FORMAT_R32_UINT) as usize], R32),
+        vk::FORMAT_R32G32_UINT...vk::FORMAT_R32G32_SFLOAT =>
+            plain(8, WIDE[(f - vk::FORMAT_R32G32_UINT) as usize], R32G32),
+        vk::FORMAT_R32G32B32_UINT...vk::FORMAT_R32G32B32_SFLOAT =>
+            plain(12, WIDE[(f - vk::FORMAT_R32G32B32_UINT) as usize], R32G32B32),
+        vk::FORMAT_R32G32B32A32_UINT...vk::FORMAT_R32G32B32A32_SFLOAT =>
+            plain(16, WIDE[(f - vk::FORMAT_R32G32B32A32_UINT) as usize], R32G32B32A32),
+        vk::FORMAT_R64_UINT...vk::FORMAT_R64_SFLOAT =>
+            plain(8, WIDE[(f - vk::FORMAT_R64_UINT) as usize], R64),
+        vk::FORMAT_R64G64_UINT...vk::FORMAT_R64G64_SFLOAT =>
+            plain(16, WIDE[(f - vk::FORMAT_R64G64_UINT) as usize], R64G64),
+        vk::FORMAT_R64G64B64_UINT...vk::FORMAT_R64G64B64_SFLOAT =>
+            plain(24, WIDE[(f - vk::FORMAT_R64G64B64_UINT) as usize], R64G64B64),
+        vk::FORMAT_R64G64B64A64_UINT...vk::FORMAT_R64G64B64A64_SFLOAT =>
+            plain(32, WIDE[(f - vk::FORMAT_R64G64B64A64_UINT) as usize], R64G64B64A64),
+        vk::FORMAT_B10G11R11_UFLOAT_PACK32 => FormatInfo {
+            size: 4, block_width: 1, block_height: 1, numeric: Ufloat, layout: Layout::Ufloat11_11_10
+        },
+        vk::FORMAT_E5B9G9R9_UFLOAT_PACK32 => FormatInfo {
+            size: 4, block_width: 1, block_height: 1, numeric: Ufloat, layout: Layout::SharedExponent
+        },
+        vk::FORMAT_D16_UNORM => plain(2, Unorm, D16),
+        vk::FORMAT_X8_D24_UNORM_PACK32 => plain(4, Unorm, X8D24),
+        vk::FORMAT_D32_SFLOAT => plain(4, Sfloat, D32),
+        vk::FORMAT_S8_UINT => plain(1, Uint, S8),
+        vk::FORMAT_D16_UNORM_S8_UINT => plain(4, Unorm, D16S8),
+        vk::FORMAT_D24_UNORM_S8_UINT => plain(4, Unorm, D24S8),
+        vk::FORMAT_D32_SFLOAT_S8_UINT => plain(8, Sfloat, D32S8),
+        vk::FORMAT_BC1_RGB_UNORM_BLOCK | vk::FORMAT_BC1_RGBA_UNORM_BLOCK => compressed(8, 4, 4, Unorm),
+        vk::FORMAT_BC1_RGB_SRGB_BLOCK | vk::FORMAT_BC1_RGBA_SRGB_BLOCK => compressed(8, 4, 4, Srgb),
+        vk::FORMAT_BC2_UNORM_BLOCK | vk::FORMAT_BC3_UNORM_BLOCK => compressed(16, 4, 4, Unorm),
+        vk::FORMAT_BC2_SRGB_BLOCK | vk::FORMAT_BC3_SRGB_BLOCK => compressed(16, 4, 4, Srgb),
+        vk::FORMAT_BC4_UNORM_BLOCK => compressed(8, 4, 4, Unorm),
+        vk::FORMAT_BC4_SNORM_BLOCK => compressed(8, 4, 4, Snorm),
+        vk::FORMAT_BC5_UNORM_BLOCK => compressed(16, 4, 4, Unorm),
+        vk::FORMAT_BC5_SNORM_BLOCK => compressed(16, 4, 4, Snorm),
+        vk::FORMAT_BC6H_UFLOAT_BLOCK => compressed(16, 4, 4, Ufloat),
+        vk::FORMAT_BC6H_SFLOAT_BLOCK => compressed(16, 4, 4, Sfloat),
+        vk::FORMAT_BC7_UNORM_BLOCK => compressed(16, 4, 4, Unorm),
+        vk::FORMAT_BC7_SRGB_BLOCK => compressed(16, 4, 4, Srgb),
+        vk::FORMAT_ETC2_R8G8B8_UNORM_BLOCK | vk::FORMAT_ETC2_R8G8B8A1_UNORM_BLOCK => compressed(8, 4, 4, Unorm),
+        vk::FORMAT_ETC2_R8G8B8_SRGB_BLOCK | vk::FORMAT_ETC2_R8G8B8A1_SRGB_BLOCK => compressed(8, 4, 4, Srgb),
+        vk::FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK => compressed(16, 4, 4, Unorm),
+        vk::FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK => compressed(16, 4, 4, Srgb),
+        vk::FORMAT_EAC_R11_UNORM_BLOCK => compressed(8, 4, 4, Unorm),
+        vk::FORMAT_EAC_R11_SNORM_BLOCK => compressed(8, 4, 4, Snorm),
+        vk::FORMAT_EAC_R11G11_UNORM_BLOCK => compressed(16, 4, 4, Unorm),
+        vk::FORMAT_EAC_R11G11_SNORM_BLOCK => compressed(16, 4, 4, Snorm),
+        vk::FORMAT_ASTC_4x4_UNORM_BLOCK...vk::FORMAT_ASTC_12x12_SRGB_BLOCK => {
+            const BLOCKS: [(u32, u32); 14] = [
+                (4, 4), (5, 4), (5, 5), (6, 5), (6, 6), (8, 5), (8, 6),
+                (8, 8), (10, 5), (10, 6), (10, 8), (10, 10), (12, 10), (12, 12)
+            ];
+            let i = f - vk::FORMAT_ASTC_4x4_UNORM_BLOCK;
+            let (w, h) = BLOCKS[(i / 2) as usize];
+            compressed(16, w, h, if i % 2 == 0 { Unorm } else { Srgb })
+        }
+        _ => return None
+    })
+}
+
+/// The format that copies between buffers and images use in buffer memory
+/// for one aspect of an image format. Depth and stencil are packed
+/// separately in combined formats: 16-bit depth in 2 bytes, 24-bit depth in
+/// the low bits of 4 bytes, float depth in 4 bytes and stencil in 1 byte.
+pub fn aspect_format(format: vk::Format, aspect: vk::ImageAspectFlags) -> vk::Format {
+    match (format, aspect) {
+        (vk::FORMAT_D16_UNORM_S8_UINT, vk::IMAGE_ASPECT_DEPTH_BIT) => vk::FORMAT_D16_UNORM,
+        (vk::FORMAT_D24_UNORM_S8_UINT, vk::IMAGE_ASPECT_DEPTH_BIT) => vk::FORMAT_X8_D24_UNORM_PACK32,
+        (vk::FORMAT_D32_SFLOAT_S8_UINT, vk::IMAGE_ASPECT_DEPTH_BIT) => vk::FORMAT_D32_SFLOAT,
+        (vk::FORMAT_D16_UNORM_S8_UINT, vk::IMAGE_ASPECT_STENCIL_BIT) |
+        (vk::FORMAT_D24_UNORM_S8_UINT, vk::IMAGE_ASPECT_STENCIL_BIT) |
+        (vk::FORMAT_D32_SFLOAT_S8_UINT, vk::IMAGE_ASPECT_STENCIL_BIT) => vk::FORMAT_S8_UINT,
+        _ => format
+    }
+}
+
+/// Whether a format may be used for vertex attributes. 64-bit formats would
+/// require double precision shader inputs, which are not supported.
+pub fn is_vertex_format(format: vk::Format) -> bool {
+    match info(format) {
+        Some(FormatInfo { layout: Layout::Components(components), .. }) =>
+            components.iter().all(|c| c.1 <= 32 && c.0 != D && c.0 != S),
+        Some(FormatInfo { layout: Layout::Ufloat11_11_10, .. }) => true,
+        _ => false
+    }
+}
+
+fn read_bits(data: &[u8], offset: u32, bits: u32) -> u64 {
+    let mut value = 0u64;
+    for i in 0..bits {
+        let bit = offset + i;
+        if data[(bit / 8) as usize] & (1 << (bit % 8)) != 0 {
+            value |= 1 << i;
+        }
+    }
+    value
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Decodes an unsigned float with a 5 bit exponent, as used by the packed
+/// 11 and 10 bit formats.
+fn ufloat_to_f32(value: u32, mantissa_bits: u32) -> f32 {
+    let exp = value >> mantissa_bits;
+    let mant = value & ((1 << mantissa_bits) - 1);
+    let scale = (1 << mantissa_bits) as f32;
+
+    match exp {
+        0 => (mant as f32 / scale) * (2.0f32).powi(-14),
+        31 => if mant == 0 { std::f32::INFINITY } else { std::f32::NAN },
+        _ => (1.0 + mant as f32 / scale) * (2.0f32).powi(exp as i32 - 15)
+    }
+}
+
+fn decode_component(numeric: NumericFormat, channel: Channel, raw: u64, bits: u32) -> u32 {
+    let max = ((1u64 << (bits - 1)) - 1) as f32 * 2.0 + 1.0;
+    let value = match (numeric, channel) {
+        (_, S) | (Uint, _) => return raw as u32,
+        (Sint, _) => return sign_extend(raw, bits) as u32,
+        (Unorm, _) => raw as f32 / max,
+        (Srgb, A) => raw as f32 / max,
+        (Srgb, _) => srgb_to_linear(raw as f32 / max),
+        (Snorm, _) => {
+            let smax = ((1u64 << (bits - 1)) - 1) as f32;
+            let v = sign_extend(raw, bits) as f32 / smax;
+            if v < -1.0 { -1.0 } else { v }
+        }
+        (Uscaled, _) => raw as f32,
+        (Sscaled, _) => sign_extend(raw, bits) as f32,
+        (Sfloat, _) => match bits {
+            16 => f16_to_f32(raw as u16),
+            32 => bits_to_f32(raw as u32),
+            64 => unsafe { std::mem::transmute::<u64, f64>(raw) as f32 },
+            _ => 0.0
+        },
+        (Ufloat, _) => 0.0
+    };
+    f32_to_bits(value)
+}
+
+/// Decodes one texel into RGBA order as seen by shaders: floats for
+/// normalized, scaled and float formats and integers for integer formats.
+/// Missing components are filled in from (0, 0, 0, 1). Depth is returned in
+/// the first and stencil in the second component.
+pub fn decode(info: &FormatInfo, data: &[u8]) -> [u32; 4] {
+    let one = if info.numeric.is_integer() { 1 } else { f32_to_bits(1.0) };
+    let mut out = [0, 0, 0, one];
+
+    match info.layout {
+        Layout::Components(components) => {
+            let mut offset = 0;
+            for &(channel, bits) in components {
+                let index = match channel {
+                    R | D => 0,
+                    G | S => 1,
+                    B => 2,
+                    A => 3,
+                    X => { offset += bits; continue }
+                };
+                out[index] = decode_component(info.numeric, channel, read_bits(data, offset, bits), bits);
+                offset += bits;
+            }
+        }
+        Layout::Ufloat11_11_10 => {
+            let v = read_bits(data, 0, 32) as u32;
+            out[0] = f32_to_bits(ufloat_to_f32(v & 0x7ff, 6));
+            out[1] = f32_to_bits(ufloat_to_f32((v >> 11) & 0x7ff, 6));
+            out[2] = f32_to_bits(ufloat_to_f32(v >> 22, 5));
+        }
+        Layout::SharedExponent => {
+            let v = read_bits(data, 0, 32) as u32;
+            let scale = (2.0f32).powi((v >> 27) as i32 - 15 - 9);
+            out[0] = f32_to_bits((v & 0x1ff) as f32 * scale);
+            out[1] = f32_to_bits(((v >> 9) & 0x1ff) as f32 * scale);
+            out[2] = f32_to_bits(((v >> 18) & 0x1ff) as f32 * scale);
+        }
+        Layout::Compressed => ()
+    }
+
+    out
+}
+
+fn write_bits(data: &mut [u8], offset: u32, bits: u32, value: u64) {
+    for i in 0..bits {
+        let bit = offset + i;
+        let byte = &mut data[(bit / 8) as usize];
+        if value & (1 << i) != 0 {
+            *byte |= 1 << (bit % 8);
+        } else {
+            *byte &= !(1 << (bit % 8));
+        }
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Encodes an unsigned float with a 5 bit exponent. Values too large for
+/// the format become the largest finite value.
+fn f32_to_ufloat(value: f32, mantissa_bits: u32) -> u32 {
+    let max = (30 << mantissa_bits) | ((1 << mantissa_bits) - 1);
+
+    if value.is_nan() {
+        return (31 << mantissa_bits) | 1;
+    }
+    if value == std::f32::INFINITY {
+        return 31 << mantissa_bits;
+    }
+    if !(value > 0.0) {
+        return 0;
+    }
+
+    let bits = f32_to_bits(value);
+    let exp = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    if exp >= 31 {
+        max
+    } else if exp <= 0 {
+        let denormal = (value * (2.0f32).powi(14) * (1 << mantissa_bits) as f32).round() as u32;
+        std::cmp::min(denormal, max)
+    } else {
+        ((exp as u32) << mantissa_bits) | ((bits & 0x7fffff) >> (23 - mantissa_bits))
+    }
+}
+
+fn encode_shared_exponent(value: &[u32; 4]) -> u32 {
+    const N: i32 = 9;
+    const B: i32 = 15;
+    let max = (((1 << N) - 1) as f32 / (1 << N) as f32) * (2.0f32).powi(31 - B);
+
+    let mut c = [0.0f32; 3];
+    for i in 0..3 {
+        let v = bits_to_f32(value[i]);
+        c[i] = if v > 0.0 { v.min(max) } else { 0.0 };
+    }
+
+    let max_c = c[0].max(c[1]).max(c[2]);
+    let mut exp = std::cmp::max(-B - 1, max_c.log2().floor() as i32) + 1 + B;
+    if (max_c / (2.0f32).powi(exp - B - N) + 0.5).floor() as i32 == 1 << N {
+        exp += 1;
+    }
+
+    let scale = (2.0f32).powi(exp - B - N);
+    let mut out = (exp as u32) << 27;
+    for i in 0..3 {
+        out |= ((c[i] / scale + 0.5).floor() as u32 & 0x1ff) << (9 * i);
+    }
+    out
+}
+
+fn encode_component(numeric: NumericFormat, channel: Channel, value: u32, bits: u32) -> u64 {
+    let mask = if bits == 64 { !0 } else { (1u64 << bits) - 1 };
+    let umax = mask as f32;
+    let smax = ((1u64 << (bits - 1)) - 1) as f32;
+    let f = bits_to_f32(value);
+
+    let raw = match (numeric, channel) {
+        (_, S) | (Uint, _) => value as u64,
+        (Sint, _) => value as i32 as i64 as u64,
+        (Unorm, _) | (Srgb, A) => (f.max(0.0).min(1.0) * umax).round() as u64,
+        (Srgb, _) => (linear_to_srgb(f.max(0.0).min(1.0)) * umax).round() as u64,
+        (Snorm, _) => (f.max(-1.0).min(1.0) * smax).round() as i64 as u64,
+        (Uscaled, _) => f.max(0.0).min(umax).round() as u64,
+        (Sscaled, _) => f.max(-smax - 1.0).min(smax).round() as i64 as u64,
+        (Sfloat, _) => match bits {
+            16 => f32_to_f16(f) as u64,
+            32 => value as u64,
+            64 => unsafe { std::mem::transmute::<f64, u64>(f as f64) },
+            _ => 0
+        },
+        (Ufloat, _) => 0
+    };
+    raw & mask
+}
+
+/// Encodes shader values into a texel, the inverse of `decode`. Only the
+/// channels set in `mask` are written, with bit 0 for red or depth, bit 1
+/// for green or stencil, and so on, like `VkColorComponentFlags`.
+pub fn encode(info: &FormatInfo, value: &[u32; 4], mask: u32, data: &mut [u8]) {
+    match info.layout {
+        Layout::Components(components) => {
+            let mut offset = 0;
+            for &(channel, bits) in components {
+                let index = match channel {
+                    R | D => 0,
+                    G | S => 1,
+                    B => 2,
+                    A => 3,
+                    X => { offset += bits; continue }
+                };
+                if mask & (1 << index) != 0 {
+                    write_bits(data, offset, bits, encode_component(info.numeric, channel, value[index], bits));
+                }
+                offset += bits;
+            }
+        }
+        Layout::Ufloat11_11_10 => {
+            let fields = [(0, 11, 6), (11, 11, 6), (22, 10, 5)];
+            for (i, &(offset, bits, mantissa)) in fields.iter().enumerate() {
+                if mask & (1 << i) != 0 {
+                    write_bits(data, offset, bits, f32_to_ufloat(bits_to_f32(value[i]), mantissa) as u64);
+                }
+            }
+        }
+        Layout::SharedExponent => {
+            // All channels share the exponent, so unwritten ones are
+            // re-encoded with their current value.
+            let mut merged = decode(info, data);
+            for i in 0..3 {
+                if mask & (1 << i) != 0 {
+                    merged[i] = value[i];
+                }
+            }
+            write_bits(data, 0, 32, encode_shared_exponent(&merged) as u64);
+        }
+        Layout::Compressed => ()
+    }
+}
+
+/// Features of images of a format used as attachments. Scaled formats and
+/// 64-bit components can't be written by fragment shaders.
+fn attachment_features(format: vk::Format) -> vk::FormatFeatureFlags {
+    let info = match info(format) {
+        Some(info) => info,
+        None => return 0
+    };
+
+    let color = match info.layout {
+        Layout::Components(_) if !info.is_color() => return vk::FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT,
+        Layout::Components(components) => components.iter().all(|c| c.1 <= 32),
+        Layout::Ufloat11_11_10 => true,
+        Layout::SharedExponent | Layout::Compressed => false
+    };
+
+    match info.numeric {
+        _ if !color => 0,
+        Uscaled | Sscaled => 0,
+        Uint | Sint => vk::FORMAT_FEATURE_COLOR_ATTACHMENT_BIT,
+        _ => vk::FORMAT_FEATURE_COLOR_ATTACHMENT_BIT | vk::FORMAT_FEATURE_COLOR_ATTACHMENT_BLEND_BIT
+    }
+}
+
+/// Whether images of the format can be sampled, and with which filters.
+/// Integer formats can only be read with nearest filtering.
+fn sampled_features(format: vk::Format) -> vk::FormatFeatureFlags {
+    let info = match info(format) {
+        Some(info) => info,
+        None => return 0
+    };
+
+    let sampled = match info.layout {
+        Layout::Components(components) => components.iter().all(|c| c.1 <= 32),
+        Layout::Ufloat11_11_10 | Layout::SharedExponent => true,
+        Layout::Compressed => false
+    };
+
+    match info.numeric {
+        _ if !sampled => 0,
+        Uscaled | Sscaled => 0,
+        Uint | Sint => vk::FORMAT_FEATURE_SAMPLED_IMAGE_BIT,
+        _ => vk::FORMAT_FEATURE_SAMPLED_IMAGE_BIT | vk::FORMAT_FEATURE_SAMPLED_IMAGE_FILTER_LINEAR_BIT
+    }
+}
+
+/// Whether images and texel buffers of the format can be written by
+/// shaders. Only 32-bit integers support atomics.
+fn storage_features(format: vk::Format) -> vk::FormatFeatureFlags {
+    let info = match info(format) {
+        Some(info) => info,
+        None => return 0
+    };
+
+    let storage = match info.layout {
+        Layout::Components(_) if !info.is_color() => false,
+        Layout::Components(components) => components.iter().all(|c| c.1 <= 32),
+        Layout::Ufloat11_11_10 | Layout::SharedExponent => true,
+        Layout::Compressed => false
+    };
+
+    match format {
+        _ if !storage => 0,
+        vk::FORMAT_R32_UINT | vk::FORMAT_R32_SINT =>
+            vk::FORMAT_FEATURE_STORAGE_IMAGE_BIT | vk::FORMAT_FEATURE_STORAGE_IMAGE_ATOMIC_BIT,
+        _ => match info.numeric {
+            Uscaled | Sscaled => 0,
+            _ => vk::FORMAT_FEATURE_STORAGE_IMAGE_BIT
+        }
+    }
+}
+
+/// Features of texel buffers of a format, which are read and written like
+/// images of it.
+fn texel_buffer_features(image_features: vk::FormatFeatureFlags) -> vk::FormatFeatureFlags {
+    let mut features = 0;
+    if image_features & vk::FORMAT_FEATURE_SAMPLED_IMAGE_BIT != 0 {
+        features |= vk::FORMAT_FEATURE_UNIFORM_TEXEL_BUFFER_BIT;
+    }
+    if image_features & vk::FORMAT_FEATURE_STORAGE_IMAGE_BIT != 0 {
+        features |= vk::FORMAT_FEATURE_STORAGE_TEXEL_BUFFER_BIT;
+    }
+    if image_features & vk::FORMAT_FEATURE_STORAGE_IMAGE_ATOMIC_BIT != 0 {
+        features |= vk::FORMAT_FEATURE_STORAGE_TEXEL_BUFFER_ATOMIC_BIT;
+    }
+    features
+}
+
+/// Format features supported for a format. Images have the same layout with
+/// either tiling, so both support the same features.
+pub fn properties(format: vk::Format) -> vk::FormatProperties {
+    let image_features = attachment_features(format) | sampled_features(format) | storage_features(format);
+    let vertex_features = if is_vertex_format(format) { vk::FORMAT_FEATURE_VERTEX_BUFFER_BIT } else { 0 };
+
+    vk::FormatProperties {
+        linearTilingFeatures: image_features,
+        optimalTilingFeatures: image_features,
+        bufferFeatures: vertex_features | texel_buffer_features(image_features),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vk;
+    use spirv::{bits_to_f32, f32_to_bits};
+    use super::*;
+
+    fn round_trip(format: vk::Format, value: [f32; 4]) -> ([u8; 8], [f32; 4]) {
+        let info = info(format).unwrap();
+        let mut data = [0; 8];
+        encode(&info, &[f32_to_bits(value[0]), f32_to_bits(value[1]), f32_to_bits(value[2]),
+                        f32_to_bits(value[3])], !0, &mut data);
+        let decoded = decode(&info, &data);
+        (data, [bits_to_f32(decoded[0]), bits_to_f32(decoded[1]), bits_to_f32(decoded[2]),
+                bits_to_f32(decoded[3])])
+    }
+
+    /// The half float bits a value is stored as in `R16_SFLOAT`.
+    fn half_bits(value: f32) -> u16 {
+        let (data, _) = round_trip(vk::FORMAT_R16_SFLOAT, [value, 0.0, 0.0, 0.0]);
+        data[0] as u16 | (data[1] as u16) << 8
+    }
+
+    fn half_value(bits: u16) -> f32 {
+        let info = info(vk::FORMAT_R16_SFLOAT).unwrap();
+        bits_to_f32(decode(&info, &[bits as u8, (bits >> 8) as u8])[0])
+    }
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(half_bits(1.0), 0x3C00);
+        assert_eq!(half_bits(-2.0), 0xC000);
+        assert_eq!(half_bits(65504.0), 0x7BFF);
+        assert_eq!(half_bits(1.0e6), 0x7C00);
+        assert_eq!(half_bits(-::std::f32::INFINITY), 0xFC00);
+        assert_eq!(half_bits(-0.0), 0x8000);
+        assert_eq!(half_value(0x3555), 0.333251953125);
+        assert_eq!(half_value(0x7BFF), 65504.0);
+        assert_eq!(half_value(0xFC00), -::std::f32::INFINITY);
+        assert!(half_value(half_bits(::std::f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn half_float_rounding() {
+        // Ties round to even.
+        assert_eq!(half_bits(1.0 + 2f32.powi(-11)), 0x3C00);
+        assert_eq!(half_bits(1.0 + 3.0 * 2f32.powi(-11)), 0x3C02);
+        assert_eq!(half_bits(1.0 + 1.5 * 2f32.powi(-11)), 0x3C01);
+        // Rounding up can carry into the exponent.
+        assert_eq!(half_bits(2.0 - 2f32.powi(-12)), 0x4000);
+        assert_eq!(half_bits(65520.0), 0x7C00);
+    }
+
+    #[test]
+    fn half_float_denormals() {
+        assert_eq!(half_bits(2f32.powi(-24)), 0x0001);
+        assert_eq!(half_bits(2f32.powi(-15)), 0x0200);
+        assert_eq!(half_bits(2f32.powi(-25)), 0x0000);
+        assert_eq!(half_bits(1.5 * 2f32.powi(-24)), 0x0002);
+        assert_eq!(half_value(0x0001), 2f32.powi(-24));
+        assert_eq!(half_value(0x03FF), 1023.0 * 2f32.powi(-24));
+        assert_eq!(half_value(0x8200), -2f32.powi(-15));
+    }
+
+    #[test]
+    fn srgb_conversions() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-6);
+        // Linear segment near black.
+        assert_eq!(srgb_to_linear(0.02), 0.02 / 12.92);
+        assert_eq!(linear_to_srgb(0.001), 0.001 * 12.92);
+
+        let (data, _) = round_trip(vk::FORMAT_R8G8B8A8_SRGB, [0.5, 0.2159, 1.0, 0.5]);
+        assert_eq!(data[..4], [188, 128, 255, 128]);
+    }
+
+    #[test]
+    fn srgb_bytes_round_trip() {
+        let info = info(vk::FORMAT_R8G8B8A8_SRGB).unwrap();
+        for byte in 0..256 {
+            let byte = byte as u8;
+            let value = decode(&info, &[byte; 4]);
+            // Alpha is linear.
+            assert_eq!(bits_to_f32(value[3]), byte as f32 / 255.0);
+
+            let mut data = [0; 4];
+            encode(&info, &value, !0, &mut data);
+            assert_eq!(data, [byte; 4]);
+        }
+    }
+}
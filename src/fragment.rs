@@ -0,0 +1,223 @@
+use spirv;
+
+use pipeline::ShaderStage;
+use raster::{Fragment, Weights};
+use spirv::{f32_to_bits, bits_to_f32};
+use vertex::Vertex;
+
+/// Values written by the fragment shader.
+#[derive(Clone, Debug)]
+pub struct Output {
+    /// Color outputs by location.
+    pub colors: Vec<[u32; 4]>,
+    /// Second color output of location 0, for dual-source blending.
+    pub secondary: [u32; 4],
+    /// `FragDepth`, if the shader writes it.
+    pub depth: Option<f32>,
+    /// `SampleMask`, if the shader writes it.
+    pub sample_mask: Option<u32>,
+}
+
+/// Whether the depth and stencil tests run before the fragment shader
+/// instead of after it.
+pub fn early_fragment_tests(stage: &ShaderStage) -> bool {
+    let program = &stage.program;
+    program.module.execution_mode(program.entry_point.function, spirv::EXECUTION_MODE_EARLY_FRAGMENT_TESTS).is_some()
+}
+
+/// Interpolates the words of one input slot. Integer and double inputs are
+/// required to be `Flat`, so everything else is interpolated as 32-bit
+/// floats.
+fn interpolate(vertices: &[&Vertex], weights: &[f32; 3], location: usize, component: usize,
+               count: usize) -> Vec<u32> {
+    let mut words = vec![0; count];
+
+    for (k, vertex) in vertices.iter().enumerate() {
+        let output = match vertex.outputs.get(location) {
+            Some(output) => output,
+            None => continue
+        };
+        for (i, word) in words.iter_mut().enumerate() {
+            if let Some(&w) = output.get(component + i) {
+                *word = f32_to_bits(bits_to_f32(*word) + weights[k] * bits_to_f32(w));
+            }
+        }
+    }
+
+    words
+}
+
+/// Prepares an invocation of the fragment shader with the inputs and
+/// built-ins of a fragment.
+fn invocation<'a>(stage: &'a ShaderStage, resources: &'a spirv::Resources, vertices: &[&Vertex],
+                  fragment: &Fragment) -> spirv::Invocation<'a> {
+    let program = &stage.program;
+    let mut invocation = spirv::Invocation::new(program);
+    invocation.set_resources(resources);
+    invocation.set_fragment(fragment.x, fragment.y, fragment.sample.unwrap_or(0));
+
+    for slot in program.inputs.iter().filter(|s| s.builtin.is_none()) {
+        let variable = match program.entry_point.inputs.iter().find(|v| v.id == slot.variable) {
+            Some(v) => v,
+            None => continue
+        };
+        let location = slot.location as usize;
+        let component = slot.component as usize;
+
+        if variable.flat {
+            let words = vertices[0].outputs.get(location).map(|o| &o[component..]).unwrap_or(&[]);
+            invocation.write_slot(slot, words);
+            continue;
+        }
+
+        // `Sample` inputs require per-sample shading, where the center is
+        // the position of the shaded sample.
+        let weights: &Weights = if variable.centroid { &fragment.centroid } else { &fragment.center };
+        let weights = if variable.noperspective { &weights.linear } else { &weights.perspective };
+
+        let words = interpolate(vertices, weights, location, component, slot.components as usize);
+        invocation.write_slot(slot, &words);
+    }
+
+    // With per-sample shading the fragment is at the position of its
+    // sample, otherwise at the pixel center.
+    invocation.set_builtin(spirv::BUILT_IN_FRAG_COORD, &[
+        f32_to_bits(fragment.x as f32 + fragment.sample_position[0]),
+        f32_to_bits(fragment.y as f32 + fragment.sample_position[1]),
+        f32_to_bits(fragment.depth),
+        f32_to_bits(fragment.inv_w),
+    ]);
+    invocation.set_builtin(spirv::BUILT_IN_FRONT_FACING, &[fragment.front_facing as u32]);
+    invocation.set_builtin(spirv::BUILT_IN_POINT_COORD,
+                           &[f32_to_bits(fragment.point_coord[0]), f32_to_bits(fragment.point_coord[1])]);
+    invocation.set_builtin(spirv::BUILT_IN_SAMPLE_ID, &[fragment.sample.unwrap_or(0)]);
+    invocation.set_builtin(spirv::BUILT_IN_SAMPLE_POSITION,
+                           &[f32_to_bits(fragment.sample_position[0]), f32_to_bits(fragment.sample_position[1])]);
+    invocation.set_builtin(spirv::BUILT_IN_SAMPLE_MASK, &[fragment.coverage]);
+    invocation.set_builtin(spirv::BUILT_IN_HELPER_INVOCATION, &[0]);
+    invocation.enable_derivatives();
+
+    invocation
+}
+
+/// Helper invocations for the other pixels of the quad of a fragment, with
+/// their positions in the quad. Their inputs are interpolated at the same
+/// point inside their pixels as the fragment's.
+fn helpers<'a>(stage: &'a ShaderStage, resources: &'a spirv::Resources, vertices: &[&Vertex],
+               fragment: &Fragment) -> Vec<(usize, spirv::Invocation<'a>)> {
+    let own = quad_index(fragment);
+    (0..4).filter(|&i| i != own).map(|i| {
+        let helper = Fragment {
+            x: (fragment.x & !1) + i as u32 % 2,
+            y: (fragment.y & !1) + i as u32 / 2,
+            center: fragment.quad[i],
+            centroid: fragment.quad[i],
+            ..*fragment
+        };
+        let mut invocation = invocation(stage, resources, vertices, &helper);
+        invocation.set_helper();
+        invocation.set_builtin(spirv::BUILT_IN_HELPER_INVOCATION, &[1]);
+        (i, invocation)
+    }).collect()
+}
+
+/// Position of a fragment in its 2x2 quad, with x varying fastest.
+fn quad_index(fragment: &Fragment) -> usize {
+    (fragment.x % 2 + fragment.y % 2 * 2) as usize
+}
+
+fn difference(a: &[u32], b: &[u32]) -> Vec<u32> {
+    a.iter().zip(b).map(|(&a, &b)| f32_to_bits(bits_to_f32(a) - bits_to_f32(b))).collect()
+}
+
+/// Derivatives along x and y at position `index` of a quad with the given
+/// values. Fine derivatives use the row and column of the position, coarse
+/// ones the first row and column.
+fn quad_derivatives(values: &[Vec<u32>], index: usize, coarse: bool) -> (Vec<u32>, Vec<u32>) {
+    let (row, column) = if coarse { (0, 0) } else { (index / 2, index % 2) };
+    (difference(&values[row * 2 + 1], &values[row * 2]), difference(&values[2 + column], &values[column]))
+}
+
+/// Resumes the waiting invocations of a quad with the derivatives of their
+/// pending values. Helpers that have finished or were killed take on the
+/// value of the fragment's invocation.
+fn resume_quad(invocation: &mut spirv::Invocation, own: usize, helpers: &mut [(usize, spirv::Invocation)]) {
+    let (value, coarse) = match invocation.pending_derivative() {
+        Some((value, coarse)) => (value.to_vec(), coarse),
+        None => return
+    };
+
+    let mut values = vec![value; 4];
+    for &(i, ref helper) in helpers.iter() {
+        if let Some((value, _)) = helper.pending_derivative() {
+            values[i] = value.to_vec();
+        }
+    }
+
+    let (dx, dy) = quad_derivatives(&values, own, coarse);
+    invocation.resume(dx, dy);
+    for &mut (i, ref mut helper) in helpers.iter_mut() {
+        let (dx, dy) = quad_derivatives(&values, i, coarse);
+        helper.resume(dx, dy);
+    }
+}
+
+/// Runs the fragment shader for one fragment of a primitive with the given
+/// vertices, provoking vertex first. Returns `None` if the fragment was
+/// discarded.
+///
+/// Derivatives are taken between the invocations of a 2x2 quad of pixels.
+/// The first time the shader needs one, helper invocations are started for
+/// the other pixels of the quad, whether the primitive covers them or not,
+/// and they run in step with the fragment's invocation from then on.
+pub fn shade(stage: &ShaderStage, resources: &spirv::Resources, vertices: &[&Vertex],
+             fragment: &Fragment) -> Option<Output> {
+    let program = &stage.program;
+    let own = quad_index(fragment);
+    let mut invocation = invocation(stage, resources, vertices, fragment);
+    let mut helpers = Vec::new();
+
+    loop {
+        match invocation.run() {
+            spirv::Status::Killed => return None,
+            spirv::Status::Waiting => (),
+            _ => break
+        }
+
+        if helpers.is_empty() {
+            helpers = self::helpers(stage, resources, vertices, fragment);
+        }
+        for &mut (_, ref mut helper) in helpers.iter_mut() {
+            helper.run();
+        }
+        resume_quad(&mut invocation, own, &mut helpers);
+    }
+
+    let mut colors = Vec::new();
+    let mut secondary = [0; 4];
+    for slot in program.outputs.iter().filter(|s| s.builtin.is_none()) {
+        let location = slot.location as usize;
+        let color = if slot.index == 1 {
+            &mut secondary
+        } else {
+            if colors.len() <= location {
+                colors.resize(location + 1, [0; 4]);
+            }
+            &mut colors[location]
+        };
+
+        let words = invocation.read_slot(slot);
+        for (i, &w) in words.iter().enumerate() {
+            if let Some(c) = color.get_mut(slot.component as usize + i) {
+                *c = w;
+            }
+        }
+    }
+
+    Some(Output {
+        colors: colors,
+        secondary: secondary,
+        depth: invocation.builtin_output(spirv::BUILT_IN_FRAG_DEPTH).map(|w| bits_to_f32(w[0])),
+        sample_mask: invocation.builtin_output(spirv::BUILT_IN_SAMPLE_MASK).map(|w| w[0]),
+    })
+}
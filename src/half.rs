@@ -0,0 +1,64 @@
+use spirv::{bits_to_f32, f32_to_bits};
+
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half as u32) & 0x8000) << 16;
+    let exp = ((half >> 10) & 0x1f) as u32;
+    let mant = (half & 0x3ff) as u32;
+
+    let bits = match (exp, mant) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Denormal half: renormalize into a single precision float.
+            let mut e = 127 - 15 + 1;
+            let mut m = mant;
+            while m & 0x400 == 0 {
+                m <<= 1;
+                e -= 1;
+            }
+            sign | (e << 23) | ((m & 0x3ff) << 13)
+        }
+        (0x1f, 0) => sign | 0x7f800000,
+        (0x1f, _) => sign | 0x7fc00000 | (mant << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (mant << 13)
+    };
+
+    bits_to_f32(bits)
+}
+
+/// Rounds to nearest even. Values too large for a half become infinity.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = f32_to_bits(value);
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7fffff;
+
+    if exp == 0xff {
+        return sign | 0x7c00 | if mant != 0 { 0x200 } else { 0 };
+    }
+
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if e <= 0 {
+        if e < -10 {
+            return sign;
+        }
+        // Denormal half, shift in the implicit leading one.
+        let m = mant | 0x800000;
+        let shift = (14 - e) as u32;
+        let half = m >> shift;
+        let rem = m & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round = if rem > halfway || (rem == halfway && half & 1 != 0) { 1 } else { 0 };
+        return sign | (half + round) as u16;
+    }
+
+    let half = ((e as u32) << 10) | (mant >> 13);
+    let rem = mant & 0x1fff;
+    let round = if rem > 0x1000 || (rem == 0x1000 && half & 1 != 0) { 1 } else { 0 };
+
+    // A carry out of the mantissa correctly bumps the exponent.
+    sign | (half + round) as u16
+}
@@ -0,0 +1,535 @@
+use {std, vk};
+
+use format::{self, FormatInfo};
+use memory::{Buffer, DeviceMemory, ALIGNMENT};
+use multisample;
+use spirv::f32_to_bits;
+
+/// Largest width and height of 1D, 2D and cube images.
+pub const MAX_IMAGE_DIMENSION: u32 = 4096;
+pub const MAX_IMAGE_DIMENSION_3D: u32 = 256;
+pub const MAX_IMAGE_ARRAY_LAYERS: u32 = 256;
+
+/// Sample counts supported for 2D images that can be used as attachments.
+pub const SAMPLE_COUNTS: vk::SampleCountFlags =
+    vk::SAMPLE_COUNT_1_BIT | vk::SAMPLE_COUNT_2_BIT | vk::SAMPLE_COUNT_4_BIT | vk::SAMPLE_COUNT_8_BIT;
+
+/// Images are stored level by level, each level as a sequence of array
+/// layers and each layer as a sequence of depth slices of rows. The samples
+/// of a texel are stored next to each other, so linear and optimal tiling
+/// share the same layout.
+pub struct Image {
+    pub image_type: vk::ImageType,
+    pub format: vk::Format,
+    pub info: FormatInfo,
+    pub extent: [u32; 3],
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub samples: u32,
+    pub tiling: vk::ImageTiling,
+    pub usage: vk::ImageUsageFlags,
+    memory: *mut u8,
+}
+
+impl Image {
+    pub fn new(info: &vk::ImageCreateInfo) -> Result<Image, vk::Result> {
+        let format_info = match format::info(info.format) {
+            Some(i) => i,
+            None => {
+                error!("CreateImage: unsupported format {}", info.format);
+                return Err(vk::ERROR_FORMAT_NOT_SUPPORTED);
+            }
+        };
+
+        Ok(Image {
+            image_type: info.imageType,
+            format: info.format,
+            info: format_info,
+            extent: [info.extent.width, info.extent.height, info.extent.depth],
+            mip_levels: info.mipLevels,
+            array_layers: info.arrayLayers,
+            samples: info.samples,
+            tiling: info.tiling,
+            usage: info.usage,
+            memory: std::ptr::null_mut(),
+        })
+    }
+
+    /// Size of a mip level in texels.
+    pub fn level_extent(&self, level: u32) -> [u32; 3] {
+        [std::cmp::max(self.extent[0] >> level, 1),
+         std::cmp::max(self.extent[1] >> level, 1),
+         std::cmp::max(self.extent[2] >> level, 1)]
+    }
+
+    pub fn subresource_layout(&self, level: u32, layer: u32) -> vk::SubresourceLayout {
+        let mut offset = 0;
+        for l in 0..level {
+            offset += self.layer_size(l) * self.array_layers as u64;
+        }
+
+        let extent = self.level_extent(level);
+        let row_pitch = ((extent[0] + self.info.block_width - 1) / self.info.block_width) as u64
+            * self.info.size as u64 * self.samples as u64;
+        let depth_pitch = row_pitch * ((extent[1] + self.info.block_height - 1) / self.info.block_height) as u64;
+        let array_pitch = depth_pitch * extent[2] as u64;
+
+        vk::SubresourceLayout {
+            offset: offset + array_pitch * layer as u64,
+            size: array_pitch,
+            rowPitch: row_pitch,
+            arrayPitch: array_pitch,
+            depthPitch: depth_pitch,
+        }
+    }
+
+    fn layer_size(&self, level: u32) -> u64 {
+        self.subresource_layout(level, 0).arrayPitch
+    }
+
+    pub fn size(&self) -> u64 {
+        (0..self.mip_levels).fold(0, |size, l| size + self.layer_size(l) * self.array_layers as u64)
+    }
+
+    pub fn memory_requirements(&self) -> vk::MemoryRequirements {
+        vk::MemoryRequirements {
+            size: (self.size() + ALIGNMENT - 1) & !(ALIGNMENT - 1),
+            alignment: ALIGNMENT,
+            memoryTypeBits: 1,
+        }
+    }
+
+    pub fn bind(&mut self, memory: &DeviceMemory, offset: u64) {
+        self.memory = unsafe { memory.ptr().offset(offset as isize) };
+    }
+
+    pub fn is_bound(&self) -> bool {
+        !self.memory.is_null()
+    }
+
+    /// The first byte of one sample of a texel, or of the block containing
+    /// the texel for compressed formats, which is `info.size` bytes long.
+    /// Coordinates must be within the level. As with buffers, the caller is
+    /// responsible for avoiding concurrent access to the same texel.
+    pub unsafe fn texel(&self, level: u32, layer: u32, x: u32, y: u32, z: u32, sample: u32) -> *mut u8 {
+        let layout = self.subresource_layout(level, layer);
+        let size = self.info.size as u64;
+        let offset = layout.offset + z as u64 * layout.depthPitch
+            + (y / self.info.block_height) as u64 * layout.rowPitch
+            + ((x / self.info.block_width) as u64 * self.samples as u64 + sample as u64) * size;
+
+        self.memory.offset(offset as isize)
+    }
+}
+
+/// A view of a range of mip levels and array layers of an image, possibly
+/// with a different but compatible format.
+pub struct ImageView {
+    pub image: *const Image,
+    pub view_type: vk::ImageViewType,
+    pub format: vk::Format,
+    pub info: FormatInfo,
+    pub components: [vk::ComponentSwizzle; 4],
+    pub aspect: vk::ImageAspectFlags,
+    pub base_level: u32,
+    pub level_count: u32,
+    pub base_layer: u32,
+    pub layer_count: u32,
+}
+
+impl ImageView {
+    pub unsafe fn new(info: &vk::ImageViewCreateInfo) -> Result<ImageView, vk::Result> {
+        let image = &*(info.image as *const Image);
+        let format_info = match format::info(info.format) {
+            Some(i) => i,
+            None => {
+                error!("CreateImageView: unsupported format {}", info.format);
+                return Err(vk::ERROR_FORMAT_NOT_SUPPORTED);
+            }
+        };
+
+        let range = &info.subresourceRange;
+        Ok(ImageView {
+            image: image,
+            view_type: info.viewType,
+            format: info.format,
+            info: format_info,
+            components: [info.components.r, info.components.g, info.components.b, info.components.a],
+            aspect: range.aspectMask,
+            base_level: range.baseMipLevel,
+            level_count: if range.levelCount == vk::REMAINING_MIP_LEVELS {
+                image.mip_levels - range.baseMipLevel
+            } else {
+                range.levelCount
+            },
+            base_layer: range.baseArrayLayer,
+            layer_count: if range.layerCount == vk::REMAINING_ARRAY_LAYERS {
+                image.array_layers - range.baseArrayLayer
+            } else {
+                range.layerCount
+            },
+        })
+    }
+
+    pub unsafe fn image(&self) -> &Image {
+        &*self.image
+    }
+
+    /// Size of the first mip level of the view.
+    pub unsafe fn extent(&self) -> [u32; 3] {
+        self.image().level_extent(self.base_level)
+    }
+
+    /// One sample of a texel in the first mip level of the view, as used for
+    /// attachments.
+    pub unsafe fn texel(&self, layer: u32, x: u32, y: u32, sample: u32) -> *mut u8 {
+        self.image().texel(self.base_level, self.base_layer + layer, x, y, 0, sample)
+    }
+
+    /// Number of texel coordinates of the view, and whether an array layer
+    /// follows them, as shaders address texels with integer coordinates.
+    /// Cubes are addressed as arrays of faces.
+    pub fn coordinates(&self) -> (usize, bool) {
+        match self.view_type {
+            vk::IMAGE_VIEW_TYPE_1D => (1, false),
+            vk::IMAGE_VIEW_TYPE_1D_ARRAY => (1, true),
+            vk::IMAGE_VIEW_TYPE_2D => (2, false),
+            vk::IMAGE_VIEW_TYPE_2D_ARRAY | vk::IMAGE_VIEW_TYPE_CUBE | vk::IMAGE_VIEW_TYPE_CUBE_ARRAY => (2, true),
+            _ => (3, false)
+        }
+    }
+
+    /// One sample of the texel at integer `coordinate` in a level of the
+    /// view, with the components of `coordinates`. Returns `None` outside of
+    /// the view.
+    pub unsafe fn texel_at(&self, level: u32, coordinate: &[i32], sample: u32) -> Option<*mut u8> {
+        let image = self.image();
+        let (dims, arrayed) = self.coordinates();
+        if level >= self.level_count || sample >= image.samples || coordinate.len() < dims + arrayed as usize {
+            return None;
+        }
+
+        let extent = image.level_extent(self.base_level + level);
+        let mut texel = [0; 3];
+        for i in 0..dims {
+            if coordinate[i] < 0 || coordinate[i] as u32 >= extent[i] {
+                return None;
+            }
+            texel[i] = coordinate[i] as u32;
+        }
+        let layer = if arrayed { coordinate[dims] } else { 0 };
+        if layer < 0 || layer as u32 >= self.layer_count {
+            return None;
+        }
+
+        Some(image.texel(self.base_level + level, self.base_layer + layer as u32, texel[0], texel[1], texel[2],
+                         sample))
+    }
+
+    /// Whether shaders see texels of the view as integers.
+    pub fn is_integer(&self) -> bool {
+        self.info.numeric.is_integer() || self.aspect == vk::IMAGE_ASPECT_STENCIL_BIT
+    }
+
+    /// Decodes a texel of the view as shaders read it. Depth and stencil are
+    /// read from the red component, as (d, 0, 0, 1) or (s, 0, 0, 1).
+    pub fn decode(&self, data: &[u8]) -> [u32; 4] {
+        let texel = format::decode(&self.info, data);
+        if self.aspect == vk::IMAGE_ASPECT_STENCIL_BIT {
+            [texel[1], 0, 0, 1]
+        } else if !self.info.is_color() {
+            [texel[0], 0, 0, f32_to_bits(1.0)]
+        } else {
+            texel
+        }
+    }
+
+    /// Applies the component swizzle of the view to a decoded texel.
+    pub fn swizzle(&self, value: [u32; 4]) -> [u32; 4] {
+        let one = if self.is_integer() { 1 } else { f32_to_bits(1.0) };
+        let mut swizzled = [0; 4];
+        for (i, &swizzle) in self.components.iter().enumerate() {
+            swizzled[i] = match swizzle {
+                vk::COMPONENT_SWIZZLE_ZERO => 0,
+                vk::COMPONENT_SWIZZLE_ONE => one,
+                vk::COMPONENT_SWIZZLE_R => value[0],
+                vk::COMPONENT_SWIZZLE_G => value[1],
+                vk::COMPONENT_SWIZZLE_B => value[2],
+                vk::COMPONENT_SWIZZLE_A => value[3],
+                _ => value[i]
+            };
+        }
+        swizzled
+    }
+
+    /// The subresource of the first mip level of the view.
+    pub fn subresource(&self, layers: u32) -> Subresource {
+        Subresource {
+            aspect: self.aspect,
+            level: self.base_level,
+            base_layer: self.base_layer,
+            layer_count: layers,
+        }
+    }
+}
+
+/// A range of array layers of one mip level.
+#[derive(Clone, Copy, Debug)]
+pub struct Subresource {
+    pub aspect: vk::ImageAspectFlags,
+    pub level: u32,
+    pub base_layer: u32,
+    pub layer_count: u32,
+}
+
+impl Subresource {
+    pub fn from_vk(s: &vk::ImageSubresourceLayers) -> Subresource {
+        Subresource {
+            aspect: s.aspectMask,
+            level: s.mipLevel,
+            base_layer: s.baseArrayLayer,
+            layer_count: s.layerCount,
+        }
+    }
+}
+
+/// A region of one image and the matching region of another, for copies and
+/// resolves.
+#[derive(Clone, Copy, Debug)]
+pub struct Region {
+    pub src: Subresource,
+    pub src_offset: [i32; 3],
+    pub dst: Subresource,
+    pub dst_offset: [i32; 3],
+    pub extent: [u32; 3],
+}
+
+impl Region {
+    pub fn from_resolve(r: &vk::ImageResolve) -> Region {
+        Region {
+            src: Subresource::from_vk(&r.srcSubresource),
+            src_offset: [r.srcOffset.x, r.srcOffset.y, r.srcOffset.z],
+            dst: Subresource::from_vk(&r.dstSubresource),
+            dst_offset: [r.dstOffset.x, r.dstOffset.y, r.dstOffset.z],
+            extent: [r.extent.width, r.extent.height, r.extent.depth],
+        }
+    }
+}
+
+/// Resolves a region of a multisampled image into a single-sampled one.
+pub unsafe fn resolve(src: &Image, dst: &Image, region: &Region) {
+    for layer in 0..region.src.layer_count {
+        for z in 0..region.extent[2] {
+            for y in 0..region.extent[1] {
+                for x in 0..region.extent[0] {
+                    let (sx, sy, sz) = (region.src_offset[0] as u32 + x, region.src_offset[1] as u32 + y,
+                                        region.src_offset[2] as u32 + z);
+                    let (dx, dy, dz) = (region.dst_offset[0] as u32 + x, region.dst_offset[1] as u32 + y,
+                                        region.dst_offset[2] as u32 + z);
+
+                    let samples: Vec<[u32; 4]> = (0..src.samples).map(|s| {
+                        let texel = src.texel(region.src.level, region.src.base_layer + layer, sx, sy, sz, s);
+                        format::decode(&src.info, std::slice::from_raw_parts(texel, src.info.size as usize))
+                    }).collect();
+                    let value = multisample::resolve(&src.info, &samples);
+
+                    let texel = dst.texel(region.dst.level, region.dst.base_layer + layer, dx, dy, dz, 0);
+                    format::encode(&dst.info, &value, !0,
+                                   std::slice::from_raw_parts_mut(texel, dst.info.size as usize));
+                }
+            }
+        }
+    }
+}
+
+/// A region of an image and the buffer memory it is copied to or from. Row
+/// length and image height give the size of the rows and slices in the
+/// buffer in texels, and are taken from the extent when zero.
+#[derive(Clone, Copy, Debug)]
+pub struct BufferImageCopy {
+    pub buffer_offset: u64,
+    pub row_length: u32,
+    pub image_height: u32,
+    pub subresource: Subresource,
+    pub offset: [i32; 3],
+    pub extent: [u32; 3],
+}
+
+impl BufferImageCopy {
+    pub fn from_vk(r: &vk::BufferImageCopy) -> BufferImageCopy {
+        BufferImageCopy {
+            buffer_offset: r.bufferOffset,
+            row_length: r.bufferRowLength,
+            image_height: r.bufferImageHeight,
+            subresource: Subresource::from_vk(&r.imageSubresource),
+            offset: [r.imageOffset.x, r.imageOffset.y, r.imageOffset.z],
+            extent: [r.imageExtent.width, r.imageExtent.height, r.imageExtent.depth],
+        }
+    }
+}
+
+/// Calls `copy` with the buffer element and the image texel, or block for
+/// compressed formats, of every element of a copy region, after checking
+/// that the region lies within the buffer and the image. Array layers follow
+/// each other in the buffer like depth slices.
+unsafe fn for_each_element<F>(name: &str, buffer: &Buffer, image: &Image, region: &BufferImageCopy, mut copy: F)
+    where F: FnMut(&FormatInfo, &mut [u8], &mut [u8])
+{
+    let subresource = &region.subresource;
+    let info = match subresource.aspect {
+        vk::IMAGE_ASPECT_COLOR_BIT if image.info.is_color() => Some(image.info),
+        vk::IMAGE_ASPECT_DEPTH_BIT if image.info.has_channel(format::Channel::D) => Some(image.info),
+        vk::IMAGE_ASPECT_STENCIL_BIT if image.info.has_channel(format::Channel::S) => Some(image.info),
+        _ => None
+    }.and_then(|_| format::info(format::aspect_format(image.format, subresource.aspect)));
+    let info = match info {
+        Some(info) => info,
+        None => {
+            error!("{}: invalid aspect {:#x} for format {}", name, subresource.aspect, image.format);
+            return;
+        }
+    };
+
+    let (block_width, block_height) = (image.info.block_width, image.info.block_height);
+    let blocks = |texels: u32, block: u32| ((texels + block - 1) / block) as u64;
+    let row_length = if region.row_length == 0 { region.extent[0] } else { region.row_length };
+    let image_height = if region.image_height == 0 { region.extent[1] } else { region.image_height };
+    let (row_pitch, slice_pitch) = (blocks(row_length, block_width),
+                                    blocks(row_length, block_width) * blocks(image_height, block_height));
+    let extent = [blocks(region.extent[0], block_width), blocks(region.extent[1], block_height),
+                  region.extent[2] as u64];
+    let slices = subresource.layer_count as u64 * extent[2];
+    if extent[0] == 0 || extent[1] == 0 || slices == 0 {
+        return;
+    }
+
+    let in_image = subresource.level < image.mip_levels && image.samples == 1
+        && subresource.base_layer as u64 + subresource.layer_count as u64 <= image.array_layers as u64
+        && (0..3).all(|i| {
+            let block = [block_width, block_height, 1][i];
+            let level_extent = image.level_extent(subresource.level)[i] as u64;
+            region.offset[i] >= 0 && region.offset[i] as u32 % block == 0
+                && region.offset[i] as u64 + region.extent[i] as u64 <= level_extent
+        });
+    let end = region.buffer_offset
+        + ((slices - 1) * slice_pitch + (extent[1] - 1) * row_pitch + extent[0]) * info.size as u64;
+    if !buffer.is_bound() || !image.is_bound() {
+        error!("{}: buffer or image not bound to memory", name);
+        return;
+    }
+    if !in_image || row_length < region.extent[0] || image_height < region.extent[1] || end > buffer.size {
+        error!("{}: region {:?} out of bounds", name, region);
+        return;
+    }
+
+    let size = info.size as u64;
+    for layer in 0..subresource.layer_count {
+        for z in 0..extent[2] {
+            let slice = layer as u64 * extent[2] + z;
+            for y in 0..extent[1] {
+                for x in 0..extent[0] {
+                    let offset = (region.buffer_offset + ((slice * slice_pitch + y * row_pitch + x) * size)) as usize;
+                    let texel = image.texel(subresource.level, subresource.base_layer + layer,
+                                            region.offset[0] as u32 + x as u32 * block_width,
+                                            region.offset[1] as u32 + y as u32 * block_height,
+                                            region.offset[2] as u32 + z as u32, 0);
+                    copy(&info, std::slice::from_raw_parts_mut(buffer.ptr().offset(offset as isize), size as usize),
+                         std::slice::from_raw_parts_mut(texel, image.info.size as usize));
+                }
+            }
+        }
+    }
+}
+
+/// Mask of the components `format::encode` writes for an aspect.
+fn aspect_mask(aspect: vk::ImageAspectFlags) -> u32 {
+    match aspect {
+        vk::IMAGE_ASPECT_DEPTH_BIT => 0x1,
+        vk::IMAGE_ASPECT_STENCIL_BIT => 0x2,
+        _ => !0
+    }
+}
+
+/// Copies a region of a buffer into an image. Texels are copied as they are,
+/// except for single aspects of combined depth/stencil formats, which are
+/// converted from their separate packing in the buffer.
+pub unsafe fn copy_buffer_to_image(buffer: &Buffer, image: &Image, region: &BufferImageCopy) {
+    let mask = aspect_mask(region.subresource.aspect);
+    let convert = format::aspect_format(image.format, region.subresource.aspect) != image.format;
+    for_each_element("CopyBufferToImage", buffer, image, region, |info, element, texel| {
+        if !convert {
+            texel.copy_from_slice(element);
+        } else {
+            format::encode(&image.info, &format::decode(info, element), mask, texel);
+        }
+    });
+}
+
+/// Copies a region of an image into a buffer, the reverse of
+/// `copy_buffer_to_image`.
+pub unsafe fn copy_image_to_buffer(image: &Image, buffer: &Buffer, region: &BufferImageCopy) {
+    let convert = format::aspect_format(image.format, region.subresource.aspect) != image.format;
+    for_each_element("CopyImageToBuffer", buffer, image, region, |info, element, texel| {
+        if !convert {
+            element.copy_from_slice(texel);
+        } else {
+            format::encode(info, &format::decode(&image.info, texel), !0, element);
+        }
+    });
+}
+
+/// Properties of images of a format, type, tiling and usage, or `None` if no
+/// such images can be created.
+pub fn format_properties(format: vk::Format, image_type: vk::ImageType, tiling: vk::ImageTiling,
+                         usage: vk::ImageUsageFlags, flags: vk::ImageCreateFlags)
+                         -> Option<vk::ImageFormatProperties> {
+    let properties = format::properties(format);
+    let features = if tiling == vk::IMAGE_TILING_LINEAR {
+        properties.linearTilingFeatures
+    } else {
+        properties.optimalTilingFeatures
+    };
+
+    let required = [
+        (vk::IMAGE_USAGE_SAMPLED_BIT, vk::FORMAT_FEATURE_SAMPLED_IMAGE_BIT),
+        (vk::IMAGE_USAGE_STORAGE_BIT, vk::FORMAT_FEATURE_STORAGE_IMAGE_BIT),
+        (vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT, vk::FORMAT_FEATURE_COLOR_ATTACHMENT_BIT),
+        (vk::IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT, vk::FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT),
+        (vk::IMAGE_USAGE_INPUT_ATTACHMENT_BIT,
+         vk::FORMAT_FEATURE_COLOR_ATTACHMENT_BIT | vk::FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT),
+    ];
+    if features == 0 || required.iter().any(|&(u, f)| usage & u != 0 && features & f == 0) {
+        return None;
+    }
+    if flags & (vk::IMAGE_CREATE_SPARSE_BINDING_BIT | vk::IMAGE_CREATE_SPARSE_RESIDENCY_BIT
+                | vk::IMAGE_CREATE_SPARSE_ALIASED_BIT) != 0 {
+        return None;
+    }
+
+    let max_extent = match image_type {
+        vk::IMAGE_TYPE_1D => [MAX_IMAGE_DIMENSION, 1, 1],
+        vk::IMAGE_TYPE_2D => [MAX_IMAGE_DIMENSION, MAX_IMAGE_DIMENSION, 1],
+        _ => [MAX_IMAGE_DIMENSION_3D; 3],
+    };
+    let max_levels = 32 - std::cmp::max(max_extent[0], std::cmp::max(max_extent[1], max_extent[2])).leading_zeros();
+
+    let attachment = features & (vk::FORMAT_FEATURE_COLOR_ATTACHMENT_BIT
+                                 | vk::FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT) != 0;
+    let multisampled = image_type == vk::IMAGE_TYPE_2D && tiling == vk::IMAGE_TILING_OPTIMAL
+        && attachment && usage & vk::IMAGE_USAGE_STORAGE_BIT == 0
+        && flags & vk::IMAGE_CREATE_CUBE_COMPATIBLE_BIT == 0;
+
+    let linear = tiling == vk::IMAGE_TILING_LINEAR;
+
+    Some(vk::ImageFormatProperties {
+        maxExtent: vk::Extent3D {
+            width: max_extent[0],
+            height: max_extent[1],
+            depth: max_extent[2],
+        },
+        maxMipLevels: if linear { 1 } else { max_levels },
+        maxArrayLayers: if linear || image_type == vk::IMAGE_TYPE_3D { 1 } else { MAX_IMAGE_ARRAY_LAYERS },
+        sampleCounts: if multisampled { SAMPLE_COUNTS } else { vk::SAMPLE_COUNT_1_BIT },
+        maxResourceSize: 1 << 31,
+    })
+}
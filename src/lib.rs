@@ -17,7 +17,64 @@ mod vk {
     pub use self::structs_icd::*;
 }
 
+pub mod spirv {
+    mod consts;
+    mod module;
+    mod reflect;
+    mod specialize;
+    mod glsl;
+    mod interp;
+
+    pub use self::consts::*;
+    pub use self::module::*;
+    pub use self::reflect::*;
+    pub use self::specialize::*;
+    pub use self::interp::*;
+}
+
 pub mod api;
+pub mod half;
+pub mod shader;
+pub mod descriptor;
+pub mod pipeline;
+pub mod format;
+pub mod memory;
+pub mod command;
+pub mod vertex;
+pub mod assembly;
+pub mod raster;
+pub mod tile;
+pub mod fragment;
+pub mod compute;
+pub mod pool;
+pub mod depth;
+pub mod blend;
+pub mod image;
+pub mod sampler;
+pub mod multisample;
+pub mod renderpass;
+
+pub use shader::ShaderModule;
+pub use descriptor::{DescriptorSetLayout, DescriptorPool, DescriptorSet};
+pub use pipeline::{PipelineLayout, Pipeline, GraphicsPipeline, ComputePipeline};
+pub use memory::{DeviceMemory, Buffer, BufferView};
+pub use image::{Image, ImageView};
+pub use sampler::Sampler;
+pub use renderpass::{RenderPass, Framebuffer};
+pub use command::CommandBuffer;
+
+/// Environment variable that enables validation of API usage that is too
+/// costly to check on every call.
+pub const VALIDATION_VAR: &'static str = "SOFTVK_VALIDATION";
+
+/// Whether costly validation is enabled, by setting `VALIDATION_VAR` to
+/// anything but `0`.
+pub fn validation_enabled() -> bool {
+    match std::env::var(VALIDATION_VAR) {
+        Ok(value) => value != "0",
+        Err(_) => false
+    }
+}
 
 pub struct Dispatched<T> {
     magic: usize,
@@ -53,12 +110,18 @@ impl<T> std::ops::DerefMut for Dispatched<T> {
 
 pub struct Queue;
 
+impl Queue {
+    pub unsafe fn submit(&self, command_buffers: &[&CommandBuffer]) {
+        for buffer in command_buffers {
+            buffer.execute();
+        }
+    }
+}
+
 pub struct Device;
 
 pub struct Swapchain;
 
-pub struct CommandBuffer;
-
 impl Device {
     pub fn create_queue(&self, family: u32, id: u32) -> Option<Box<Dispatched<Queue>>> {
         Some(Box::new(Dispatched::new(Queue)))
@@ -69,7 +132,17 @@ impl Device {
     }
 
     pub fn create_command_buffer(&self) -> Box<Dispatched<CommandBuffer>> {
-        Box::new(Dispatched::new(CommandBuffer))
+        Box::new(Dispatched::new(CommandBuffer::new()))
+    }
+
+    pub fn create_shader_module(&self, code: &[u32]) -> Result<Box<ShaderModule>, vk::Result> {
+        match ShaderModule::new(code) {
+            Ok(module) => Ok(Box::new(module)),
+            Err(e) => {
+                error!("create_shader_module: invalid SPIR-V: {:?}", e);
+                Err(vk::ERROR_INITIALIZATION_FAILED)
+            }
+        }
     }
 }
 
@@ -83,7 +156,7 @@ impl PhysicalDevice {
     pub fn queue_families(&self) -> Vec<vk::QueueFamilyProperties> {
         vec![
             vk::QueueFamilyProperties {
-                queueFlags: vk::QUEUE_GRAPHICS_BIT,
+                queueFlags: vk::QUEUE_GRAPHICS_BIT | vk::QUEUE_COMPUTE_BIT,
                 queueCount: 1,
                 timestampValidBits: 0,
                 minImageTransferGranularity: vk::Extent3D {
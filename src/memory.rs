@@ -0,0 +1,375 @@
+use {std, vk};
+
+use format::{self, FormatInfo, Layout};
+
+/// Alignment of every allocation, large enough for any texel or uniform
+/// buffer offset the device reports.
+pub const ALIGNMENT: u64 = 256;
+
+/// Alignment of the offsets of texel and storage buffers, so that the words
+/// shaders access atomically are aligned.
+pub const STORAGE_ALIGNMENT: u64 = 16;
+
+/// Largest number of texels in a buffer view.
+pub const MAX_TEXEL_BUFFER_ELEMENTS: u32 = 1 << 27;
+
+/// Largest amount of data that `CmdUpdateBuffer` accepts.
+pub const MAX_UPDATE_SIZE: u64 = 65536;
+
+/// All memory is host memory, so there is a single heap with a single type
+/// that supports everything.
+pub fn properties() -> vk::PhysicalDeviceMemoryProperties {
+    let mut props: vk::PhysicalDeviceMemoryProperties = unsafe { std::mem::zeroed() };
+
+    props.memoryTypeCount = 1;
+    props.memoryTypes[0].propertyFlags = vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT |
+        vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT |
+        vk::MEMORY_PROPERTY_HOST_CACHED_BIT;
+    props.memoryTypes[0].heapIndex = 0;
+    props.memoryHeapCount = 1;
+    props.memoryHeaps[0].size = 1 << 31;
+    props.memoryHeaps[0].flags = vk::MEMORY_HEAP_DEVICE_LOCAL_BIT;
+
+    props
+}
+
+/// Memory is a zeroed vector with room to align the start of the
+/// allocation to `ALIGNMENT`, so that reads of uninitialized memory are at
+/// least deterministic.
+pub struct DeviceMemory {
+    ptr: *mut u8,
+    size: usize,
+    #[allow(dead_code)]
+    data: Vec<u8>,
+}
+
+impl DeviceMemory {
+    pub fn new(info: &vk::MemoryAllocateInfo) -> Result<DeviceMemory, vk::Result> {
+        if info.memoryTypeIndex != 0 {
+            error!("DeviceMemory: invalid memory type {}", info.memoryTypeIndex);
+            return Err(vk::ERROR_OUT_OF_DEVICE_MEMORY);
+        }
+
+        let size = info.allocationSize as usize;
+        let mut data = match size.checked_add(ALIGNMENT as usize) {
+            Some(capacity) => vec![0u8; capacity],
+            None => return Err(vk::ERROR_OUT_OF_DEVICE_MEMORY)
+        };
+        let misalignment = data.as_ptr() as usize % ALIGNMENT as usize;
+        let offset = (ALIGNMENT as usize - misalignment) % ALIGNMENT as usize;
+        let ptr = unsafe { data.as_mut_ptr().offset(offset as isize) };
+
+        Ok(DeviceMemory {
+            ptr: ptr,
+            size: size,
+            data: data,
+        })
+    }
+
+    pub fn ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+pub struct Buffer {
+    pub size: u64,
+    pub usage: vk::BufferUsageFlags,
+    memory: *mut u8,
+}
+
+impl Buffer {
+    pub fn new(info: &vk::BufferCreateInfo) -> Buffer {
+        Buffer {
+            size: info.size,
+            usage: info.usage,
+            memory: std::ptr::null_mut(),
+        }
+    }
+
+    pub fn memory_requirements(&self) -> vk::MemoryRequirements {
+        vk::MemoryRequirements {
+            size: (self.size + ALIGNMENT - 1) & !(ALIGNMENT - 1),
+            alignment: ALIGNMENT,
+            memoryTypeBits: 1,
+        }
+    }
+
+    pub fn bind(&mut self, memory: &DeviceMemory, offset: u64) {
+        self.memory = unsafe { memory.ptr().offset(offset as isize) };
+    }
+
+    pub fn is_bound(&self) -> bool {
+        !self.memory.is_null()
+    }
+
+    /// Start of the memory bound to the buffer, or null if it isn't bound.
+    /// Unlike `data` this doesn't claim access to the bytes, so it is used
+    /// for writes and when two ranges of the same memory are accessed.
+    pub fn ptr(&self) -> *mut u8 {
+        self.memory
+    }
+
+    /// Contents of the buffer. The caller must make sure that there are no
+    /// concurrent writes to the bytes it reads, as with any memory shared
+    /// with the application.
+    pub unsafe fn data(&self) -> &[u8] {
+        if self.memory.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(self.memory, self.size as usize)
+        }
+    }
+}
+
+/// A region of a buffer to buffer copy.
+#[derive(Clone, Copy, Debug)]
+pub struct BufferCopy {
+    pub src_offset: u64,
+    pub dst_offset: u64,
+    pub size: u64,
+}
+
+impl BufferCopy {
+    pub fn from_vk(c: &vk::BufferCopy) -> BufferCopy {
+        BufferCopy {
+            src_offset: c.srcOffset,
+            dst_offset: c.dstOffset,
+            size: c.size,
+        }
+    }
+}
+
+/// Whether `size` bytes at `offset` lie within the buffer.
+fn in_bounds(buffer: &Buffer, offset: u64, size: u64) -> bool {
+    offset <= buffer.size && size <= buffer.size - offset
+}
+
+/// Whether the source ranges of any of the regions overlap in memory with
+/// the destination ranges of any of them. Buffers bound to the same memory
+/// can overlap even if they are different objects.
+pub unsafe fn regions_overlap(src: &Buffer, dst: &Buffer, regions: &[BufferCopy]) -> bool {
+    let src_base = src.ptr() as u64;
+    let dst_base = dst.ptr() as u64;
+
+    regions.iter().any(|a| regions.iter().any(|b| {
+        let src_start = src_base + a.src_offset;
+        let dst_start = dst_base + b.dst_offset;
+        src_start < dst_start + b.size && dst_start < src_start + a.size
+    }))
+}
+
+/// Copies a region from one buffer to another. Overlapping ranges are
+/// invalid, but are copied as if through a temporary buffer. The source and
+/// destination may be the same buffer, so only raw pointers are used.
+pub unsafe fn copy_buffer(src: &Buffer, dst: &Buffer, region: &BufferCopy) {
+    if !src.is_bound() || !dst.is_bound() {
+        error!("CopyBuffer: buffer not bound to memory");
+        return;
+    }
+    if !in_bounds(src, region.src_offset, region.size) || !in_bounds(dst, region.dst_offset, region.size) {
+        error!("CopyBuffer: region {:?} out of bounds", region);
+        return;
+    }
+
+    std::ptr::copy(src.ptr().offset(region.src_offset as isize) as *const u8,
+                   dst.ptr().offset(region.dst_offset as isize),
+                   region.size as usize);
+}
+
+/// Writes `data` to a buffer at `offset`.
+pub unsafe fn update_buffer(dst: &Buffer, offset: u64, data: &[u8]) {
+    if !dst.is_bound() {
+        error!("UpdateBuffer: buffer not bound to memory");
+        return;
+    }
+    if !in_bounds(dst, offset, data.len() as u64) {
+        error!("UpdateBuffer: {} bytes at offset {} out of bounds", data.len(), offset);
+        return;
+    }
+
+    std::ptr::copy_nonoverlapping(data.as_ptr(), dst.ptr().offset(offset as isize), data.len());
+}
+
+/// Fills `size` bytes of a buffer at `offset` with copies of a word. A size
+/// of `WHOLE_SIZE` fills up to the last whole word of the buffer.
+pub unsafe fn fill_buffer(dst: &Buffer, offset: u64, size: u64, word: u32) {
+    let size = if size == vk::WHOLE_SIZE {
+        let size = dst.size.saturating_sub(offset);
+        size - size % 4
+    } else {
+        size
+    };
+    if !dst.is_bound() {
+        error!("FillBuffer: buffer not bound to memory");
+        return;
+    }
+    if !in_bounds(dst, offset, size) {
+        error!("FillBuffer: {} bytes at offset {} out of bounds", size, offset);
+        return;
+    }
+
+    let bytes = [word as u8, (word >> 8) as u8, (word >> 16) as u8, (word >> 24) as u8];
+    let start = dst.ptr().offset(offset as isize);
+    for i in 0..size as usize {
+        *start.offset(i as isize) = bytes[i % 4];
+    }
+}
+
+/// A range of a buffer seen as an array of texels of a format.
+pub struct BufferView {
+    pub buffer: *const Buffer,
+    pub format: vk::Format,
+    pub info: FormatInfo,
+    pub offset: u64,
+    /// Size of the view in bytes, with `WHOLE_SIZE` resolved.
+    pub range: u64,
+}
+
+impl BufferView {
+    pub unsafe fn new(info: &vk::BufferViewCreateInfo) -> Result<BufferView, vk::Result> {
+        let buffer = &*(info.buffer as *const Buffer);
+        let format_info = match format::info(info.format) {
+            Some(FormatInfo { layout: Layout::Compressed, .. }) | None => {
+                error!("CreateBufferView: unsupported format {}", info.format);
+                return Err(vk::ERROR_FORMAT_NOT_SUPPORTED);
+            }
+            Some(i) => i
+        };
+
+        // A whole size view ends at the last texel that fits in the buffer.
+        let range = if info.range == vk::WHOLE_SIZE {
+            let size = buffer.size.saturating_sub(info.offset);
+            size - size % format_info.size as u64
+        } else {
+            info.range
+        };
+
+        Ok(BufferView {
+            buffer: buffer,
+            format: info.format,
+            info: format_info,
+            offset: info.offset,
+            range: range,
+        })
+    }
+
+    /// Number of texels in the view.
+    pub fn len(&self) -> u32 {
+        std::cmp::min(self.range / self.info.size as u64, MAX_TEXEL_BUFFER_ELEMENTS as u64) as u32
+    }
+
+    /// The first byte of a texel, or `None` if the texel is outside of the
+    /// view or the buffer. Like `Buffer::ptr` this doesn't claim access to
+    /// the texel, which other invocations may access at the same time.
+    pub unsafe fn texel(&self, index: u32) -> Option<*mut u8> {
+        let buffer = &*self.buffer;
+        let size = self.info.size as u64;
+        let offset = self.offset + index as u64 * size;
+        if index >= self.len() || !buffer.is_bound() || offset + size > buffer.size {
+            return None;
+        }
+        Some(buffer.ptr().offset(offset as isize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {std, vk};
+    use super::*;
+
+    fn memory(size: u64) -> DeviceMemory {
+        DeviceMemory::new(&vk::MemoryAllocateInfo {
+            sType: vk::STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
+            pNext: std::ptr::null(),
+            allocationSize: size,
+            memoryTypeIndex: 0,
+        }).unwrap()
+    }
+
+    fn buffer(memory: &DeviceMemory, offset: u64, size: u64) -> Buffer {
+        let mut buffer = Buffer::new(&vk::BufferCreateInfo {
+            sType: vk::STRUCTURE_TYPE_BUFFER_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            size: size,
+            usage: vk::BUFFER_USAGE_TRANSFER_SRC_BIT | vk::BUFFER_USAGE_TRANSFER_DST_BIT,
+            sharingMode: vk::SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null(),
+        });
+        buffer.bind(memory, offset);
+        buffer
+    }
+
+    fn region(src_offset: u64, dst_offset: u64, size: u64) -> BufferCopy {
+        BufferCopy { src_offset: src_offset, dst_offset: dst_offset, size: size }
+    }
+
+    #[test]
+    fn bounds() {
+        let memory = memory(64);
+        let buffer = buffer(&memory, 0, 64);
+        assert!(in_bounds(&buffer, 0, 64));
+        assert!(in_bounds(&buffer, 60, 4));
+        assert!(in_bounds(&buffer, 64, 0));
+        assert!(!in_bounds(&buffer, 61, 4));
+        assert!(!in_bounds(&buffer, 65, 0));
+        assert!(!in_bounds(&buffer, 8, !0 - 4));
+    }
+
+    #[test]
+    fn overlap_within_a_buffer() {
+        let memory = memory(64);
+        let buffer = buffer(&memory, 0, 64);
+        unsafe {
+            assert!(!regions_overlap(&buffer, &buffer, &[region(0, 16, 16)]));
+            assert!(!regions_overlap(&buffer, &buffer, &[region(16, 0, 16)]));
+            assert!(regions_overlap(&buffer, &buffer, &[region(0, 15, 16)]));
+            assert!(regions_overlap(&buffer, &buffer, &[region(8, 8, 1)]));
+            // The source of one region against the destination of another.
+            assert!(!regions_overlap(&buffer, &buffer, &[region(0, 32, 8), region(16, 48, 8)]));
+            assert!(regions_overlap(&buffer, &buffer, &[region(0, 32, 8), region(36, 48, 8)]));
+        }
+    }
+
+    #[test]
+    fn overlap_between_buffers() {
+        let first = memory(128);
+        let second = memory(128);
+        let a = buffer(&first, 0, 64);
+        let b = buffer(&first, 32, 64);
+        let c = buffer(&second, 0, 64);
+        unsafe {
+            // b starts 32 bytes into a.
+            assert!(regions_overlap(&a, &b, &[region(32, 0, 8)]));
+            assert!(regions_overlap(&b, &a, &[region(0, 32, 8)]));
+            assert!(!regions_overlap(&a, &b, &[region(0, 0, 32)]));
+            assert!(!regions_overlap(&a, &c, &[region(0, 0, 64)]));
+        }
+    }
+
+    #[test]
+    fn copies() {
+        let memory = memory(64);
+        let buffer = buffer(&memory, 0, 64);
+        unsafe {
+            for i in 0..64 {
+                *buffer.ptr().offset(i) = i as u8;
+            }
+
+            // Overlapping ranges copy as if through a temporary.
+            copy_buffer(&buffer, &buffer, &region(0, 4, 8));
+            assert_eq!(&buffer.data()[..14], &[0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 6, 7, 12, 13]);
+
+            // Out of bounds regions are skipped.
+            copy_buffer(&buffer, &buffer, &region(60, 0, 8));
+            assert_eq!(buffer.data()[0], 0);
+            copy_buffer(&buffer, &buffer, &region(0, 60, 8));
+            assert_eq!(&buffer.data()[60..], &[60, 61, 62, 63]);
+        }
+    }
+}
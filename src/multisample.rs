@@ -0,0 +1,98 @@
+use spirv;
+
+use format::FormatInfo;
+use fragment::Output;
+use pipeline::{MultisampleState, ShaderStage};
+use spirv::{bits_to_f32, f32_to_bits};
+
+/// Largest supported sample count.
+pub const MAX_SAMPLES: u32 = 8;
+
+const POSITIONS_1: [[f32; 2]; 1] = [[0.5, 0.5]];
+const POSITIONS_2: [[f32; 2]; 2] = [[0.75, 0.75], [0.25, 0.25]];
+const POSITIONS_4: [[f32; 2]; 4] = [[0.375, 0.125], [0.875, 0.375], [0.125, 0.625], [0.625, 0.875]];
+const POSITIONS_8: [[f32; 2]; 8] = [
+    [0.5625, 0.3125], [0.4375, 0.6875], [0.8125, 0.5625], [0.3125, 0.1875],
+    [0.1875, 0.8125], [0.0625, 0.4375], [0.6875, 0.9375], [0.9375, 0.0625],
+];
+
+/// The standard sample locations inside a pixel for a sample count.
+pub fn positions(samples: u32) -> &'static [[f32; 2]] {
+    match samples {
+        2 => &POSITIONS_2,
+        4 => &POSITIONS_4,
+        8 => &POSITIONS_8,
+        _ => &POSITIONS_1,
+    }
+}
+
+/// Coverage mask with every sample of a pixel set.
+pub fn all_samples(samples: u32) -> u32 {
+    (1 << samples) - 1
+}
+
+/// Whether the fragment shader runs once per covered sample rather than once
+/// per pixel. This is the case when `minSampleShading` asks for more than one
+/// shaded sample, or when the shader reads per-sample values.
+pub fn per_sample_shading(state: &MultisampleState, stage: &ShaderStage) -> bool {
+    if state.samples <= 1 {
+        return false;
+    }
+    if state.sample_shading && state.min_sample_shading * state.samples as f32 > 1.0 {
+        return true;
+    }
+
+    stage.program.entry_point.inputs.iter().any(|v| {
+        v.sample || v.builtin == Some(spirv::BUILT_IN_SAMPLE_ID) || v.builtin == Some(spirv::BUILT_IN_SAMPLE_POSITION)
+    })
+}
+
+/// Coverage with the first `round(alpha * samples)` samples set. With
+/// alpha-to-coverage, this is combined with the rasterized coverage.
+fn alpha_coverage(alpha: f32, samples: u32) -> u32 {
+    let count = (alpha.max(0.0).min(1.0) * samples as f32).round() as u32;
+    all_samples(count)
+}
+
+/// The samples a shaded fragment writes to: the rasterized coverage limited
+/// by the pipeline sample mask, the `SampleMask` output and
+/// alpha-to-coverage.
+pub fn coverage(state: &MultisampleState, rasterized: u32, output: &Output) -> u32 {
+    let mut coverage = rasterized & state.sample_mask.get(0).cloned().unwrap_or(!0);
+
+    if let Some(mask) = output.sample_mask {
+        coverage &= mask;
+    }
+    if state.alpha_to_coverage {
+        if let Some(color) = output.colors.get(0) {
+            coverage &= alpha_coverage(bits_to_f32(color[3]), state.samples);
+        }
+    }
+
+    coverage
+}
+
+/// Replaces the alpha of every color output with one, after coverage has
+/// been computed.
+pub fn alpha_to_one(state: &MultisampleState, output: &mut Output) {
+    if state.alpha_to_one {
+        for color in &mut output.colors {
+            color[3] = f32_to_bits(1.0);
+        }
+    }
+}
+
+/// Combines decoded samples of a texel into a single value: the average for
+/// normalized and float formats and the first sample for integer formats.
+pub fn resolve(info: &FormatInfo, samples: &[[u32; 4]]) -> [u32; 4] {
+    if info.numeric.is_integer() || samples.len() <= 1 {
+        return samples.get(0).cloned().unwrap_or([0; 4]);
+    }
+
+    let mut value = [0; 4];
+    for i in 0..4 {
+        let sum = samples.iter().fold(0.0, |sum, s| sum + bits_to_f32(s[i]));
+        value[i] = f32_to_bits(sum / samples.len() as f32);
+    }
+    value
+}
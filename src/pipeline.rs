@@ -0,0 +1,854 @@
+use std::collections::HashMap;
+
+use {std, vk, spirv};
+
+use descriptor::DescriptorSetLayout;
+use renderpass::RenderPass;
+use shader::ShaderModule;
+
+/// Size of push constant storage in bytes, as reported in the device limits.
+pub const MAX_PUSH_CONSTANTS_SIZE: u32 = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PushConstantRange {
+    pub stages: vk::ShaderStageFlags,
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Debug)]
+pub enum LayoutError {
+    MissingSet { set: u32, binding: u32 },
+    MissingBinding { set: u32, binding: u32 },
+    DescriptorTypeMismatch { set: u32, binding: u32, shader: vk::DescriptorType, layout: vk::DescriptorType },
+    DescriptorCountTooSmall { set: u32, binding: u32, shader: u32, layout: u32 },
+    StageNotEnabled { set: u32, binding: u32 },
+    PushConstantsNotCovered { offset: u32, size: u32 },
+}
+
+#[derive(Clone)]
+pub struct PipelineLayout {
+    pub set_layouts: Vec<DescriptorSetLayout>,
+    pub push_constant_ranges: Vec<PushConstantRange>,
+}
+
+impl PipelineLayout {
+    pub unsafe fn new(info: &vk::PipelineLayoutCreateInfo) -> PipelineLayout {
+        let set_layouts = slice(info.pSetLayouts, info.setLayoutCount);
+        let ranges = slice(info.pPushConstantRanges, info.pushConstantRangeCount);
+
+        PipelineLayout {
+            set_layouts: set_layouts.iter()
+                .map(|&l| (*(l as *const DescriptorSetLayout)).clone())
+                .collect(),
+            push_constant_ranges: ranges.iter().map(|r| PushConstantRange {
+                stages: r.stageFlags,
+                offset: r.offset,
+                size: r.size,
+            }).collect()
+        }
+    }
+
+    /// Whether descriptor sets bound with this layout can be used with
+    /// pipelines of another layout at set number `set`: both have the same
+    /// push constant ranges and identically defined set layouts up to it.
+    pub fn is_compatible(&self, other: &PipelineLayout, set: usize) -> bool {
+        self.push_constant_ranges == other.push_constant_ranges &&
+            set < self.set_layouts.len() && set < other.set_layouts.len() &&
+            self.set_layouts[..set + 1] == other.set_layouts[..set + 1]
+    }
+
+    /// Checks that every descriptor and push constant statically used by
+    /// an entry point is provided by this layout for the entry point's stage.
+    pub fn check_entry_point(&self, entry: &spirv::EntryPoint) -> Result<(), LayoutError> {
+        for d in &entry.descriptors {
+            let (set, binding) = (d.set, d.binding);
+
+            let layout = match self.set_layouts.get(set as usize) {
+                Some(layout) => layout,
+                None => return Err(LayoutError::MissingSet { set: set, binding: binding })
+            };
+
+            let b = match layout.binding(binding) {
+                Some(b) => b,
+                None => return Err(LayoutError::MissingBinding { set: set, binding: binding })
+            };
+
+            if !descriptor_type_compatible(d.descriptor_type, b.descriptor_type) {
+                return Err(LayoutError::DescriptorTypeMismatch {
+                    set: set, binding: binding, shader: d.descriptor_type, layout: b.descriptor_type
+                });
+            }
+
+            if b.count < std::cmp::max(d.count, 1) {
+                return Err(LayoutError::DescriptorCountTooSmall {
+                    set: set, binding: binding, shader: d.count, layout: b.count
+                });
+            }
+
+            if b.stages & entry.stage == 0 {
+                return Err(LayoutError::StageNotEnabled { set: set, binding: binding });
+            }
+        }
+
+        if let Some(ref block) = entry.push_constants {
+            for m in &block.members {
+                if !self.push_constants_cover(entry.stage, m.offset, m.size) {
+                    return Err(LayoutError::PushConstantsNotCovered { offset: m.offset, size: m.size });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `CmdPushConstants` may update bytes `offset..offset + size`
+    /// for `stages`: each of the stages needs ranges covering them, and each
+    /// range overlapping them has to be updated for all of its stages.
+    pub fn check_push_constants(&self, stages: vk::ShaderStageFlags, offset: u32, size: u32) -> bool {
+        let end = match offset.checked_add(size) {
+            Some(end) if end <= MAX_PUSH_CONSTANTS_SIZE => end,
+            _ => return false
+        };
+
+        let covered = (0..32).map(|bit| 1 << bit)
+            .filter(|&stage| stages & stage != 0)
+            .all(|stage| self.push_constants_cover(stage, offset, size));
+        let overlapping = self.push_constant_ranges.iter()
+            .filter(|r| r.offset < end && offset < r.offset + r.size)
+            .all(|r| r.stages & !stages == 0);
+
+        covered && overlapping
+    }
+
+    fn push_constants_cover(&self, stage: vk::ShaderStageFlagBits, offset: u32, size: u32) -> bool {
+        let mut covered = offset;
+        let end = offset + size;
+
+        // Ranges may be split, so keep extending the covered prefix until
+        // no range with this stage continues it.
+        loop {
+            if covered >= end {
+                return true;
+            }
+
+            let next = self.push_constant_ranges.iter()
+                .filter(|r| r.stages & stage != 0 && r.offset <= covered && r.offset + r.size > covered)
+                .map(|r| r.offset + r.size)
+                .max();
+
+            match next {
+                Some(n) => covered = n,
+                None => return false
+            }
+        }
+    }
+}
+
+/// Whether a layout binding can back a shader resource of a descriptor type.
+/// Separate images and samplers may come from combined image samplers, and
+/// texel buffers whose use isn't known until runtime reflect as uniform
+/// texel buffers.
+fn descriptor_type_compatible(shader: vk::DescriptorType, layout: vk::DescriptorType) -> bool {
+    match (shader, layout) {
+        (a, b) if a == b => true,
+        (vk::DESCRIPTOR_TYPE_UNIFORM_BUFFER, vk::DESCRIPTOR_TYPE_UNIFORM_BUFFER_DYNAMIC) => true,
+        (vk::DESCRIPTOR_TYPE_STORAGE_BUFFER, vk::DESCRIPTOR_TYPE_STORAGE_BUFFER_DYNAMIC) => true,
+        (vk::DESCRIPTOR_TYPE_SAMPLED_IMAGE, vk::DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER) => true,
+        (vk::DESCRIPTOR_TYPE_SAMPLER, vk::DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER) => true,
+        (vk::DESCRIPTOR_TYPE_UNIFORM_TEXEL_BUFFER, vk::DESCRIPTOR_TYPE_STORAGE_TEXEL_BUFFER) => true,
+        _ => false
+    }
+}
+
+/// A shader stage of a pipeline. The module is copied into the program so
+/// that the shader module object may be destroyed once the pipeline has been
+/// created.
+pub struct ShaderStage {
+    pub stage: vk::ShaderStageFlagBits,
+    pub program: spirv::Program,
+}
+
+impl ShaderStage {
+    pub unsafe fn new(info: &vk::PipelineShaderStageCreateInfo) -> Result<ShaderStage, vk::Result> {
+        let module: &ShaderModule = &*(info.module as *const ShaderModule);
+        let name = std::ffi::CStr::from_ptr(info.pName).to_string_lossy();
+
+        match module.entry_point(&name) {
+            Some(e) if e.stage == info.stage => (),
+            Some(_) => {
+                error!("ShaderStage: entry point {} does not match stage {:#x}", name, info.stage);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+            None => {
+                error!("ShaderStage: no entry point named {}", name);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+        }
+
+        // Specialization is always applied so that later stages only ever
+        // see regular constants, even when no values are supplied.
+        let values = if info.pSpecializationInfo.is_null() {
+            HashMap::new()
+        } else {
+            try!(specialization_values(&*info.pSpecializationInfo))
+        };
+        let module = match spirv::specialize(module.module(), &values) {
+            Ok(module) => module,
+            Err(e) => {
+                error!("ShaderStage: specialized module is invalid: {:?}", e);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+        };
+
+        let entry_point = match spirv::reflect(&module).into_iter().find(|e| e.name == name) {
+            Some(e) => e,
+            None => {
+                error!("ShaderStage: entry point {} lost in specialization", name);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+        };
+
+        Ok(ShaderStage {
+            stage: info.stage,
+            program: spirv::Program::new(module, entry_point),
+        })
+    }
+
+    pub fn entry_point(&self) -> &spirv::EntryPoint {
+        &self.program.entry_point
+    }
+}
+
+/// Values of the specialization constants by SpecId. Fails if a map entry
+/// doesn't lie within the data.
+unsafe fn specialization_values(info: &vk::SpecializationInfo) -> Result<HashMap<u32, Vec<u32>>, vk::Result> {
+    let entries = slice(info.pMapEntries, info.mapEntryCount);
+    let data = if info.pData.is_null() {
+        &[][..]
+    } else {
+        std::slice::from_raw_parts(info.pData as *const u8, info.dataSize)
+    };
+
+    let mut values = HashMap::new();
+    for entry in entries {
+        let start = entry.offset as usize;
+        let end = match start.checked_add(entry.size) {
+            Some(end) if end <= data.len() => end,
+            _ => {
+                error!("specialization_values: constant {} out of data bounds", entry.constantID);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+        };
+
+        let mut words = vec![0u32; (entry.size + 3) / 4];
+        for (i, &byte) in data[start..end].iter().enumerate() {
+            words[i / 4] |= (byte as u32) << ((i % 4) * 8);
+        }
+        values.insert(entry.constantID, words);
+    }
+
+    Ok(values)
+}
+
+unsafe fn create_stages(layout: &PipelineLayout, infos: &[vk::PipelineShaderStageCreateInfo])
+    -> Result<Vec<ShaderStage>, vk::Result>
+{
+    let mut stages = Vec::new();
+
+    for info in infos {
+        let stage = try!(ShaderStage::new(info));
+
+        if let Err(e) = layout.check_entry_point(stage.entry_point()) {
+            error!("create_stages: entry point {} does not match pipeline layout: {:?}",
+                   stage.entry_point().name, e);
+            return Err(vk::ERROR_INITIALIZATION_FAILED);
+        }
+
+        stages.push(stage);
+    }
+
+    Ok(stages)
+}
+
+/// A slice from a pointer and count in a create info, which may be null when
+/// the count is zero.
+pub unsafe fn slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if ptr.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, count as usize)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VertexBinding {
+    pub binding: u32,
+    pub stride: u32,
+    pub input_rate: vk::VertexInputRate,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub binding: u32,
+    pub format: vk::Format,
+    pub offset: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct VertexInputState {
+    pub bindings: Vec<VertexBinding>,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexInputState {
+    unsafe fn new(info: &vk::PipelineVertexInputStateCreateInfo) -> VertexInputState {
+        VertexInputState {
+            bindings: slice(info.pVertexBindingDescriptions, info.vertexBindingDescriptionCount)
+                .iter().map(|b| VertexBinding {
+                    binding: b.binding,
+                    stride: b.stride,
+                    input_rate: b.inputRate,
+                }).collect(),
+            attributes: slice(info.pVertexAttributeDescriptions, info.vertexAttributeDescriptionCount)
+                .iter().map(|a| VertexAttribute {
+                    location: a.location,
+                    binding: a.binding,
+                    format: a.format,
+                    offset: a.offset,
+                }).collect(),
+        }
+    }
+
+    pub fn binding(&self, binding: u32) -> Option<&VertexBinding> {
+        self.bindings.iter().find(|b| b.binding == binding)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct InputAssemblyState {
+    pub topology: vk::PrimitiveTopology,
+    pub primitive_restart: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TessellationState {
+    pub patch_control_points: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Viewport {
+    pub fn from_vk(v: &vk::Viewport) -> Viewport {
+        Viewport {
+            x: v.x,
+            y: v.y,
+            width: v.width,
+            height: v.height,
+            min_depth: v.minDepth,
+            max_depth: v.maxDepth,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn from_vk(r: &vk::Rect2D) -> Rect {
+        Rect {
+            x: r.offset.x,
+            y: r.offset.y,
+            width: r.extent.width,
+            height: r.extent.height,
+        }
+    }
+
+    /// The part of the rect that is also inside `other`, which is empty if
+    /// they don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let x0 = std::cmp::max(self.x as i64, other.x as i64);
+        let y0 = std::cmp::max(self.y as i64, other.y as i64);
+        let x1 = std::cmp::min(self.x as i64 + self.width as i64, other.x as i64 + other.width as i64);
+        let y1 = std::cmp::min(self.y as i64 + self.height as i64, other.y as i64 + other.height as i64);
+
+        Rect {
+            x: x0 as i32,
+            y: y0 as i32,
+            width: std::cmp::max(x1 - x0, 0) as u32,
+            height: std::cmp::max(y1 - y0, 0) as u32,
+        }
+    }
+}
+
+/// Viewports and scissors. When either is dynamic the corresponding list is
+/// empty and only the count is meaningful.
+#[derive(Clone, Debug)]
+pub struct ViewportState {
+    pub viewport_count: u32,
+    pub scissor_count: u32,
+    pub viewports: Vec<Viewport>,
+    pub scissors: Vec<Rect>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RasterizationState {
+    pub depth_clamp: bool,
+    pub rasterizer_discard: bool,
+    pub polygon_mode: vk::PolygonMode,
+    pub cull_mode: vk::CullModeFlags,
+    pub front_face: vk::FrontFace,
+    pub depth_bias: bool,
+    pub depth_bias_constant_factor: f32,
+    pub depth_bias_clamp: f32,
+    pub depth_bias_slope_factor: f32,
+    pub line_width: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct MultisampleState {
+    pub samples: u32,
+    pub sample_shading: bool,
+    pub min_sample_shading: f32,
+    /// One bit per sample; all samples are enabled when no mask is given.
+    pub sample_mask: Vec<u32>,
+    pub alpha_to_coverage: bool,
+    pub alpha_to_one: bool,
+}
+
+impl Default for MultisampleState {
+    fn default() -> MultisampleState {
+        MultisampleState {
+            samples: 1,
+            sample_shading: false,
+            min_sample_shading: 0.0,
+            sample_mask: vec![!0],
+            alpha_to_coverage: false,
+            alpha_to_one: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct StencilState {
+    pub fail_op: vk::StencilOp,
+    pub pass_op: vk::StencilOp,
+    pub depth_fail_op: vk::StencilOp,
+    pub compare_op: vk::CompareOp,
+    pub compare_mask: u32,
+    pub write_mask: u32,
+    pub reference: u32,
+}
+
+impl StencilState {
+    fn from_vk(s: &vk::StencilOpState) -> StencilState {
+        StencilState {
+            fail_op: s.failOp,
+            pass_op: s.passOp,
+            depth_fail_op: s.depthFailOp,
+            compare_op: s.compareOp,
+            compare_mask: s.compareMask,
+            write_mask: s.writeMask,
+            reference: s.reference,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DepthStencilState {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_compare_op: vk::CompareOp,
+    pub depth_bounds_test: bool,
+    pub stencil_test: bool,
+    pub front: StencilState,
+    pub back: StencilState,
+    pub min_depth_bounds: f32,
+    pub max_depth_bounds: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ColorBlendAttachment {
+    pub blend: bool,
+    pub src_color_factor: vk::BlendFactor,
+    pub dst_color_factor: vk::BlendFactor,
+    pub color_op: vk::BlendOp,
+    pub src_alpha_factor: vk::BlendFactor,
+    pub dst_alpha_factor: vk::BlendFactor,
+    pub alpha_op: vk::BlendOp,
+    pub write_mask: vk::ColorComponentFlags,
+}
+
+#[derive(Clone, Debug)]
+pub struct ColorBlendState {
+    pub logic_op_enable: bool,
+    pub logic_op: vk::LogicOp,
+    pub attachments: Vec<ColorBlendAttachment>,
+    pub blend_constants: [f32; 4],
+}
+
+/// Set of pipeline states that are taken from the command buffer instead of
+/// the pipeline.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DynamicStates(u32);
+
+impl DynamicStates {
+    unsafe fn new(info: *const vk::PipelineDynamicStateCreateInfo) -> DynamicStates {
+        if info.is_null() {
+            return DynamicStates(0);
+        }
+
+        // Only the core states fit in the mask; states of extensions that
+        // aren't supported are ignored.
+        let states = slice((*info).pDynamicStates, (*info).dynamicStateCount);
+        DynamicStates(states.iter().filter(|&&s| s < 32).fold(0, |mask, &s| mask | (1 << s)))
+    }
+
+    pub fn contains(&self, state: vk::DynamicState) -> bool {
+        state < 32 && self.0 & (1 << state) != 0
+    }
+}
+
+/// A user-defined value passed from one shader stage to the next.
+#[derive(Clone, Debug)]
+pub struct Varying {
+    pub location: u32,
+    pub component: u32,
+    pub ty: spirv::DataType,
+    pub flat: bool,
+    pub noperspective: bool,
+    pub centroid: bool,
+    pub sample: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct StageLink {
+    pub producer: vk::ShaderStageFlagBits,
+    pub consumer: vk::ShaderStageFlagBits,
+    pub varyings: Vec<Varying>,
+}
+
+fn scalar_and_count(ty: &spirv::DataType) -> Option<(&spirv::DataType, u32)> {
+    match *ty {
+        spirv::DataType::Vector(ref c, n) => Some((&**c, n)),
+        spirv::DataType::Bool | spirv::DataType::Int { .. } | spirv::DataType::Float { .. } => Some((ty, 1)),
+        _ => None
+    }
+}
+
+/// Output types may have more vector components than the matching input.
+fn interface_types_match(output: &spirv::DataType, input: &spirv::DataType) -> bool {
+    if output == input {
+        return true;
+    }
+
+    match (scalar_and_count(output), scalar_and_count(input)) {
+        (Some((os, on)), Some((is, n))) => os == is && on >= n,
+        _ => false
+    }
+}
+
+/// Type of the value a stage reads or writes for each vertex. Inputs of
+/// tessellation and geometry shaders and outputs of tessellation control
+/// shaders are arrays with an element per vertex, except for patch
+/// variables. Returns `None` if such a variable isn't an array.
+fn vertex_type(stage: vk::ShaderStageFlagBits, variable: &spirv::InterfaceVariable, input: bool)
+               -> Option<&spirv::DataType> {
+    let arrayed = !variable.patch && match stage {
+        vk::SHADER_STAGE_TESSELLATION_CONTROL_BIT => true,
+        vk::SHADER_STAGE_TESSELLATION_EVALUATION_BIT | vk::SHADER_STAGE_GEOMETRY_BIT => input,
+        _ => false
+    };
+
+    match variable.ty {
+        spirv::DataType::Array(ref element, _) if arrayed => Some(element),
+        _ if arrayed => None,
+        ref ty => Some(ty)
+    }
+}
+
+fn link_stages(producer: &ShaderStage, consumer: &ShaderStage) -> Result<StageLink, vk::Result> {
+    let mut varyings = Vec::new();
+
+    for input in consumer.entry_point().inputs.iter().filter(|i| i.builtin.is_none()) {
+        let location = match input.location {
+            Some(l) => l,
+            None => {
+                error!("link_stages: input {:?} has no location", input.name);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+        };
+
+        let output = producer.entry_point().outputs.iter()
+            .find(|o| o.builtin.is_none() && o.location == Some(location) && o.component == input.component);
+
+        let input_ty = match vertex_type(consumer.stage, input, true) {
+            Some(ty) => ty,
+            None => {
+                error!("link_stages: input at location {} is not an array of {:?}", location, input.ty);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+        };
+
+        match output.map(|o| (o, vertex_type(producer.stage, o, false))) {
+            Some((_, Some(output_ty))) if interface_types_match(output_ty, input_ty) => (),
+            Some((o, _)) => {
+                error!("link_stages: type mismatch at location {}: {:?} output, {:?} input",
+                       location, o.ty, input.ty);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+            None => {
+                error!("link_stages: no output of stage {:#x} for input at location {}",
+                       producer.stage, location);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+        }
+
+        varyings.push(Varying {
+            location: location,
+            component: input.component,
+            ty: input_ty.clone(),
+            flat: input.flat,
+            noperspective: input.noperspective,
+            centroid: input.centroid,
+            sample: input.sample,
+        });
+    }
+
+    Ok(StageLink {
+        producer: producer.stage,
+        consumer: consumer.stage,
+        varyings: varyings,
+    })
+}
+
+pub struct GraphicsPipeline {
+    pub layout: PipelineLayout,
+    /// Shader stages ordered from vertex to fragment.
+    pub stages: Vec<ShaderStage>,
+    pub links: Vec<StageLink>,
+    pub vertex_input: VertexInputState,
+    pub input_assembly: InputAssemblyState,
+    pub tessellation: Option<TessellationState>,
+    pub viewport: Option<ViewportState>,
+    pub rasterization: RasterizationState,
+    pub multisample: MultisampleState,
+    pub depth_stencil: Option<DepthStencilState>,
+    pub color_blend: Option<ColorBlendState>,
+    pub dynamic: DynamicStates,
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+}
+
+impl GraphicsPipeline {
+    pub unsafe fn new(info: &vk::GraphicsPipelineCreateInfo) -> Result<GraphicsPipeline, vk::Result> {
+        let layout = (*(info.layout as *const PipelineLayout)).clone();
+
+        let mut stages = try!(create_stages(&layout, slice(info.pStages, info.stageCount)));
+        stages.sort_by(|a, b| a.stage.cmp(&b.stage));
+
+        if stages.first().map(|s| s.stage) != Some(vk::SHADER_STAGE_VERTEX_BIT) {
+            error!("GraphicsPipeline: no vertex stage");
+            return Err(vk::ERROR_INITIALIZATION_FAILED);
+        }
+
+        let mut links = Vec::new();
+        for pair in stages.windows(2) {
+            links.push(try!(link_stages(&pair[0], &pair[1])));
+        }
+
+        let dynamic = DynamicStates::new(info.pDynamicState);
+
+        let vi = &*info.pVertexInputState;
+        let ia = &*info.pInputAssemblyState;
+        let rs = &*info.pRasterizationState;
+
+        let render_pass = &*(info.renderPass as *const RenderPass);
+        let subpass = match render_pass.subpasses.get(info.subpass as usize) {
+            Some(subpass) => subpass,
+            None => {
+                error!("GraphicsPipeline: subpass {} not in render pass", info.subpass);
+                return Err(vk::ERROR_INITIALIZATION_FAILED);
+            }
+        };
+
+        let has_tessellation = stages.iter().any(|s| s.stage == vk::SHADER_STAGE_TESSELLATION_CONTROL_BIT);
+        let rasterizer_discard = rs.rasterizerDiscardEnable != vk::FALSE;
+        let has_depth_stencil = subpass.depth_stencil_attachment.is_some();
+        let has_color = subpass.color_attachments.iter().any(|r| r.index().is_some());
+
+        // States that the specification says are ignored may be garbage
+        // pointers, so they must not be read.
+        let tessellation = if has_tessellation && !info.pTessellationState.is_null() {
+            Some(TessellationState { patch_control_points: (*info.pTessellationState).patchControlPoints })
+        } else {
+            None
+        };
+
+        let viewport = if !rasterizer_discard && !info.pViewportState.is_null() {
+            let vs = &*info.pViewportState;
+            Some(ViewportState {
+                viewport_count: vs.viewportCount,
+                scissor_count: vs.scissorCount,
+                viewports: if dynamic.contains(vk::DYNAMIC_STATE_VIEWPORT) {
+                    Vec::new()
+                } else {
+                    slice(vs.pViewports, vs.viewportCount).iter().map(Viewport::from_vk).collect()
+                },
+                scissors: if dynamic.contains(vk::DYNAMIC_STATE_SCISSOR) {
+                    Vec::new()
+                } else {
+                    slice(vs.pScissors, vs.scissorCount).iter().map(Rect::from_vk).collect()
+                },
+            })
+        } else {
+            None
+        };
+
+        let multisample = if !rasterizer_discard && !info.pMultisampleState.is_null() {
+            let ms = &*info.pMultisampleState;
+            let samples = ms.rasterizationSamples;
+            MultisampleState {
+                samples: samples,
+                sample_shading: ms.sampleShadingEnable != vk::FALSE,
+                min_sample_shading: ms.minSampleShading,
+                sample_mask: if ms.pSampleMask.is_null() {
+                    vec![!0; ((samples + 31) / 32) as usize]
+                } else {
+                    slice(ms.pSampleMask, (samples + 31) / 32).to_vec()
+                },
+                alpha_to_coverage: ms.alphaToCoverageEnable != vk::FALSE,
+                alpha_to_one: ms.alphaToOneEnable != vk::FALSE,
+            }
+        } else {
+            MultisampleState::default()
+        };
+
+        let depth_stencil = if !rasterizer_discard && has_depth_stencil && !info.pDepthStencilState.is_null() {
+            let ds = &*info.pDepthStencilState;
+            Some(DepthStencilState {
+                depth_test: ds.depthTestEnable != vk::FALSE,
+                depth_write: ds.depthWriteEnable != vk::FALSE,
+                depth_compare_op: ds.depthCompareOp,
+                depth_bounds_test: ds.depthBoundsTestEnable != vk::FALSE,
+                stencil_test: ds.stencilTestEnable != vk::FALSE,
+                front: StencilState::from_vk(&ds.front),
+                back: StencilState::from_vk(&ds.back),
+                min_depth_bounds: ds.minDepthBounds,
+                max_depth_bounds: ds.maxDepthBounds,
+            })
+        } else {
+            None
+        };
+
+        let color_blend = if !rasterizer_discard && has_color && !info.pColorBlendState.is_null() {
+            let cb = &*info.pColorBlendState;
+            Some(ColorBlendState {
+                logic_op_enable: cb.logicOpEnable != vk::FALSE,
+                logic_op: cb.logicOp,
+                attachments: slice(cb.pAttachments, cb.attachmentCount).iter().map(|a| ColorBlendAttachment {
+                    blend: a.blendEnable != vk::FALSE,
+                    src_color_factor: a.srcColorBlendFactor,
+                    dst_color_factor: a.dstColorBlendFactor,
+                    color_op: a.colorBlendOp,
+                    src_alpha_factor: a.srcAlphaBlendFactor,
+                    dst_alpha_factor: a.dstAlphaBlendFactor,
+                    alpha_op: a.alphaBlendOp,
+                    write_mask: a.colorWriteMask,
+                }).collect(),
+                blend_constants: cb.blendConstants,
+            })
+        } else {
+            None
+        };
+
+        Ok(GraphicsPipeline {
+            layout: layout,
+            stages: stages,
+            links: links,
+            vertex_input: VertexInputState::new(vi),
+            input_assembly: InputAssemblyState {
+                topology: ia.topology,
+                primitive_restart: ia.primitiveRestartEnable != vk::FALSE,
+            },
+            tessellation: tessellation,
+            viewport: viewport,
+            rasterization: RasterizationState {
+                depth_clamp: rs.depthClampEnable != vk::FALSE,
+                rasterizer_discard: rasterizer_discard,
+                polygon_mode: rs.polygonMode,
+                cull_mode: rs.cullMode,
+                front_face: rs.frontFace,
+                depth_bias: rs.depthBiasEnable != vk::FALSE,
+                depth_bias_constant_factor: rs.depthBiasConstantFactor,
+                depth_bias_clamp: rs.depthBiasClamp,
+                depth_bias_slope_factor: rs.depthBiasSlopeFactor,
+                line_width: rs.lineWidth,
+            },
+            multisample: multisample,
+            depth_stencil: depth_stencil,
+            color_blend: color_blend,
+            dynamic: dynamic,
+            render_pass: info.renderPass,
+            subpass: info.subpass,
+        })
+    }
+
+    pub fn stage(&self, stage: vk::ShaderStageFlagBits) -> Option<&ShaderStage> {
+        self.stages.iter().find(|s| s.stage == stage)
+    }
+
+    /// Varyings read by the fragment shader, as linked to the last
+    /// pre-rasterization stage.
+    pub fn fragment_inputs(&self) -> &[Varying] {
+        match self.links.last() {
+            Some(link) if link.consumer == vk::SHADER_STAGE_FRAGMENT_BIT => &link.varyings,
+            _ => &[]
+        }
+    }
+}
+
+pub struct ComputePipeline {
+    pub layout: PipelineLayout,
+    pub stage: ShaderStage,
+}
+
+impl ComputePipeline {
+    pub unsafe fn new(info: &vk::ComputePipelineCreateInfo) -> Result<ComputePipeline, vk::Result> {
+        let layout = (*(info.layout as *const PipelineLayout)).clone();
+        let mut stages = try!(create_stages(&layout, std::slice::from_raw_parts(&info.stage, 1)));
+
+        Ok(ComputePipeline {
+            layout: layout,
+            stage: stages.pop().unwrap(),
+        })
+    }
+}
+
+pub enum Pipeline {
+    Graphics(GraphicsPipeline),
+    Compute(ComputePipeline),
+}
+
+impl Pipeline {
+    pub fn layout(&self) -> &PipelineLayout {
+        match *self {
+            Pipeline::Graphics(ref p) => &p.layout,
+            Pipeline::Compute(ref p) => &p.layout,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use {std, libc};
+
+use std::sync::{Mutex, MutexGuard, Once, ONCE_INIT};
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+/// Environment variable with the number of worker threads. Defaults to the
+/// number of online CPUs.
+pub const THREADS_VAR: &'static str = "SOFTVK_THREADS";
+
+/// A job borrowed for the duration of `run`, which waits for every worker
+/// to finish with it before returning.
+struct Job(*const (Fn() + Sync + 'static));
+
+unsafe impl Send for Job {}
+
+/// Worker threads, each waiting for jobs on its own channel. Each worker
+/// reports on `done` whether the job returned or panicked.
+struct Pool {
+    workers: Vec<Sender<Job>>,
+    done: Receiver<bool>,
+}
+
+static INIT: Once = ONCE_INIT;
+static mut POOL: *const Mutex<Pool> = 0 as *const Mutex<Pool>;
+
+/// Number of threads requested through `THREADS_VAR`, or the number of CPUs.
+fn thread_count() -> usize {
+    if let Ok(value) = std::env::var(THREADS_VAR) {
+        match value.trim().parse::<usize>() {
+            Ok(n) if n > 0 => return n,
+            _ => warn!("{}: ignoring invalid thread count {:?}", THREADS_VAR, value),
+        }
+    }
+
+    let cpus = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    if cpus > 0 { cpus as usize } else { 1 }
+}
+
+fn worker(jobs: Receiver<Job>, done: Sender<bool>) {
+    for job in jobs.iter() {
+        let job = unsafe { &*job.0 };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job()));
+        if done.send(result.is_ok()).is_err() {
+            return;
+        }
+    }
+}
+
+fn pool() -> &'static Mutex<Pool> {
+    unsafe {
+        INIT.call_once(|| {
+            let (done_sender, done) = channel();
+            let workers = (0..thread_count()).map(|i| {
+                let (sender, jobs) = channel();
+                let done = done_sender.clone();
+                let spawned = std::thread::Builder::new()
+                    .name(format!("softvk-worker-{}", i))
+                    .spawn(move || worker(jobs, done));
+                if let Err(e) = spawned {
+                    panic!("pool: failed to spawn worker thread: {}", e);
+                }
+                sender
+            }).collect();
+
+            POOL = Box::into_raw(Box::new(Mutex::new(Pool {
+                workers: workers,
+                done: done,
+            })));
+        });
+        &*POOL
+    }
+}
+
+/// Locks the pool. A job that panicked doesn't leave the pool in a bad
+/// state, so poisoning is ignored.
+fn lock() -> MutexGuard<'static, Pool> {
+    match pool().lock() {
+        Ok(pool) => pool,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Number of threads that `run` runs a job on.
+pub fn threads() -> usize {
+    lock().workers.len()
+}
+
+/// Runs `job` once on every worker thread and returns when all of them have
+/// finished. Jobs split their work between the workers themselves, usually
+/// by taking items from a shared counter. With a single thread the job runs
+/// on the calling thread instead.
+///
+/// Jobs of different callers don't overlap; a job must not call `run`.
+pub fn run(job: &(Fn() + Sync)) {
+    let pool = lock();
+    if pool.workers.len() == 1 {
+        job();
+        return;
+    }
+
+    // Workers don't hold on to the job after reporting on it, so it can be
+    // borrowed for as long as this function waits for them.
+    let job: *const (Fn() + Sync + 'static) = unsafe { std::mem::transmute(job) };
+    for worker in pool.workers.iter() {
+        if worker.send(Job(job)).is_err() {
+            panic!("run: worker thread exited");
+        }
+    }
+
+    let mut panicked = false;
+    for _ in 0..pool.workers.len() {
+        match pool.done.recv() {
+            Ok(ok) => panicked |= !ok,
+            Err(_) => panic!("run: worker thread exited"),
+        }
+    }
+    if panicked {
+        panic!("run: job panicked on a worker thread");
+    }
+}
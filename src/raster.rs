@@ -0,0 +1,818 @@
+use {std, vk};
+
+use depth::DepthBias;
+use multisample::{self, MAX_SAMPLES};
+use pipeline::{RasterizationState, Viewport, Rect};
+
+/// Number of fractional bits of vertex positions in framebuffer space, as
+/// reported in `subPixelPrecisionBits`.
+pub const SUB_PIXEL_BITS: u32 = 8;
+
+/// Largest viewport width and height.
+pub const MAX_VIEWPORT_DIMENSION: u32 = 4096;
+
+/// Viewports must lie within this range, which keeps snapped coordinates and
+/// edge functions well within 64 bits.
+pub const VIEWPORT_BOUNDS: [f32; 2] = [-8192.0, 8191.0];
+
+/// Supported point sizes and line widths. Both are continuous, up to the
+/// sub-pixel precision.
+pub const POINT_SIZE_RANGE: [f32; 2] = [1.0, 256.0];
+pub const LINE_WIDTH_RANGE: [f32; 2] = [1.0, 64.0];
+pub const GRANULARITY: f32 = 1.0 / (1 << SUB_PIXEL_BITS) as f32;
+
+const ONE: i64 = 1 << SUB_PIXEL_BITS;
+const HALF: i64 = ONE / 2;
+
+/// A vertex produced by clipping, as a weighted sum of the vertices of the
+/// original primitive.
+#[derive(Clone, Copy, Debug)]
+struct ClipVertex {
+    position: [f32; 4],
+    weights: [f32; 3],
+}
+
+/// A clipped vertex after perspective divide and viewport transform.
+#[derive(Clone, Copy, Debug)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    weights: [f32; 3],
+}
+
+/// Barycentric weights of a point relative to the vertices of the primitive
+/// as passed to the rasterizer, not to any clipped vertices.
+#[derive(Clone, Copy, Debug)]
+pub struct Weights {
+    /// Perspective-correct weights.
+    pub perspective: [f32; 3],
+    /// Weights in framebuffer space, for `NoPerspective`.
+    pub linear: [f32; 3],
+}
+
+/// A covered pixel.
+#[derive(Clone, Copy, Debug)]
+pub struct Fragment {
+    pub x: u32,
+    pub y: u32,
+    /// Framebuffer depth at the pixel center, after depth clamping.
+    pub depth: f32,
+    /// Depth at every sample position, for the per-sample depth tests.
+    pub sample_depths: [f32; MAX_SAMPLES as usize],
+    /// Reciprocal of the clip space w coordinate.
+    pub inv_w: f32,
+    /// Weights at the pixel center.
+    pub center: Weights,
+    /// Weights at a covered point inside the pixel, for `Centroid`. This is
+    /// the center if all samples are covered.
+    pub centroid: Weights,
+    /// Weights at the same point of every pixel of the 2x2 quad the fragment
+    /// belongs to, with x varying fastest. Derivatives are the differences
+    /// between invocations shaded at these points.
+    pub quad: [Weights; 4],
+    pub front_facing: bool,
+    /// Coordinate inside a point sprite, for `PointCoord`.
+    pub point_coord: [f32; 2],
+    /// Covered samples of the pixel, one bit per sample.
+    pub coverage: u32,
+    /// With per-sample shading, the only covered sample and its position
+    /// inside the pixel. Otherwise the fragment covers the pixel center.
+    pub sample: Option<u32>,
+    pub sample_position: [f32; 2],
+}
+
+/// Clips a polygon against the plane where `distance` is non-negative.
+fn clip_plane<F: Fn(&[f32; 4]) -> f32>(polygon: Vec<ClipVertex>, distance: F) -> Vec<ClipVertex> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for i in 0..polygon.len() {
+        let a = &polygon[i];
+        let b = &polygon[(i + 1) % polygon.len()];
+        let da = distance(&a.position);
+        let db = distance(&b.position);
+
+        if da >= 0.0 {
+            clipped.push(*a);
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            let t = da / (da - db);
+            let mut v = *a;
+            for j in 0..4 {
+                v.position[j] = a.position[j] + t * (b.position[j] - a.position[j]);
+            }
+            for j in 0..3 {
+                v.weights[j] = a.weights[j] + t * (b.weights[j] - a.weights[j]);
+            }
+            clipped.push(v);
+        }
+    }
+
+    clipped
+}
+
+/// Distances to the planes of the view volume. Without depth clamping, this
+/// includes the near and far planes.
+fn plane_distances(p: &[f32; 4], depth_clamp: bool) -> [f32; 6] {
+    if depth_clamp {
+        [p[3] + p[0], p[3] - p[0], p[3] + p[1], p[3] - p[1], 0.0, 0.0]
+    } else {
+        [p[3] + p[0], p[3] - p[0], p[3] + p[1], p[3] - p[1], p[2], p[3] - p[2]]
+    }
+}
+
+fn clip_polygon(mut polygon: Vec<ClipVertex>, depth_clamp: bool) -> Vec<ClipVertex> {
+    for plane in 0..6 {
+        polygon = clip_plane(polygon, |p| plane_distances(p, depth_clamp)[plane]);
+    }
+    polygon
+}
+
+/// Clips a line segment against the view volume, returning `None` if
+/// nothing of it is left.
+fn clip_line(mut a: ClipVertex, mut b: ClipVertex, depth_clamp: bool) -> Option<(ClipVertex, ClipVertex)> {
+    for plane in 0..6 {
+        let da = plane_distances(&a.position, depth_clamp)[plane];
+        let db = plane_distances(&b.position, depth_clamp)[plane];
+        if da < 0.0 && db < 0.0 {
+            return None;
+        }
+
+        if da < 0.0 || db < 0.0 {
+            let t = da / (da - db);
+            let mut v = a;
+            for j in 0..4 {
+                v.position[j] = a.position[j] + t * (b.position[j] - a.position[j]);
+            }
+            for j in 0..3 {
+                v.weights[j] = a.weights[j] + t * (b.weights[j] - a.weights[j]);
+            }
+            if da < 0.0 { a = v; } else { b = v; }
+        }
+    }
+
+    Some((a, b))
+}
+
+fn to_screen(v: &ClipVertex, viewport: &Viewport) -> ScreenVertex {
+    let inv_w = 1.0 / v.position[3];
+    let x = v.position[0] * inv_w;
+    let y = v.position[1] * inv_w;
+    let z = v.position[2] * inv_w;
+
+    ScreenVertex {
+        x: viewport.width * 0.5 * x + viewport.x + viewport.width * 0.5,
+        y: viewport.height * 0.5 * y + viewport.y + viewport.height * 0.5,
+        z: (viewport.max_depth - viewport.min_depth) * z + viewport.min_depth,
+        inv_w: inv_w,
+        weights: v.weights,
+    }
+}
+
+/// Snaps a framebuffer coordinate to sub-pixel precision.
+fn snap(x: f32) -> i64 {
+    (x as f64 * ONE as f64).round() as i64
+}
+
+/// Whether the edge from `a` to `b` is a top or left edge of a triangle whose
+/// interior lies where its edge functions are positive.
+fn is_top_left(a: (i64, i64), b: (i64, i64)) -> bool {
+    let dx = b.0 - a.0;
+    let dy = b.1 - a.1;
+    dy < 0 || (dy == 0 && dx > 0)
+}
+
+/// Twice the signed area of the triangle (a, b, p).
+fn edge(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+/// The larger of the depth gradients along x and y of a polygon, and its
+/// largest depth.
+fn depth_slope(polygon: &[ScreenVertex]) -> (f32, f32) {
+    let max_depth = polygon.iter().fold(0.0f32, |m, v| m.max(v.z.abs()));
+
+    let (a, b, c) = (&polygon[0], &polygon[1], &polygon[2]);
+    let det = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+    if det == 0.0 {
+        return (0.0, max_depth);
+    }
+
+    let dzdx = ((b.z - a.z) * (c.y - a.y) - (c.z - a.z) * (b.y - a.y)) / det;
+    let dzdy = ((c.z - a.z) * (b.x - a.x) - (b.z - a.z) * (c.x - a.x)) / det;
+    (dzdx.abs().max(dzdy.abs()), max_depth)
+}
+
+const VERTEX_WEIGHTS: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Converts clip space positions into framebuffer fragments within `bounds`.
+/// Pixels on an edge shared by two triangles are covered by exactly one of
+/// them according to the top-left rule.
+pub struct Rasterizer<'a> {
+    pub state: &'a RasterizationState,
+    pub viewport: Viewport,
+    pub bounds: Rect,
+    pub line_width: f32,
+    /// Depth bias for polygons, if enabled.
+    pub depth_bias: Option<DepthBias>,
+    /// Format of the depth attachment, which determines the constant depth
+    /// bias.
+    pub depth_format: vk::Format,
+    /// Samples per pixel, which are at the standard sample locations.
+    pub samples: u32,
+    /// Whether a fragment is produced for every covered sample instead of
+    /// every covered pixel.
+    pub per_sample: bool,
+}
+
+impl<'a> Rasterizer<'a> {
+    fn depth_range(&self) -> (f32, f32) {
+        let v = &self.viewport;
+        if v.min_depth <= v.max_depth { (v.min_depth, v.max_depth) } else { (v.max_depth, v.min_depth) }
+    }
+
+    /// Passes a fragment on to `emit`, applying depth bias and clamping.
+    fn output<F: FnMut(&Fragment)>(&self, mut fragment: Fragment, bias: f32, emit: &mut F) {
+        let (min_depth, max_depth) = self.depth_range();
+        let depth_clamp = self.state.depth_clamp;
+        let adjust = |depth: &mut f32| {
+            *depth += bias;
+            if depth_clamp {
+                *depth = depth.max(min_depth).min(max_depth);
+            }
+        };
+
+        adjust(&mut fragment.depth);
+        for depth in &mut fragment.sample_depths {
+            adjust(depth);
+        }
+        emit(&fragment);
+    }
+
+    /// The part of `bounds` that a primitive with the given vertices can
+    /// cover, or `None` if it covers nothing. `radius` extends the box by
+    /// half the width of wide lines and points. Primitives with a vertex
+    /// behind the eye may cover anything after clipping.
+    pub fn bounding_box(&self, positions: &[[f32; 4]], radius: f32) -> Option<Rect> {
+        let mut min = [std::f32::INFINITY; 2];
+        let mut max = [std::f32::NEG_INFINITY; 2];
+        for position in positions {
+            if !(position[3] > 0.0) {
+                return Some(self.bounds);
+            }
+            let v = to_screen(&ClipVertex { position: *position, weights: [0.0; 3] }, &self.viewport);
+            if !v.x.is_finite() || !v.y.is_finite() {
+                return Some(self.bounds);
+            }
+            min = [min[0].min(v.x), min[1].min(v.y)];
+            max = [max[0].max(v.x), max[1].max(v.y)];
+        }
+
+        // Snapping may move an edge by up to half a sub-pixel, so one more
+        // pixel is added on each side.
+        let x0 = std::cmp::max(self.bounds.x as i64, (min[0] - radius).floor() as i64 - 1);
+        let y0 = std::cmp::max(self.bounds.y as i64, (min[1] - radius).floor() as i64 - 1);
+        let x1 = std::cmp::min(self.bounds.x as i64 + self.bounds.width as i64, (max[0] + radius).ceil() as i64 + 1);
+        let y1 = std::cmp::min(self.bounds.y as i64 + self.bounds.height as i64, (max[1] + radius).ceil() as i64 + 1);
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+
+        Some(Rect {
+            x: x0 as i32,
+            y: y0 as i32,
+            width: (x1 - x0) as u32,
+            height: (y1 - y0) as u32,
+        })
+    }
+
+    /// Rasterizes a triangle according to the polygon mode. `point_sizes`
+    /// are only used for `POLYGON_MODE_POINT`.
+    pub fn triangle<F: FnMut(&Fragment)>(&self, positions: &[[f32; 4]; 3], point_sizes: &[f32; 3], mut emit: F) {
+        let polygon = clip_polygon(vec![
+            ClipVertex { position: positions[0], weights: VERTEX_WEIGHTS[0] },
+            ClipVertex { position: positions[1], weights: VERTEX_WEIGHTS[1] },
+            ClipVertex { position: positions[2], weights: VERTEX_WEIGHTS[2] },
+        ], self.state.depth_clamp);
+        if polygon.len() < 3 || polygon.iter().any(|v| v.position[3] <= 0.0) {
+            return;
+        }
+
+        let screen: Vec<ScreenVertex> = polygon.iter().map(|v| to_screen(v, &self.viewport)).collect();
+
+        // Facing is determined from the area in framebuffer coordinates,
+        // where y points down.
+        let mut sum = 0.0;
+        for i in 0..screen.len() {
+            let a = &screen[i];
+            let b = &screen[(i + 1) % screen.len()];
+            sum += a.x * b.y - b.x * a.y;
+        }
+        let area = -0.5 * sum;
+        if area == 0.0 {
+            return;
+        }
+        let front_facing = if self.state.front_face == vk::FRONT_FACE_COUNTER_CLOCKWISE {
+            area > 0.0
+        } else {
+            area < 0.0
+        };
+        let culled = if front_facing { vk::CULL_MODE_FRONT_BIT } else { vk::CULL_MODE_BACK_BIT };
+        if self.state.cull_mode & culled != 0 {
+            return;
+        }
+
+        let bias = match self.depth_bias {
+            Some(ref depth_bias) => {
+                let (slope, max_depth) = depth_slope(&screen);
+                depth_bias.offset(self.depth_format, slope, max_depth)
+            }
+            None => 0.0
+        };
+
+        let mut emit = |fragment: &Fragment| {
+            let mut fragment = *fragment;
+            fragment.front_facing = front_facing;
+            emit(&fragment);
+        };
+
+        match self.state.polygon_mode {
+            vk::POLYGON_MODE_LINE => {
+                for i in 0..3 {
+                    let j = (i + 1) % 3;
+                    self.segment(ClipVertex { position: positions[i], weights: VERTEX_WEIGHTS[i] },
+                                 ClipVertex { position: positions[j], weights: VERTEX_WEIGHTS[j] },
+                                 bias, &mut emit);
+                }
+            }
+            vk::POLYGON_MODE_POINT => {
+                for i in 0..3 {
+                    self.sprite(ClipVertex { position: positions[i], weights: VERTEX_WEIGHTS[i] },
+                                point_sizes[i], bias, &mut emit);
+                }
+            }
+            _ => {
+                for i in 1..screen.len() - 1 {
+                    let triangle = [screen[0], screen[i], screen[i + 1]];
+                    rasterize_screen_triangle(&triangle, &self.bounds, self.samples, self.per_sample,
+                                              |fragment| self.output(fragment, bias, &mut emit));
+                }
+            }
+        }
+    }
+
+    /// Rasterizes a line segment of the current line width.
+    pub fn line<F: FnMut(&Fragment)>(&self, positions: &[[f32; 4]; 2], mut emit: F) {
+        self.segment(ClipVertex { position: positions[0], weights: VERTEX_WEIGHTS[0] },
+                     ClipVertex { position: positions[1], weights: VERTEX_WEIGHTS[1] },
+                     0.0, &mut emit);
+    }
+
+    /// Rasterizes a point sprite.
+    pub fn point<F: FnMut(&Fragment)>(&self, position: &[f32; 4], size: f32, mut emit: F) {
+        self.sprite(ClipVertex { position: *position, weights: VERTEX_WEIGHTS[0] }, size, 0.0, &mut emit);
+    }
+
+    /// Rasterizes a line. Lines one pixel wide without multisampling follow
+    /// the diamond-exit rule of Bresenham's algorithm. Other lines are
+    /// parallelograms whose ends are parallel to the minor axis, extending
+    /// half the line width to either side.
+    fn segment<F: FnMut(&Fragment)>(&self, a: ClipVertex, b: ClipVertex, bias: f32, emit: &mut F) {
+        let (a, b) = match clip_line(a, b, self.state.depth_clamp) {
+            Some(line) => line,
+            None => return
+        };
+        if a.position[3] <= 0.0 || b.position[3] <= 0.0 {
+            return;
+        }
+
+        let a = to_screen(&a, &self.viewport);
+        let b = to_screen(&b, &self.viewport);
+
+        let width = self.line_width.max(LINE_WIDTH_RANGE[0]).min(LINE_WIDTH_RANGE[1]);
+        if width == 1.0 && self.samples == 1 {
+            self.bresenham(&a, &b, bias, emit);
+            return;
+        }
+
+        let half = width * 0.5;
+        let (ox, oy) = if (b.x - a.x).abs() >= (b.y - a.y).abs() { (0.0, half) } else { (half, 0.0) };
+        let offset = |v: &ScreenVertex, s: f32| ScreenVertex { x: v.x + s * ox, y: v.y + s * oy, ..*v };
+
+        let corners = [offset(&a, -1.0), offset(&b, -1.0), offset(&b, 1.0), offset(&a, 1.0)];
+        for triangle in &[[corners[0], corners[1], corners[2]], [corners[0], corners[2], corners[3]]] {
+            rasterize_screen_triangle(triangle, &self.bounds, self.samples, self.per_sample,
+                                      |fragment| self.output(fragment, bias, &mut *emit));
+        }
+    }
+
+    /// Rasterizes a line one pixel wide with the diamond-exit rule: a pixel is
+    /// covered if the segment leaves the diamond inscribed in it, which gives
+    /// one pixel per column (or row) along the major axis. The pixel holding
+    /// the end of the segment is left out so that connected segments don't
+    /// overlap. Attributes are interpolated at the projection of the pixel
+    /// center onto the segment.
+    fn bresenham<F: FnMut(&Fragment)>(&self, a: &ScreenVertex, b: &ScreenVertex, bias: f32, emit: &mut F) {
+        let unsnap = |x: f32| snap(x) as f64 / ONE as f64;
+        let (ax, ay, bx, by) = (unsnap(a.x), unsnap(a.y), unsnap(b.x), unsnap(b.y));
+        let (dx, dy) = (bx - ax, by - ay);
+        let length2 = dx * dx + dy * dy;
+        if length2 == 0.0 {
+            return;
+        }
+        let x_major = dx.abs() >= dy.abs();
+
+        // Distance from a pixel center in the metric of the diamond, which
+        // is covered below one half.
+        let distance = |x: f64, y: f64, px: i64, py: i64| (x - px as f64 - 0.5).abs() + (y - py as f64 - 0.5).abs();
+        // The distance along the segment is smallest at an end or where it
+        // crosses the row or column of the center.
+        let covered = |px: i64, py: i64| {
+            if distance(bx, by, px, py) < 0.5 {
+                return false;
+            }
+            let crossings = [
+                Some(0.0),
+                Some(1.0),
+                if dx != 0.0 { Some((px as f64 + 0.5 - ax) / dx) } else { None },
+                if dy != 0.0 { Some((py as f64 + 0.5 - ay) / dy) } else { None },
+            ];
+            crossings.iter().filter_map(|&t| t)
+                .any(|t| t >= 0.0 && t <= 1.0 && distance(ax + t * dx, ay + t * dy, px, py) < 0.5)
+        };
+
+        // Position along the segment of the projection of a point.
+        let param = |x: f64, y: f64| (((x - ax) * dx + (y - ay) * dy) / length2) as f32;
+        let interpolate = |t: f32| {
+            let q = [(1.0 - t) * a.inv_w, t * b.inv_w];
+            let q_sum = q[0] + q[1];
+            let mut weights = Weights {
+                perspective: [0.0; 3],
+                linear: [0.0; 3],
+            };
+            for i in 0..3 {
+                weights.perspective[i] = (q[0] * a.weights[i] + q[1] * b.weights[i]) / q_sum;
+                weights.linear[i] = (1.0 - t) * a.weights[i] + t * b.weights[i];
+            }
+            (weights, (1.0 - t) * a.z + t * b.z, q_sum)
+        };
+
+        let x_bounds = (std::cmp::max(self.bounds.x as i64, 0), self.bounds.x as i64 + self.bounds.width as i64);
+        let y_bounds = (std::cmp::max(self.bounds.y as i64, 0), self.bounds.y as i64 + self.bounds.height as i64);
+        let (start, end) = if x_major { (ax.min(bx), ax.max(bx)) } else { (ay.min(by), ay.max(by)) };
+        for i in start.floor() as i64..end.floor() as i64 + 1 {
+            let center = i as f64 + 0.5;
+            let t = (if x_major { (center - ax) / dx } else { (center - ay) / dy }).max(0.0).min(1.0);
+            let minor = (if x_major { ay + t * dy } else { ax + t * dx }).floor() as i64;
+
+            for j in minor - 1..minor + 2 {
+                let (x, y) = if x_major { (i, j) } else { (j, i) };
+                if x < x_bounds.0 || x >= x_bounds.1 || y < y_bounds.0 || y >= y_bounds.1 || !covered(x, y) {
+                    continue;
+                }
+
+                let (center, depth, inv_w) = interpolate(param(x as f64 + 0.5, y as f64 + 0.5).max(0.0).min(1.0));
+                let (qx, qy) = (x & !1, y & !1);
+                let quad_weights = |x: i64, y: i64| interpolate(param(x as f64 + 0.5, y as f64 + 0.5)).0;
+                self.output(Fragment {
+                    x: x as u32,
+                    y: y as u32,
+                    depth: depth,
+                    sample_depths: [depth; MAX_SAMPLES as usize],
+                    inv_w: inv_w,
+                    center: center,
+                    centroid: center,
+                    quad: [quad_weights(qx, qy), quad_weights(qx + 1, qy), quad_weights(qx, qy + 1),
+                           quad_weights(qx + 1, qy + 1)],
+                    front_facing: true,
+                    point_coord: [0.0; 2],
+                    coverage: 1,
+                    sample: if self.per_sample { Some(0) } else { None },
+                    sample_position: [0.5, 0.5],
+                }, bias, emit);
+            }
+        }
+    }
+
+    /// Rasterizes a point as a square of `size` pixels centered on the
+    /// vertex, covering the samples inside the square. Points whose vertex
+    /// is outside the view volume are discarded.
+    fn sprite<F: FnMut(&Fragment)>(&self, v: ClipVertex, size: f32, bias: f32, emit: &mut F) {
+        if v.position[3] <= 0.0 || plane_distances(&v.position, self.state.depth_clamp).iter().any(|&d| d < 0.0) {
+            return;
+        }
+
+        let v = to_screen(&v, &self.viewport);
+        let size = size.max(POINT_SIZE_RANGE[0]).min(POINT_SIZE_RANGE[1]);
+        let half = size * 0.5;
+
+        let x0 = std::cmp::max(self.bounds.x as i64, (v.x - half).floor() as i64);
+        let x1 = std::cmp::min(self.bounds.x as i64 + self.bounds.width as i64, (v.x + half).ceil() as i64);
+        let y0 = std::cmp::max(self.bounds.y as i64, (v.y - half).floor() as i64);
+        let y1 = std::cmp::min(self.bounds.y as i64 + self.bounds.height as i64, (v.y + half).ceil() as i64);
+
+        let weights = Weights {
+            perspective: v.weights,
+            linear: v.weights,
+        };
+        let positions = multisample::positions(self.samples);
+        let point_coord = |x: f32, y: f32| [0.5 + (x - v.x) / size, 0.5 + (y - v.y) / size];
+
+        for y in std::cmp::max(y0, 0)..y1 {
+            for x in std::cmp::max(x0, 0)..x1 {
+                let (fx, fy) = (x as f32, y as f32);
+                let mut coverage = 0;
+                for (i, p) in positions.iter().enumerate() {
+                    let (sx, sy) = (fx + p[0], fy + p[1]);
+                    if sx >= v.x - half && sx < v.x + half && sy >= v.y - half && sy < v.y + half {
+                        coverage |= 1 << i;
+                    }
+                }
+                if coverage == 0 {
+                    continue;
+                }
+
+                let fragment = Fragment {
+                    x: x as u32,
+                    y: y as u32,
+                    depth: v.z,
+                    sample_depths: [v.z; MAX_SAMPLES as usize],
+                    inv_w: v.inv_w,
+                    center: weights,
+                    centroid: weights,
+                    quad: [weights; 4],
+                    front_facing: true,
+                    point_coord: point_coord(fx + 0.5, fy + 0.5),
+                    coverage: coverage,
+                    sample: None,
+                    sample_position: [0.5, 0.5],
+                };
+
+                if self.per_sample {
+                    for (i, p) in positions.iter().enumerate().filter(|&(i, _)| coverage & 1 << i != 0) {
+                        self.output(Fragment {
+                            point_coord: point_coord(fx + p[0], fy + p[1]),
+                            coverage: 1 << i,
+                            sample: Some(i as u32),
+                            sample_position: *p,
+                            ..fragment
+                        }, bias, emit);
+                    }
+                } else {
+                    self.output(fragment, bias, emit);
+                }
+            }
+        }
+    }
+}
+
+/// Emits the fragments of a triangle in framebuffer coordinates. Each sample
+/// is tested at its exact position, since the standard sample locations are
+/// multiples of the sub-pixel precision.
+fn rasterize_screen_triangle<F: FnMut(Fragment)>(triangle: &[ScreenVertex; 3], bounds: &Rect, samples: u32,
+                                                 per_sample: bool, mut emit: F) {
+    let mut v = *triangle;
+    let mut p = [(0, 0); 3];
+    for i in 0..3 {
+        p[i] = (snap(v[i].x), snap(v[i].y));
+    }
+
+    let mut area = edge(p[0], p[1], p[2]);
+    if area == 0 {
+        return;
+    }
+    if area < 0 {
+        v.swap(1, 2);
+        p.swap(1, 2);
+        area = -area;
+    }
+
+    let bias = [
+        if is_top_left(p[1], p[2]) { 0 } else { -1 },
+        if is_top_left(p[2], p[0]) { 0 } else { -1 },
+        if is_top_left(p[0], p[1]) { 0 } else { -1 },
+    ];
+
+    let min_x = std::cmp::min(p[0].0, std::cmp::min(p[1].0, p[2].0));
+    let max_x = std::cmp::max(p[0].0, std::cmp::max(p[1].0, p[2].0));
+    let min_y = std::cmp::min(p[0].1, std::cmp::min(p[1].1, p[2].1));
+    let max_y = std::cmp::max(p[0].1, std::cmp::max(p[1].1, p[2].1));
+
+    let x0 = std::cmp::max(std::cmp::max(bounds.x as i64, 0), min_x >> SUB_PIXEL_BITS);
+    let x1 = std::cmp::min(bounds.x as i64 + bounds.width as i64 - 1, max_x >> SUB_PIXEL_BITS);
+    let y0 = std::cmp::max(std::cmp::max(bounds.y as i64, 0), min_y >> SUB_PIXEL_BITS);
+    let y1 = std::cmp::min(bounds.y as i64 + bounds.height as i64 - 1, max_y >> SUB_PIXEL_BITS);
+
+    let inv_area = 1.0 / area as f64;
+    let positions = multisample::positions(samples);
+    let all_samples = multisample::all_samples(positions.len() as u32);
+
+    // Weights, depth and 1/w at a point with the given edge functions.
+    let interpolate = |e: &[i64; 3]| {
+        let l = [(e[0] as f64 * inv_area) as f32, (e[1] as f64 * inv_area) as f32,
+                 (e[2] as f64 * inv_area) as f32];
+        let q = [l[0] * v[0].inv_w, l[1] * v[1].inv_w, l[2] * v[2].inv_w];
+        let q_sum = q[0] + q[1] + q[2];
+
+        let mut weights = Weights {
+            perspective: [0.0; 3],
+            linear: [0.0; 3],
+        };
+        for k in 0..3 {
+            for i in 0..3 {
+                weights.perspective[i] += q[k] / q_sum * v[k].weights[i];
+                weights.linear[i] += l[k] * v[k].weights[i];
+            }
+        }
+
+        (weights, l[0] * v[0].z + l[1] * v[1].z + l[2] * v[2].z, q_sum)
+    };
+
+    // Edge functions at an offset inside a pixel, in sub-pixel units.
+    let edges_at = |x: i64, y: i64, offset: (i64, i64)| {
+        let point = ((x << SUB_PIXEL_BITS) + offset.0, (y << SUB_PIXEL_BITS) + offset.1);
+        [edge(p[1], p[2], point), edge(p[2], p[0], point), edge(p[0], p[1], point)]
+    };
+    let quad = |x: i64, y: i64, offset: (i64, i64)| {
+        let (x, y) = (x & !1, y & !1);
+        [interpolate(&edges_at(x, y, offset)).0, interpolate(&edges_at(x + 1, y, offset)).0,
+         interpolate(&edges_at(x, y + 1, offset)).0, interpolate(&edges_at(x + 1, y + 1, offset)).0]
+    };
+
+    for y in y0..y1 + 1 {
+        for x in x0..x1 + 1 {
+            let edges = |offset: (i64, i64)| edges_at(x, y, offset);
+
+            let mut coverage = 0;
+            let mut sample_edges = [[0; 3]; MAX_SAMPLES as usize];
+            for (i, position) in positions.iter().enumerate() {
+                let e = edges(((position[0] * ONE as f32) as i64, (position[1] * ONE as f32) as i64));
+                if e[0] + bias[0] >= 0 && e[1] + bias[1] >= 0 && e[2] + bias[2] >= 0 {
+                    coverage |= 1 << i;
+                }
+                sample_edges[i] = e;
+            }
+            if coverage == 0 {
+                continue;
+            }
+
+            let mut sample_depths = [0.0; MAX_SAMPLES as usize];
+            for i in 0..positions.len() {
+                sample_depths[i] = interpolate(&sample_edges[i]).1;
+            }
+
+            let (center, depth, inv_w) = interpolate(&edges((HALF, HALF)));
+            let fragment = Fragment {
+                x: x as u32,
+                y: y as u32,
+                depth: depth,
+                sample_depths: sample_depths,
+                inv_w: inv_w,
+                center: center,
+                centroid: if coverage == all_samples {
+                    center
+                } else {
+                    interpolate(&sample_edges[coverage.trailing_zeros() as usize]).0
+                },
+                quad: quad(x, y, (HALF, HALF)),
+                front_facing: true,
+                point_coord: [0.0; 2],
+                coverage: coverage,
+                sample: None,
+                sample_position: [0.5, 0.5],
+            };
+
+            if per_sample {
+                for (i, position) in positions.iter().enumerate().filter(|&(i, _)| coverage & 1 << i != 0) {
+                    let (weights, depth, inv_w) = interpolate(&sample_edges[i]);
+                    emit(Fragment {
+                        depth: depth,
+                        inv_w: inv_w,
+                        center: weights,
+                        centroid: weights,
+                        quad: quad(x, y, ((position[0] * ONE as f32) as i64, (position[1] * ONE as f32) as i64)),
+                        coverage: 1 << i,
+                        sample: Some(i as u32),
+                        sample_position: *position,
+                        ..fragment
+                    });
+                }
+            } else {
+                emit(fragment);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use vk;
+    use pipeline::{RasterizationState, Rect, Viewport};
+    use super::*;
+
+    const STATE: RasterizationState = RasterizationState {
+        depth_clamp: false,
+        rasterizer_discard: false,
+        polygon_mode: vk::POLYGON_MODE_FILL,
+        cull_mode: vk::CULL_MODE_NONE,
+        front_face: vk::FRONT_FACE_COUNTER_CLOCKWISE,
+        depth_bias: false,
+        depth_bias_constant_factor: 0.0,
+        depth_bias_clamp: 0.0,
+        depth_bias_slope_factor: 0.0,
+        line_width: 1.0,
+    };
+
+    /// A rasterizer for an 8x8 framebuffer with one sample per pixel.
+    fn rasterizer(state: &RasterizationState) -> Rasterizer {
+        Rasterizer {
+            state: state,
+            viewport: Viewport { x: 0.0, y: 0.0, width: 8.0, height: 8.0, min_depth: 0.0, max_depth: 1.0 },
+            bounds: Rect { x: 0, y: 0, width: 8, height: 8 },
+            line_width: 1.0,
+            depth_bias: None,
+            depth_format: vk::FORMAT_D32_SFLOAT,
+            samples: 1,
+            per_sample: false,
+        }
+    }
+
+    /// Clip space position of a point in framebuffer coordinates.
+    fn clip(x: f32, y: f32) -> [f32; 4] {
+        [x / 4.0 - 1.0, y / 4.0 - 1.0, 0.5, 1.0]
+    }
+
+    /// Number of times the triangles cover each pixel.
+    fn coverage(triangles: &[[(f32, f32); 3]]) -> HashMap<(u32, u32), u32> {
+        let rasterizer = rasterizer(&STATE);
+        let mut covered = HashMap::new();
+        for t in triangles {
+            let positions = [clip(t[0].0, t[0].1), clip(t[1].0, t[1].1), clip(t[2].0, t[2].1)];
+            rasterizer.triangle(&positions, &[1.0; 3], |f| *covered.entry((f.x, f.y)).or_insert(0) += 1);
+        }
+        covered
+    }
+
+    /// The two triangles of a rectangle, split along a diagonal.
+    fn rectangle(x0: f32, y0: f32, x1: f32, y1: f32) -> [[(f32, f32); 3]; 2] {
+        [[(x0, y0), (x1, y0), (x1, y1)], [(x0, y0), (x1, y1), (x0, y1)]]
+    }
+
+    fn columns(covered: &HashMap<(u32, u32), u32>) -> Vec<u32> {
+        let mut columns: Vec<u32> = covered.keys().map(|&(x, _)| x).collect();
+        columns.sort();
+        columns.dedup();
+        columns
+    }
+
+    #[test]
+    fn shared_edges_cover_pixels_once() {
+        // Every edge goes through pixel centers.
+        let mut triangles = rectangle(0.5, 0.5, 4.5, 4.5).to_vec();
+        triangles.extend(rectangle(4.5, 0.5, 7.5, 4.5).iter().cloned());
+        triangles.extend(rectangle(0.5, 4.5, 7.5, 7.5).iter().cloned());
+        let covered = coverage(&triangles);
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let expected = if x < 7 && y < 7 { 1 } else { 0 };
+                assert_eq!(covered.get(&(x, y)).cloned().unwrap_or(0), expected, "pixel {}, {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn top_and_left_edges_are_inside() {
+        // Pixel centers on the left and top edges are covered, those on the
+        // right and bottom edges aren't, whatever the winding order.
+        let covered = coverage(&rectangle(1.5, 1.5, 3.5, 3.5));
+        let mut pixels: Vec<(u32, u32)> = covered.keys().cloned().collect();
+        pixels.sort();
+        assert_eq!(pixels, vec![(1, 1), (1, 2), (2, 1), (2, 2)]);
+
+        let reversed: Vec<[(f32, f32); 3]> = rectangle(1.5, 1.5, 3.5, 3.5).iter().map(|t| [t[0], t[2], t[1]]).collect();
+        assert_eq!(coverage(&reversed), covered);
+    }
+
+    #[test]
+    fn vertices_snap_to_sub_pixels() {
+        assert_eq!(snap(0.5), HALF);
+        assert_eq!(snap(2.0 + 0.4 * GRANULARITY), 2 * ONE);
+        assert_eq!(snap(2.0 + 0.6 * GRANULARITY), 2 * ONE + 1);
+
+        // A left edge less than half a sub-pixel right of the centers of
+        // column 2 snaps onto them and covers them.
+        assert_eq!(columns(&coverage(&rectangle(2.5 + 0.4 * GRANULARITY, 0.0, 5.0, 4.0))), vec![2, 3, 4]);
+        assert_eq!(columns(&coverage(&rectangle(2.5 + GRANULARITY, 0.0, 5.0, 4.0))), vec![3, 4]);
+    }
+
+    #[test]
+    fn degenerate_triangles_cover_nothing() {
+        assert!(coverage(&[[(1.0, 1.0), (4.0, 4.0), (6.0, 6.0)]]).is_empty());
+        // Collapses to a line once snapped.
+        assert!(coverage(&[[(1.0, 1.0), (6.0, 1.0), (3.0, 1.0 + 0.4 * GRANULARITY)]]).is_empty());
+    }
+}
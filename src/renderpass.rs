@@ -0,0 +1,345 @@
+use {std, vk};
+
+use format::{self, Channel};
+use image::{self, ImageView, Region};
+use pipeline::{Rect, slice};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Attachment {
+    pub format: vk::Format,
+    pub samples: u32,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub stencil_load_op: vk::AttachmentLoadOp,
+    pub stencil_store_op: vk::AttachmentStoreOp,
+    pub initial_layout: vk::ImageLayout,
+    pub final_layout: vk::ImageLayout,
+}
+
+impl Attachment {
+    fn from_vk(a: &vk::AttachmentDescription) -> Attachment {
+        Attachment {
+            format: a.format,
+            samples: a.samples,
+            load_op: a.loadOp,
+            store_op: a.storeOp,
+            stencil_load_op: a.stencilLoadOp,
+            stencil_store_op: a.stencilStoreOp,
+            initial_layout: a.initialLayout,
+            final_layout: a.finalLayout,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AttachmentReference {
+    pub attachment: u32,
+    pub layout: vk::ImageLayout,
+}
+
+impl AttachmentReference {
+    fn from_vk(r: &vk::AttachmentReference) -> AttachmentReference {
+        AttachmentReference {
+            attachment: r.attachment,
+            layout: r.layout,
+        }
+    }
+
+    /// Index of the attachment, or `None` for `ATTACHMENT_UNUSED`.
+    pub fn index(&self) -> Option<usize> {
+        if self.attachment == vk::ATTACHMENT_UNUSED {
+            None
+        } else {
+            Some(self.attachment as usize)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Subpass {
+    pub input_attachments: Vec<AttachmentReference>,
+    pub color_attachments: Vec<AttachmentReference>,
+    /// One per color attachment, or empty if nothing is resolved.
+    pub resolve_attachments: Vec<AttachmentReference>,
+    pub depth_stencil_attachment: Option<AttachmentReference>,
+    pub preserve_attachments: Vec<u32>,
+}
+
+impl Subpass {
+    unsafe fn from_vk(s: &vk::SubpassDescription) -> Subpass {
+        Subpass {
+            input_attachments: slice(s.pInputAttachments, s.inputAttachmentCount).iter()
+                .map(AttachmentReference::from_vk).collect(),
+            color_attachments: slice(s.pColorAttachments, s.colorAttachmentCount).iter()
+                .map(AttachmentReference::from_vk).collect(),
+            resolve_attachments: slice(s.pResolveAttachments, s.colorAttachmentCount).iter()
+                .map(AttachmentReference::from_vk).collect(),
+            depth_stencil_attachment: s.pDepthStencilAttachment.as_ref()
+                .map(AttachmentReference::from_vk)
+                .and_then(|r| r.index().map(|_| r)),
+            preserve_attachments: slice(s.pPreserveAttachments, s.preserveAttachmentCount).to_vec(),
+        }
+    }
+
+    /// Whether the subpass reads or writes an attachment. Preserving an
+    /// attachment doesn't count as a use.
+    pub fn uses(&self, attachment: usize) -> bool {
+        self.input_attachments.iter()
+            .chain(self.color_attachments.iter())
+            .chain(self.resolve_attachments.iter())
+            .chain(self.depth_stencil_attachment.iter())
+            .any(|r| r.index() == Some(attachment))
+    }
+}
+
+/// An execution and memory dependency between two subpasses, or between a
+/// subpass and the commands outside the render pass for `SUBPASS_EXTERNAL`.
+#[derive(Clone, Copy, Debug)]
+pub struct Dependency {
+    pub src_subpass: u32,
+    pub dst_subpass: u32,
+    pub src_stage_mask: vk::PipelineStageFlags,
+    pub dst_stage_mask: vk::PipelineStageFlags,
+    pub src_access_mask: vk::AccessFlags,
+    pub dst_access_mask: vk::AccessFlags,
+    pub flags: vk::DependencyFlags,
+}
+
+impl Dependency {
+    fn from_vk(d: &vk::SubpassDependency) -> Dependency {
+        Dependency {
+            src_subpass: d.srcSubpass,
+            dst_subpass: d.dstSubpass,
+            src_stage_mask: d.srcStageMask,
+            dst_stage_mask: d.dstStageMask,
+            src_access_mask: d.srcAccessMask,
+            dst_access_mask: d.dstAccessMask,
+            flags: d.dependencyFlags,
+        }
+    }
+}
+
+/// A render pass. Subpasses run one after the other, and every draw of a
+/// subpass has finished writing its attachments before the next subpass
+/// begins, so the dependencies between them hold without extra work. They
+/// are kept so that anything reordering rendering can respect them.
+pub struct RenderPass {
+    pub attachments: Vec<Attachment>,
+    pub subpasses: Vec<Subpass>,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl RenderPass {
+    pub unsafe fn new(info: &vk::RenderPassCreateInfo) -> RenderPass {
+        RenderPass {
+            attachments: slice(info.pAttachments, info.attachmentCount).iter().map(Attachment::from_vk).collect(),
+            subpasses: slice(info.pSubpasses, info.subpassCount).iter().map(|s| Subpass::from_vk(s)).collect(),
+            dependencies: slice(info.pDependencies, info.dependencyCount).iter().map(Dependency::from_vk).collect(),
+        }
+    }
+
+    /// The subpass where an attachment is first used, where its load op
+    /// applies.
+    pub fn first_use(&self, attachment: usize) -> Option<usize> {
+        self.subpasses.iter().position(|s| s.uses(attachment))
+    }
+}
+
+pub struct Framebuffer {
+    pub attachments: Vec<*const ImageView>,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+}
+
+impl Framebuffer {
+    pub unsafe fn new(info: &vk::FramebufferCreateInfo) -> Framebuffer {
+        Framebuffer {
+            attachments: slice(info.pAttachments, info.attachmentCount).iter()
+                .map(|&v| v as *const ImageView).collect(),
+            width: info.width,
+            height: info.height,
+            layers: info.layers,
+        }
+    }
+
+    pub unsafe fn attachment(&self, index: usize) -> &ImageView {
+        &*self.attachments[index]
+    }
+
+    /// Number of layers rendered to in an attachment.
+    unsafe fn layers(&self, index: usize) -> u32 {
+        std::cmp::min(self.layers, self.attachment(index).layer_count)
+    }
+}
+
+/// Writes `value` with the channels in `mask` to every sample of the texels
+/// of `rect` in the given layers of the first mip level of a view. The rect
+/// is clipped to the view.
+pub unsafe fn clear_view(view: &ImageView, value: &[u32; 4], mask: u32, rect: &Rect, base_layer: u32,
+                         layer_count: u32) {
+    let extent = view.extent();
+    let x0 = std::cmp::max(rect.x, 0) as u32;
+    let y0 = std::cmp::max(rect.y, 0) as u32;
+    let x1 = std::cmp::min(rect.x as i64 + rect.width as i64, extent[0] as i64);
+    let y1 = std::cmp::min(rect.y as i64 + rect.height as i64, extent[1] as i64);
+    let samples = view.image().samples;
+    let size = view.info.size as usize;
+
+    for layer in base_layer..base_layer + layer_count {
+        for y in y0..std::cmp::max(y1, 0) as u32 {
+            for x in x0..std::cmp::max(x1, 0) as u32 {
+                for sample in 0..samples {
+                    let texel = view.texel(layer, x, y, sample);
+                    format::encode(&view.info, value, mask, std::slice::from_raw_parts_mut(texel, size));
+                }
+            }
+        }
+    }
+}
+
+/// A render pass instance between `CmdBeginRenderPass` and
+/// `CmdEndRenderPass`.
+///
+/// Attachments are rendered to in place, so image layouts don't change how
+/// they are stored and layout transitions need no work. For the same reason
+/// `ATTACHMENT_STORE_OP_STORE` is implicit, and `DONT_CARE` leaves the
+/// rendered contents, which are as good as any undefined contents.
+/// Attachments that a subpass doesn't use, including its preserve
+/// attachments, are left alone until their next use.
+pub struct RenderPassState {
+    pub render_pass: *const RenderPass,
+    pub framebuffer: *const Framebuffer,
+    pub area: Rect,
+    /// Clear values by attachment, as the raw words of `VkClearValue`.
+    pub clear_values: Vec<[u32; 4]>,
+    pub subpass: usize,
+}
+
+impl RenderPassState {
+    pub unsafe fn render_pass(&self) -> &RenderPass {
+        &*self.render_pass
+    }
+
+    pub unsafe fn framebuffer(&self) -> &Framebuffer {
+        &*self.framebuffer
+    }
+
+    /// The render area clipped to the framebuffer.
+    pub unsafe fn bounds(&self) -> Rect {
+        let framebuffer = self.framebuffer();
+        self.area.intersection(&Rect {
+            x: 0,
+            y: 0,
+            width: framebuffer.width,
+            height: framebuffer.height,
+        })
+    }
+
+    pub unsafe fn current_subpass(&self) -> &Subpass {
+        &self.render_pass().subpasses[self.subpass]
+    }
+
+    unsafe fn view(&self, reference: &AttachmentReference) -> Option<&ImageView> {
+        reference.index().map(|i| self.framebuffer().attachment(i))
+    }
+
+    /// Views of the color attachments of the current subpass, by location.
+    pub unsafe fn color_attachments(&self) -> Vec<Option<&ImageView>> {
+        self.current_subpass().color_attachments.iter().map(|r| self.view(r)).collect()
+    }
+
+    pub unsafe fn depth_stencil_attachment(&self) -> Option<&ImageView> {
+        self.current_subpass().depth_stencil_attachment.as_ref().and_then(|r| self.view(r))
+    }
+
+    /// Views of the input attachments of the current subpass, by input
+    /// attachment index.
+    pub unsafe fn input_attachments(&self) -> Vec<Option<*const ImageView>> {
+        self.current_subpass().input_attachments.iter()
+            .map(|r| self.view(r).map(|v| v as *const ImageView))
+            .collect()
+    }
+
+    pub unsafe fn begin(&self) {
+        self.begin_subpass();
+    }
+
+    pub unsafe fn next_subpass(&mut self) {
+        self.end_subpass();
+        self.subpass += 1;
+        self.begin_subpass();
+    }
+
+    pub unsafe fn end(&self) {
+        self.end_subpass();
+    }
+
+    /// Applies the load ops of the attachments first used in the current
+    /// subpass. `LOAD` keeps the contents and `DONT_CARE` may leave anything,
+    /// so only `CLEAR` writes to the attachment.
+    unsafe fn begin_subpass(&self) {
+        let render_pass = self.render_pass();
+        let framebuffer = self.framebuffer();
+
+        for (i, attachment) in render_pass.attachments.iter().enumerate() {
+            if render_pass.first_use(i) != Some(self.subpass) {
+                continue;
+            }
+
+            let view = framebuffer.attachment(i);
+            let mut mask = 0;
+            if view.info.is_color() {
+                if attachment.load_op == vk::ATTACHMENT_LOAD_OP_CLEAR {
+                    mask = 0xF;
+                }
+            } else {
+                if attachment.load_op == vk::ATTACHMENT_LOAD_OP_CLEAR && view.info.has_channel(Channel::D) {
+                    mask |= 1 << 0;
+                }
+                if attachment.stencil_load_op == vk::ATTACHMENT_LOAD_OP_CLEAR && view.info.has_channel(Channel::S) {
+                    mask |= 1 << 1;
+                }
+            }
+
+            if mask != 0 {
+                let value = self.clear_values.get(i).cloned().unwrap_or([0; 4]);
+                clear_view(view, &value, mask, &self.area, 0, framebuffer.layers(i));
+            }
+        }
+    }
+
+    /// Resolves the multisampled color attachments of the current subpass
+    /// into their resolve attachments.
+    unsafe fn end_subpass(&self) {
+        let subpass = self.current_subpass();
+        let framebuffer = self.framebuffer();
+
+        for (color, resolve) in subpass.color_attachments.iter().zip(subpass.resolve_attachments.iter()) {
+            let (src, dst) = match (color.index(), resolve.index()) {
+                (Some(src), Some(dst)) => (src, dst),
+                _ => continue
+            };
+
+            let src_view = framebuffer.attachment(src);
+            let dst_view = framebuffer.attachment(dst);
+            let extent = src_view.extent();
+            let x = std::cmp::max(self.area.x, 0);
+            let y = std::cmp::max(self.area.y, 0);
+            let width = std::cmp::min(self.area.x as i64 + self.area.width as i64, extent[0] as i64) - x as i64;
+            let height = std::cmp::min(self.area.y as i64 + self.area.height as i64, extent[1] as i64) - y as i64;
+            if width <= 0 || height <= 0 {
+                continue;
+            }
+
+            let layers = std::cmp::min(framebuffer.layers(src), framebuffer.layers(dst));
+            image::resolve(src_view.image(), dst_view.image(), &Region {
+                src: src_view.subresource(layers),
+                src_offset: [x, y, 0],
+                dst: dst_view.subresource(layers),
+                dst_offset: [x, y, 0],
+                extent: [width as u32, height as u32, 1],
+            });
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use {std, vk};
+
+use depth;
+use format::NumericFormat;
+use image::ImageView;
+use spirv::{bits_to_f32, f32_to_bits};
+
+/// Largest magnitude of the sum of the sampler and shader LOD biases, as
+/// reported in `maxSamplerLodBias`.
+pub const MAX_LOD_BIAS: f32 = 15.0;
+
+/// Largest number of samples taken by anisotropic filtering, as reported in
+/// `maxSamplerAnisotropy`.
+pub const MAX_ANISOTROPY: f32 = 16.0;
+
+/// For each cube face in layer order, the component of the direction and
+/// its sign that give the face coordinates s and t and the major axis.
+const CUBE_FACES: [[(usize, f32); 3]; 6] = [
+    [(2, -1.0), (1, -1.0), (0, 1.0)],
+    [(2, 1.0), (1, -1.0), (0, -1.0)],
+    [(0, 1.0), (2, 1.0), (1, 1.0)],
+    [(0, 1.0), (2, -1.0), (1, -1.0)],
+    [(0, 1.0), (1, -1.0), (2, 1.0)],
+    [(0, -1.0), (1, -1.0), (2, -1.0)],
+];
+
+/// How the level of detail of a sample is chosen.
+#[derive(Clone, Copy, Debug)]
+pub enum Lod {
+    /// From the derivatives of the coordinates along x and y, plus the bias
+    /// given by the shader.
+    Gradients { dx: [f32; 3], dy: [f32; 3], bias: f32 },
+    Explicit(f32),
+}
+
+pub struct Sampler {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    /// Address modes for u, v and w.
+    pub address_modes: [vk::SamplerAddressMode; 3],
+    pub mip_lod_bias: f32,
+    pub anisotropy_enable: bool,
+    pub max_anisotropy: f32,
+    pub compare_enable: bool,
+    pub compare_op: vk::CompareOp,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub border_color: vk::BorderColor,
+    pub unnormalized_coordinates: bool,
+}
+
+/// Index of the cube face a direction points at.
+fn cube_face(d: &[f32; 3]) -> usize {
+    let (x, y, z) = (d[0].abs(), d[1].abs(), d[2].abs());
+    if z >= x && z >= y {
+        if d[2] >= 0.0 { 4 } else { 5 }
+    } else if y >= x {
+        if d[1] >= 0.0 { 2 } else { 3 }
+    } else {
+        if d[0] >= 0.0 { 0 } else { 1 }
+    }
+}
+
+/// The direction pointing at the face coordinates `sc` and `tc`, which
+/// range from -1 to 1 across the face.
+fn cube_direction(face: usize, sc: f32, tc: f32) -> [f32; 3] {
+    let axes = &CUBE_FACES[face];
+    let mut d = [0.0; 3];
+    d[axes[0].0] = axes[0].1 * sc;
+    d[axes[1].0] = axes[1].1 * tc;
+    d[axes[2].0] = axes[2].1;
+    d
+}
+
+/// Rounds to the nearest integer, with ties to even, as array layers are
+/// selected.
+fn round_even(x: f32) -> f32 {
+    let floor = x.floor();
+    let fraction = x - floor;
+    if fraction > 0.5 || (fraction == 0.5 && floor % 2.0 != 0.0) { floor + 1.0 } else { floor }
+}
+
+/// Applies an address mode to an integer texel coordinate. Returns `None`
+/// for border texels.
+fn wrap(mode: vk::SamplerAddressMode, i: i32, size: i32) -> Option<i32> {
+    let mirror = |i: i32| if i >= 0 { i } else { -(1 + i) };
+    let clamp = |i: i32| std::cmp::max(0, std::cmp::min(i, size - 1));
+
+    match mode {
+        vk::SAMPLER_ADDRESS_MODE_REPEAT => Some(((i % size) + size) % size),
+        vk::SAMPLER_ADDRESS_MODE_MIRRORED_REPEAT => {
+            let t = ((i % (2 * size)) + 2 * size) % (2 * size);
+            Some(if t < size { t } else { 2 * size - 1 - t })
+        }
+        vk::SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER => if i >= 0 && i < size { Some(i) } else { None },
+        vk::SAMPLER_ADDRESS_MODE_MIRROR_CLAMP_TO_EDGE => Some(clamp(mirror(i))),
+        _ => Some(clamp(i))
+    }
+}
+
+fn length(v: &[f32; 3]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+/// The parts of a sample that don't depend on the mip level.
+struct Lookup<'a> {
+    sampler: &'a Sampler,
+    view: &'a ImageView,
+    /// Number of texel coordinates: 3 for 3D images and 2 for cube faces.
+    dims: usize,
+    cube: bool,
+    /// Layer of the view sampled from, or the first layer of the cube.
+    layer: u32,
+    /// Whether texels are integers, which are never filtered.
+    integer: bool,
+    /// The reference value of a depth comparison.
+    reference: Option<f32>,
+}
+
+impl<'a> Lookup<'a> {
+    fn border(&self) -> [u32; 4] {
+        let (zero, one) = if self.integer { (0, 1) } else { (0, f32_to_bits(1.0)) };
+        match self.sampler.border_color {
+            vk::BORDER_COLOR_FLOAT_OPAQUE_BLACK | vk::BORDER_COLOR_INT_OPAQUE_BLACK => [zero, zero, zero, one],
+            vk::BORDER_COLOR_FLOAT_OPAQUE_WHITE | vk::BORDER_COLOR_INT_OPAQUE_WHITE => [one; 4],
+            _ => [zero; 4]
+        }
+    }
+
+    /// Reads a texel for filtering. With depth comparison, this is one if
+    /// the comparison with the depth of the texel passes and zero otherwise.
+    unsafe fn fetch(&self, level: u32, face: usize, texel: [i32; 3]) -> [u32; 4] {
+        let value = self.texel(level, face, texel);
+        match self.reference {
+            Some(reference) => {
+                let passed = depth::compare(self.sampler.compare_op, reference, bits_to_f32(value[0]));
+                [f32_to_bits(if passed { 1.0 } else { 0.0 }), 0, 0, f32_to_bits(1.0)]
+            }
+            None => value
+        }
+    }
+
+    /// Reads a texel of a level of the view, applying the address modes.
+    /// Texels past the edge of a cube face are read from the adjacent face.
+    unsafe fn texel(&self, level: u32, mut face: usize, mut texel: [i32; 3]) -> [u32; 4] {
+        let view = self.view;
+        let image = view.image();
+        let level = view.base_level + level;
+        let extent = image.level_extent(level);
+        let size = [extent[0] as i32, extent[1] as i32, extent[2] as i32];
+
+        if self.cube {
+            if texel[0] < 0 || texel[0] >= size[0] || texel[1] < 0 || texel[1] >= size[1] {
+                let sc = 2.0 * (texel[0] as f32 + 0.5) / size[0] as f32 - 1.0;
+                let tc = 2.0 * (texel[1] as f32 + 0.5) / size[1] as f32 - 1.0;
+                let (f, st) = project_cube(&cube_direction(face, sc, tc));
+                face = f;
+                texel = [(st[0] * size[0] as f32).floor() as i32, (st[1] * size[1] as f32).floor() as i32, 0];
+            }
+            for i in 0..2 {
+                texel[i] = std::cmp::max(0, std::cmp::min(texel[i], size[i] - 1));
+            }
+        } else {
+            for i in 0..self.dims {
+                texel[i] = match wrap(self.sampler.address_modes[i], texel[i], size[i]) {
+                    Some(t) => t,
+                    None => return self.border()
+                };
+            }
+        }
+
+        let layer = view.base_layer + self.layer + face as u32;
+        let texel = image.texel(level, layer, texel[0] as u32, texel[1] as u32, texel[2] as u32, 0);
+        view.decode(std::slice::from_raw_parts(texel, image.info.size as usize))
+    }
+
+    /// Samples one level at the texel coordinates `u`.
+    unsafe fn filter(&self, level: u32, face: usize, u: &[f32; 3], linear: bool, offset: &[i32; 3]) -> [u32; 4] {
+        if !linear || self.integer {
+            let mut texel = [0; 3];
+            for i in 0..self.dims {
+                texel[i] = u[i].floor() as i32 + offset[i];
+            }
+            return self.fetch(level, face, texel);
+        }
+
+        let mut base = [0; 3];
+        let mut fraction = [0.0; 3];
+        for i in 0..self.dims {
+            let t = u[i] - 0.5;
+            base[i] = t.floor() as i32 + offset[i];
+            fraction[i] = t - t.floor();
+        }
+
+        let mut sum = [0.0; 4];
+        for corner in 0..1 << self.dims {
+            let mut texel = base;
+            let mut weight = 1.0;
+            for i in 0..self.dims {
+                if corner & 1 << i != 0 {
+                    texel[i] += 1;
+                    weight *= fraction[i];
+                } else {
+                    weight *= 1.0 - fraction[i];
+                }
+            }
+            if weight == 0.0 {
+                continue;
+            }
+
+            let value = self.fetch(level, face, texel);
+            for c in 0..4 {
+                sum[c] += weight * bits_to_f32(value[c]);
+            }
+        }
+
+        [f32_to_bits(sum[0]), f32_to_bits(sum[1]), f32_to_bits(sum[2]), f32_to_bits(sum[3])]
+    }
+}
+
+/// The face a direction points at and the coordinates on that face, which
+/// range from 0 to 1.
+fn project_cube(d: &[f32; 3]) -> (usize, [f32; 2]) {
+    let face = cube_face(d);
+    let axes = &CUBE_FACES[face];
+    let ma = d[axes[2].0].abs();
+    let s = 0.5 * (axes[0].1 * d[axes[0].0] / ma + 1.0);
+    let t = 0.5 * (axes[1].1 * d[axes[1].0] / ma + 1.0);
+    (face, [s, t])
+}
+
+/// Derivatives of the face coordinates of a direction `d` on `face`, given
+/// the derivatives `dd` of the direction.
+fn project_cube_gradient(face: usize, d: &[f32; 3], dd: &[f32; 3]) -> [f32; 3] {
+    let axes = &CUBE_FACES[face];
+    let ma = axes[2].1 * d[axes[2].0];
+    let dma = axes[2].1 * dd[axes[2].0];
+    let mut gradient = [0.0; 3];
+    for i in 0..2 {
+        let (c, dc) = (axes[i].1 * d[axes[i].0], axes[i].1 * dd[axes[i].0]);
+        gradient[i] = 0.5 * (dc * ma - c * dma) / (ma * ma);
+    }
+    gradient
+}
+
+impl Sampler {
+    pub fn new(info: &vk::SamplerCreateInfo) -> Sampler {
+        Sampler {
+            mag_filter: info.magFilter,
+            min_filter: info.minFilter,
+            mipmap_mode: info.mipmapMode,
+            address_modes: [info.addressModeU, info.addressModeV, info.addressModeW],
+            mip_lod_bias: info.mipLodBias,
+            anisotropy_enable: info.anisotropyEnable != vk::FALSE,
+            max_anisotropy: info.maxAnisotropy,
+            compare_enable: info.compareEnable != vk::FALSE,
+            compare_op: info.compareOp,
+            min_lod: info.minLod,
+            max_lod: info.maxLod,
+            border_color: info.borderColor,
+            unnormalized_coordinates: info.unnormalizedCoordinates != vk::FALSE,
+        }
+    }
+
+    /// Samples a view at `coordinate`, which has the components a shader
+    /// passes for the view type: the texel coordinates, or the direction for
+    /// cubes, followed by the array layer for arrays. `offset` is added to
+    /// the texel coordinates of every level. The result is decoded as
+    /// `format::decode` does, after applying the component swizzle of the
+    /// view.
+    ///
+    /// If the sampler compares depth, texels are compared with `reference`
+    /// before filtering, so that linear filtering gives the fraction of
+    /// passing texels in the 2x2 footprint.
+    pub unsafe fn sample(&self, view: &ImageView, coordinate: &[f32], lod: Lod, offset: &[i32; 3],
+                         reference: Option<f32>) -> [u32; 4] {
+        let coordinate = |i: usize| coordinate.get(i).cloned().unwrap_or(0.0);
+        let (dims, arrayed, cube) = match view.view_type {
+            vk::IMAGE_VIEW_TYPE_1D => (1, false, false),
+            vk::IMAGE_VIEW_TYPE_1D_ARRAY => (1, true, false),
+            vk::IMAGE_VIEW_TYPE_2D => (2, false, false),
+            vk::IMAGE_VIEW_TYPE_2D_ARRAY => (2, true, false),
+            vk::IMAGE_VIEW_TYPE_CUBE => (3, false, true),
+            vk::IMAGE_VIEW_TYPE_CUBE_ARRAY => (3, true, true),
+            _ => (3, false, false)
+        };
+
+        let layer = if arrayed {
+            let layers = if cube { view.layer_count / 6 } else { view.layer_count };
+            let layer = round_even(coordinate(dims)).max(0.0).min(layers as f32 - 1.0) as u32;
+            if cube { layer * 6 } else { layer }
+        } else {
+            0
+        };
+
+        let (mut gradients, bias) = match lod {
+            Lod::Gradients { dx, dy, bias } => (Some((dx, dy)), bias),
+            Lod::Explicit(_) => (None, 0.0)
+        };
+
+        // Coordinates normalized to the level, or on the cube face.
+        let mut st = [coordinate(0), coordinate(1), coordinate(2)];
+        let mut face = 0;
+        if cube {
+            let (f, face_st) = project_cube(&st);
+            gradients = gradients.map(|(dx, dy)| {
+                (project_cube_gradient(f, &st, &dx), project_cube_gradient(f, &st, &dy))
+            });
+            face = f;
+            st = [face_st[0], face_st[1], 0.0];
+        }
+
+        let lookup = Lookup {
+            sampler: self,
+            view: view,
+            dims: if cube { 2 } else { dims },
+            cube: cube,
+            layer: layer,
+            integer: view.is_integer(),
+            // Fixed-point depth can't be compared with values outside [0, 1].
+            reference: match reference {
+                _ if !self.compare_enable => None,
+                Some(r) if view.info.numeric == NumericFormat::Unorm => Some(r.max(0.0).min(1.0)),
+                r => r
+            },
+        };
+
+        let extent = view.extent();
+        let scale = |level: u32, i: usize| {
+            if self.unnormalized_coordinates { 1.0 } else { std::cmp::max(extent[i] >> level, 1) as f32 }
+        };
+
+        // The array layer doesn't count towards the level of detail.
+        // Anisotropic filtering takes several samples spread along the longer
+        // of the gradients, with the level of detail of the length between
+        // them.
+        let mut axis = [0.0; 3];
+        let mut samples = 1;
+        let lambda = match (lod, gradients) {
+            (Lod::Explicit(lod), _) => lod,
+            (_, Some((dx, dy))) => {
+                let mut scaled = ([0.0; 3], [0.0; 3]);
+                for i in 0..lookup.dims {
+                    scaled.0[i] = dx[i] * scale(0, i);
+                    scaled.1[i] = dy[i] * scale(0, i);
+                }
+                let (px, py) = (length(&scaled.0), length(&scaled.1));
+                let (p_max, p_min) = (px.max(py), px.min(py));
+
+                if self.anisotropy_enable && !lookup.integer && p_min > 0.0 {
+                    let n = (p_max / p_min).ceil().min(self.max_anisotropy.max(1.0).min(MAX_ANISOTROPY));
+                    samples = n as u32;
+                    axis = if px >= py { dx } else { dy };
+                    (p_max / n).log2()
+                } else {
+                    p_max.log2()
+                }
+            }
+            _ => 0.0
+        };
+        let bias = (self.mip_lod_bias + bias).max(-MAX_LOD_BIAS).min(MAX_LOD_BIAS);
+        let lambda = if self.unnormalized_coordinates {
+            0.0
+        } else {
+            (lambda + bias).max(self.min_lod).min(self.max_lod)
+        };
+
+        let linear = if lambda <= 0.0 { self.mag_filter } else { self.min_filter } == vk::FILTER_LINEAR;
+        let max_level = view.level_count.saturating_sub(1) as f32;
+        let d = lambda.max(0.0).min(max_level);
+        let levels = if self.mipmap_mode == vk::SAMPLER_MIPMAP_MODE_LINEAR && !lookup.integer {
+            let low = d.floor();
+            vec![(low as u32, 1.0 - (d - low)), ((low + 1.0).min(max_level) as u32, d - low)]
+        } else {
+            vec![(((d + 0.5).ceil() - 1.0).max(0.0) as u32, 1.0)]
+        };
+
+        let mut value = [0; 4];
+        let mut sum = [0.0; 4];
+        for &(level, weight) in &levels {
+            if weight == 0.0 {
+                continue;
+            }
+            for k in 0..samples {
+                let t = (k as f32 + 0.5) / samples as f32 - 0.5;
+                let mut u = [0.0; 3];
+                for i in 0..lookup.dims {
+                    u[i] = (st[i] + t * axis[i]) * scale(level, i);
+                }
+
+                value = lookup.filter(level, face, &u, linear, offset);
+                for c in 0..4 {
+                    sum[c] += weight / samples as f32 * bits_to_f32(value[c]);
+                }
+            }
+        }
+        if levels.len() > 1 || samples > 1 {
+            for c in 0..4 {
+                value[c] = f32_to_bits(sum[c]);
+            }
+        }
+
+        view.swizzle(value)
+    }
+}
@@ -0,0 +1,34 @@
+use spirv;
+
+/// A shader module created from SPIR-V code, along with the interface of
+/// each of its entry points.
+pub struct ShaderModule {
+    module: spirv::Module,
+    entry_points: Vec<spirv::EntryPoint>,
+}
+
+impl ShaderModule {
+    pub fn new(code: &[u32]) -> Result<ShaderModule, spirv::ParseError> {
+        let module = try!(spirv::Module::from_words(code));
+        let entry_points = spirv::reflect(&module);
+
+        Ok(ShaderModule {
+            module: module,
+            entry_points: entry_points,
+        })
+    }
+
+    pub fn module(&self) -> &spirv::Module {
+        &self.module
+    }
+
+    /// Entry points of the module with their stage, interface variables,
+    /// descriptor and push constant usage and specialization constants.
+    pub fn entry_points(&self) -> &[spirv::EntryPoint] {
+        &self.entry_points
+    }
+
+    pub fn entry_point(&self, name: &str) -> Option<&spirv::EntryPoint> {
+        self.entry_points.iter().find(|e| e.name == name)
+    }
+}
@@ -0,0 +1,622 @@
+#![allow(dead_code, bad_style)]
+
+pub const MAGIC_NUMBER: u32 = 0x07230203;
+
+pub type Id = u32;
+
+pub type Op = u16;
+pub const OP_NOP: u16 = 0;
+pub const OP_UNDEF: u16 = 1;
+pub const OP_SOURCE_CONTINUED: u16 = 2;
+pub const OP_SOURCE: u16 = 3;
+pub const OP_SOURCE_EXTENSION: u16 = 4;
+pub const OP_NAME: u16 = 5;
+pub const OP_MEMBER_NAME: u16 = 6;
+pub const OP_STRING: u16 = 7;
+pub const OP_LINE: u16 = 8;
+pub const OP_EXTENSION: u16 = 10;
+pub const OP_EXT_INST_IMPORT: u16 = 11;
+pub const OP_EXT_INST: u16 = 12;
+pub const OP_MEMORY_MODEL: u16 = 14;
+pub const OP_ENTRY_POINT: u16 = 15;
+pub const OP_EXECUTION_MODE: u16 = 16;
+pub const OP_CAPABILITY: u16 = 17;
+pub const OP_TYPE_VOID: u16 = 19;
+pub const OP_TYPE_BOOL: u16 = 20;
+pub const OP_TYPE_INT: u16 = 21;
+pub const OP_TYPE_FLOAT: u16 = 22;
+pub const OP_TYPE_VECTOR: u16 = 23;
+pub const OP_TYPE_MATRIX: u16 = 24;
+pub const OP_TYPE_IMAGE: u16 = 25;
+pub const OP_TYPE_SAMPLER: u16 = 26;
+pub const OP_TYPE_SAMPLED_IMAGE: u16 = 27;
+pub const OP_TYPE_ARRAY: u16 = 28;
+pub const OP_TYPE_RUNTIME_ARRAY: u16 = 29;
+pub const OP_TYPE_STRUCT: u16 = 30;
+pub const OP_TYPE_OPAQUE: u16 = 31;
+pub const OP_TYPE_POINTER: u16 = 32;
+pub const OP_TYPE_FUNCTION: u16 = 33;
+pub const OP_TYPE_EVENT: u16 = 34;
+pub const OP_TYPE_DEVICE_EVENT: u16 = 35;
+pub const OP_TYPE_RESERVE_ID: u16 = 36;
+pub const OP_TYPE_QUEUE: u16 = 37;
+pub const OP_TYPE_PIPE: u16 = 38;
+pub const OP_TYPE_FORWARD_POINTER: u16 = 39;
+pub const OP_CONSTANT_TRUE: u16 = 41;
+pub const OP_CONSTANT_FALSE: u16 = 42;
+pub const OP_CONSTANT: u16 = 43;
+pub const OP_CONSTANT_COMPOSITE: u16 = 44;
+pub const OP_CONSTANT_SAMPLER: u16 = 45;
+pub const OP_CONSTANT_NULL: u16 = 46;
+pub const OP_SPEC_CONSTANT_TRUE: u16 = 48;
+pub const OP_SPEC_CONSTANT_FALSE: u16 = 49;
+pub const OP_SPEC_CONSTANT: u16 = 50;
+pub const OP_SPEC_CONSTANT_COMPOSITE: u16 = 51;
+pub const OP_SPEC_CONSTANT_OP: u16 = 52;
+pub const OP_FUNCTION: u16 = 54;
+pub const OP_FUNCTION_PARAMETER: u16 = 55;
+pub const OP_FUNCTION_END: u16 = 56;
+pub const OP_FUNCTION_CALL: u16 = 57;
+pub const OP_VARIABLE: u16 = 59;
+pub const OP_IMAGE_TEXEL_POINTER: u16 = 60;
+pub const OP_LOAD: u16 = 61;
+pub const OP_STORE: u16 = 62;
+pub const OP_COPY_MEMORY: u16 = 63;
+pub const OP_COPY_MEMORY_SIZED: u16 = 64;
+pub const OP_ACCESS_CHAIN: u16 = 65;
+pub const OP_IN_BOUNDS_ACCESS_CHAIN: u16 = 66;
+pub const OP_PTR_ACCESS_CHAIN: u16 = 67;
+pub const OP_ARRAY_LENGTH: u16 = 68;
+pub const OP_GENERIC_PTR_MEM_SEMANTICS: u16 = 69;
+pub const OP_IN_BOUNDS_PTR_ACCESS_CHAIN: u16 = 70;
+pub const OP_DECORATE: u16 = 71;
+pub const OP_MEMBER_DECORATE: u16 = 72;
+pub const OP_DECORATION_GROUP: u16 = 73;
+pub const OP_GROUP_DECORATE: u16 = 74;
+pub const OP_GROUP_MEMBER_DECORATE: u16 = 75;
+pub const OP_VECTOR_EXTRACT_DYNAMIC: u16 = 77;
+pub const OP_VECTOR_INSERT_DYNAMIC: u16 = 78;
+pub const OP_VECTOR_SHUFFLE: u16 = 79;
+pub const OP_COMPOSITE_CONSTRUCT: u16 = 80;
+pub const OP_COMPOSITE_EXTRACT: u16 = 81;
+pub const OP_COMPOSITE_INSERT: u16 = 82;
+pub const OP_COPY_OBJECT: u16 = 83;
+pub const OP_TRANSPOSE: u16 = 84;
+pub const OP_SAMPLED_IMAGE: u16 = 86;
+pub const OP_IMAGE_SAMPLE_IMPLICIT_LOD: u16 = 87;
+pub const OP_IMAGE_SAMPLE_EXPLICIT_LOD: u16 = 88;
+pub const OP_IMAGE_SAMPLE_DREF_IMPLICIT_LOD: u16 = 89;
+pub const OP_IMAGE_SAMPLE_DREF_EXPLICIT_LOD: u16 = 90;
+pub const OP_IMAGE_SAMPLE_PROJ_IMPLICIT_LOD: u16 = 91;
+pub const OP_IMAGE_SAMPLE_PROJ_EXPLICIT_LOD: u16 = 92;
+pub const OP_IMAGE_SAMPLE_PROJ_DREF_IMPLICIT_LOD: u16 = 93;
+pub const OP_IMAGE_SAMPLE_PROJ_DREF_EXPLICIT_LOD: u16 = 94;
+pub const OP_IMAGE_FETCH: u16 = 95;
+pub const OP_IMAGE_GATHER: u16 = 96;
+pub const OP_IMAGE_DREF_GATHER: u16 = 97;
+pub const OP_IMAGE_READ: u16 = 98;
+pub const OP_IMAGE_WRITE: u16 = 99;
+pub const OP_IMAGE: u16 = 100;
+pub const OP_IMAGE_QUERY_FORMAT: u16 = 101;
+pub const OP_IMAGE_QUERY_ORDER: u16 = 102;
+pub const OP_IMAGE_QUERY_SIZE_LOD: u16 = 103;
+pub const OP_IMAGE_QUERY_SIZE: u16 = 104;
+pub const OP_IMAGE_QUERY_LOD: u16 = 105;
+pub const OP_IMAGE_QUERY_LEVELS: u16 = 106;
+pub const OP_IMAGE_QUERY_SAMPLES: u16 = 107;
+pub const OP_CONVERT_F_TO_U: u16 = 109;
+pub const OP_CONVERT_F_TO_S: u16 = 110;
+pub const OP_CONVERT_S_TO_F: u16 = 111;
+pub const OP_CONVERT_U_TO_F: u16 = 112;
+pub const OP_UCONVERT: u16 = 113;
+pub const OP_SCONVERT: u16 = 114;
+pub const OP_FCONVERT: u16 = 115;
+pub const OP_QUANTIZE_TO_F16: u16 = 116;
+pub const OP_CONVERT_PTR_TO_U: u16 = 117;
+pub const OP_SAT_CONVERT_S_TO_U: u16 = 118;
+pub const OP_SAT_CONVERT_U_TO_S: u16 = 119;
+pub const OP_CONVERT_U_TO_PTR: u16 = 120;
+pub const OP_PTR_CAST_TO_GENERIC: u16 = 121;
+pub const OP_GENERIC_CAST_TO_PTR: u16 = 122;
+pub const OP_GENERIC_CAST_TO_PTR_EXPLICIT: u16 = 123;
+pub const OP_BITCAST: u16 = 124;
+pub const OP_SNEGATE: u16 = 126;
+pub const OP_FNEGATE: u16 = 127;
+pub const OP_IADD: u16 = 128;
+pub const OP_FADD: u16 = 129;
+pub const OP_ISUB: u16 = 130;
+pub const OP_FSUB: u16 = 131;
+pub const OP_IMUL: u16 = 132;
+pub const OP_FMUL: u16 = 133;
+pub const OP_UDIV: u16 = 134;
+pub const OP_SDIV: u16 = 135;
+pub const OP_FDIV: u16 = 136;
+pub const OP_UMOD: u16 = 137;
+pub const OP_SREM: u16 = 138;
+pub const OP_SMOD: u16 = 139;
+pub const OP_FREM: u16 = 140;
+pub const OP_FMOD: u16 = 141;
+pub const OP_VECTOR_TIMES_SCALAR: u16 = 142;
+pub const OP_MATRIX_TIMES_SCALAR: u16 = 143;
+pub const OP_VECTOR_TIMES_MATRIX: u16 = 144;
+pub const OP_MATRIX_TIMES_VECTOR: u16 = 145;
+pub const OP_MATRIX_TIMES_MATRIX: u16 = 146;
+pub const OP_OUTER_PRODUCT: u16 = 147;
+pub const OP_DOT: u16 = 148;
+pub const OP_IADD_CARRY: u16 = 149;
+pub const OP_ISUB_BORROW: u16 = 150;
+pub const OP_UMUL_EXTENDED: u16 = 151;
+pub const OP_SMUL_EXTENDED: u16 = 152;
+pub const OP_ANY: u16 = 154;
+pub const OP_ALL: u16 = 155;
+pub const OP_IS_NAN: u16 = 156;
+pub const OP_IS_INF: u16 = 157;
+pub const OP_IS_FINITE: u16 = 158;
+pub const OP_IS_NORMAL: u16 = 159;
+pub const OP_SIGN_BIT_SET: u16 = 160;
+pub const OP_LESS_OR_GREATER: u16 = 161;
+pub const OP_ORDERED: u16 = 162;
+pub const OP_UNORDERED: u16 = 163;
+pub const OP_LOGICAL_EQUAL: u16 = 164;
+pub const OP_LOGICAL_NOT_EQUAL: u16 = 165;
+pub const OP_LOGICAL_OR: u16 = 166;
+pub const OP_LOGICAL_AND: u16 = 167;
+pub const OP_LOGICAL_NOT: u16 = 168;
+pub const OP_SELECT: u16 = 169;
+pub const OP_IEQUAL: u16 = 170;
+pub const OP_INOT_EQUAL: u16 = 171;
+pub const OP_UGREATER_THAN: u16 = 172;
+pub const OP_SGREATER_THAN: u16 = 173;
+pub const OP_UGREATER_THAN_EQUAL: u16 = 174;
+pub const OP_SGREATER_THAN_EQUAL: u16 = 175;
+pub const OP_ULESS_THAN: u16 = 176;
+pub const OP_SLESS_THAN: u16 = 177;
+pub const OP_ULESS_THAN_EQUAL: u16 = 178;
+pub const OP_SLESS_THAN_EQUAL: u16 = 179;
+pub const OP_FORD_EQUAL: u16 = 180;
+pub const OP_FUNORD_EQUAL: u16 = 181;
+pub const OP_FORD_NOT_EQUAL: u16 = 182;
+pub const OP_FUNORD_NOT_EQUAL: u16 = 183;
+pub const OP_FORD_LESS_THAN: u16 = 184;
+pub const OP_FUNORD_LESS_THAN: u16 = 185;
+pub const OP_FORD_GREATER_THAN: u16 = 186;
+pub const OP_FUNORD_GREATER_THAN: u16 = 187;
+pub const OP_FORD_LESS_THAN_EQUAL: u16 = 188;
+pub const OP_FUNORD_LESS_THAN_EQUAL: u16 = 189;
+pub const OP_FORD_GREATER_THAN_EQUAL: u16 = 190;
+pub const OP_FUNORD_GREATER_THAN_EQUAL: u16 = 191;
+pub const OP_SHIFT_RIGHT_LOGICAL: u16 = 194;
+pub const OP_SHIFT_RIGHT_ARITHMETIC: u16 = 195;
+pub const OP_SHIFT_LEFT_LOGICAL: u16 = 196;
+pub const OP_BITWISE_OR: u16 = 197;
+pub const OP_BITWISE_XOR: u16 = 198;
+pub const OP_BITWISE_AND: u16 = 199;
+pub const OP_NOT: u16 = 200;
+pub const OP_BIT_FIELD_INSERT: u16 = 201;
+pub const OP_BIT_FIELD_S_EXTRACT: u16 = 202;
+pub const OP_BIT_FIELD_U_EXTRACT: u16 = 203;
+pub const OP_BIT_REVERSE: u16 = 204;
+pub const OP_BIT_COUNT: u16 = 205;
+pub const OP_DPDX: u16 = 207;
+pub const OP_DPDY: u16 = 208;
+pub const OP_FWIDTH: u16 = 209;
+pub const OP_DPDX_FINE: u16 = 210;
+pub const OP_DPDY_FINE: u16 = 211;
+pub const OP_FWIDTH_FINE: u16 = 212;
+pub const OP_DPDX_COARSE: u16 = 213;
+pub const OP_DPDY_COARSE: u16 = 214;
+pub const OP_FWIDTH_COARSE: u16 = 215;
+pub const OP_EMIT_VERTEX: u16 = 218;
+pub const OP_END_PRIMITIVE: u16 = 219;
+pub const OP_EMIT_STREAM_VERTEX: u16 = 220;
+pub const OP_END_STREAM_PRIMITIVE: u16 = 221;
+pub const OP_CONTROL_BARRIER: u16 = 224;
+pub const OP_MEMORY_BARRIER: u16 = 225;
+pub const OP_ATOMIC_LOAD: u16 = 227;
+pub const OP_ATOMIC_STORE: u16 = 228;
+pub const OP_ATOMIC_EXCHANGE: u16 = 229;
+pub const OP_ATOMIC_COMPARE_EXCHANGE: u16 = 230;
+pub const OP_ATOMIC_COMPARE_EXCHANGE_WEAK: u16 = 231;
+pub const OP_ATOMIC_IINCREMENT: u16 = 232;
+pub const OP_ATOMIC_IDECREMENT: u16 = 233;
+pub const OP_ATOMIC_IADD: u16 = 234;
+pub const OP_ATOMIC_ISUB: u16 = 235;
+pub const OP_ATOMIC_SMIN: u16 = 236;
+pub const OP_ATOMIC_UMIN: u16 = 237;
+pub const OP_ATOMIC_SMAX: u16 = 238;
+pub const OP_ATOMIC_UMAX: u16 = 239;
+pub const OP_ATOMIC_AND: u16 = 240;
+pub const OP_ATOMIC_OR: u16 = 241;
+pub const OP_ATOMIC_XOR: u16 = 242;
+pub const OP_PHI: u16 = 245;
+pub const OP_LOOP_MERGE: u16 = 246;
+pub const OP_SELECTION_MERGE: u16 = 247;
+pub const OP_LABEL: u16 = 248;
+pub const OP_BRANCH: u16 = 249;
+pub const OP_BRANCH_CONDITIONAL: u16 = 250;
+pub const OP_SWITCH: u16 = 251;
+pub const OP_KILL: u16 = 252;
+pub const OP_RETURN: u16 = 253;
+pub const OP_RETURN_VALUE: u16 = 254;
+pub const OP_UNREACHABLE: u16 = 255;
+pub const OP_LIFETIME_START: u16 = 256;
+pub const OP_LIFETIME_STOP: u16 = 257;
+pub const OP_NO_LINE: u16 = 317;
+
+pub type ExecutionModel = u32;
+pub const EXECUTION_MODEL_VERTEX: u32 = 0;
+pub const EXECUTION_MODEL_TESSELLATION_CONTROL: u32 = 1;
+pub const EXECUTION_MODEL_TESSELLATION_EVALUATION: u32 = 2;
+pub const EXECUTION_MODEL_GEOMETRY: u32 = 3;
+pub const EXECUTION_MODEL_FRAGMENT: u32 = 4;
+pub const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+pub const EXECUTION_MODEL_KERNEL: u32 = 6;
+
+pub type AddressingModel = u32;
+pub const ADDRESSING_MODEL_LOGICAL: u32 = 0;
+pub const ADDRESSING_MODEL_PHYSICAL32: u32 = 1;
+pub const ADDRESSING_MODEL_PHYSICAL64: u32 = 2;
+
+pub type MemoryModel = u32;
+pub const MEMORY_MODEL_SIMPLE: u32 = 0;
+pub const MEMORY_MODEL_GLSL450: u32 = 1;
+pub const MEMORY_MODEL_OPEN_CL: u32 = 2;
+
+pub type ExecutionMode = u32;
+pub const EXECUTION_MODE_INVOCATIONS: u32 = 0;
+pub const EXECUTION_MODE_SPACING_EQUAL: u32 = 1;
+pub const EXECUTION_MODE_SPACING_FRACTIONAL_EVEN: u32 = 2;
+pub const EXECUTION_MODE_SPACING_FRACTIONAL_ODD: u32 = 3;
+pub const EXECUTION_MODE_VERTEX_ORDER_CW: u32 = 4;
+pub const EXECUTION_MODE_VERTEX_ORDER_CCW: u32 = 5;
+pub const EXECUTION_MODE_PIXEL_CENTER_INTEGER: u32 = 6;
+pub const EXECUTION_MODE_ORIGIN_UPPER_LEFT: u32 = 7;
+pub const EXECUTION_MODE_ORIGIN_LOWER_LEFT: u32 = 8;
+pub const EXECUTION_MODE_EARLY_FRAGMENT_TESTS: u32 = 9;
+pub const EXECUTION_MODE_POINT_MODE: u32 = 10;
+pub const EXECUTION_MODE_XFB: u32 = 11;
+pub const EXECUTION_MODE_DEPTH_REPLACING: u32 = 12;
+pub const EXECUTION_MODE_DEPTH_GREATER: u32 = 14;
+pub const EXECUTION_MODE_DEPTH_LESS: u32 = 15;
+pub const EXECUTION_MODE_DEPTH_UNCHANGED: u32 = 16;
+pub const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+pub const EXECUTION_MODE_LOCAL_SIZE_HINT: u32 = 18;
+pub const EXECUTION_MODE_INPUT_POINTS: u32 = 19;
+pub const EXECUTION_MODE_INPUT_LINES: u32 = 20;
+pub const EXECUTION_MODE_INPUT_LINES_ADJACENCY: u32 = 21;
+pub const EXECUTION_MODE_TRIANGLES: u32 = 22;
+pub const EXECUTION_MODE_INPUT_TRIANGLES_ADJACENCY: u32 = 23;
+pub const EXECUTION_MODE_QUADS: u32 = 24;
+pub const EXECUTION_MODE_ISOLINES: u32 = 25;
+pub const EXECUTION_MODE_OUTPUT_VERTICES: u32 = 26;
+pub const EXECUTION_MODE_OUTPUT_POINTS: u32 = 27;
+pub const EXECUTION_MODE_OUTPUT_LINE_STRIP: u32 = 28;
+pub const EXECUTION_MODE_OUTPUT_TRIANGLE_STRIP: u32 = 29;
+pub const EXECUTION_MODE_VEC_TYPE_HINT: u32 = 30;
+pub const EXECUTION_MODE_CONTRACTION_OFF: u32 = 31;
+
+pub type StorageClass = u32;
+pub const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+pub const STORAGE_CLASS_INPUT: u32 = 1;
+pub const STORAGE_CLASS_UNIFORM: u32 = 2;
+pub const STORAGE_CLASS_OUTPUT: u32 = 3;
+pub const STORAGE_CLASS_WORKGROUP: u32 = 4;
+pub const STORAGE_CLASS_CROSS_WORKGROUP: u32 = 5;
+pub const STORAGE_CLASS_PRIVATE: u32 = 6;
+pub const STORAGE_CLASS_FUNCTION: u32 = 7;
+pub const STORAGE_CLASS_GENERIC: u32 = 8;
+pub const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+pub const STORAGE_CLASS_ATOMIC_COUNTER: u32 = 10;
+pub const STORAGE_CLASS_IMAGE: u32 = 11;
+pub const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+pub type Dim = u32;
+pub const DIM_1D: u32 = 0;
+pub const DIM_2D: u32 = 1;
+pub const DIM_3D: u32 = 2;
+pub const DIM_CUBE: u32 = 3;
+pub const DIM_RECT: u32 = 4;
+pub const DIM_BUFFER: u32 = 5;
+pub const DIM_SUBPASS_DATA: u32 = 6;
+
+pub type ImageFormat = u32;
+pub const IMAGE_FORMAT_UNKNOWN: u32 = 0;
+pub const IMAGE_FORMAT_RGBA32F: u32 = 1;
+pub const IMAGE_FORMAT_RGBA16F: u32 = 2;
+pub const IMAGE_FORMAT_R32F: u32 = 3;
+pub const IMAGE_FORMAT_RGBA8: u32 = 4;
+pub const IMAGE_FORMAT_RGBA8_SNORM: u32 = 5;
+pub const IMAGE_FORMAT_RG32F: u32 = 6;
+pub const IMAGE_FORMAT_RG16F: u32 = 7;
+pub const IMAGE_FORMAT_R11F_G11F_B10F: u32 = 8;
+pub const IMAGE_FORMAT_R16F: u32 = 9;
+pub const IMAGE_FORMAT_RGBA16: u32 = 10;
+pub const IMAGE_FORMAT_RGB10_A2: u32 = 11;
+pub const IMAGE_FORMAT_RG16: u32 = 12;
+pub const IMAGE_FORMAT_RG8: u32 = 13;
+pub const IMAGE_FORMAT_R16: u32 = 14;
+pub const IMAGE_FORMAT_R8: u32 = 15;
+pub const IMAGE_FORMAT_RGBA16_SNORM: u32 = 16;
+pub const IMAGE_FORMAT_RG16_SNORM: u32 = 17;
+pub const IMAGE_FORMAT_RG8_SNORM: u32 = 18;
+pub const IMAGE_FORMAT_R16_SNORM: u32 = 19;
+pub const IMAGE_FORMAT_R8_SNORM: u32 = 20;
+pub const IMAGE_FORMAT_RGBA32I: u32 = 21;
+pub const IMAGE_FORMAT_RGBA16I: u32 = 22;
+pub const IMAGE_FORMAT_RGBA8I: u32 = 23;
+pub const IMAGE_FORMAT_R32I: u32 = 24;
+pub const IMAGE_FORMAT_RG32I: u32 = 25;
+pub const IMAGE_FORMAT_RG16I: u32 = 26;
+pub const IMAGE_FORMAT_RG8I: u32 = 27;
+pub const IMAGE_FORMAT_R16I: u32 = 28;
+pub const IMAGE_FORMAT_R8I: u32 = 29;
+pub const IMAGE_FORMAT_RGBA32UI: u32 = 30;
+pub const IMAGE_FORMAT_RGBA16UI: u32 = 31;
+pub const IMAGE_FORMAT_RGBA8UI: u32 = 32;
+pub const IMAGE_FORMAT_R32UI: u32 = 33;
+pub const IMAGE_FORMAT_RGB10A2UI: u32 = 34;
+pub const IMAGE_FORMAT_RG32UI: u32 = 35;
+pub const IMAGE_FORMAT_RG16UI: u32 = 36;
+pub const IMAGE_FORMAT_RG8UI: u32 = 37;
+pub const IMAGE_FORMAT_R16UI: u32 = 38;
+pub const IMAGE_FORMAT_R8UI: u32 = 39;
+
+pub type ImageOperands = u32;
+pub const IMAGE_OPERANDS_BIAS: u32 = 0x1;
+pub const IMAGE_OPERANDS_LOD: u32 = 0x2;
+pub const IMAGE_OPERANDS_GRAD: u32 = 0x4;
+pub const IMAGE_OPERANDS_CONST_OFFSET: u32 = 0x8;
+pub const IMAGE_OPERANDS_OFFSET: u32 = 0x10;
+pub const IMAGE_OPERANDS_CONST_OFFSETS: u32 = 0x20;
+pub const IMAGE_OPERANDS_SAMPLE: u32 = 0x40;
+pub const IMAGE_OPERANDS_MIN_LOD: u32 = 0x80;
+
+pub type Decoration = u32;
+pub const DECORATION_RELAXED_PRECISION: u32 = 0;
+pub const DECORATION_SPEC_ID: u32 = 1;
+pub const DECORATION_BLOCK: u32 = 2;
+pub const DECORATION_BUFFER_BLOCK: u32 = 3;
+pub const DECORATION_ROW_MAJOR: u32 = 4;
+pub const DECORATION_COL_MAJOR: u32 = 5;
+pub const DECORATION_ARRAY_STRIDE: u32 = 6;
+pub const DECORATION_MATRIX_STRIDE: u32 = 7;
+pub const DECORATION_GLSL_SHARED: u32 = 8;
+pub const DECORATION_GLSL_PACKED: u32 = 9;
+pub const DECORATION_C_PACKED: u32 = 10;
+pub const DECORATION_BUILT_IN: u32 = 11;
+pub const DECORATION_NO_PERSPECTIVE: u32 = 13;
+pub const DECORATION_FLAT: u32 = 14;
+pub const DECORATION_PATCH: u32 = 15;
+pub const DECORATION_CENTROID: u32 = 16;
+pub const DECORATION_SAMPLE: u32 = 17;
+pub const DECORATION_INVARIANT: u32 = 18;
+pub const DECORATION_RESTRICT: u32 = 19;
+pub const DECORATION_ALIASED: u32 = 20;
+pub const DECORATION_VOLATILE: u32 = 21;
+pub const DECORATION_CONSTANT: u32 = 22;
+pub const DECORATION_COHERENT: u32 = 23;
+pub const DECORATION_NON_WRITABLE: u32 = 24;
+pub const DECORATION_NON_READABLE: u32 = 25;
+pub const DECORATION_UNIFORM: u32 = 26;
+pub const DECORATION_SATURATED_CONVERSION: u32 = 28;
+pub const DECORATION_STREAM: u32 = 29;
+pub const DECORATION_LOCATION: u32 = 30;
+pub const DECORATION_COMPONENT: u32 = 31;
+pub const DECORATION_INDEX: u32 = 32;
+pub const DECORATION_BINDING: u32 = 33;
+pub const DECORATION_DESCRIPTOR_SET: u32 = 34;
+pub const DECORATION_OFFSET: u32 = 35;
+pub const DECORATION_XFB_BUFFER: u32 = 36;
+pub const DECORATION_XFB_STRIDE: u32 = 37;
+pub const DECORATION_FUNC_PARAM_ATTR: u32 = 38;
+pub const DECORATION_FP_ROUNDING_MODE: u32 = 39;
+pub const DECORATION_FP_FAST_MATH_MODE: u32 = 40;
+pub const DECORATION_LINKAGE_ATTRIBUTES: u32 = 41;
+pub const DECORATION_NO_CONTRACTION: u32 = 42;
+pub const DECORATION_INPUT_ATTACHMENT_INDEX: u32 = 43;
+pub const DECORATION_ALIGNMENT: u32 = 44;
+
+pub type BuiltIn = u32;
+pub const BUILT_IN_POSITION: u32 = 0;
+pub const BUILT_IN_POINT_SIZE: u32 = 1;
+pub const BUILT_IN_CLIP_DISTANCE: u32 = 3;
+pub const BUILT_IN_CULL_DISTANCE: u32 = 4;
+pub const BUILT_IN_VERTEX_ID: u32 = 5;
+pub const BUILT_IN_INSTANCE_ID: u32 = 6;
+pub const BUILT_IN_PRIMITIVE_ID: u32 = 7;
+pub const BUILT_IN_INVOCATION_ID: u32 = 8;
+pub const BUILT_IN_LAYER: u32 = 9;
+pub const BUILT_IN_VIEWPORT_INDEX: u32 = 10;
+pub const BUILT_IN_TESS_LEVEL_OUTER: u32 = 11;
+pub const BUILT_IN_TESS_LEVEL_INNER: u32 = 12;
+pub const BUILT_IN_TESS_COORD: u32 = 13;
+pub const BUILT_IN_PATCH_VERTICES: u32 = 14;
+pub const BUILT_IN_FRAG_COORD: u32 = 15;
+pub const BUILT_IN_POINT_COORD: u32 = 16;
+pub const BUILT_IN_FRONT_FACING: u32 = 17;
+pub const BUILT_IN_SAMPLE_ID: u32 = 18;
+pub const BUILT_IN_SAMPLE_POSITION: u32 = 19;
+pub const BUILT_IN_SAMPLE_MASK: u32 = 20;
+pub const BUILT_IN_FRAG_DEPTH: u32 = 22;
+pub const BUILT_IN_HELPER_INVOCATION: u32 = 23;
+pub const BUILT_IN_NUM_WORKGROUPS: u32 = 24;
+pub const BUILT_IN_WORKGROUP_SIZE: u32 = 25;
+pub const BUILT_IN_WORKGROUP_ID: u32 = 26;
+pub const BUILT_IN_LOCAL_INVOCATION_ID: u32 = 27;
+pub const BUILT_IN_GLOBAL_INVOCATION_ID: u32 = 28;
+pub const BUILT_IN_LOCAL_INVOCATION_INDEX: u32 = 29;
+pub const BUILT_IN_WORK_DIM: u32 = 30;
+pub const BUILT_IN_GLOBAL_SIZE: u32 = 31;
+pub const BUILT_IN_ENQUEUED_WORKGROUP_SIZE: u32 = 32;
+pub const BUILT_IN_GLOBAL_OFFSET: u32 = 33;
+pub const BUILT_IN_GLOBAL_LINEAR_ID: u32 = 34;
+pub const BUILT_IN_SUBGROUP_SIZE: u32 = 36;
+pub const BUILT_IN_SUBGROUP_MAX_SIZE: u32 = 37;
+pub const BUILT_IN_NUM_SUBGROUPS: u32 = 38;
+pub const BUILT_IN_NUM_ENQUEUED_SUBGROUPS: u32 = 39;
+pub const BUILT_IN_SUBGROUP_ID: u32 = 40;
+pub const BUILT_IN_SUBGROUP_LOCAL_INVOCATION_ID: u32 = 41;
+pub const BUILT_IN_VERTEX_INDEX: u32 = 42;
+pub const BUILT_IN_INSTANCE_INDEX: u32 = 43;
+
+pub type Scope = u32;
+pub const SCOPE_CROSS_DEVICE: u32 = 0;
+pub const SCOPE_DEVICE: u32 = 1;
+pub const SCOPE_WORKGROUP: u32 = 2;
+pub const SCOPE_SUBGROUP: u32 = 3;
+pub const SCOPE_INVOCATION: u32 = 4;
+
+pub type MemorySemantics = u32;
+pub const MEMORY_SEMANTICS_ACQUIRE: u32 = 0x2;
+pub const MEMORY_SEMANTICS_RELEASE: u32 = 0x4;
+pub const MEMORY_SEMANTICS_ACQUIRE_RELEASE: u32 = 0x8;
+pub const MEMORY_SEMANTICS_SEQUENTIALLY_CONSISTENT: u32 = 0x10;
+pub const MEMORY_SEMANTICS_UNIFORM_MEMORY: u32 = 0x40;
+pub const MEMORY_SEMANTICS_SUBGROUP_MEMORY: u32 = 0x80;
+pub const MEMORY_SEMANTICS_WORKGROUP_MEMORY: u32 = 0x100;
+pub const MEMORY_SEMANTICS_CROSS_WORKGROUP_MEMORY: u32 = 0x200;
+pub const MEMORY_SEMANTICS_ATOMIC_COUNTER_MEMORY: u32 = 0x400;
+pub const MEMORY_SEMANTICS_IMAGE_MEMORY: u32 = 0x800;
+
+pub type Capability = u32;
+pub const CAPABILITY_MATRIX: u32 = 0;
+pub const CAPABILITY_SHADER: u32 = 1;
+pub const CAPABILITY_GEOMETRY: u32 = 2;
+pub const CAPABILITY_TESSELLATION: u32 = 3;
+pub const CAPABILITY_ADDRESSES: u32 = 4;
+pub const CAPABILITY_LINKAGE: u32 = 5;
+pub const CAPABILITY_KERNEL: u32 = 6;
+pub const CAPABILITY_VECTOR16: u32 = 7;
+pub const CAPABILITY_FLOAT16_BUFFER: u32 = 8;
+pub const CAPABILITY_FLOAT16: u32 = 9;
+pub const CAPABILITY_FLOAT64: u32 = 10;
+pub const CAPABILITY_INT64: u32 = 11;
+pub const CAPABILITY_INT64_ATOMICS: u32 = 12;
+pub const CAPABILITY_IMAGE_BASIC: u32 = 13;
+pub const CAPABILITY_IMAGE_READ_WRITE: u32 = 14;
+pub const CAPABILITY_IMAGE_MIPMAP: u32 = 15;
+pub const CAPABILITY_PIPES: u32 = 17;
+pub const CAPABILITY_GROUPS: u32 = 18;
+pub const CAPABILITY_DEVICE_ENQUEUE: u32 = 19;
+pub const CAPABILITY_LITERAL_SAMPLER: u32 = 20;
+pub const CAPABILITY_ATOMIC_STORAGE: u32 = 21;
+pub const CAPABILITY_INT16: u32 = 22;
+pub const CAPABILITY_TESSELLATION_POINT_SIZE: u32 = 23;
+pub const CAPABILITY_GEOMETRY_POINT_SIZE: u32 = 24;
+pub const CAPABILITY_IMAGE_GATHER_EXTENDED: u32 = 25;
+pub const CAPABILITY_STORAGE_IMAGE_MULTISAMPLE: u32 = 27;
+pub const CAPABILITY_UNIFORM_BUFFER_ARRAY_DYNAMIC_INDEXING: u32 = 28;
+pub const CAPABILITY_SAMPLED_IMAGE_ARRAY_DYNAMIC_INDEXING: u32 = 29;
+pub const CAPABILITY_STORAGE_BUFFER_ARRAY_DYNAMIC_INDEXING: u32 = 30;
+pub const CAPABILITY_STORAGE_IMAGE_ARRAY_DYNAMIC_INDEXING: u32 = 31;
+pub const CAPABILITY_CLIP_DISTANCE: u32 = 32;
+pub const CAPABILITY_CULL_DISTANCE: u32 = 33;
+pub const CAPABILITY_IMAGE_CUBE_ARRAY: u32 = 34;
+pub const CAPABILITY_SAMPLE_RATE_SHADING: u32 = 35;
+pub const CAPABILITY_IMAGE_RECT: u32 = 36;
+pub const CAPABILITY_SAMPLED_RECT: u32 = 37;
+pub const CAPABILITY_GENERIC_POINTER: u32 = 38;
+pub const CAPABILITY_INT8: u32 = 39;
+pub const CAPABILITY_INPUT_ATTACHMENT: u32 = 40;
+pub const CAPABILITY_SPARSE_RESIDENCY: u32 = 41;
+pub const CAPABILITY_MIN_LOD: u32 = 42;
+pub const CAPABILITY_SAMPLED1_D: u32 = 43;
+pub const CAPABILITY_IMAGE1_D: u32 = 44;
+pub const CAPABILITY_SAMPLED_CUBE_ARRAY: u32 = 45;
+pub const CAPABILITY_SAMPLED_BUFFER: u32 = 46;
+pub const CAPABILITY_IMAGE_BUFFER: u32 = 47;
+pub const CAPABILITY_IMAGE_MS_ARRAY: u32 = 48;
+pub const CAPABILITY_STORAGE_IMAGE_EXTENDED_FORMATS: u32 = 49;
+pub const CAPABILITY_IMAGE_QUERY: u32 = 50;
+pub const CAPABILITY_DERIVATIVE_CONTROL: u32 = 51;
+pub const CAPABILITY_INTERPOLATION_FUNCTION: u32 = 52;
+pub const CAPABILITY_TRANSFORM_FEEDBACK: u32 = 53;
+pub const CAPABILITY_GEOMETRY_STREAMS: u32 = 54;
+pub const CAPABILITY_STORAGE_IMAGE_READ_WITHOUT_FORMAT: u32 = 55;
+pub const CAPABILITY_STORAGE_IMAGE_WRITE_WITHOUT_FORMAT: u32 = 56;
+pub const CAPABILITY_MULTI_VIEWPORT: u32 = 57;
+
+// GLSL.std.450 extended instruction set
+
+pub type GlslStd450 = u32;
+pub const GLSL_STD_450_ROUND: u32 = 1;
+pub const GLSL_STD_450_ROUND_EVEN: u32 = 2;
+pub const GLSL_STD_450_TRUNC: u32 = 3;
+pub const GLSL_STD_450_FABS: u32 = 4;
+pub const GLSL_STD_450_SABS: u32 = 5;
+pub const GLSL_STD_450_FSIGN: u32 = 6;
+pub const GLSL_STD_450_SSIGN: u32 = 7;
+pub const GLSL_STD_450_FLOOR: u32 = 8;
+pub const GLSL_STD_450_CEIL: u32 = 9;
+pub const GLSL_STD_450_FRACT: u32 = 10;
+pub const GLSL_STD_450_RADIANS: u32 = 11;
+pub const GLSL_STD_450_DEGREES: u32 = 12;
+pub const GLSL_STD_450_SIN: u32 = 13;
+pub const GLSL_STD_450_COS: u32 = 14;
+pub const GLSL_STD_450_TAN: u32 = 15;
+pub const GLSL_STD_450_ASIN: u32 = 16;
+pub const GLSL_STD_450_ACOS: u32 = 17;
+pub const GLSL_STD_450_ATAN: u32 = 18;
+pub const GLSL_STD_450_SINH: u32 = 19;
+pub const GLSL_STD_450_COSH: u32 = 20;
+pub const GLSL_STD_450_TANH: u32 = 21;
+pub const GLSL_STD_450_ASINH: u32 = 22;
+pub const GLSL_STD_450_ACOSH: u32 = 23;
+pub const GLSL_STD_450_ATANH: u32 = 24;
+pub const GLSL_STD_450_ATAN2: u32 = 25;
+pub const GLSL_STD_450_POW: u32 = 26;
+pub const GLSL_STD_450_EXP: u32 = 27;
+pub const GLSL_STD_450_LOG: u32 = 28;
+pub const GLSL_STD_450_EXP2: u32 = 29;
+pub const GLSL_STD_450_LOG2: u32 = 30;
+pub const GLSL_STD_450_SQRT: u32 = 31;
+pub const GLSL_STD_450_INVERSE_SQRT: u32 = 32;
+pub const GLSL_STD_450_DETERMINANT: u32 = 33;
+pub const GLSL_STD_450_MATRIX_INVERSE: u32 = 34;
+pub const GLSL_STD_450_MODF: u32 = 35;
+pub const GLSL_STD_450_MODF_STRUCT: u32 = 36;
+pub const GLSL_STD_450_FMIN: u32 = 37;
+pub const GLSL_STD_450_UMIN: u32 = 38;
+pub const GLSL_STD_450_SMIN: u32 = 39;
+pub const GLSL_STD_450_FMAX: u32 = 40;
+pub const GLSL_STD_450_UMAX: u32 = 41;
+pub const GLSL_STD_450_SMAX: u32 = 42;
+pub const GLSL_STD_450_FCLAMP: u32 = 43;
+pub const GLSL_STD_450_UCLAMP: u32 = 44;
+pub const GLSL_STD_450_SCLAMP: u32 = 45;
+pub const GLSL_STD_450_FMIX: u32 = 46;
+pub const GLSL_STD_450_IMIX: u32 = 47;
+pub const GLSL_STD_450_STEP: u32 = 48;
+pub const GLSL_STD_450_SMOOTH_STEP: u32 = 49;
+pub const GLSL_STD_450_FMA: u32 = 50;
+pub const GLSL_STD_450_FREXP: u32 = 51;
+pub const GLSL_STD_450_FREXP_STRUCT: u32 = 52;
+pub const GLSL_STD_450_LDEXP: u32 = 53;
+pub const GLSL_STD_450_PACK_SNORM4X8: u32 = 54;
+pub const GLSL_STD_450_PACK_UNORM4X8: u32 = 55;
+pub const GLSL_STD_450_PACK_SNORM2X16: u32 = 56;
+pub const GLSL_STD_450_PACK_UNORM2X16: u32 = 57;
+pub const GLSL_STD_450_PACK_HALF2X16: u32 = 58;
+pub const GLSL_STD_450_PACK_DOUBLE2X32: u32 = 59;
+pub const GLSL_STD_450_UNPACK_SNORM2X16: u32 = 60;
+pub const GLSL_STD_450_UNPACK_UNORM2X16: u32 = 61;
+pub const GLSL_STD_450_UNPACK_HALF2X16: u32 = 62;
+pub const GLSL_STD_450_UNPACK_SNORM4X8: u32 = 63;
+pub const GLSL_STD_450_UNPACK_UNORM4X8: u32 = 64;
+pub const GLSL_STD_450_UNPACK_DOUBLE2X32: u32 = 65;
+pub const GLSL_STD_450_LENGTH: u32 = 66;
+pub const GLSL_STD_450_DISTANCE: u32 = 67;
+pub const GLSL_STD_450_CROSS: u32 = 68;
+pub const GLSL_STD_450_NORMALIZE: u32 = 69;
+pub const GLSL_STD_450_FACE_FORWARD: u32 = 70;
+pub const GLSL_STD_450_REFLECT: u32 = 71;
+pub const GLSL_STD_450_REFRACT: u32 = 72;
+pub const GLSL_STD_450_FIND_ILSB: u32 = 73;
+pub const GLSL_STD_450_FIND_SMSB: u32 = 74;
+pub const GLSL_STD_450_FIND_UMSB: u32 = 75;
+pub const GLSL_STD_450_INTERPOLATE_AT_CENTROID: u32 = 76;
+pub const GLSL_STD_450_INTERPOLATE_AT_SAMPLE: u32 = 77;
+pub const GLSL_STD_450_INTERPOLATE_AT_OFFSET: u32 = 78;
+pub const GLSL_STD_450_NMIN: u32 = 79;
+pub const GLSL_STD_450_NMAX: u32 = 80;
+pub const GLSL_STD_450_NCLAMP: u32 = 81;
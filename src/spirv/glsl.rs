@@ -0,0 +1,258 @@
+use std;
+
+use half::{f16_to_f32, f32_to_f16};
+use spirv::consts::*;
+use spirv::module::{bits_to_f32, f32_to_bits};
+
+fn f(x: u32) -> f32 {
+    bits_to_f32(x)
+}
+
+fn b(x: f32) -> u32 {
+    f32_to_bits(x)
+}
+
+fn map<F: Fn(f32) -> f32>(x: &[u32], op: F) -> Vec<u32> {
+    x.iter().map(|&x| b(op(f(x)))).collect()
+}
+
+fn map2<F: Fn(f32, f32) -> f32>(x: &[u32], y: &[u32], op: F) -> Vec<u32> {
+    x.iter().zip(y).map(|(&x, &y)| b(op(f(x), f(y)))).collect()
+}
+
+fn map3<F: Fn(f32, f32, f32) -> f32>(x: &[u32], y: &[u32], z: &[u32], op: F) -> Vec<u32> {
+    x.iter().zip(y).zip(z).map(|((&x, &y), &z)| b(op(f(x), f(y), f(z)))).collect()
+}
+
+fn imap2<F: Fn(u32, u32) -> u32>(x: &[u32], y: &[u32], op: F) -> Vec<u32> {
+    x.iter().zip(y).map(|(&x, &y)| op(x, y)).collect()
+}
+
+fn dot(x: &[u32], y: &[u32]) -> f32 {
+    x.iter().zip(y).map(|(&x, &y)| f(x) * f(y)).sum()
+}
+
+fn round_even(x: f32) -> f32 {
+    let r = x.round();
+    if (x - x.trunc()).abs() == 0.5 && r % 2.0 != 0.0 {
+        r - x.signum()
+    } else {
+        r
+    }
+}
+
+fn fmin(x: f32, y: f32) -> f32 {
+    if y < x { y } else { x }
+}
+
+fn fmax(x: f32, y: f32) -> f32 {
+    if x < y { y } else { x }
+}
+
+fn clamp(x: f32, lo: f32, hi: f32) -> f32 {
+    fmin(fmax(x, lo), hi)
+}
+
+/// Determinant of a square matrix stored column major.
+fn determinant(m: &[f32], n: usize) -> f32 {
+    if n == 1 {
+        return m[0];
+    }
+
+    let mut det = 0.0;
+    for col in 0..n {
+        let minor = minor_matrix(m, n, col, 0);
+        let sign = if col % 2 == 0 { 1.0 } else { -1.0 };
+        det += sign * m[col * n] * determinant(&minor, n - 1);
+    }
+    det
+}
+
+/// The matrix with one column and one row removed.
+fn minor_matrix(m: &[f32], n: usize, skip_col: usize, skip_row: usize) -> Vec<f32> {
+    let mut minor = Vec::with_capacity((n - 1) * (n - 1));
+    for c in (0..n).filter(|&c| c != skip_col) {
+        for r in (0..n).filter(|&r| r != skip_row) {
+            minor.push(m[c * n + r]);
+        }
+    }
+    minor
+}
+
+fn inverse(m: &[f32], n: usize) -> Vec<f32> {
+    let det = determinant(m, n);
+    let mut inv = vec![0.0; n * n];
+
+    for c in 0..n {
+        for r in 0..n {
+            // Adjugate: transposed cofactor matrix.
+            let minor = minor_matrix(m, n, r, c);
+            let sign = if (r + c) % 2 == 0 { 1.0 } else { -1.0 };
+            let cofactor = if n == 1 { 1.0 } else { sign * determinant(&minor, n - 1) };
+            inv[c * n + r] = cofactor / det;
+        }
+    }
+    inv
+}
+
+fn find_msb(x: u32) -> u32 {
+    if x == 0 { !0 } else { 31 - x.leading_zeros() }
+}
+
+fn pack_norm(values: &[u32], bits: u32, signed: bool) -> u32 {
+    let mut packed = 0;
+    for (i, &v) in values.iter().enumerate() {
+        let v = f(v);
+        let field = if signed {
+            let max = ((1 << (bits - 1)) - 1) as f32;
+            ((clamp(v, -1.0, 1.0) * max).round() as i32) as u32
+        } else {
+            let max = ((1u32 << bits) - 1) as f32;
+            (clamp(v, 0.0, 1.0) * max).round() as u32
+        };
+        packed |= (field & ((1 << bits) - 1)) << (i as u32 * bits);
+    }
+    packed
+}
+
+fn unpack_norm(packed: u32, count: u32, bits: u32, signed: bool) -> Vec<u32> {
+    (0..count).map(|i| {
+        let field = (packed >> (i * bits)) & ((1 << bits) - 1);
+        if signed {
+            let shift = 32 - bits;
+            let v = ((field << shift) as i32) >> shift;
+            let max = ((1 << (bits - 1)) - 1) as f32;
+            b(clamp(v as f32 / max, -1.0, 1.0))
+        } else {
+            b(field as f32 / ((1u32 << bits) - 1) as f32)
+        }
+    }).collect()
+}
+
+/// Evaluates a GLSL.std.450 instruction on flattened operands. Instructions
+/// that access memory or depend on other invocations (Modf, Frexp and the
+/// interpolation functions) are handled by the interpreter and return None
+/// here, as do instructions on unsupported 64-bit types.
+pub fn eval(inst: GlslStd450, args: &[Vec<u32>]) -> Option<Vec<u32>> {
+    let x = &args[0][..];
+    let y = args.get(1).map(|a| &a[..]).unwrap_or(&[]);
+    let z = args.get(2).map(|a| &a[..]).unwrap_or(&[]);
+
+    Some(match inst {
+        GLSL_STD_450_ROUND => map(x, |x| x.round()),
+        GLSL_STD_450_ROUND_EVEN => map(x, round_even),
+        GLSL_STD_450_TRUNC => map(x, |x| x.trunc()),
+        GLSL_STD_450_FABS => map(x, |x| x.abs()),
+        GLSL_STD_450_SABS => x.iter().map(|&x| (x as i32).wrapping_abs() as u32).collect(),
+        GLSL_STD_450_FSIGN => map(x, |x| if x > 0.0 { 1.0 } else if x < 0.0 { -1.0 } else { 0.0 }),
+        GLSL_STD_450_SSIGN => x.iter().map(|&x| (x as i32).signum() as u32).collect(),
+        GLSL_STD_450_FLOOR => map(x, |x| x.floor()),
+        GLSL_STD_450_CEIL => map(x, |x| x.ceil()),
+        GLSL_STD_450_FRACT => map(x, |x| x - x.floor()),
+        GLSL_STD_450_RADIANS => map(x, |x| x.to_radians()),
+        GLSL_STD_450_DEGREES => map(x, |x| x.to_degrees()),
+        GLSL_STD_450_SIN => map(x, |x| x.sin()),
+        GLSL_STD_450_COS => map(x, |x| x.cos()),
+        GLSL_STD_450_TAN => map(x, |x| x.tan()),
+        GLSL_STD_450_ASIN => map(x, |x| x.asin()),
+        GLSL_STD_450_ACOS => map(x, |x| x.acos()),
+        GLSL_STD_450_ATAN => map(x, |x| x.atan()),
+        GLSL_STD_450_SINH => map(x, |x| x.sinh()),
+        GLSL_STD_450_COSH => map(x, |x| x.cosh()),
+        GLSL_STD_450_TANH => map(x, |x| x.tanh()),
+        GLSL_STD_450_ASINH => map(x, |x| x.asinh()),
+        GLSL_STD_450_ACOSH => map(x, |x| x.acosh()),
+        GLSL_STD_450_ATANH => map(x, |x| x.atanh()),
+        GLSL_STD_450_ATAN2 => map2(x, y, |y, x| y.atan2(x)),
+        GLSL_STD_450_POW => map2(x, y, |x, y| x.powf(y)),
+        GLSL_STD_450_EXP => map(x, |x| x.exp()),
+        GLSL_STD_450_LOG => map(x, |x| x.ln()),
+        GLSL_STD_450_EXP2 => map(x, |x| x.exp2()),
+        GLSL_STD_450_LOG2 => map(x, |x| x.log2()),
+        GLSL_STD_450_SQRT => map(x, |x| x.sqrt()),
+        GLSL_STD_450_INVERSE_SQRT => map(x, |x| 1.0 / x.sqrt()),
+        GLSL_STD_450_DETERMINANT => {
+            let m: Vec<f32> = x.iter().map(|&x| f(x)).collect();
+            let n = (m.len() as f32).sqrt() as usize;
+            vec![b(determinant(&m, n))]
+        }
+        GLSL_STD_450_MATRIX_INVERSE => {
+            let m: Vec<f32> = x.iter().map(|&x| f(x)).collect();
+            let n = (m.len() as f32).sqrt() as usize;
+            inverse(&m, n).into_iter().map(b).collect()
+        }
+        GLSL_STD_450_FMIN => map2(x, y, fmin),
+        GLSL_STD_450_UMIN => imap2(x, y, std::cmp::min),
+        GLSL_STD_450_SMIN => imap2(x, y, |x, y| std::cmp::min(x as i32, y as i32) as u32),
+        GLSL_STD_450_FMAX => map2(x, y, fmax),
+        GLSL_STD_450_UMAX => imap2(x, y, std::cmp::max),
+        GLSL_STD_450_SMAX => imap2(x, y, |x, y| std::cmp::max(x as i32, y as i32) as u32),
+        GLSL_STD_450_FCLAMP => map3(x, y, z, clamp),
+        GLSL_STD_450_UCLAMP => x.iter().zip(y).zip(z)
+            .map(|((&x, &lo), &hi)| std::cmp::min(std::cmp::max(x, lo), hi)).collect(),
+        GLSL_STD_450_SCLAMP => x.iter().zip(y).zip(z)
+            .map(|((&x, &lo), &hi)| std::cmp::min(std::cmp::max(x as i32, lo as i32), hi as i32) as u32).collect(),
+        GLSL_STD_450_FMIX => map3(x, y, z, |x, y, a| x * (1.0 - a) + y * a),
+        GLSL_STD_450_IMIX => x.iter().zip(y).zip(z)
+            .map(|((&x, &y), &a)| if a != 0 { y } else { x }).collect(),
+        GLSL_STD_450_STEP => map2(x, y, |edge, x| if x < edge { 0.0 } else { 1.0 }),
+        GLSL_STD_450_SMOOTH_STEP => map3(x, y, z, |e0, e1, x| {
+            let t = clamp((x - e0) / (e1 - e0), 0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        }),
+        GLSL_STD_450_FMA => map3(x, y, z, |a, b, c| a.mul_add(b, c)),
+        GLSL_STD_450_LDEXP => x.iter().zip(y).map(|(&x, &e)| b(f(x) * (2.0f32).powi(e as i32))).collect(),
+        GLSL_STD_450_PACK_SNORM4X8 => vec![pack_norm(x, 8, true)],
+        GLSL_STD_450_PACK_UNORM4X8 => vec![pack_norm(x, 8, false)],
+        GLSL_STD_450_PACK_SNORM2X16 => vec![pack_norm(x, 16, true)],
+        GLSL_STD_450_PACK_UNORM2X16 => vec![pack_norm(x, 16, false)],
+        GLSL_STD_450_PACK_HALF2X16 => vec![f32_to_f16(f(x[0])) as u32 | (f32_to_f16(f(x[1])) as u32) << 16],
+        GLSL_STD_450_UNPACK_SNORM2X16 => unpack_norm(x[0], 2, 16, true),
+        GLSL_STD_450_UNPACK_UNORM2X16 => unpack_norm(x[0], 2, 16, false),
+        GLSL_STD_450_UNPACK_SNORM4X8 => unpack_norm(x[0], 4, 8, true),
+        GLSL_STD_450_UNPACK_UNORM4X8 => unpack_norm(x[0], 4, 8, false),
+        GLSL_STD_450_UNPACK_HALF2X16 => vec![b(f16_to_f32(x[0] as u16)), b(f16_to_f32((x[0] >> 16) as u16))],
+        GLSL_STD_450_LENGTH => vec![b(dot(x, x).sqrt())],
+        GLSL_STD_450_DISTANCE => {
+            let d = map2(x, y, |x, y| x - y);
+            vec![b(dot(&d, &d).sqrt())]
+        }
+        GLSL_STD_450_CROSS => {
+            let (x0, x1, x2) = (f(x[0]), f(x[1]), f(x[2]));
+            let (y0, y1, y2) = (f(y[0]), f(y[1]), f(y[2]));
+            vec![b(x1 * y2 - y1 * x2), b(x2 * y0 - y2 * x0), b(x0 * y1 - y0 * x1)]
+        }
+        GLSL_STD_450_NORMALIZE => {
+            let len = dot(x, x).sqrt();
+            map(x, |x| x / len)
+        }
+        GLSL_STD_450_FACE_FORWARD => {
+            let (n, i, nref) = (x, y, z);
+            if dot(nref, i) < 0.0 { n.to_vec() } else { map(n, |n| -n) }
+        }
+        GLSL_STD_450_REFLECT => {
+            let (i, n) = (x, y);
+            let d = dot(n, i);
+            map2(i, n, |i, n| i - 2.0 * d * n)
+        }
+        GLSL_STD_450_REFRACT => {
+            let (i, n, eta) = (x, y, f(z[0]));
+            let d = dot(n, i);
+            let k = 1.0 - eta * eta * (1.0 - d * d);
+            if k < 0.0 {
+                vec![0; i.len()]
+            } else {
+                map2(i, n, |i, n| eta * i - (eta * d + k.sqrt()) * n)
+            }
+        }
+        GLSL_STD_450_FIND_ILSB => x.iter().map(|&x| if x == 0 { !0 } else { x.trailing_zeros() }).collect(),
+        GLSL_STD_450_FIND_SMSB => x.iter().map(|&x| find_msb(if (x as i32) < 0 { !x } else { x })).collect(),
+        GLSL_STD_450_FIND_UMSB => x.iter().map(|&x| find_msb(x)).collect(),
+        GLSL_STD_450_NMIN => map2(x, y, |x, y| if x.is_nan() { y } else if y.is_nan() { x } else { fmin(x, y) }),
+        GLSL_STD_450_NMAX => map2(x, y, |x, y| if x.is_nan() { y } else if y.is_nan() { x } else { fmax(x, y) }),
+        GLSL_STD_450_NCLAMP => map3(x, y, z, |x, lo, hi| {
+            if x.is_nan() { lo } else { clamp(x, lo, hi) }
+        }),
+        _ => return None
+    })
+}
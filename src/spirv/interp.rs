@@ -0,0 +1,1944 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use std;
+
+use descriptor::{BoundDescriptorSet, Descriptor};
+use format::{self, FormatInfo};
+use half::{f16_to_f32, f32_to_f16};
+use image::ImageView;
+use memory::BufferView;
+use sampler::{Sampler, Lod};
+use spirv::consts::*;
+use spirv::module::*;
+use spirv::reflect::*;
+use spirv::glsl;
+
+/// A pointer into memory accessible by an invocation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pointer {
+    /// Memory private to the invocation. Values are stored flattened with one
+    /// word per scalar component, so offsets count words.
+    Local { block: usize, offset: usize, ty: Id },
+    /// Memory of a `Workgroup` variable, shared by the invocations of a
+    /// workgroup and laid out like local memory.
+    Workgroup { block: usize, offset: usize, ty: Id },
+    /// A variable backed by descriptors, or an element of an array of them.
+    /// Loading an image, sampler or sampled image gives the words (variable,
+    /// element), which are resolved to an object when the shader uses it.
+    /// `OpSampledImage` joins the words of an image and a sampler.
+    /// Buffer blocks are resolved to a `Buffer` pointer when accessed.
+    Opaque { variable: Id, element: u32, ty: Id },
+    /// Memory laid out with explicit offsets and strides, such as a uniform
+    /// or storage buffer, or a texel from `OpImageTexelPointer`. Offsets
+    /// count bytes; reads past `size` give zeros and writes past it are
+    /// dropped.
+    Buffer { data: *mut u8, size: usize, offset: usize, ty: Id, layout: MatrixLayout },
+}
+
+impl Pointer {
+    pub fn ty(&self) -> Id {
+        match *self {
+            Pointer::Local { ty, .. } | Pointer::Workgroup { ty, .. } | Pointer::Opaque { ty, .. } |
+            Pointer::Buffer { ty, .. } => ty,
+        }
+    }
+}
+
+/// Layout of a matrix in explicitly laid out memory, from the decorations of
+/// the struct member holding it. A vector with a `RowMajor` layout is a
+/// column of a row major matrix, so its components are a stride apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatrixLayout {
+    Packed,
+    ColumnMajor(u32),
+    RowMajor(u32),
+}
+
+/// Objects outside of an invocation that its shader accesses. The pointers
+/// must stay valid while the invocation runs.
+#[derive(Clone, Default)]
+pub struct Resources {
+    /// Input attachments of the current subpass, by input attachment index.
+    pub input_attachments: Vec<Option<*const ImageView>>,
+    /// Bound descriptor sets, by set number.
+    pub descriptor_sets: Vec<Option<BoundDescriptorSet>>,
+    /// Contents of push constant storage.
+    pub push_constants: Vec<u8>,
+}
+
+/// An image or texel buffer, whose texels shaders address with integer
+/// coordinates.
+#[derive(Clone, Copy)]
+enum Texels<'r> {
+    Image(&'r ImageView),
+    Buffer(&'r BufferView),
+}
+
+impl<'r> Texels<'r> {
+    /// The first byte of one sample of a texel of a level, or `None` if it
+    /// is outside of the image. Texel buffers have a single level and
+    /// sample.
+    unsafe fn texel(&self, coordinate: &[i32], level: u32, sample: u32) -> Option<*mut u8> {
+        match *self {
+            Texels::Image(view) => view.texel_at(level, coordinate, sample),
+            Texels::Buffer(_) if level != 0 || sample != 0 => None,
+            Texels::Buffer(view) => coordinate.first().and_then(|&i| view.texel(i as u32))
+        }
+    }
+
+    /// Size of a texel in bytes.
+    fn size(&self) -> usize {
+        self.info().size as usize
+    }
+
+    fn info(&self) -> &'r FormatInfo {
+        match *self {
+            Texels::Image(view) => &view.info,
+            Texels::Buffer(view) => &view.info,
+        }
+    }
+
+    fn decode(&self, data: &[u8]) -> [u32; 4] {
+        match *self {
+            Texels::Image(view) => view.decode(data),
+            Texels::Buffer(view) => format::decode(&view.info, data),
+        }
+    }
+}
+
+/// Applies `op` to the word at `data` as one atomic operation and returns
+/// the previous value. Every atomic instruction is sequentially consistent,
+/// which is at least as strong as any memory semantics a shader asks for.
+unsafe fn atomic_update<F: Fn(u32) -> u32>(data: *mut u8, op: F) -> u32 {
+    let word = &*(data as *const AtomicU32);
+    let mut old = word.load(Ordering::SeqCst);
+    loop {
+        match word.compare_exchange_weak(old, op(old), Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => return old,
+            Err(current) => old = current,
+        }
+    }
+}
+
+/// The operand words of an optional image operand, which follow the operand
+/// mask in order of their bits.
+fn image_operand(operands: &[u32], operand: u32) -> Option<&[u32]> {
+    let mask = match operands.first() {
+        Some(&mask) if mask & operand != 0 => mask,
+        _ => return None
+    };
+
+    let mut offset = 1;
+    for bit in 0..32 {
+        let flag = 1 << bit;
+        let count = if flag == IMAGE_OPERANDS_GRAD { 2 } else { 1 };
+        if flag == operand {
+            return operands.get(offset..offset + count);
+        }
+        if mask & flag != 0 {
+            offset += count;
+        }
+    }
+    None
+}
+
+/// Result of an instruction. Composite values are flattened into their
+/// scalar components in declaration order; matrices are column major.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Words(Vec<u32>),
+    Pointer(Pointer),
+}
+
+struct Code {
+    opcode: Op,
+    operands: Vec<u32>,
+}
+
+struct FunctionCode {
+    params: Vec<Id>,
+    code: Vec<Code>,
+    labels: HashMap<Id, usize>,
+}
+
+/// One location, or one built-in, of an interface variable.
+#[derive(Clone, Copy, Debug)]
+pub struct Slot {
+    pub variable: Id,
+    /// Word offset inside the variable.
+    pub offset: usize,
+    pub components: u32,
+    pub location: u32,
+    pub component: u32,
+    pub index: u32,
+    pub builtin: Option<BuiltIn>,
+}
+
+/// An entry point prepared for execution.
+pub struct Program {
+    pub module: Module,
+    pub entry_point: EntryPoint,
+    functions: HashMap<Id, FunctionCode>,
+    sizes: HashMap<Id, usize>,
+    /// Result type of every id, or 0.
+    value_types: Vec<Id>,
+    constants: HashMap<Id, Vec<u32>>,
+    globals: Vec<Id>,
+    glsl: Option<Id>,
+    pub inputs: Vec<Slot>,
+    pub outputs: Vec<Slot>,
+}
+
+fn has_result_type(opcode: Op) -> bool {
+    match opcode {
+        OP_NOP | OP_LINE | OP_NO_LINE | OP_LABEL | OP_STORE | OP_COPY_MEMORY | OP_COPY_MEMORY_SIZED |
+        OP_BRANCH | OP_BRANCH_CONDITIONAL | OP_SWITCH | OP_RETURN | OP_RETURN_VALUE | OP_KILL |
+        OP_UNREACHABLE | OP_SELECTION_MERGE | OP_LOOP_MERGE | OP_IMAGE_WRITE | OP_CONTROL_BARRIER |
+        OP_MEMORY_BARRIER | OP_ATOMIC_STORE | OP_EMIT_VERTEX | OP_END_PRIMITIVE | OP_EMIT_STREAM_VERTEX |
+        OP_END_STREAM_PRIMITIVE | OP_LIFETIME_START | OP_LIFETIME_STOP | OP_FUNCTION_END => false,
+        _ => true
+    }
+}
+
+fn type_size(module: &Module, ty: Id) -> usize {
+    match module.types.get(&ty) {
+        Some(&Type::Bool) | Some(&Type::Int { .. }) | Some(&Type::Float { .. }) => 1,
+        Some(&Type::Vector { component, count }) => type_size(module, component) * count as usize,
+        Some(&Type::Matrix { column, columns }) => type_size(module, column) * columns as usize,
+        Some(&Type::Array { element, length }) =>
+            type_size(module, element) * module.array_length(length) as usize,
+        Some(&Type::Struct { ref members }) => members.iter().map(|&m| type_size(module, m)).sum(),
+        _ => 0
+    }
+}
+
+/// Reads a scalar from explicitly laid out memory. Values wider than 32 bits
+/// keep their low word and 16-bit floats are widened.
+fn read_scalar(data: &[u8], offset: usize, ty: &Type) -> u32 {
+    let size = match *ty {
+        Type::Int { width, .. } | Type::Float { width } => width as usize / 8,
+        _ => 4
+    };
+    if offset + size > data.len() {
+        return 0;
+    }
+
+    let mut word = 0;
+    for i in 0..std::cmp::min(size, 4) {
+        word |= (data[offset + i] as u32) << (8 * i);
+    }
+
+    match *ty {
+        Type::Bool => (word != 0) as u32,
+        Type::Float { width: 16 } => f32_to_bits(f16_to_f32(word as u16)),
+        Type::Int { width, signed: true } if width < 32 => ((word << (32 - width)) as i32 >> (32 - width)) as u32,
+        _ => word
+    }
+}
+
+fn write_scalar(data: &mut [u8], offset: usize, ty: &Type, word: u32) {
+    let (size, word) = match *ty {
+        Type::Int { width, signed } => (width as usize / 8, if width > 32 && signed && (word as i32) < 0 {
+            // Sign extend into the high word.
+            word as i32 as i64 as u64
+        } else {
+            word as u64
+        }),
+        Type::Float { width: 16 } => (2, f32_to_f16(bits_to_f32(word)) as u64),
+        Type::Float { width } => (width as usize / 8, word as u64),
+        _ => (4, (word != 0) as u64)
+    };
+    if offset + size > data.len() {
+        return;
+    }
+
+    for i in 0..size {
+        data[offset + i] = (word >> (8 * i)) as u8;
+    }
+}
+
+unsafe fn slice_or_empty<'a>(data: *mut u8, size: usize) -> &'a mut [u8] {
+    if data.is_null() {
+        &mut []
+    } else {
+        std::slice::from_raw_parts_mut(data, size)
+    }
+}
+
+fn location_count(module: &Module, ty: Id) -> u32 {
+    match module.types.get(&ty) {
+        Some(&Type::Matrix { columns, .. }) => columns,
+        Some(&Type::Array { element, length }) => location_count(module, element) * module.array_length(length),
+        Some(&Type::Struct { ref members }) => members.iter().map(|&m| location_count(module, m)).sum(),
+        _ => 1
+    }
+}
+
+impl Program {
+    pub fn new(module: Module, entry_point: EntryPoint) -> Program {
+        let mut sizes = HashMap::new();
+        for &ty in module.types.keys() {
+            sizes.insert(ty, type_size(&module, ty));
+        }
+
+        let mut value_types = vec![0; module.bound as usize];
+        let mut functions = HashMap::new();
+        for (&id, function) in &module.functions {
+            let mut code = FunctionCode {
+                params: Vec::new(),
+                code: Vec::new(),
+                labels: HashMap::new(),
+            };
+
+            for inst in module.function_instructions(function) {
+                match inst.opcode {
+                    OP_FUNCTION | OP_FUNCTION_END => continue,
+                    OP_FUNCTION_PARAMETER => code.params.push(inst.operands[1]),
+                    OP_LABEL => { code.labels.insert(inst.operands[0], code.code.len()); }
+                    _ => ()
+                }
+
+                if has_result_type(inst.opcode) && inst.operands.len() >= 2 {
+                    value_types[inst.operands[1] as usize] = inst.operands[0];
+                }
+
+                if inst.opcode != OP_FUNCTION_PARAMETER {
+                    code.code.push(Code {
+                        opcode: inst.opcode,
+                        operands: inst.operands.to_vec(),
+                    });
+                }
+            }
+
+            functions.insert(id, code);
+        }
+
+        let mut program = Program {
+            globals: module.static_globals(entry_point.function),
+            glsl: module.ext_imports.iter()
+                .find(|&(_, name)| name == "GLSL.std.450")
+                .map(|(&id, _)| id),
+            module: module,
+            entry_point: entry_point,
+            functions: functions,
+            sizes: sizes,
+            value_types: value_types,
+            constants: HashMap::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+
+        let ids: Vec<Id> = program.module.constants.keys().cloned().collect();
+        for id in ids {
+            let mut words = Vec::new();
+            program.flatten_constant(id, &mut words);
+            let ty = program.module.constants[&id].ty;
+            program.value_types[id as usize] = ty;
+            program.constants.insert(id, words);
+        }
+        for (&id, var) in &program.module.variables {
+            program.value_types[id as usize] = var.ty;
+        }
+
+        program.inputs = program.interface_slots(&program.entry_point.inputs);
+        program.outputs = program.interface_slots(&program.entry_point.outputs);
+
+        program
+    }
+
+    fn flatten_constant(&self, id: Id, out: &mut Vec<u32>) {
+        let constant = match self.module.constants.get(&id) {
+            Some(c) => c,
+            None => return
+        };
+
+        match constant.value {
+            ConstantValue::Bool(b) => out.push(b as u32),
+            ConstantValue::Scalar(ref words) => out.push(words[0]),
+            ConstantValue::Composite(ref parts) => for &part in parts {
+                self.flatten_constant(part, out);
+            },
+            ConstantValue::Null | ConstantValue::Op { .. } => {
+                let size = self.size_of(constant.ty);
+                out.extend(std::iter::repeat(0).take(size));
+            }
+        }
+    }
+
+    /// The `Workgroup` variables the entry point uses, with the types they
+    /// point to, in the order of their blocks in `WorkgroupMemory`.
+    pub fn workgroup_variables(&self) -> Vec<(Id, Id)> {
+        self.globals.iter()
+            .map(|id| (*id, &self.module.variables[id]))
+            .filter(|&(_, var)| var.storage == STORAGE_CLASS_WORKGROUP)
+            .map(|(id, var)| (id, self.module.pointee(var.ty).map(|p| p.1).unwrap_or(0)))
+            .collect()
+    }
+
+    /// Number of words in the flattened representation of a type.
+    pub fn size_of(&self, ty: Id) -> usize {
+        self.sizes.get(&ty).cloned().unwrap_or(0)
+    }
+
+    /// Word offset and type of a member or element of a composite type.
+    pub fn element(&self, ty: Id, index: u32) -> (usize, Id) {
+        match self.module.types.get(&ty) {
+            Some(&Type::Vector { component: element, .. }) |
+            Some(&Type::Matrix { column: element, .. }) |
+            Some(&Type::Array { element, .. }) |
+            Some(&Type::RuntimeArray { element }) =>
+                (self.size_of(element) * index as usize, element),
+            Some(&Type::Struct { ref members }) => {
+                let offset = members[..index as usize].iter().map(|&m| self.size_of(m)).sum();
+                (offset, members[index as usize])
+            }
+            _ => (0, 0)
+        }
+    }
+
+    /// Number of members, elements, columns or components of a composite
+    /// type, or 0 for scalars.
+    fn element_count(&self, ty: Id) -> u32 {
+        match self.module.types.get(&ty) {
+            Some(&Type::Vector { count, .. }) => count,
+            Some(&Type::Matrix { columns, .. }) => columns,
+            Some(&Type::Array { length, .. }) => self.module.array_length(length),
+            Some(&Type::Struct { ref members }) => members.len() as u32,
+            _ => 0
+        }
+    }
+
+    /// Byte offset, type and matrix layout of a member or element of a type
+    /// in explicitly laid out memory.
+    pub fn explicit_element(&self, ty: Id, layout: MatrixLayout, index: u32) -> (usize, Id, MatrixLayout) {
+        let module = &self.module;
+        let index = index as usize;
+
+        match module.types.get(&ty) {
+            Some(&Type::Vector { component, .. }) => {
+                let stride = match layout {
+                    MatrixLayout::RowMajor(stride) => stride,
+                    _ => module.explicit_size(component, None)
+                };
+                (stride as usize * index, component, MatrixLayout::Packed)
+            }
+            Some(&Type::Matrix { column, .. }) => match layout {
+                MatrixLayout::RowMajor(_) => {
+                    let component = self.element(column, 0).1;
+                    (module.explicit_size(component, None) as usize * index, column, layout)
+                }
+                MatrixLayout::ColumnMajor(stride) => (stride as usize * index, column, MatrixLayout::Packed),
+                MatrixLayout::Packed => {
+                    (module.explicit_size(column, None) as usize * index, column, MatrixLayout::Packed)
+                }
+            },
+            Some(&Type::Array { element, .. }) | Some(&Type::RuntimeArray { element }) => {
+                let stride = match module.decoration(ty, DECORATION_ARRAY_STRIDE) {
+                    Some(s) => s[0],
+                    None => module.explicit_size(element, match layout {
+                        MatrixLayout::ColumnMajor(stride) | MatrixLayout::RowMajor(stride) => Some(stride),
+                        MatrixLayout::Packed => None
+                    })
+                };
+                (stride as usize * index, element, layout)
+            }
+            Some(&Type::Struct { ref members }) => {
+                let member = index as u32;
+                let offset = module.member_decoration(ty, member, DECORATION_OFFSET).map(|o| o[0]).unwrap_or(0);
+                let layout = match module.member_decoration(ty, member, DECORATION_MATRIX_STRIDE) {
+                    Some(s) if module.member_decoration(ty, member, DECORATION_ROW_MAJOR).is_some() =>
+                        MatrixLayout::RowMajor(s[0]),
+                    Some(s) => MatrixLayout::ColumnMajor(s[0]),
+                    None => MatrixLayout::Packed
+                };
+                (offset as usize, members[index], layout)
+            }
+            _ => (0, 0, MatrixLayout::Packed)
+        }
+    }
+
+    /// Reads a value of a type from explicitly laid out memory into its
+    /// flattened words.
+    fn load_explicit(&self, data: &[u8], offset: usize, ty: Id, layout: MatrixLayout, out: &mut Vec<u32>) {
+        match self.module.types.get(&ty) {
+            Some(scalar @ &Type::Bool) | Some(scalar @ &Type::Int { .. }) | Some(scalar @ &Type::Float { .. }) => {
+                out.push(read_scalar(data, offset, scalar));
+            }
+            _ => for i in 0..self.element_count(ty) {
+                let (element_offset, element, element_layout) = self.explicit_element(ty, layout, i);
+                self.load_explicit(data, offset + element_offset, element, element_layout, out);
+            }
+        }
+    }
+
+    /// Writes flattened words to explicitly laid out memory, returning the
+    /// number of words used.
+    fn store_explicit(&self, data: &mut [u8], offset: usize, ty: Id, layout: MatrixLayout, words: &[u32]) -> usize {
+        match self.module.types.get(&ty) {
+            Some(scalar @ &Type::Bool) | Some(scalar @ &Type::Int { .. }) | Some(scalar @ &Type::Float { .. }) => {
+                if let Some(&word) = words.first() {
+                    write_scalar(data, offset, scalar, word);
+                }
+                1
+            }
+            _ => {
+                let mut used = 0;
+                for i in 0..self.element_count(ty) {
+                    let (element_offset, element, element_layout) = self.explicit_element(ty, layout, i);
+                    used += self.store_explicit(data, offset + element_offset, element, element_layout,
+                                                &words[std::cmp::min(used, words.len())..]);
+                }
+                used
+            }
+        }
+    }
+
+    fn interface_slots(&self, variables: &[InterfaceVariable]) -> Vec<Slot> {
+        let mut slots = Vec::new();
+
+        for v in variables {
+            let var_ty = self.module.variables[&v.id].ty;
+            let pointee = self.module.pointee(var_ty).map(|p| p.1).unwrap_or(0);
+            let (offset, ty) = match v.member {
+                Some(m) => self.element(pointee, m),
+                None => (0, pointee)
+            };
+
+            if v.builtin.is_some() {
+                slots.push(Slot {
+                    variable: v.id,
+                    offset: offset,
+                    components: self.size_of(ty) as u32,
+                    location: 0,
+                    component: 0,
+                    index: 0,
+                    builtin: v.builtin,
+                });
+            } else {
+                let first = slots.len();
+                self.location_slots(v.id, ty, offset, v.location.unwrap_or(0), v.component, &mut slots);
+                for slot in &mut slots[first..] {
+                    slot.index = v.index;
+                }
+            }
+        }
+
+        slots
+    }
+
+    fn location_slots(&self, variable: Id, ty: Id, offset: usize, location: u32, component: u32,
+                      slots: &mut Vec<Slot>) {
+        let count = match self.module.types.get(&ty) {
+            Some(&Type::Matrix { columns, .. }) => Some(columns),
+            Some(&Type::Array { length, .. }) => Some(self.module.array_length(length)),
+            Some(&Type::Struct { ref members }) => Some(members.len() as u32),
+            _ => None
+        };
+
+        match count {
+            Some(count) => {
+                let mut location = location;
+                for i in 0..count {
+                    let (element_offset, element) = self.element(ty, i);
+                    self.location_slots(variable, element, offset + element_offset, location, component, slots);
+                    location += location_count(&self.module, element);
+                }
+            }
+            None => slots.push(Slot {
+                variable: variable,
+                offset: offset,
+                components: self.size_of(ty) as u32,
+                location: location,
+                component: component,
+                index: 0,
+                builtin: None,
+            })
+        }
+    }
+}
+
+/// Memory of the `Workgroup` variables of a program, which the invocations
+/// of one workgroup share. There is a block for every workgroup variable.
+pub struct WorkgroupMemory {
+    blocks: RefCell<Vec<Vec<u32>>>,
+}
+
+impl WorkgroupMemory {
+    pub fn new(program: &Program) -> WorkgroupMemory {
+        WorkgroupMemory {
+            blocks: RefCell::new(program.workgroup_variables().iter().map(|&(_, ty)| {
+                vec![0; program.size_of(ty)]
+            }).collect()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Ready,
+    Finished,
+    /// The invocation executed OpKill.
+    Killed,
+    /// The invocation needs the derivatives of a value, which come from the
+    /// other invocations of its quad. See `pending_derivative`.
+    Waiting,
+    /// The invocation executed OpControlBarrier and waits for the rest of
+    /// its workgroup. See `pass_barrier`.
+    Barrier,
+}
+
+struct Frame {
+    function: Id,
+    pc: usize,
+    block: Id,
+    previous: Id,
+    /// Where the caller wants the return value.
+    result: Option<Id>,
+    memory_mark: usize,
+}
+
+/// Execution state of one shader invocation.
+pub struct Invocation<'a> {
+    program: &'a Program,
+    values: Vec<Option<Value>>,
+    memory: Vec<Vec<u32>>,
+    frames: Vec<Frame>,
+    status: Status,
+    resources: Option<&'a Resources>,
+    /// Framebuffer position and sample of a fragment shader invocation.
+    fragment: [u32; 3],
+    /// Whether derivatives are computed by waiting for the rest of the quad.
+    /// Otherwise they are zero.
+    derivatives: bool,
+    /// A helper invocation only computes derivatives for the rest of its
+    /// quad and doesn't write to memory outside of itself.
+    helper: bool,
+    /// The value whose derivatives the invocation is waiting for, and
+    /// whether coarse derivatives are enough.
+    pending: Option<(Vec<u32>, bool)>,
+    /// The derivatives along x and y the waiting instruction continues with.
+    gradients: Option<(Vec<u32>, Vec<u32>)>,
+    /// Memory shared with the rest of the workgroup of a compute shader
+    /// invocation.
+    workgroup: Option<&'a WorkgroupMemory>,
+}
+
+fn f(x: u32) -> f32 {
+    bits_to_f32(x)
+}
+
+fn b(x: f32) -> u32 {
+    f32_to_bits(x)
+}
+
+fn unary<F: Fn(u32) -> u32>(x: &[u32], op: F) -> Vec<u32> {
+    x.iter().map(|&x| op(x)).collect()
+}
+
+fn binary<F: Fn(u32, u32) -> u32>(x: &[u32], y: &[u32], op: F) -> Vec<u32> {
+    x.iter().zip(y).map(|(&x, &y)| op(x, y)).collect()
+}
+
+fn fbinary<F: Fn(f32, f32) -> f32>(x: &[u32], y: &[u32], op: F) -> Vec<u32> {
+    binary(x, y, |x, y| b(op(f(x), f(y))))
+}
+
+fn fcompare<F: Fn(f32, f32) -> bool>(x: &[u32], y: &[u32], op: F) -> Vec<u32> {
+    binary(x, y, |x, y| op(f(x), f(y)) as u32)
+}
+
+fn compare<F: Fn(u32, u32) -> bool>(x: &[u32], y: &[u32], op: F) -> Vec<u32> {
+    binary(x, y, |x, y| op(x, y) as u32)
+}
+
+fn scompare<F: Fn(i32, i32) -> bool>(x: &[u32], y: &[u32], op: F) -> Vec<u32> {
+    binary(x, y, |x, y| op(x as i32, y as i32) as u32)
+}
+
+fn bitfield_mask(offset: u32, count: u32) -> u32 {
+    if count >= 32 { !0 } else { ((1u32 << count) - 1) << offset }
+}
+
+fn shl(x: u32, n: u32) -> u32 {
+    x.checked_shl(n).unwrap_or(0)
+}
+
+fn shr(x: u32, n: u32) -> u32 {
+    x.checked_shr(n).unwrap_or(0)
+}
+
+impl<'a> Invocation<'a> {
+    pub fn new(program: &'a Program) -> Invocation<'a> {
+        let mut invocation = Invocation {
+            program: program,
+            values: vec![None; program.module.bound as usize],
+            memory: Vec::new(),
+            frames: Vec::new(),
+            status: Status::Ready,
+            resources: None,
+            fragment: [0; 3],
+            derivatives: false,
+            helper: false,
+            pending: None,
+            gradients: None,
+            workgroup: None,
+        };
+
+        for &id in &program.globals {
+            let var = &program.module.variables[&id];
+            let pointee = program.module.pointee(var.ty).map(|p| p.1).unwrap_or(0);
+            let pointer = match var.storage {
+                STORAGE_CLASS_UNIFORM_CONSTANT | STORAGE_CLASS_UNIFORM | STORAGE_CLASS_STORAGE_BUFFER |
+                STORAGE_CLASS_PUSH_CONSTANT => {
+                    Pointer::Opaque { variable: id, element: 0, ty: pointee }
+                }
+                _ => invocation.allocate(pointee, var.initializer)
+            };
+            invocation.values[id as usize] = Some(Value::Pointer(pointer));
+        }
+
+        invocation.frames.push(Frame {
+            function: program.entry_point.function,
+            pc: 0,
+            block: 0,
+            previous: 0,
+            result: None,
+            memory_mark: invocation.memory.len(),
+        });
+
+        invocation
+    }
+
+    fn allocate(&mut self, ty: Id, initializer: Option<Id>) -> Pointer {
+        let words = match initializer {
+            Some(init) => self.get(init).to_vec(),
+            None => vec![0; self.program.size_of(ty)]
+        };
+        self.memory.push(words);
+
+        Pointer::Local { block: self.memory.len() - 1, offset: 0, ty: ty }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn set_resources(&mut self, resources: &'a Resources) {
+        self.resources = Some(resources);
+    }
+
+    /// Sets the position of a fragment shader invocation, which input
+    /// attachments are read relative to.
+    pub fn set_fragment(&mut self, x: u32, y: u32, sample: u32) {
+        self.fragment = [x, y, sample];
+    }
+
+    /// Makes the invocation part of a workgroup, sharing the memory of its
+    /// `Workgroup` variables and waiting for the rest of the workgroup at
+    /// barriers. Without a workgroup, every invocation has its own copy of
+    /// the variables and barriers do nothing.
+    pub fn set_workgroup(&mut self, memory: &'a WorkgroupMemory) {
+        for (block, (id, ty)) in self.program.workgroup_variables().into_iter().enumerate() {
+            let pointer = Pointer::Workgroup { block: block, offset: 0, ty: ty };
+            self.values[id as usize] = Some(Value::Pointer(pointer));
+        }
+        self.workgroup = Some(memory);
+    }
+
+    /// Continues an invocation waiting at a barrier, once every invocation
+    /// of its workgroup has reached it.
+    pub fn pass_barrier(&mut self) {
+        if self.status == Status::Barrier {
+            self.status = Status::Ready;
+        }
+    }
+
+    /// Makes derivative instructions and implicit LOD sampling wait for the
+    /// rest of the quad instead of using derivatives of zero.
+    pub fn enable_derivatives(&mut self) {
+        self.derivatives = true;
+    }
+
+    pub fn set_helper(&mut self) {
+        self.helper = true;
+    }
+
+    /// While the invocation is waiting, the value whose derivatives it needs
+    /// and whether coarse derivatives are enough.
+    pub fn pending_derivative(&self) -> Option<(&[u32], bool)> {
+        self.pending.as_ref().map(|&(ref value, coarse)| (&value[..], coarse))
+    }
+
+    /// Continues a waiting invocation with the derivatives along x and y of
+    /// the pending value.
+    pub fn resume(&mut self, dx: Vec<u32>, dy: Vec<u32>) {
+        if self.status == Status::Waiting {
+            self.pending = None;
+            self.gradients = Some((dx, dy));
+            self.status = Status::Ready;
+        }
+    }
+
+    /// Derivatives of `value` along x and y, or `None` after making the
+    /// current instruction wait for them. The instruction runs again once the
+    /// invocation is resumed.
+    fn derivatives(&mut self, value: Vec<u32>, coarse: bool) -> Option<(Vec<u32>, Vec<u32>)> {
+        if !self.derivatives {
+            return Some((vec![0; value.len()], vec![0; value.len()]));
+        }
+        if let Some(gradients) = self.gradients.take() {
+            return Some(gradients);
+        }
+
+        self.pending = Some((value, coarse));
+        self.status = Status::Waiting;
+        self.frames.last_mut().unwrap().pc -= 1;
+        None
+    }
+
+    fn variable_block(&self, variable: Id) -> usize {
+        match self.values[variable as usize] {
+            Some(Value::Pointer(Pointer::Local { block, .. })) => block,
+            _ => panic!("variable_block: {} is not a variable", variable)
+        }
+    }
+
+    /// Writes the value of an input slot. Extra words are ignored so that
+    /// four component attributes may be written to narrower inputs.
+    pub fn write_slot(&mut self, slot: &Slot, words: &[u32]) {
+        let block = self.variable_block(slot.variable);
+        let count = std::cmp::min(words.len(), slot.components as usize);
+        self.memory[block][slot.offset..slot.offset + count].copy_from_slice(&words[..count]);
+    }
+
+    pub fn read_slot(&self, slot: &Slot) -> &[u32] {
+        let block = self.variable_block(slot.variable);
+        &self.memory[block][slot.offset..slot.offset + slot.components as usize]
+    }
+
+    /// Sets every input slot for a built-in, if the shader uses it.
+    pub fn set_builtin(&mut self, builtin: BuiltIn, words: &[u32]) {
+        let program = self.program;
+        for slot in program.inputs.iter().filter(|s| s.builtin == Some(builtin)) {
+            self.write_slot(slot, words);
+        }
+    }
+
+    pub fn builtin_output(&self, builtin: BuiltIn) -> Option<&[u32]> {
+        self.program.outputs.iter()
+            .find(|s| s.builtin == Some(builtin))
+            .map(|s| self.read_slot(s))
+    }
+
+    fn get(&self, id: Id) -> &[u32] {
+        match self.values[id as usize] {
+            Some(Value::Words(ref words)) => words,
+            Some(Value::Pointer(_)) => panic!("get: {} is a pointer", id),
+            None => match self.program.constants.get(&id) {
+                Some(words) => words,
+                None => &[]
+            }
+        }
+    }
+
+    fn pointer(&self, id: Id) -> Pointer {
+        match self.values[id as usize] {
+            Some(Value::Pointer(p)) => p,
+            _ => panic!("pointer: {} is not a pointer", id)
+        }
+    }
+
+    fn set(&mut self, id: Id, words: Vec<u32>) {
+        self.values[id as usize] = Some(Value::Words(words));
+    }
+
+    /// Turns a pointer to a buffer block backed by a descriptor into a
+    /// pointer to the buffer memory the descriptor refers to, and a pointer
+    /// to the push constant block into one to push constant storage. Other
+    /// pointers are returned unchanged.
+    fn resolve(&self, pointer: Pointer) -> Pointer {
+        let (variable, element, ty) = match pointer {
+            Pointer::Opaque { variable, element, ty } => (variable, element, ty),
+            _ => return pointer
+        };
+        match self.program.module.types.get(&ty) {
+            Some(&Type::Struct { .. }) => (),
+            _ => return pointer
+        }
+
+        if self.program.module.variables[&variable].storage == STORAGE_CLASS_PUSH_CONSTANT {
+            let data = self.resources.map(|r| &r.push_constants[..]).unwrap_or(&[]);
+            return Pointer::Buffer {
+                data: data.as_ptr() as *mut u8,
+                size: data.len(),
+                offset: 0,
+                ty: ty,
+                layout: MatrixLayout::Packed,
+            };
+        }
+
+        let binding = match self.program.entry_point.descriptors.iter().find(|d| d.id == variable) {
+            Some(binding) => binding,
+            None => return pointer
+        };
+        let buffer = self.resources
+            .and_then(|r| r.descriptor_sets.get(binding.set as usize))
+            .and_then(|s| s.as_ref())
+            .and_then(|s| unsafe { s.buffer(binding.binding, element) });
+
+        let (data, size) = buffer.unwrap_or_else(|| {
+            error!("Invocation: no buffer bound to set {} binding {}", binding.set, binding.binding);
+            (std::ptr::null_mut(), 0)
+        });
+        Pointer::Buffer { data: data, size: size, offset: 0, ty: ty, layout: MatrixLayout::Packed }
+    }
+
+    fn workgroup_memory(&self) -> std::cell::Ref<Vec<Vec<u32>>> {
+        match self.workgroup {
+            Some(memory) => memory.blocks.borrow(),
+            None => panic!("workgroup_memory: the invocation has no workgroup")
+        }
+    }
+
+    fn workgroup_memory_mut(&self) -> std::cell::RefMut<Vec<Vec<u32>>> {
+        match self.workgroup {
+            Some(memory) => memory.blocks.borrow_mut(),
+            None => panic!("workgroup_memory: the invocation has no workgroup")
+        }
+    }
+
+    fn load(&self, pointer: Pointer) -> Vec<u32> {
+        match self.resolve(pointer) {
+            Pointer::Local { block, offset, ty } => {
+                let size = self.program.size_of(ty);
+                self.memory[block][offset..offset + size].to_vec()
+            }
+            Pointer::Workgroup { block, offset, ty } => {
+                let size = self.program.size_of(ty);
+                self.workgroup_memory()[block][offset..offset + size].to_vec()
+            }
+            Pointer::Opaque { variable, element, .. } => vec![variable, element],
+            Pointer::Buffer { data, size, offset, ty, layout } => {
+                let mut words = Vec::with_capacity(self.program.size_of(ty));
+                let data = unsafe { slice_or_empty(data, size) };
+                self.program.load_explicit(data, offset, ty, layout, &mut words);
+                words
+            }
+        }
+    }
+
+    fn store(&mut self, pointer: Pointer, words: &[u32]) {
+        match self.resolve(pointer) {
+            Pointer::Local { block, offset, .. } => {
+                self.memory[block][offset..offset + words.len()].copy_from_slice(words);
+            }
+            Pointer::Workgroup { block, offset, .. } => {
+                self.workgroup_memory_mut()[block][offset..offset + words.len()].copy_from_slice(words);
+            }
+            Pointer::Opaque { variable, .. } => error!("Invocation: store to opaque variable {}", variable),
+            Pointer::Buffer { .. } if self.helper => (),
+            Pointer::Buffer { data, size, offset, ty, layout } => {
+                let data = unsafe { slice_or_empty(data, size) };
+                self.program.store_explicit(data, offset, ty, layout, words);
+            }
+        }
+    }
+
+    /// Offset and type of an element of a composite in local or workgroup
+    /// memory. Indices past the end are clamped to the last element, since
+    /// there is no memory behind it.
+    fn local_element(&self, ty: Id, index: Id) -> (usize, Id) {
+        let count = self.program.element_count(ty);
+        let index = std::cmp::min(self.get(index).first().cloned().unwrap_or(0), count.saturating_sub(1));
+        self.program.element(ty, index)
+    }
+
+    fn access_chain(&self, base: Pointer, indices: &[Id]) -> Pointer {
+        match base {
+            Pointer::Local { block, mut offset, mut ty } => {
+                for &index in indices {
+                    let (element_offset, element) = self.local_element(ty, index);
+                    offset += element_offset;
+                    ty = element;
+                }
+                Pointer::Local { block: block, offset: offset, ty: ty }
+            }
+            Pointer::Workgroup { block, mut offset, mut ty } => {
+                for &index in indices {
+                    let (element_offset, element) = self.local_element(ty, index);
+                    offset += element_offset;
+                    ty = element;
+                }
+                Pointer::Workgroup { block: block, offset: offset, ty: ty }
+            }
+            Pointer::Opaque { variable, mut element, mut ty } => {
+                for (i, &index) in indices.iter().enumerate() {
+                    let length = match self.program.module.types.get(&ty) {
+                        Some(&Type::Array { length, .. }) => self.program.module.array_length(length),
+                        // Only the outermost dimension may be unsized.
+                        Some(&Type::RuntimeArray { .. }) => 0,
+                        _ => {
+                            // The rest indexes into a buffer block.
+                            let block = Pointer::Opaque { variable: variable, element: element, ty: ty };
+                            return self.access_chain(self.resolve(block), &indices[i..]);
+                        }
+                    };
+                    element = element * length + self.get(index)[0];
+                    ty = self.program.element(ty, 0).1;
+                }
+                Pointer::Opaque { variable: variable, element: element, ty: ty }
+            }
+            Pointer::Buffer { data, size, mut offset, mut ty, mut layout } => {
+                for &index in indices {
+                    let (element_offset, element, element_layout) =
+                        self.program.explicit_element(ty, layout, self.get(index)[0]);
+                    offset += element_offset;
+                    ty = element;
+                    layout = element_layout;
+                }
+                Pointer::Buffer { data: data, size: size, offset: offset, ty: ty, layout: layout }
+            }
+        }
+    }
+
+    /// Number of elements of the runtime array that is member `member` of
+    /// the buffer block `pointer` points to.
+    fn array_length(&self, pointer: Pointer, member: u32) -> u32 {
+        match self.resolve(pointer) {
+            Pointer::Buffer { size, offset, ty, layout, .. } => {
+                let (member_offset, array, array_layout) = self.program.explicit_element(ty, layout, member);
+                let stride = self.program.explicit_element(array, array_layout, 1).0;
+                if stride == 0 || offset + member_offset > size {
+                    0
+                } else {
+                    ((size - offset - member_offset) / stride) as u32
+                }
+            }
+            _ => 0
+        }
+    }
+
+    fn value_type(&self, id: Id) -> Id {
+        self.program.value_types[id as usize]
+    }
+
+    /// Runs the invocation until it finishes or has to wait for derivatives.
+    pub fn run(&mut self) -> Status {
+        while self.status == Status::Ready {
+            self.step();
+        }
+        self.status
+    }
+
+    fn step(&mut self) {
+        let program = self.program;
+        let (function, pc) = {
+            let frame = self.frames.last().unwrap();
+            (frame.function, frame.pc)
+        };
+        let code = &program.functions[&function];
+        let inst = &code.code[pc];
+        let ops = &inst.operands[..];
+
+        self.frames.last_mut().unwrap().pc += 1;
+
+        match inst.opcode {
+            OP_LABEL => {
+                let previous = {
+                    let frame = self.frames.last_mut().unwrap();
+                    frame.previous = frame.block;
+                    frame.block = ops[0];
+                    frame.previous
+                };
+
+                // All OpPhi of a block take their values at the same time.
+                let mut phis = Vec::new();
+                let mut next = pc + 1;
+                while next < code.code.len() && code.code[next].opcode == OP_PHI {
+                    let phi = &code.code[next].operands;
+                    let value = phi[2..].chunks(2)
+                        .find(|pair| pair[1] == previous)
+                        .map(|pair| pair[0]);
+                    let value = match value {
+                        Some(v) => self.values[v as usize].clone()
+                            .unwrap_or_else(|| Value::Words(self.get(v).to_vec())),
+                        None => Value::Words(vec![0; program.size_of(phi[0])])
+                    };
+                    phis.push((phi[1], value));
+                    next += 1;
+                }
+                for (id, value) in phis {
+                    self.values[id as usize] = Some(value);
+                }
+                self.frames.last_mut().unwrap().pc = next;
+            }
+            OP_PHI => (),
+            OP_BRANCH => self.branch(ops[0]),
+            OP_BRANCH_CONDITIONAL => {
+                let target = if self.get(ops[0])[0] != 0 { ops[1] } else { ops[2] };
+                self.branch(target);
+            }
+            OP_SWITCH => {
+                let selector = self.get(ops[0])[0];
+                let target = ops[2..].chunks(2)
+                    .find(|pair| pair[0] == selector)
+                    .map(|pair| pair[1])
+                    .unwrap_or(ops[1]);
+                self.branch(target);
+            }
+            OP_RETURN => self.ret(None),
+            OP_RETURN_VALUE => {
+                let value = self.values[ops[0] as usize].clone()
+                    .unwrap_or_else(|| Value::Words(self.get(ops[0]).to_vec()));
+                self.ret(Some(value));
+            }
+            OP_KILL => self.status = Status::Killed,
+            OP_UNREACHABLE => {
+                error!("Invocation: reached OpUnreachable");
+                self.status = Status::Finished;
+            }
+            OP_FUNCTION_CALL => {
+                let callee = &program.functions[&ops[2]];
+                let args: Vec<Option<Value>> = ops[3..].iter()
+                    .map(|&a| self.values[a as usize].clone()
+                        .or_else(|| Some(Value::Words(self.get(a).to_vec()))))
+                    .collect();
+                for (&param, arg) in callee.params.iter().zip(args) {
+                    self.values[param as usize] = arg;
+                }
+                let mark = self.memory.len();
+                self.frames.push(Frame {
+                    function: ops[2],
+                    pc: 0,
+                    block: 0,
+                    previous: 0,
+                    result: Some(ops[1]),
+                    memory_mark: mark,
+                });
+            }
+            OP_NOP | OP_LINE | OP_NO_LINE | OP_SELECTION_MERGE | OP_LOOP_MERGE |
+            OP_LIFETIME_START | OP_LIFETIME_STOP => (),
+            OP_VARIABLE => {
+                let pointee = program.module.pointee(ops[0]).map(|p| p.1).unwrap_or(0);
+                let pointer = self.allocate(pointee, ops.get(3).cloned());
+                self.values[ops[1] as usize] = Some(Value::Pointer(pointer));
+            }
+            OP_LOAD => {
+                let words = self.load(self.pointer(ops[2]));
+                self.set(ops[1], words);
+            }
+            OP_STORE => {
+                let words = self.get(ops[1]).to_vec();
+                let pointer = self.pointer(ops[0]);
+                self.store(pointer, &words);
+            }
+            OP_COPY_MEMORY => {
+                let words = self.load(self.pointer(ops[1]));
+                let pointer = self.pointer(ops[0]);
+                self.store(pointer, &words);
+            }
+            OP_ACCESS_CHAIN | OP_IN_BOUNDS_ACCESS_CHAIN => {
+                let pointer = self.access_chain(self.pointer(ops[2]), &ops[3..]);
+                self.values[ops[1] as usize] = Some(Value::Pointer(pointer));
+            }
+            OP_ARRAY_LENGTH => {
+                let length = self.array_length(self.pointer(ops[2]), ops[3]);
+                self.set(ops[1], vec![length]);
+            }
+            OP_COPY_OBJECT => {
+                let value = self.values[ops[2] as usize].clone()
+                    .unwrap_or_else(|| Value::Words(self.get(ops[2]).to_vec()));
+                self.values[ops[1] as usize] = Some(value);
+            }
+            OP_UNDEF => {
+                let size = program.size_of(ops[0]);
+                self.set(ops[1], vec![0; size]);
+            }
+            OP_EXT_INST => self.ext_inst(ops),
+            // Memory is shared directly, so writes are visible to other
+            // invocations as soon as they are made.
+            OP_MEMORY_BARRIER => (),
+            OP_CONTROL_BARRIER => {
+                if self.workgroup.is_some() {
+                    self.status = Status::Barrier;
+                }
+            }
+            OP_IMAGE_READ => {
+                let words = self.image_read(ops);
+                self.set(ops[1], words);
+            }
+            OP_IMAGE_FETCH => {
+                let words = self.image_fetch(ops);
+                self.set(ops[1], words);
+            }
+            OP_IMAGE_WRITE => self.image_write(ops),
+            OP_IMAGE_QUERY_SIZE | OP_IMAGE_QUERY_SIZE_LOD | OP_IMAGE_QUERY_LEVELS | OP_IMAGE_QUERY_SAMPLES => {
+                let words = self.image_query(inst.opcode, ops);
+                self.set(ops[1], words);
+            }
+            OP_IMAGE_TEXEL_POINTER => {
+                let pointer = self.texel_pointer(ops);
+                self.values[ops[1] as usize] = Some(Value::Pointer(pointer));
+            }
+            OP_ATOMIC_STORE => {
+                let value = self.get(ops[3])[0];
+                let pointer = self.pointer(ops[0]);
+                self.atomic(pointer, |_| value);
+            }
+            OP_ATOMIC_LOAD | OP_ATOMIC_EXCHANGE | OP_ATOMIC_COMPARE_EXCHANGE | OP_ATOMIC_COMPARE_EXCHANGE_WEAK |
+            OP_ATOMIC_IINCREMENT | OP_ATOMIC_IDECREMENT | OP_ATOMIC_IADD | OP_ATOMIC_ISUB | OP_ATOMIC_SMIN |
+            OP_ATOMIC_UMIN | OP_ATOMIC_SMAX | OP_ATOMIC_UMAX | OP_ATOMIC_AND | OP_ATOMIC_OR | OP_ATOMIC_XOR => {
+                let old = self.atomic_op(inst.opcode, ops);
+                self.set(ops[1], vec![old]);
+            }
+            OP_IMAGE_SAMPLE_IMPLICIT_LOD | OP_IMAGE_SAMPLE_EXPLICIT_LOD |
+            OP_IMAGE_SAMPLE_DREF_IMPLICIT_LOD | OP_IMAGE_SAMPLE_DREF_EXPLICIT_LOD |
+            OP_IMAGE_SAMPLE_PROJ_IMPLICIT_LOD | OP_IMAGE_SAMPLE_PROJ_EXPLICIT_LOD |
+            OP_IMAGE_SAMPLE_PROJ_DREF_IMPLICIT_LOD | OP_IMAGE_SAMPLE_PROJ_DREF_EXPLICIT_LOD => {
+                if let Some(words) = self.image_sample(inst.opcode, ops) {
+                    self.set(ops[1], words);
+                }
+            }
+            OP_DPDX | OP_DPDY | OP_FWIDTH | OP_DPDX_FINE | OP_DPDY_FINE | OP_FWIDTH_FINE |
+            OP_DPDX_COARSE | OP_DPDY_COARSE | OP_FWIDTH_COARSE => {
+                let coarse = inst.opcode >= OP_DPDX_COARSE;
+                let value = self.get(ops[2]).to_vec();
+                if let Some((dx, dy)) = self.derivatives(value, coarse) {
+                    let words = match inst.opcode {
+                        OP_DPDX | OP_DPDX_FINE | OP_DPDX_COARSE => dx,
+                        OP_DPDY | OP_DPDY_FINE | OP_DPDY_COARSE => dy,
+                        _ => fbinary(&dx, &dy, |x, y| x.abs() + y.abs())
+                    };
+                    self.set(ops[1], words);
+                }
+            }
+            _ => {
+                let words = self.compute(inst.opcode, ops);
+                self.set(ops[1], words);
+            }
+        }
+    }
+
+    fn branch(&mut self, target: Id) {
+        let frame = self.frames.last_mut().unwrap();
+        frame.pc = self.program.functions[&frame.function].labels[&target];
+    }
+
+    fn ret(&mut self, value: Option<Value>) {
+        let frame = self.frames.pop().unwrap();
+        self.memory.truncate(frame.memory_mark);
+
+        if self.frames.is_empty() {
+            self.status = Status::Finished;
+        } else if let (Some(result), Some(value)) = (frame.result, value) {
+            self.values[result as usize] = Some(value);
+        }
+    }
+
+    fn ext_inst(&mut self, ops: &[u32]) {
+        if Some(ops[2]) != self.program.glsl {
+            error!("Invocation: unknown extended instruction set {}", ops[2]);
+            self.set(ops[1], vec![0; self.program.size_of(ops[0])]);
+            return;
+        }
+
+        let inst = ops[3];
+        let args = &ops[4..];
+
+        let words = match inst {
+            GLSL_STD_450_MODF => {
+                let x = self.get(args[0]).to_vec();
+                let whole: Vec<u32> = unary(&x, |x| b(f(x).trunc()));
+                let pointer = self.pointer(args[1]);
+                self.store(pointer, &whole);
+                binary(&x, &whole, |x, w| b(f(x) - f(w)))
+            }
+            GLSL_STD_450_MODF_STRUCT => {
+                let x = self.get(args[0]).to_vec();
+                let whole: Vec<u32> = unary(&x, |x| b(f(x).trunc()));
+                let mut words = binary(&x, &whole, |x, w| b(f(x) - f(w)));
+                words.extend(whole);
+                words
+            }
+            GLSL_STD_450_FREXP | GLSL_STD_450_FREXP_STRUCT => {
+                let x = self.get(args[0]).to_vec();
+                let mut significands = Vec::new();
+                let mut exponents = Vec::new();
+                for &x in &x {
+                    let (s, e) = frexp(f(x));
+                    significands.push(b(s));
+                    exponents.push(e as u32);
+                }
+                if inst == GLSL_STD_450_FREXP {
+                    let pointer = self.pointer(args[1]);
+                    self.store(pointer, &exponents);
+                } else {
+                    significands.extend(exponents);
+                }
+                significands
+            }
+            _ => {
+                let values: Vec<Vec<u32>> = args.iter().map(|&a| self.get(a).to_vec()).collect();
+                match glsl::eval(inst, &values) {
+                    Some(words) => words,
+                    None => {
+                        error!("Invocation: unsupported GLSL.std.450 instruction {}", inst);
+                        vec![0; self.program.size_of(ops[0])]
+                    }
+                }
+            }
+        };
+
+        self.set(ops[1], words);
+    }
+
+    fn image_read(&self, ops: &[u32]) -> Vec<u32> {
+        let program = self.program;
+        let size = program.size_of(ops[0]);
+        let (variable, element) = {
+            let image = self.get(ops[2]);
+            (image[0], image[1])
+        };
+
+        let image_type = match self.image_type(ops[2]) {
+            Some(image) => image,
+            None => {
+                error!("Invocation: OpImageRead on a value that isn't an image");
+                return vec![0; size];
+            }
+        };
+        if image_type.dim != DIM_SUBPASS_DATA {
+            let sample = image_operand(&ops[4..], IMAGE_OPERANDS_SAMPLE).map(|s| self.get(s[0])[0]).unwrap_or(0);
+            let texel = unsafe { self.read_texel(self.get(ops[2]), self.get(ops[3]), 0, sample) };
+            return texel[..std::cmp::min(size, 4)].to_vec();
+        }
+
+        let index = program.entry_point.descriptors.iter()
+            .find(|d| d.id == variable)
+            .and_then(|d| d.input_attachment_index)
+            .map(|i| i + element);
+        let view = match (index, self.resources) {
+            (Some(index), Some(resources)) => match resources.input_attachments.get(index as usize) {
+                Some(&Some(view)) => unsafe { &*view },
+                _ => return vec![0; size]
+            },
+            _ => return vec![0; size]
+        };
+
+        let offset = self.get(ops[3]);
+        let x = (self.fragment[0] as i32 + offset[0] as i32) as u32;
+        let y = (self.fragment[1] as i32 + offset[1] as i32) as u32;
+        let sample = match image_operand(&ops[4..], IMAGE_OPERANDS_SAMPLE) {
+            Some(s) => self.get(s[0])[0],
+            None if image_type.multisampled => self.fragment[2],
+            None => 0
+        };
+
+        let extent = unsafe { view.extent() };
+        if x >= extent[0] || y >= extent[1] || sample >= unsafe { view.image().samples } {
+            return vec![0; size];
+        }
+
+        let texel = view.decode(unsafe {
+            ::std::slice::from_raw_parts(view.texel(0, x, y, sample), view.info.size as usize)
+        });
+        texel[..std::cmp::min(size, 4)].to_vec()
+    }
+
+    /// The type of an image, or of the image of a sampled image.
+    fn image_type(&self, id: Id) -> Option<ImageType> {
+        let types = &self.program.module.types;
+        match types.get(&self.value_type(id)) {
+            Some(&Type::Image(image)) => Some(image),
+            Some(&Type::SampledImage { image }) => match types.get(&image) {
+                Some(&Type::Image(image)) => Some(image),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    /// The image or texel buffer an image value refers to.
+    unsafe fn texels(&self, image: &[u32]) -> Option<Texels> {
+        match self.descriptor(image[0], image[1]) {
+            Some(Descriptor::Image { view, .. }) if !view.is_null() => Some(Texels::Image(&*view)),
+            Some(Descriptor::TexelBuffer(view)) if view != 0 => Some(Texels::Buffer(&*(view as *const BufferView))),
+            _ => {
+                error!("Invocation: no image bound to variable {}", image[0]);
+                None
+            }
+        }
+    }
+
+    /// Reads the texel at integer coordinates of an image or texel buffer,
+    /// decoded with the format of the view. Texels outside of the image read
+    /// as zero.
+    unsafe fn read_texel(&self, image: &[u32], coordinate: &[u32], level: u32, sample: u32) -> [u32; 4] {
+        let coordinate: Vec<i32> = coordinate.iter().map(|&c| c as i32).collect();
+        self.texels(image)
+            .and_then(|t| t.texel(&coordinate, level, sample)
+                      .map(|data| t.decode(::std::slice::from_raw_parts(data, t.size()))))
+            .unwrap_or([0; 4])
+    }
+
+    /// Reads a texel of a sampled image or uniform texel buffer without a
+    /// sampler. Image texels go through the component swizzle of the view.
+    fn image_fetch(&self, ops: &[u32]) -> Vec<u32> {
+        let size = self.program.size_of(ops[0]);
+        let image = self.get(ops[2]);
+        let operands = &ops[4..];
+
+        let mut coordinate = self.get(ops[3]).to_vec();
+        let offset = image_operand(operands, IMAGE_OPERANDS_CONST_OFFSET)
+            .or_else(|| image_operand(operands, IMAGE_OPERANDS_OFFSET))
+            .map(|o| self.get(o[0]))
+            .unwrap_or(&[]);
+        for (c, &o) in coordinate.iter_mut().zip(offset) {
+            *c = c.wrapping_add(o);
+        }
+        let level = image_operand(operands, IMAGE_OPERANDS_LOD).map(|l| self.get(l[0])[0]).unwrap_or(0);
+        let sample = image_operand(operands, IMAGE_OPERANDS_SAMPLE).map(|s| self.get(s[0])[0]).unwrap_or(0);
+
+        let texel = unsafe {
+            let texel = self.read_texel(image, &coordinate, level, sample);
+            match self.texels(image) {
+                Some(Texels::Image(view)) => view.swizzle(texel),
+                _ => texel
+            }
+        };
+        texel[..std::cmp::min(size, 4)].to_vec()
+    }
+
+    /// Writes a texel of a storage image or storage texel buffer, encoded
+    /// with the format of the view. Writes outside of the image are dropped.
+    fn image_write(&self, ops: &[u32]) {
+        if self.helper {
+            return;
+        }
+
+        let coordinate: Vec<i32> = self.get(ops[1]).iter().map(|&c| c as i32).collect();
+        let mut value = [0; 4];
+        for (v, &w) in value.iter_mut().zip(self.get(ops[2])) {
+            *v = w;
+        }
+        let sample = image_operand(&ops[3..], IMAGE_OPERANDS_SAMPLE).map(|s| self.get(s[0])[0]).unwrap_or(0);
+
+        unsafe {
+            if let Some(texels) = self.texels(self.get(ops[0])) {
+                if let Some(data) = texels.texel(&coordinate, 0, sample) {
+                    format::encode(texels.info(), &value, !0, ::std::slice::from_raw_parts_mut(data, texels.size()));
+                }
+            }
+        }
+    }
+
+    /// Answers `OpImageQuerySize`, `OpImageQuerySizeLod`,
+    /// `OpImageQueryLevels` and `OpImageQuerySamples`. Sizes have the texel
+    /// coordinates of the image type followed by the number of layers of
+    /// arrays, counting cubes rather than faces for cube arrays.
+    fn image_query(&self, opcode: Op, ops: &[u32]) -> Vec<u32> {
+        let size = self.program.size_of(ops[0]);
+        let (image_type, texels) = match (self.image_type(ops[2]), unsafe { self.texels(self.get(ops[2])) }) {
+            (Some(image_type), Some(texels)) => (image_type, texels),
+            _ => return vec![0; size]
+        };
+
+        let view = match texels {
+            Texels::Image(view) => view,
+            Texels::Buffer(view) => return match opcode {
+                OP_IMAGE_QUERY_SIZE => vec![view.len()],
+                _ => vec![0; size]
+            }
+        };
+
+        let image = unsafe { view.image() };
+        let level = match opcode {
+            OP_IMAGE_QUERY_LEVELS => return vec![view.level_count],
+            OP_IMAGE_QUERY_SAMPLES => return vec![image.samples],
+            OP_IMAGE_QUERY_SIZE_LOD => self.get(ops[3])[0],
+            _ => 0
+        };
+
+        let extent = image.level_extent(view.base_level + level);
+        let mut words = match image_type.dim {
+            DIM_1D => vec![extent[0]],
+            DIM_3D => extent.to_vec(),
+            _ => vec![extent[0], extent[1]]
+        };
+        if image_type.arrayed {
+            words.push(if image_type.dim == DIM_CUBE { view.layer_count / 6 } else { view.layer_count });
+        }
+        words.resize(size, 0);
+        words
+    }
+
+    /// A pointer to a texel of a storage image or storage texel buffer, for
+    /// atomic instructions. Texels outside of the image give a pointer to
+    /// nothing.
+    fn texel_pointer(&self, ops: &[u32]) -> Pointer {
+        let ty = self.program.module.pointee(ops[0]).map(|p| p.1).unwrap_or(0);
+        let image = self.load(self.pointer(ops[2]));
+        let coordinate: Vec<i32> = self.get(ops[3]).iter().map(|&c| c as i32).collect();
+        let sample = self.get(ops[4])[0];
+
+        let (data, size) = unsafe {
+            self.texels(&image)
+                .and_then(|t| t.texel(&coordinate, 0, sample).map(|data| (data, t.size())))
+                .unwrap_or((::std::ptr::null_mut(), 0))
+        };
+        Pointer::Buffer { data: data, size: size, offset: 0, ty: ty, layout: MatrixLayout::Packed }
+    }
+
+    /// Atomically replaces the 32-bit word a pointer points to with `op` of
+    /// its value, and returns the previous value. Words outside of their
+    /// buffer or image read as zero and aren't written, and neither is
+    /// anything by helper invocations.
+    fn atomic<F: Fn(u32) -> u32>(&mut self, pointer: Pointer, op: F) -> u32 {
+        match self.resolve(pointer) {
+            Pointer::Local { block, offset, .. } => {
+                let old = self.memory[block][offset];
+                self.memory[block][offset] = op(old);
+                old
+            }
+            Pointer::Workgroup { block, offset, .. } => {
+                let mut memory = self.workgroup_memory_mut();
+                let old = memory[block][offset];
+                memory[block][offset] = op(old);
+                old
+            }
+            Pointer::Opaque { variable, .. } => {
+                error!("Invocation: atomic access to opaque variable {}", variable);
+                0
+            }
+            Pointer::Buffer { data, size, offset, .. } => {
+                if data.is_null() || offset + 4 > size {
+                    return 0;
+                }
+                let word = unsafe { data.offset(offset as isize) };
+                if word as usize % 4 != 0 {
+                    error!("Invocation: atomic access to unaligned memory");
+                    return 0;
+                }
+                if self.helper {
+                    return unsafe { atomic_update(word, |x| x) };
+                }
+                unsafe { atomic_update(word, op) }
+            }
+        }
+    }
+
+    fn atomic_op(&mut self, opcode: Op, ops: &[u32]) -> u32 {
+        let pointer = self.pointer(ops[2]);
+        let value = ops.get(5).map(|&v| self.get(v).first().cloned().unwrap_or(0)).unwrap_or(0);
+
+        match opcode {
+            OP_ATOMIC_LOAD => self.atomic(pointer, |x| x),
+            OP_ATOMIC_EXCHANGE => self.atomic(pointer, |_| value),
+            OP_ATOMIC_COMPARE_EXCHANGE | OP_ATOMIC_COMPARE_EXCHANGE_WEAK => {
+                let (value, comparator) = (self.get(ops[6])[0], self.get(ops[7])[0]);
+                self.atomic(pointer, |x| if x == comparator { value } else { x })
+            }
+            OP_ATOMIC_IINCREMENT => self.atomic(pointer, |x| x.wrapping_add(1)),
+            OP_ATOMIC_IDECREMENT => self.atomic(pointer, |x| x.wrapping_sub(1)),
+            OP_ATOMIC_IADD => self.atomic(pointer, |x| x.wrapping_add(value)),
+            OP_ATOMIC_ISUB => self.atomic(pointer, |x| x.wrapping_sub(value)),
+            OP_ATOMIC_SMIN => self.atomic(pointer, |x| std::cmp::min(x as i32, value as i32) as u32),
+            OP_ATOMIC_UMIN => self.atomic(pointer, |x| std::cmp::min(x, value)),
+            OP_ATOMIC_SMAX => self.atomic(pointer, |x| std::cmp::max(x as i32, value as i32) as u32),
+            OP_ATOMIC_UMAX => self.atomic(pointer, |x| std::cmp::max(x, value)),
+            OP_ATOMIC_AND => self.atomic(pointer, |x| x & value),
+            OP_ATOMIC_OR => self.atomic(pointer, |x| x | value),
+            _ => self.atomic(pointer, |x| x ^ value)
+        }
+    }
+
+    /// The descriptor an element of a descriptor-backed variable refers to.
+    fn descriptor(&self, variable: Id, element: u32) -> Option<Descriptor> {
+        let binding = match self.program.entry_point.descriptors.iter().find(|d| d.id == variable) {
+            Some(binding) => binding,
+            None => return None
+        };
+        self.resources
+            .and_then(|r| r.descriptor_sets.get(binding.set as usize))
+            .and_then(|s| s.as_ref())
+            .and_then(|s| unsafe { (*s.set).descriptor(binding.binding, element) })
+    }
+
+    /// The image view and sampler a sampled image value refers to: either a
+    /// combined image sampler, or an image and a sampler joined by
+    /// `OpSampledImage`.
+    unsafe fn sampled_image(&self, words: &[u32]) -> Option<(&ImageView, &Sampler)> {
+        let image = self.descriptor(words[0], words[1]);
+        let sampler = if words.len() >= 4 { self.descriptor(words[2], words[3]) } else { image };
+
+        let view = match image {
+            Some(Descriptor::Image { view, .. }) if !view.is_null() => &*view,
+            _ => return None
+        };
+        let sampler = match sampler {
+            Some(Descriptor::Sampler(sampler)) | Some(Descriptor::Image { sampler, .. }) if sampler != 0 => sampler,
+            _ => return None
+        };
+        Some((view, &*(sampler as *const Sampler)))
+    }
+
+    /// Samples an image, comparing depth for the `Dref` instructions.
+    /// Returns `None` if the invocation has to wait for the derivatives of
+    /// the coordinates for an implicit level of detail.
+    fn image_sample(&mut self, opcode: Op, ops: &[u32]) -> Option<Vec<u32>> {
+        let size = self.program.size_of(ops[0]);
+        let mut coordinate: Vec<f32> = self.get(ops[3]).iter().map(|&c| f(c)).collect();
+        let (mut reference, operands) = match opcode {
+            OP_IMAGE_SAMPLE_DREF_IMPLICIT_LOD | OP_IMAGE_SAMPLE_DREF_EXPLICIT_LOD |
+            OP_IMAGE_SAMPLE_PROJ_DREF_IMPLICIT_LOD | OP_IMAGE_SAMPLE_PROJ_DREF_EXPLICIT_LOD => {
+                (Some(f(self.get(ops[4])[0])), &ops[5..])
+            }
+            _ => (None, &ops[4..])
+        };
+
+        if opcode >= OP_IMAGE_SAMPLE_PROJ_IMPLICIT_LOD {
+            if let Some(q) = coordinate.pop() {
+                for c in &mut coordinate {
+                    *c /= q;
+                }
+                reference = reference.map(|r| r / q);
+            }
+        }
+
+        let vector = |words: &[u32]| {
+            let mut v = [0.0; 3];
+            for (v, &w) in v.iter_mut().zip(words) {
+                *v = f(w);
+            }
+            v
+        };
+        let bias = image_operand(operands, IMAGE_OPERANDS_BIAS).map(|b| f(self.get(b[0])[0])).unwrap_or(0.0);
+        let lod = if let Some(lod) = image_operand(operands, IMAGE_OPERANDS_LOD) {
+            Lod::Explicit(f(self.get(lod[0])[0]))
+        } else if let Some(grad) = image_operand(operands, IMAGE_OPERANDS_GRAD) {
+            Lod::Gradients { dx: vector(self.get(grad[0])), dy: vector(self.get(grad[1])), bias: bias }
+        } else {
+            let value = coordinate.iter().map(|&c| b(c)).collect();
+            let (dx, dy) = match self.derivatives(value, false) {
+                Some(gradients) => gradients,
+                None => return None
+            };
+            Lod::Gradients { dx: vector(&dx), dy: vector(&dy), bias: bias }
+        };
+
+        let mut offset = [0; 3];
+        let offset_words = image_operand(operands, IMAGE_OPERANDS_CONST_OFFSET)
+            .or_else(|| image_operand(operands, IMAGE_OPERANDS_OFFSET))
+            .map(|o| self.get(o[0]))
+            .unwrap_or(&[]);
+        for (o, &w) in offset.iter_mut().zip(offset_words) {
+            *o = w as i32;
+        }
+
+        let texel = unsafe {
+            match self.sampled_image(self.get(ops[2])) {
+                Some((view, sampler)) => sampler.sample(view, &coordinate, lod, &offset, reference),
+                None => {
+                    error!("Invocation: sampling without an image and a sampler bound");
+                    [0; 4]
+                }
+            }
+        };
+        Some(texel[..std::cmp::min(size, 4)].to_vec())
+    }
+
+    /// Evaluates instructions that only depend on their operand values.
+    fn compute(&self, opcode: Op, ops: &[u32]) -> Vec<u32> {
+        let program = self.program;
+        let result_type = ops[0];
+        let x = || self.get(ops[2]);
+        let y = || self.get(ops[3]);
+
+        match opcode {
+            OP_CONVERT_F_TO_U => unary(x(), |x| {
+                let v = f(x);
+                if v <= 0.0 || v.is_nan() { 0 } else if v >= 4294967296.0 { !0 } else { v as u32 }
+            }),
+            OP_CONVERT_F_TO_S => unary(x(), |x| f(x) as i32 as u32),
+            OP_CONVERT_S_TO_F => unary(x(), |x| b(x as i32 as f32)),
+            OP_CONVERT_U_TO_F => unary(x(), |x| b(x as f32)),
+            OP_UCONVERT | OP_SCONVERT | OP_FCONVERT | OP_BITCAST => x().to_vec(),
+            OP_QUANTIZE_TO_F16 => unary(x(), |x| b(f16_to_f32(f32_to_f16(f(x))))),
+            OP_SAT_CONVERT_S_TO_U => unary(x(), |x| if (x as i32) < 0 { 0 } else { x }),
+            OP_SAT_CONVERT_U_TO_S => unary(x(), |x| std::cmp::min(x, std::i32::MAX as u32)),
+
+            OP_SNEGATE => unary(x(), |x| (x as i32).wrapping_neg() as u32),
+            OP_FNEGATE => unary(x(), |x| b(-f(x))),
+            OP_IADD => binary(x(), y(), |x, y| x.wrapping_add(y)),
+            OP_FADD => fbinary(x(), y(), |x, y| x + y),
+            OP_ISUB => binary(x(), y(), |x, y| x.wrapping_sub(y)),
+            OP_FSUB => fbinary(x(), y(), |x, y| x - y),
+            OP_IMUL => binary(x(), y(), |x, y| x.wrapping_mul(y)),
+            OP_FMUL => fbinary(x(), y(), |x, y| x * y),
+            OP_UDIV => binary(x(), y(), |x, y| if y == 0 { 0 } else { x / y }),
+            OP_SDIV => binary(x(), y(), |x, y| {
+                if y == 0 { 0 } else { (x as i32).wrapping_div(y as i32) as u32 }
+            }),
+            OP_FDIV => fbinary(x(), y(), |x, y| x / y),
+            OP_UMOD => binary(x(), y(), |x, y| if y == 0 { 0 } else { x % y }),
+            OP_SREM => binary(x(), y(), |x, y| {
+                if y == 0 { 0 } else { (x as i32).wrapping_rem(y as i32) as u32 }
+            }),
+            OP_SMOD => binary(x(), y(), |x, y| {
+                if y == 0 {
+                    return 0;
+                }
+                let r = (x as i32).wrapping_rem(y as i32);
+                if r != 0 && (r < 0) != ((y as i32) < 0) { r.wrapping_add(y as i32) as u32 } else { r as u32 }
+            }),
+            OP_FREM => fbinary(x(), y(), |x, y| x % y),
+            OP_FMOD => fbinary(x(), y(), |x, y| x - y * (x / y).floor()),
+            OP_VECTOR_TIMES_SCALAR | OP_MATRIX_TIMES_SCALAR => {
+                let s = f(y()[0]);
+                unary(x(), |x| b(f(x) * s))
+            }
+            OP_DOT => vec![b(x().iter().zip(y()).map(|(&x, &y)| f(x) * f(y)).sum())],
+            OP_MATRIX_TIMES_VECTOR => {
+                let (m, v) = (x(), y());
+                let rows = program.size_of(result_type);
+                (0..rows).map(|r| {
+                    b((0..v.len()).map(|c| f(m[c * rows + r]) * f(v[c])).sum())
+                }).collect()
+            }
+            OP_VECTOR_TIMES_MATRIX => {
+                let (v, m) = (x(), y());
+                let rows = v.len();
+                let columns = program.size_of(result_type);
+                (0..columns).map(|c| {
+                    b((0..rows).map(|r| f(v[r]) * f(m[c * rows + r])).sum())
+                }).collect()
+            }
+            OP_MATRIX_TIMES_MATRIX => {
+                let (l, r) = (x(), y());
+                let (columns, rows) = match program.module.types.get(&result_type) {
+                    Some(&Type::Matrix { column, columns }) => (columns as usize, program.size_of(column)),
+                    _ => (0, 0)
+                };
+                let inner = r.len() / columns;
+                let mut out = vec![0; columns * rows];
+                for c in 0..columns {
+                    for row in 0..rows {
+                        out[c * rows + row] = b((0..inner).map(|k| f(l[k * rows + row]) * f(r[c * inner + k])).sum());
+                    }
+                }
+                out
+            }
+            OP_OUTER_PRODUCT => {
+                let (col, row) = (x(), y());
+                let mut out = Vec::with_capacity(col.len() * row.len());
+                for &r in row {
+                    for &c in col {
+                        out.push(b(f(c) * f(r)));
+                    }
+                }
+                out
+            }
+            OP_TRANSPOSE => {
+                let m = x();
+                let (columns, rows) = match program.module.types.get(&result_type) {
+                    Some(&Type::Matrix { column, columns }) => (columns as usize, program.size_of(column)),
+                    _ => (0, 0)
+                };
+                let mut out = vec![0; columns * rows];
+                for c in 0..columns {
+                    for r in 0..rows {
+                        out[c * rows + r] = m[r * columns + c];
+                    }
+                }
+                out
+            }
+            OP_IADD_CARRY => {
+                let (x, y) = (x(), y());
+                let mut sum: Vec<u32> = binary(x, y, |x, y| x.wrapping_add(y));
+                let carry: Vec<u32> = binary(x, y, |x, y| x.checked_add(y).is_none() as u32);
+                sum.extend(carry);
+                sum
+            }
+            OP_ISUB_BORROW => {
+                let (x, y) = (x(), y());
+                let mut diff: Vec<u32> = binary(x, y, |x, y| x.wrapping_sub(y));
+                let borrow: Vec<u32> = binary(x, y, |x, y| (x < y) as u32);
+                diff.extend(borrow);
+                diff
+            }
+            OP_UMUL_EXTENDED | OP_SMUL_EXTENDED => {
+                let (x, y) = (x(), y());
+                let products: Vec<u64> = x.iter().zip(y).map(|(&x, &y)| {
+                    if opcode == OP_UMUL_EXTENDED {
+                        x as u64 * y as u64
+                    } else {
+                        (x as i32 as i64 * y as i32 as i64) as u64
+                    }
+                }).collect();
+                let mut out: Vec<u32> = products.iter().map(|&p| p as u32).collect();
+                out.extend(products.iter().map(|&p| (p >> 32) as u32));
+                out
+            }
+
+            OP_ANY => vec![x().iter().any(|&x| x != 0) as u32],
+            OP_ALL => vec![x().iter().all(|&x| x != 0) as u32],
+            OP_IS_NAN => unary(x(), |x| f(x).is_nan() as u32),
+            OP_IS_INF => unary(x(), |x| f(x).is_infinite() as u32),
+            OP_IS_FINITE => unary(x(), |x| f(x).is_finite() as u32),
+            OP_IS_NORMAL => unary(x(), |x| f(x).is_normal() as u32),
+            OP_SIGN_BIT_SET => unary(x(), |x| x >> 31),
+            OP_LESS_OR_GREATER => fcompare(x(), y(), |x, y| x < y || x > y),
+            OP_ORDERED => fcompare(x(), y(), |x, y| !x.is_nan() && !y.is_nan()),
+            OP_UNORDERED => fcompare(x(), y(), |x, y| x.is_nan() || y.is_nan()),
+            OP_LOGICAL_EQUAL | OP_IEQUAL => compare(x(), y(), |x, y| x == y),
+            OP_LOGICAL_NOT_EQUAL | OP_INOT_EQUAL => compare(x(), y(), |x, y| x != y),
+            OP_LOGICAL_OR => compare(x(), y(), |x, y| x != 0 || y != 0),
+            OP_LOGICAL_AND => compare(x(), y(), |x, y| x != 0 && y != 0),
+            OP_LOGICAL_NOT => unary(x(), |x| (x == 0) as u32),
+            OP_SELECT => {
+                let (cond, a, c) = (x(), y(), self.get(ops[4]));
+                if cond.len() == 1 {
+                    if cond[0] != 0 { a.to_vec() } else { c.to_vec() }
+                } else {
+                    cond.iter().zip(a.iter().zip(c)).map(|(&s, (&a, &c))| if s != 0 { a } else { c }).collect()
+                }
+            }
+            OP_UGREATER_THAN => compare(x(), y(), |x, y| x > y),
+            OP_SGREATER_THAN => scompare(x(), y(), |x, y| x > y),
+            OP_UGREATER_THAN_EQUAL => compare(x(), y(), |x, y| x >= y),
+            OP_SGREATER_THAN_EQUAL => scompare(x(), y(), |x, y| x >= y),
+            OP_ULESS_THAN => compare(x(), y(), |x, y| x < y),
+            OP_SLESS_THAN => scompare(x(), y(), |x, y| x < y),
+            OP_ULESS_THAN_EQUAL => compare(x(), y(), |x, y| x <= y),
+            OP_SLESS_THAN_EQUAL => scompare(x(), y(), |x, y| x <= y),
+            OP_FORD_EQUAL => fcompare(x(), y(), |x, y| x == y),
+            OP_FUNORD_EQUAL => fcompare(x(), y(), |x, y| x == y || x.is_nan() || y.is_nan()),
+            OP_FORD_NOT_EQUAL => fcompare(x(), y(), |x, y| x != y && !x.is_nan() && !y.is_nan()),
+            OP_FUNORD_NOT_EQUAL => fcompare(x(), y(), |x, y| x != y),
+            OP_FORD_LESS_THAN => fcompare(x(), y(), |x, y| x < y),
+            OP_FUNORD_LESS_THAN => fcompare(x(), y(), |x, y| !(x >= y)),
+            OP_FORD_GREATER_THAN => fcompare(x(), y(), |x, y| x > y),
+            OP_FUNORD_GREATER_THAN => fcompare(x(), y(), |x, y| !(x <= y)),
+            OP_FORD_LESS_THAN_EQUAL => fcompare(x(), y(), |x, y| x <= y),
+            OP_FUNORD_LESS_THAN_EQUAL => fcompare(x(), y(), |x, y| !(x > y)),
+            OP_FORD_GREATER_THAN_EQUAL => fcompare(x(), y(), |x, y| x >= y),
+            OP_FUNORD_GREATER_THAN_EQUAL => fcompare(x(), y(), |x, y| !(x < y)),
+
+            OP_SHIFT_RIGHT_LOGICAL => binary(x(), y(), shr),
+            OP_SHIFT_RIGHT_ARITHMETIC => binary(x(), y(), |x, n| {
+                if n >= 32 { ((x as i32) >> 31) as u32 } else { ((x as i32) >> n) as u32 }
+            }),
+            OP_SHIFT_LEFT_LOGICAL => binary(x(), y(), shl),
+            OP_BITWISE_OR => binary(x(), y(), |x, y| x | y),
+            OP_BITWISE_XOR => binary(x(), y(), |x, y| x ^ y),
+            OP_BITWISE_AND => binary(x(), y(), |x, y| x & y),
+            OP_NOT => unary(x(), |x| !x),
+            OP_BIT_FIELD_INSERT => {
+                let (offset, count) = (self.get(ops[4])[0], self.get(ops[5])[0]);
+                let mask = bitfield_mask(offset, count);
+                binary(x(), y(), |base, insert| (base & !mask) | (shl(insert, offset) & mask))
+            }
+            OP_BIT_FIELD_S_EXTRACT | OP_BIT_FIELD_U_EXTRACT => {
+                let (offset, count) = (y()[0], self.get(ops[4])[0]);
+                unary(x(), |base| {
+                    if count == 0 {
+                        return 0;
+                    }
+                    let field = shr(base, offset) & bitfield_mask(0, count);
+                    if opcode == OP_BIT_FIELD_S_EXTRACT && count < 32 {
+                        let shift = 32 - count;
+                        (((field << shift) as i32) >> shift) as u32
+                    } else {
+                        field
+                    }
+                })
+            }
+            OP_BIT_REVERSE => unary(x(), |x| {
+                let mut r = 0;
+                for i in 0..32 {
+                    r |= ((x >> i) & 1) << (31 - i);
+                }
+                r
+            }),
+            OP_BIT_COUNT => unary(x(), |x| x.count_ones()),
+
+            OP_VECTOR_EXTRACT_DYNAMIC => {
+                let index = y()[0] as usize;
+                vec![x().get(index).cloned().unwrap_or(0)]
+            }
+            OP_VECTOR_INSERT_DYNAMIC => {
+                let mut v = x().to_vec();
+                let index = self.get(ops[4])[0] as usize;
+                if index < v.len() {
+                    v[index] = y()[0];
+                }
+                v
+            }
+            OP_VECTOR_SHUFFLE => {
+                let mut both = x().to_vec();
+                both.extend_from_slice(y());
+                ops[4..].iter().map(|&c| both.get(c as usize).cloned().unwrap_or(0)).collect()
+            }
+            OP_SAMPLED_IMAGE => {
+                let mut words = x()[..2].to_vec();
+                words.extend_from_slice(&y()[..2]);
+                words
+            }
+            OP_IMAGE => x()[..2].to_vec(),
+            OP_COMPOSITE_CONSTRUCT => {
+                let mut words = Vec::new();
+                for &part in &ops[2..] {
+                    words.extend_from_slice(self.get(part));
+                }
+                words
+            }
+            OP_COMPOSITE_EXTRACT => {
+                let (offset, ty) = self.composite_offset(self.value_type(ops[2]), &ops[3..]);
+                x()[offset..offset + program.size_of(ty)].to_vec()
+            }
+            OP_COMPOSITE_INSERT => {
+                let mut words = y().to_vec();
+                let (offset, _) = self.composite_offset(result_type, &ops[4..]);
+                let object = x();
+                words[offset..offset + object.len()].copy_from_slice(object);
+                words
+            }
+
+            _ => {
+                error!("Invocation: unsupported instruction {}", opcode);
+                vec![0; program.size_of(result_type)]
+            }
+        }
+    }
+
+    fn composite_offset(&self, ty: Id, indices: &[u32]) -> (usize, Id) {
+        let mut offset = 0;
+        let mut ty = ty;
+        for &index in indices {
+            let (element_offset, element) = self.program.element(ty, index);
+            offset += element_offset;
+            ty = element;
+        }
+        (offset, ty)
+    }
+}
+
+fn frexp(x: f32) -> (f32, i32) {
+    if x == 0.0 || !x.is_finite() {
+        return (x, 0);
+    }
+
+    let mut exp = x.abs().log2().floor() as i32 + 1;
+    let mut significand = x / (2.0f32).powi(exp);
+    // Correct for rounding in log2 near powers of two.
+    if significand.abs() >= 1.0 {
+        significand /= 2.0;
+        exp += 1;
+    } else if significand.abs() < 0.5 {
+        significand *= 2.0;
+        exp -= 1;
+    }
+    (significand, exp)
+}
@@ -0,0 +1,613 @@
+use std::collections::HashMap;
+
+use spirv::consts::*;
+
+#[derive(Debug)]
+pub enum ParseError {
+    TooShort,
+    BadMagic(u32),
+    BadInstruction { offset: usize },
+    Truncated { offset: usize },
+    UnterminatedFunction(Id),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageType {
+    pub sampled_type: Id,
+    pub dim: Dim,
+    pub depth: u32,
+    pub arrayed: bool,
+    pub multisampled: bool,
+    pub sampled: u32,
+    pub format: ImageFormat,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Void,
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: Id, count: u32 },
+    Matrix { column: Id, columns: u32 },
+    Image(ImageType),
+    Sampler,
+    SampledImage { image: Id },
+    Array { element: Id, length: Id },
+    RuntimeArray { element: Id },
+    Struct { members: Vec<Id> },
+    Pointer { storage: StorageClass, pointee: Id },
+    Function { result: Id, params: Vec<Id> },
+    Opaque,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstantValue {
+    Bool(bool),
+    Scalar(Vec<u32>),
+    Composite(Vec<Id>),
+    Null,
+    Op { opcode: Op, operands: Vec<u32> },
+}
+
+#[derive(Clone, Debug)]
+pub struct Constant {
+    pub ty: Id,
+    pub spec: bool,
+    pub value: ConstantValue,
+}
+
+#[derive(Clone, Debug)]
+pub struct Variable {
+    pub ty: Id,
+    pub storage: StorageClass,
+    pub initializer: Option<Id>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EntryPointDecl {
+    pub model: ExecutionModel,
+    pub function: Id,
+    pub name: String,
+    pub interface: Vec<Id>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub ty: Id,
+    pub result_type: Id,
+    /// Word offsets of the OpFunction instruction and of the OpFunctionEnd
+    /// that closes it.
+    pub start: usize,
+    pub end: usize,
+    /// Global variables referenced directly from the function body.
+    pub globals: Vec<Id>,
+    pub calls: Vec<Id>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Instruction<'a> {
+    pub opcode: Op,
+    pub operands: &'a [u32],
+    pub offset: usize,
+}
+
+pub struct Instructions<'a> {
+    words: &'a [u32],
+    offset: usize,
+    end: usize,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Instruction<'a>;
+
+    fn next(&mut self) -> Option<Instruction<'a>> {
+        if self.offset >= self.end {
+            return None;
+        }
+
+        let word = self.words[self.offset];
+        let count = (word >> 16) as usize;
+        let inst = Instruction {
+            opcode: (word & 0xffff) as Op,
+            operands: &self.words[self.offset + 1..self.offset + count],
+            offset: self.offset,
+        };
+        self.offset += count;
+
+        Some(inst)
+    }
+}
+
+/// Decodes a nul-terminated literal string, returning it together with the
+/// number of words it occupies.
+pub fn parse_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (i, &word) in words.iter().enumerate() {
+        for shift in 0..4 {
+            let byte = (word >> (shift * 8)) as u8;
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+pub fn bits_to_f32(bits: u32) -> f32 {
+    unsafe { ::std::mem::transmute(bits) }
+}
+
+pub fn f32_to_bits(value: f32) -> u32 {
+    unsafe { ::std::mem::transmute(value) }
+}
+
+/// Parsed SPIR-V binary. The raw words are kept so that functions can be
+/// walked again by later stages; everything in the module-level sections
+/// is indexed by result id.
+#[derive(Clone)]
+pub struct Module {
+    words: Vec<u32>,
+    pub bound: u32,
+    pub entry_points: Vec<EntryPointDecl>,
+    pub execution_modes: HashMap<Id, Vec<(ExecutionMode, Vec<u32>)>>,
+    pub names: HashMap<Id, String>,
+    pub member_names: HashMap<(Id, u32), String>,
+    pub decorations: HashMap<Id, Vec<(Decoration, Vec<u32>)>>,
+    pub member_decorations: HashMap<(Id, u32), Vec<(Decoration, Vec<u32>)>>,
+    pub ext_imports: HashMap<Id, String>,
+    pub types: HashMap<Id, Type>,
+    pub constants: HashMap<Id, Constant>,
+    pub variables: HashMap<Id, Variable>,
+    pub functions: HashMap<Id, Function>,
+}
+
+impl Module {
+    pub fn from_words(code: &[u32]) -> Result<Module, ParseError> {
+        if code.len() < 5 {
+            return Err(ParseError::TooShort);
+        }
+
+        let words: Vec<u32> = if code[0] == MAGIC_NUMBER {
+            code.to_vec()
+        } else if code[0] == MAGIC_NUMBER.swap_bytes() {
+            code.iter().map(|w| w.swap_bytes()).collect()
+        } else {
+            return Err(ParseError::BadMagic(code[0]));
+        };
+
+        let mut module = Module {
+            bound: words[3],
+            words: Vec::new(),
+            entry_points: Vec::new(),
+            execution_modes: HashMap::new(),
+            names: HashMap::new(),
+            member_names: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+            ext_imports: HashMap::new(),
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+        };
+
+        let mut offset = 5;
+        while offset < words.len() {
+            let count = (words[offset] >> 16) as usize;
+            if count == 0 {
+                return Err(ParseError::BadInstruction { offset: offset });
+            }
+            if offset + count > words.len() {
+                return Err(ParseError::Truncated { offset: offset });
+            }
+            offset += count;
+        }
+
+        module.words = words;
+        try!(module.index());
+
+        Ok(module)
+    }
+
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    pub fn instructions(&self) -> Instructions {
+        Instructions { words: &self.words, offset: 5, end: self.words.len() }
+    }
+
+    /// Instructions of a function, from its OpFunction up to and including
+    /// the matching OpFunctionEnd.
+    pub fn function_instructions(&self, function: &Function) -> Instructions {
+        let end_len = (self.words[function.end] >> 16) as usize;
+        Instructions { words: &self.words, offset: function.start, end: function.end + end_len }
+    }
+
+    fn index(&mut self) -> Result<(), ParseError> {
+        let mut groups: HashMap<Id, Vec<(Decoration, Vec<u32>)>> = HashMap::new();
+        let mut current: Option<(Id, Function)> = None;
+
+        let words = self.words.clone();
+        let iter = Instructions { words: &words, offset: 5, end: words.len() };
+
+        for inst in iter {
+            let ops = inst.operands;
+            if ops.len() < min_operands(inst.opcode) {
+                return Err(ParseError::BadInstruction { offset: inst.offset });
+            }
+
+            if let Some((_, ref mut function)) = current {
+                match inst.opcode {
+                    OP_FUNCTION_END => {
+                        function.end = inst.offset;
+                    }
+                    OP_FUNCTION_CALL => {
+                        function.calls.push(ops[2]);
+                        for &id in &ops[3..] {
+                            if self.variables.contains_key(&id) && !function.globals.contains(&id) {
+                                function.globals.push(id);
+                            }
+                        }
+                    }
+                    _ => {
+                        for id in referenced_ids(&inst) {
+                            if self.variables.contains_key(&id) && !function.globals.contains(&id) {
+                                function.globals.push(id);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if inst.opcode == OP_FUNCTION_END {
+                match current.take() {
+                    Some((id, function)) => { self.functions.insert(id, function); }
+                    None => return Err(ParseError::BadInstruction { offset: inst.offset }),
+                }
+                continue;
+            }
+
+            if current.is_some() {
+                continue;
+            }
+
+            match inst.opcode {
+                OP_NAME => {
+                    self.names.insert(ops[0], parse_string(&ops[1..]).0);
+                }
+                OP_MEMBER_NAME => {
+                    self.member_names.insert((ops[0], ops[1]), parse_string(&ops[2..]).0);
+                }
+                OP_EXT_INST_IMPORT => {
+                    self.ext_imports.insert(ops[0], parse_string(&ops[1..]).0);
+                }
+                OP_ENTRY_POINT => {
+                    let (name, len) = parse_string(&ops[2..]);
+                    self.entry_points.push(EntryPointDecl {
+                        model: ops[0],
+                        function: ops[1],
+                        name: name,
+                        interface: ops[2 + len..].to_vec(),
+                    });
+                }
+                OP_EXECUTION_MODE => {
+                    self.execution_modes.entry(ops[0]).or_insert_with(Vec::new)
+                        .push((ops[1], ops[2..].to_vec()));
+                }
+                OP_DECORATE => {
+                    self.decorations.entry(ops[0]).or_insert_with(Vec::new)
+                        .push((ops[1], ops[2..].to_vec()));
+                }
+                OP_MEMBER_DECORATE => {
+                    self.member_decorations.entry((ops[0], ops[1])).or_insert_with(Vec::new)
+                        .push((ops[2], ops[3..].to_vec()));
+                }
+                OP_DECORATION_GROUP => {
+                    let decs = self.decorations.remove(&ops[0]).unwrap_or(Vec::new());
+                    groups.insert(ops[0], decs);
+                }
+                OP_GROUP_DECORATE => {
+                    let decs = groups.get(&ops[0]).cloned().unwrap_or(Vec::new());
+                    for &target in &ops[1..] {
+                        self.decorations.entry(target).or_insert_with(Vec::new)
+                            .extend(decs.iter().cloned());
+                    }
+                }
+                OP_GROUP_MEMBER_DECORATE => {
+                    let decs = groups.get(&ops[0]).cloned().unwrap_or(Vec::new());
+                    for pair in ops[1..].chunks(2) {
+                        if pair.len() == 2 {
+                            self.member_decorations.entry((pair[0], pair[1])).or_insert_with(Vec::new)
+                                .extend(decs.iter().cloned());
+                        }
+                    }
+                }
+                OP_TYPE_VOID => { self.types.insert(ops[0], Type::Void); }
+                OP_TYPE_BOOL => { self.types.insert(ops[0], Type::Bool); }
+                OP_TYPE_INT => {
+                    self.types.insert(ops[0], Type::Int { width: ops[1], signed: ops[2] != 0 });
+                }
+                OP_TYPE_FLOAT => {
+                    self.types.insert(ops[0], Type::Float { width: ops[1] });
+                }
+                OP_TYPE_VECTOR => {
+                    self.types.insert(ops[0], Type::Vector { component: ops[1], count: ops[2] });
+                }
+                OP_TYPE_MATRIX => {
+                    self.types.insert(ops[0], Type::Matrix { column: ops[1], columns: ops[2] });
+                }
+                OP_TYPE_IMAGE => {
+                    self.types.insert(ops[0], Type::Image(ImageType {
+                        sampled_type: ops[1],
+                        dim: ops[2],
+                        depth: ops[3],
+                        arrayed: ops[4] != 0,
+                        multisampled: ops[5] != 0,
+                        sampled: ops[6],
+                        format: ops[7],
+                    }));
+                }
+                OP_TYPE_SAMPLER => { self.types.insert(ops[0], Type::Sampler); }
+                OP_TYPE_SAMPLED_IMAGE => {
+                    self.types.insert(ops[0], Type::SampledImage { image: ops[1] });
+                }
+                OP_TYPE_ARRAY => {
+                    self.types.insert(ops[0], Type::Array { element: ops[1], length: ops[2] });
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    self.types.insert(ops[0], Type::RuntimeArray { element: ops[1] });
+                }
+                OP_TYPE_STRUCT => {
+                    self.types.insert(ops[0], Type::Struct { members: ops[1..].to_vec() });
+                }
+                OP_TYPE_POINTER => {
+                    self.types.insert(ops[0], Type::Pointer { storage: ops[1], pointee: ops[2] });
+                }
+                OP_TYPE_FUNCTION => {
+                    self.types.insert(ops[0], Type::Function { result: ops[1], params: ops[2..].to_vec() });
+                }
+                OP_TYPE_OPAQUE | OP_TYPE_EVENT | OP_TYPE_DEVICE_EVENT | OP_TYPE_RESERVE_ID |
+                OP_TYPE_QUEUE | OP_TYPE_PIPE => {
+                    self.types.insert(ops[0], Type::Opaque);
+                }
+                OP_CONSTANT_TRUE | OP_CONSTANT_FALSE |
+                OP_SPEC_CONSTANT_TRUE | OP_SPEC_CONSTANT_FALSE => {
+                    let value = inst.opcode == OP_CONSTANT_TRUE || inst.opcode == OP_SPEC_CONSTANT_TRUE;
+                    let spec = inst.opcode == OP_SPEC_CONSTANT_TRUE || inst.opcode == OP_SPEC_CONSTANT_FALSE;
+                    self.constants.insert(ops[1], Constant {
+                        ty: ops[0], spec: spec, value: ConstantValue::Bool(value)
+                    });
+                }
+                OP_CONSTANT | OP_SPEC_CONSTANT => {
+                    self.constants.insert(ops[1], Constant {
+                        ty: ops[0],
+                        spec: inst.opcode == OP_SPEC_CONSTANT,
+                        value: ConstantValue::Scalar(ops[2..].to_vec())
+                    });
+                }
+                OP_CONSTANT_COMPOSITE | OP_SPEC_CONSTANT_COMPOSITE => {
+                    self.constants.insert(ops[1], Constant {
+                        ty: ops[0],
+                        spec: inst.opcode == OP_SPEC_CONSTANT_COMPOSITE,
+                        value: ConstantValue::Composite(ops[2..].to_vec())
+                    });
+                }
+                OP_CONSTANT_NULL => {
+                    self.constants.insert(ops[1], Constant {
+                        ty: ops[0], spec: false, value: ConstantValue::Null
+                    });
+                }
+                OP_SPEC_CONSTANT_OP => {
+                    self.constants.insert(ops[1], Constant {
+                        ty: ops[0],
+                        spec: true,
+                        value: ConstantValue::Op { opcode: ops[2] as Op, operands: ops[3..].to_vec() }
+                    });
+                }
+                OP_VARIABLE => {
+                    self.variables.insert(ops[1], Variable {
+                        ty: ops[0],
+                        storage: ops[2],
+                        initializer: ops.get(3).cloned(),
+                    });
+                }
+                OP_FUNCTION => {
+                    current = Some((ops[1], Function {
+                        ty: ops[3],
+                        result_type: ops[0],
+                        start: inst.offset,
+                        end: 0,
+                        globals: Vec::new(),
+                        calls: Vec::new(),
+                    }));
+                }
+                _ => ()
+            }
+        }
+
+        match current {
+            Some((id, _)) => Err(ParseError::UnterminatedFunction(id)),
+            None => Ok(())
+        }
+    }
+
+    pub fn decoration(&self, id: Id, decoration: Decoration) -> Option<&[u32]> {
+        self.decorations.get(&id)
+            .and_then(|decs| decs.iter().find(|d| d.0 == decoration))
+            .map(|d| &d.1[..])
+    }
+
+    pub fn has_decoration(&self, id: Id, decoration: Decoration) -> bool {
+        self.decoration(id, decoration).is_some()
+    }
+
+    pub fn member_decoration(&self, id: Id, member: u32, decoration: Decoration) -> Option<&[u32]> {
+        self.member_decorations.get(&(id, member))
+            .and_then(|decs| decs.iter().find(|d| d.0 == decoration))
+            .map(|d| &d.1[..])
+    }
+
+    pub fn execution_mode(&self, function: Id, mode: ExecutionMode) -> Option<&[u32]> {
+        self.execution_modes.get(&function)
+            .and_then(|modes| modes.iter().find(|m| m.0 == mode))
+            .map(|m| &m.1[..])
+    }
+
+    /// Value of an integer scalar constant. Specialization constants report
+    /// their current (default or specialized) value.
+    pub fn constant_u32(&self, id: Id) -> Option<u32> {
+        match self.constants.get(&id) {
+            Some(&Constant { value: ConstantValue::Scalar(ref words), .. }) => words.first().cloned(),
+            Some(&Constant { value: ConstantValue::Bool(b), .. }) => Some(b as u32),
+            Some(&Constant { value: ConstantValue::Null, .. }) => Some(0),
+            _ => None
+        }
+    }
+
+    pub fn array_length(&self, length: Id) -> u32 {
+        self.constant_u32(length).unwrap_or(0)
+    }
+
+    /// Pointee type of a pointer type.
+    pub fn pointee(&self, ty: Id) -> Option<(StorageClass, Id)> {
+        match self.types.get(&ty) {
+            Some(&Type::Pointer { storage, pointee }) => Some((storage, pointee)),
+            _ => None
+        }
+    }
+
+    /// Size in bytes of a type laid out with explicit Offset, ArrayStride and
+    /// MatrixStride decorations. `matrix_stride` comes from the enclosing
+    /// struct member and applies to matrices (and arrays of matrices).
+    pub fn explicit_size(&self, ty: Id, matrix_stride: Option<u32>) -> u32 {
+        match self.types.get(&ty) {
+            Some(&Type::Bool) => 4,
+            Some(&Type::Int { width, .. }) | Some(&Type::Float { width }) => width / 8,
+            Some(&Type::Vector { component, count }) => self.explicit_size(component, None) * count,
+            Some(&Type::Matrix { column, columns }) => match matrix_stride {
+                Some(stride) => stride * columns,
+                None => self.explicit_size(column, None) * columns
+            },
+            Some(&Type::Array { element, length }) => {
+                let stride = match self.decoration(ty, DECORATION_ARRAY_STRIDE) {
+                    Some(s) => s[0],
+                    None => self.explicit_size(element, matrix_stride)
+                };
+                stride * self.array_length(length)
+            }
+            Some(&Type::RuntimeArray { .. }) => 0,
+            Some(&Type::Struct { ref members }) => {
+                let mut size = 0;
+                for (i, &member) in members.iter().enumerate() {
+                    let i = i as u32;
+                    let offset = self.member_decoration(ty, i, DECORATION_OFFSET).map(|o| o[0]).unwrap_or(size);
+                    let stride = self.member_decoration(ty, i, DECORATION_MATRIX_STRIDE).map(|s| s[0]);
+                    let end = offset + self.explicit_size(member, stride);
+                    if end > size {
+                        size = end;
+                    }
+                }
+                size
+            }
+            _ => 0
+        }
+    }
+
+    /// Global variables statically used by a function and everything it
+    /// calls.
+    pub fn static_globals(&self, function: Id) -> Vec<Id> {
+        let mut visited = Vec::new();
+        let mut globals = Vec::new();
+        let mut stack = vec![function];
+
+        while let Some(f) = stack.pop() {
+            if visited.contains(&f) {
+                continue;
+            }
+            visited.push(f);
+
+            if let Some(function) = self.functions.get(&f) {
+                for &g in &function.globals {
+                    if !globals.contains(&g) {
+                        globals.push(g);
+                    }
+                }
+                stack.extend(function.calls.iter().cloned());
+            }
+        }
+
+        globals
+    }
+}
+
+/// The fewest operands of an instruction that `Module::index` decodes, so
+/// that short instructions are rejected instead of read past their end.
+fn min_operands(opcode: Op) -> usize {
+    match opcode {
+        OP_TYPE_IMAGE => 8,
+        OP_FUNCTION => 4,
+        OP_MEMBER_NAME | OP_ENTRY_POINT | OP_MEMBER_DECORATE | OP_TYPE_INT | OP_TYPE_VECTOR | OP_TYPE_MATRIX |
+        OP_TYPE_ARRAY | OP_TYPE_POINTER | OP_CONSTANT | OP_SPEC_CONSTANT | OP_SPEC_CONSTANT_OP | OP_VARIABLE |
+        OP_FUNCTION_CALL => 3,
+        OP_NAME | OP_EXT_INST_IMPORT | OP_EXECUTION_MODE | OP_DECORATE | OP_TYPE_FLOAT | OP_TYPE_SAMPLED_IMAGE |
+        OP_TYPE_RUNTIME_ARRAY | OP_TYPE_FUNCTION | OP_CONSTANT_TRUE | OP_CONSTANT_FALSE | OP_SPEC_CONSTANT_TRUE |
+        OP_SPEC_CONSTANT_FALSE | OP_CONSTANT_COMPOSITE | OP_SPEC_CONSTANT_COMPOSITE | OP_CONSTANT_NULL => 2,
+        OP_DECORATION_GROUP | OP_GROUP_DECORATE | OP_GROUP_MEMBER_DECORATE | OP_TYPE_VOID | OP_TYPE_BOOL |
+        OP_TYPE_SAMPLER | OP_TYPE_STRUCT | OP_TYPE_OPAQUE | OP_TYPE_EVENT | OP_TYPE_DEVICE_EVENT |
+        OP_TYPE_RESERVE_ID | OP_TYPE_QUEUE | OP_TYPE_PIPE => 1,
+        _ => 0
+    }
+}
+
+/// Operands of an instruction inside a function body that may name another
+/// result id, decoded from the operand kinds of each opcode so that literals
+/// and masks are never mistaken for ids. Instructions whose operands aren't
+/// known reference nothing.
+pub fn referenced_ids(inst: &Instruction) -> Vec<Id> {
+    let ops = inst.operands;
+    match inst.opcode {
+        OP_UNDEF | OP_FUNCTION_PARAMETER | OP_FUNCTION_END | OP_FUNCTION_CALL | OP_IMAGE_TEXEL_POINTER |
+        OP_ACCESS_CHAIN | OP_IN_BOUNDS_ACCESS_CHAIN | OP_PTR_ACCESS_CHAIN | OP_GENERIC_PTR_MEM_SEMANTICS |
+        OP_IN_BOUNDS_PTR_ACCESS_CHAIN | OP_VECTOR_EXTRACT_DYNAMIC | OP_VECTOR_INSERT_DYNAMIC |
+        OP_COMPOSITE_CONSTRUCT | OP_COPY_OBJECT | OP_TRANSPOSE | OP_SAMPLED_IMAGE |
+        OP_IMAGE...OP_IMAGE_QUERY_SAMPLES | OP_CONVERT_F_TO_U...OP_BITCAST | OP_SNEGATE...OP_BIT_COUNT |
+        OP_DPDX...OP_FWIDTH_COARSE | OP_EMIT_VERTEX...OP_END_STREAM_PRIMITIVE | OP_CONTROL_BARRIER |
+        OP_MEMORY_BARRIER | OP_ATOMIC_LOAD...OP_ATOMIC_XOR | OP_PHI | OP_LABEL | OP_BRANCH | OP_KILL |
+        OP_RETURN | OP_RETURN_VALUE | OP_UNREACHABLE => ops.to_vec(),
+
+        // Storage class, function control and image operand masks sit
+        // between ids.
+        OP_VARIABLE | OP_FUNCTION => skip_literal(ops, 2),
+        OP_EXT_INST => skip_literal(ops, 3),
+        OP_IMAGE_WRITE => skip_literal(ops, 3),
+        OP_IMAGE_SAMPLE_IMPLICIT_LOD | OP_IMAGE_SAMPLE_EXPLICIT_LOD | OP_IMAGE_SAMPLE_PROJ_IMPLICIT_LOD |
+        OP_IMAGE_SAMPLE_PROJ_EXPLICIT_LOD | OP_IMAGE_FETCH | OP_IMAGE_READ => skip_literal(ops, 4),
+        OP_IMAGE_SAMPLE_DREF_IMPLICIT_LOD | OP_IMAGE_SAMPLE_DREF_EXPLICIT_LOD |
+        OP_IMAGE_SAMPLE_PROJ_DREF_IMPLICIT_LOD | OP_IMAGE_SAMPLE_PROJ_DREF_EXPLICIT_LOD |
+        OP_IMAGE_GATHER | OP_IMAGE_DREF_GATHER => skip_literal(ops, 5),
+
+        // Trailing literals: memory access masks, indices, sizes, branch
+        // weights, loop and selection controls and line numbers. Switch case
+        // literals may span several words, and their targets are labels.
+        OP_LINE | OP_SELECTION_MERGE | OP_LIFETIME_START | OP_LIFETIME_STOP => leading(ops, 1),
+        OP_STORE | OP_COPY_MEMORY | OP_LOOP_MERGE | OP_SWITCH => leading(ops, 2),
+        OP_LOAD | OP_COPY_MEMORY_SIZED | OP_ARRAY_LENGTH | OP_COMPOSITE_EXTRACT |
+        OP_BRANCH_CONDITIONAL => leading(ops, 3),
+        OP_VECTOR_SHUFFLE | OP_COMPOSITE_INSERT => leading(ops, 4),
+
+        _ => Vec::new()
+    }
+}
+
+/// The first `count` operands.
+fn leading(ops: &[u32], count: usize) -> Vec<Id> {
+    ops[..::std::cmp::min(count, ops.len())].to_vec()
+}
+
+/// All operands except the literal at `index`.
+fn skip_literal(ops: &[u32], index: usize) -> Vec<Id> {
+    ops.iter().enumerate().filter(|&(i, _)| i != index).map(|(_, &id)| id).collect()
+}
@@ -0,0 +1,396 @@
+use vk;
+
+use spirv::consts::*;
+use spirv::module::*;
+
+/// Resolved description of a SPIR-V data type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataType {
+    Void,
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector(Box<DataType>, u32),
+    Matrix(Box<DataType>, u32),
+    Array(Box<DataType>, Option<u32>),
+    Struct(Vec<DataType>),
+    Image(ImageType),
+    Sampler,
+    SampledImage(ImageType),
+    Pointer(StorageClass, Box<DataType>),
+    Other,
+}
+
+impl DataType {
+    pub fn from_id(module: &Module, ty: Id) -> DataType {
+        match module.types.get(&ty) {
+            Some(&Type::Void) => DataType::Void,
+            Some(&Type::Bool) => DataType::Bool,
+            Some(&Type::Int { width, signed }) => DataType::Int { width: width, signed: signed },
+            Some(&Type::Float { width }) => DataType::Float { width: width },
+            Some(&Type::Vector { component, count }) =>
+                DataType::Vector(Box::new(DataType::from_id(module, component)), count),
+            Some(&Type::Matrix { column, columns }) =>
+                DataType::Matrix(Box::new(DataType::from_id(module, column)), columns),
+            Some(&Type::Array { element, length }) =>
+                DataType::Array(Box::new(DataType::from_id(module, element)), Some(module.array_length(length))),
+            Some(&Type::RuntimeArray { element }) =>
+                DataType::Array(Box::new(DataType::from_id(module, element)), None),
+            Some(&Type::Struct { ref members }) =>
+                DataType::Struct(members.iter().map(|&m| DataType::from_id(module, m)).collect()),
+            Some(&Type::Image(image)) => DataType::Image(image),
+            Some(&Type::Sampler) => DataType::Sampler,
+            Some(&Type::SampledImage { image }) => match module.types.get(&image) {
+                Some(&Type::Image(image)) => DataType::SampledImage(image),
+                _ => DataType::Other
+            },
+            Some(&Type::Pointer { storage, pointee }) =>
+                DataType::Pointer(storage, Box::new(DataType::from_id(module, pointee))),
+            _ => DataType::Other
+        }
+    }
+
+    /// Number of interface locations consumed by a value of this type.
+    pub fn location_count(&self) -> u32 {
+        match *self {
+            DataType::Vector(ref c, n) => match **c {
+                DataType::Float { width: 64 } | DataType::Int { width: 64, .. } if n > 2 => 2,
+                _ => 1
+            },
+            DataType::Matrix(ref c, n) => c.location_count() * n,
+            DataType::Array(ref e, n) => e.location_count() * n.unwrap_or(1),
+            DataType::Struct(ref members) => members.iter().map(|m| m.location_count()).sum(),
+            _ => 1
+        }
+    }
+}
+
+/// A variable in an entry point's Input or Output interface. Members of
+/// built-in blocks such as gl_PerVertex are reported individually with
+/// `member` set.
+#[derive(Clone, Debug)]
+pub struct InterfaceVariable {
+    pub id: Id,
+    pub name: Option<String>,
+    pub member: Option<u32>,
+    pub location: Option<u32>,
+    pub component: u32,
+    /// Blend equation input of a fragment output, for dual-source blending.
+    pub index: u32,
+    pub builtin: Option<BuiltIn>,
+    pub ty: DataType,
+    pub flat: bool,
+    pub noperspective: bool,
+    pub centroid: bool,
+    pub sample: bool,
+    pub patch: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct DescriptorBinding {
+    pub id: Id,
+    pub name: Option<String>,
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// Array size of the binding, zero for runtime-sized arrays.
+    pub count: u32,
+    pub input_attachment_index: Option<u32>,
+    pub ty: DataType,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockMember {
+    pub name: Option<String>,
+    pub offset: u32,
+    pub size: u32,
+    pub ty: DataType,
+}
+
+#[derive(Clone, Debug)]
+pub struct PushConstantBlock {
+    pub id: Id,
+    pub name: Option<String>,
+    pub size: u32,
+    pub members: Vec<BlockMember>,
+}
+
+impl PushConstantBlock {
+    /// Start and end offsets of the bytes covered by the block's members.
+    pub fn range(&self) -> (u32, u32) {
+        let start = self.members.iter().map(|m| m.offset).min().unwrap_or(0);
+        (start, self.size)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SpecConstant {
+    pub id: Id,
+    pub constant_id: u32,
+    pub ty: DataType,
+    pub default: Vec<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EntryPoint {
+    pub name: String,
+    pub function: Id,
+    pub execution_model: ExecutionModel,
+    pub stage: vk::ShaderStageFlagBits,
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+    pub descriptors: Vec<DescriptorBinding>,
+    pub push_constants: Option<PushConstantBlock>,
+    pub spec_constants: Vec<SpecConstant>,
+    pub local_size: Option<[u32; 3]>,
+}
+
+pub fn stage_for_model(model: ExecutionModel) -> vk::ShaderStageFlagBits {
+    match model {
+        EXECUTION_MODEL_VERTEX => vk::SHADER_STAGE_VERTEX_BIT,
+        EXECUTION_MODEL_TESSELLATION_CONTROL => vk::SHADER_STAGE_TESSELLATION_CONTROL_BIT,
+        EXECUTION_MODEL_TESSELLATION_EVALUATION => vk::SHADER_STAGE_TESSELLATION_EVALUATION_BIT,
+        EXECUTION_MODEL_GEOMETRY => vk::SHADER_STAGE_GEOMETRY_BIT,
+        EXECUTION_MODEL_FRAGMENT => vk::SHADER_STAGE_FRAGMENT_BIT,
+        EXECUTION_MODEL_GL_COMPUTE => vk::SHADER_STAGE_COMPUTE_BIT,
+        _ => 0
+    }
+}
+
+pub fn reflect(module: &Module) -> Vec<EntryPoint> {
+    module.entry_points.iter().map(|decl| reflect_entry_point(module, decl)).collect()
+}
+
+fn reflect_entry_point(module: &Module, decl: &EntryPointDecl) -> EntryPoint {
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+
+    for &id in &decl.interface {
+        let var = match module.variables.get(&id) {
+            Some(var) => var,
+            None => continue
+        };
+
+        let list = match var.storage {
+            STORAGE_CLASS_INPUT => &mut inputs,
+            STORAGE_CLASS_OUTPUT => &mut outputs,
+            _ => continue
+        };
+
+        let pointee = match module.pointee(var.ty) {
+            Some((_, pointee)) => pointee,
+            None => continue
+        };
+
+        interface_variables(module, id, pointee, list);
+    }
+
+    let mut descriptors = Vec::new();
+    let mut push_constants = None;
+
+    for id in module.static_globals(decl.function) {
+        let var = &module.variables[&id];
+        let pointee = match module.pointee(var.ty) {
+            Some((_, pointee)) => pointee,
+            None => continue
+        };
+
+        if var.storage == STORAGE_CLASS_PUSH_CONSTANT {
+            push_constants = Some(push_constant_block(module, id, pointee));
+            continue;
+        }
+
+        if let Some((descriptor_type, count)) = descriptor_type(module, var.storage, pointee) {
+            descriptors.push(DescriptorBinding {
+                id: id,
+                name: module.names.get(&id).cloned(),
+                set: module.decoration(id, DECORATION_DESCRIPTOR_SET).map(|d| d[0]).unwrap_or(0),
+                binding: module.decoration(id, DECORATION_BINDING).map(|d| d[0]).unwrap_or(0),
+                descriptor_type: descriptor_type,
+                count: count,
+                input_attachment_index: module.decoration(id, DECORATION_INPUT_ATTACHMENT_INDEX).map(|d| d[0]),
+                ty: DataType::from_id(module, pointee),
+            });
+        }
+    }
+
+    descriptors.sort_by(|a, b| (a.set, a.binding).cmp(&(b.set, b.binding)));
+
+    let mut spec_constants: Vec<SpecConstant> = module.constants.iter()
+        .filter_map(|(&id, constant)| {
+            let constant_id = match module.decoration(id, DECORATION_SPEC_ID) {
+                Some(d) => d[0],
+                None => return None
+            };
+            let default = match constant.value {
+                ConstantValue::Bool(b) => vec![b as u32],
+                ConstantValue::Scalar(ref words) => words.clone(),
+                _ => Vec::new()
+            };
+            Some(SpecConstant {
+                id: id,
+                constant_id: constant_id,
+                ty: DataType::from_id(module, constant.ty),
+                default: default,
+            })
+        })
+        .collect();
+    spec_constants.sort_by(|a, b| a.constant_id.cmp(&b.constant_id));
+
+    // A constant decorated as the WorkgroupSize built-in overrides the
+    // LocalSize execution mode, which lets it be specialized.
+    let workgroup_size = module.constants.iter()
+        .find(|&(&id, _)| module.decoration(id, DECORATION_BUILT_IN) == Some(&[BUILT_IN_WORKGROUP_SIZE][..]))
+        .and_then(|(_, c)| match c.value {
+            ConstantValue::Composite(ref ids) if ids.len() == 3 => Some([
+                module.constant_u32(ids[0]).unwrap_or(1),
+                module.constant_u32(ids[1]).unwrap_or(1),
+                module.constant_u32(ids[2]).unwrap_or(1),
+            ]),
+            _ => None
+        });
+
+    let local_size = workgroup_size.or_else(|| {
+        module.execution_mode(decl.function, EXECUTION_MODE_LOCAL_SIZE).map(|s| [s[0], s[1], s[2]])
+    });
+
+    EntryPoint {
+        name: decl.name.clone(),
+        function: decl.function,
+        execution_model: decl.model,
+        stage: stage_for_model(decl.model),
+        inputs: inputs,
+        outputs: outputs,
+        descriptors: descriptors,
+        push_constants: push_constants,
+        spec_constants: spec_constants,
+        local_size: local_size,
+    }
+}
+
+fn interface_variables(module: &Module, id: Id, ty: Id, list: &mut Vec<InterfaceVariable>) {
+    let has = |dec| module.has_decoration(id, dec);
+
+    let base = InterfaceVariable {
+        id: id,
+        name: module.names.get(&id).cloned(),
+        member: None,
+        location: module.decoration(id, DECORATION_LOCATION).map(|d| d[0]),
+        component: module.decoration(id, DECORATION_COMPONENT).map(|d| d[0]).unwrap_or(0),
+        index: module.decoration(id, DECORATION_INDEX).map(|d| d[0]).unwrap_or(0),
+        builtin: module.decoration(id, DECORATION_BUILT_IN).map(|d| d[0]),
+        ty: DataType::from_id(module, ty),
+        flat: has(DECORATION_FLAT),
+        noperspective: has(DECORATION_NO_PERSPECTIVE),
+        centroid: has(DECORATION_CENTROID),
+        sample: has(DECORATION_SAMPLE),
+        patch: has(DECORATION_PATCH),
+    };
+
+    // Blocks carry their decorations on the members instead of the variable.
+    let block = match module.types.get(&ty) {
+        Some(&Type::Struct { ref members }) if module.has_decoration(ty, DECORATION_BLOCK) =>
+            Some((ty, members)),
+        Some(&Type::Array { element, .. }) => match module.types.get(&element) {
+            Some(&Type::Struct { ref members }) if module.has_decoration(element, DECORATION_BLOCK) =>
+                Some((element, members)),
+            _ => None
+        },
+        _ => None
+    };
+
+    let (block_ty, members) = match block {
+        Some(b) => b,
+        None => {
+            list.push(base);
+            return;
+        }
+    };
+
+    let mut location = base.location;
+    for (i, &member) in members.iter().enumerate() {
+        let i = i as u32;
+        let mhas = |dec| module.member_decoration(block_ty, i, dec).is_some() || has(dec);
+        let member_location = module.member_decoration(block_ty, i, DECORATION_LOCATION).map(|d| d[0])
+            .or(location);
+        let builtin = module.member_decoration(block_ty, i, DECORATION_BUILT_IN).map(|d| d[0]);
+        let member_ty = DataType::from_id(module, member);
+
+        if builtin.is_none() {
+            location = member_location.map(|l| l + member_ty.location_count());
+        }
+
+        list.push(InterfaceVariable {
+            member: Some(i),
+            name: module.member_names.get(&(block_ty, i)).cloned().or(base.name.clone()),
+            location: if builtin.is_some() { None } else { member_location },
+            component: module.member_decoration(block_ty, i, DECORATION_COMPONENT).map(|d| d[0]).unwrap_or(0),
+            builtin: builtin,
+            ty: member_ty,
+            flat: mhas(DECORATION_FLAT),
+            noperspective: mhas(DECORATION_NO_PERSPECTIVE),
+            centroid: mhas(DECORATION_CENTROID),
+            sample: mhas(DECORATION_SAMPLE),
+            patch: mhas(DECORATION_PATCH),
+            .. base.clone()
+        });
+    }
+}
+
+fn push_constant_block(module: &Module, id: Id, ty: Id) -> PushConstantBlock {
+    let mut members = Vec::new();
+
+    if let Some(&Type::Struct { members: ref member_types }) = module.types.get(&ty) {
+        for (i, &member) in member_types.iter().enumerate() {
+            let i = i as u32;
+            let stride = module.member_decoration(ty, i, DECORATION_MATRIX_STRIDE).map(|s| s[0]);
+            members.push(BlockMember {
+                name: module.member_names.get(&(ty, i)).cloned(),
+                offset: module.member_decoration(ty, i, DECORATION_OFFSET).map(|o| o[0]).unwrap_or(0),
+                size: module.explicit_size(member, stride),
+                ty: DataType::from_id(module, member),
+            });
+        }
+    }
+
+    PushConstantBlock {
+        id: id,
+        name: module.names.get(&id).cloned(),
+        size: module.explicit_size(ty, None),
+        members: members,
+    }
+}
+
+/// Descriptor type and array size expected for a resource variable, or None
+/// if the variable is not backed by a descriptor.
+pub fn descriptor_type(module: &Module, storage: StorageClass, ty: Id) -> Option<(vk::DescriptorType, u32)> {
+    let (ty, count) = match module.types.get(&ty) {
+        Some(&Type::Array { element, length }) => (element, module.array_length(length)),
+        Some(&Type::RuntimeArray { element }) => (element, 0),
+        _ => (ty, 1)
+    };
+
+    let descriptor_type = match (storage, module.types.get(&ty)) {
+        (STORAGE_CLASS_UNIFORM, Some(&Type::Struct { .. })) => {
+            if module.has_decoration(ty, DECORATION_BUFFER_BLOCK) {
+                vk::DESCRIPTOR_TYPE_STORAGE_BUFFER
+            } else {
+                vk::DESCRIPTOR_TYPE_UNIFORM_BUFFER
+            }
+        }
+        (STORAGE_CLASS_STORAGE_BUFFER, Some(&Type::Struct { .. })) => vk::DESCRIPTOR_TYPE_STORAGE_BUFFER,
+        (STORAGE_CLASS_UNIFORM_CONSTANT, Some(&Type::Sampler)) => vk::DESCRIPTOR_TYPE_SAMPLER,
+        (STORAGE_CLASS_UNIFORM_CONSTANT, Some(&Type::SampledImage { .. })) =>
+            vk::DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+        (STORAGE_CLASS_UNIFORM_CONSTANT, Some(&Type::Image(ref image))) => match (image.dim, image.sampled) {
+            (DIM_BUFFER, 2) => vk::DESCRIPTOR_TYPE_STORAGE_TEXEL_BUFFER,
+            (DIM_BUFFER, _) => vk::DESCRIPTOR_TYPE_UNIFORM_TEXEL_BUFFER,
+            (DIM_SUBPASS_DATA, _) => vk::DESCRIPTOR_TYPE_INPUT_ATTACHMENT,
+            (_, 2) => vk::DESCRIPTOR_TYPE_STORAGE_IMAGE,
+            _ => vk::DESCRIPTOR_TYPE_SAMPLED_IMAGE
+        },
+        _ => return None
+    };
+
+    Some((descriptor_type, count))
+}
@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+
+use half;
+use spirv::consts::*;
+use spirv::module::*;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Bool(bool),
+    Scalar(u64),
+    Composite(Vec<Value>),
+}
+
+/// Scalar layout of a type or of the components of a vector type.
+#[derive(Clone, Copy)]
+struct Scalar {
+    width: u32,
+}
+
+impl Scalar {
+    fn mask(&self, v: u64) -> u64 {
+        if self.width >= 64 { v } else { v & ((1u64 << self.width) - 1) }
+    }
+
+    fn sext(&self, v: u64) -> i64 {
+        if self.width >= 64 {
+            v as i64
+        } else {
+            let shift = 64 - self.width;
+            ((v << shift) as i64) >> shift
+        }
+    }
+}
+
+struct Folder<'a> {
+    module: &'a Module,
+    values: HashMap<Id, Value>,
+    bound: u32,
+    out: Vec<u32>,
+}
+
+/// Produces a copy of the module with every specialization constant turned
+/// into a regular constant. `values` maps SpecId to the words of the value
+/// supplied at pipeline creation; constants without an entry keep their
+/// default. OpSpecConstantOp instructions are evaluated. Fails if the folded
+/// module doesn't parse, which malformed values can cause.
+pub fn specialize(module: &Module, values: &HashMap<u32, Vec<u32>>) -> Result<Module, ParseError> {
+    let mut folder = Folder {
+        module: module,
+        values: HashMap::new(),
+        bound: module.bound,
+        out: module.words()[..5].to_vec(),
+    };
+
+    for inst in module.instructions() {
+        folder.instruction(&inst, values);
+    }
+
+    folder.out[3] = folder.bound;
+
+    Module::from_words(&folder.out)
+}
+
+impl<'a> Folder<'a> {
+    fn instruction(&mut self, inst: &Instruction, overrides: &HashMap<u32, Vec<u32>>) {
+        let ops = inst.operands;
+
+        match inst.opcode {
+            OP_CONSTANT_TRUE | OP_CONSTANT_FALSE => {
+                self.values.insert(ops[1], Value::Bool(inst.opcode == OP_CONSTANT_TRUE));
+            }
+            OP_CONSTANT => {
+                self.values.insert(ops[1], scalar_from_words(&ops[2..]));
+            }
+            OP_CONSTANT_COMPOSITE => {
+                let value = Value::Composite(ops[2..].iter().map(|id| self.value(*id)).collect());
+                self.values.insert(ops[1], value);
+            }
+            OP_CONSTANT_NULL | OP_UNDEF => {
+                let value = self.zero(ops[0]);
+                self.values.insert(ops[1], value);
+            }
+            OP_SPEC_CONSTANT_TRUE | OP_SPEC_CONSTANT_FALSE => {
+                let value = match self.spec_id(ops[1]).and_then(|id| overrides.get(&id)) {
+                    Some(words) => words.iter().any(|&w| w != 0),
+                    None => inst.opcode == OP_SPEC_CONSTANT_TRUE
+                };
+                self.emit_value(ops[0], ops[1], &Value::Bool(value));
+                return;
+            }
+            OP_SPEC_CONSTANT => {
+                let scalar = self.scalar(ops[0]);
+                let value = match self.spec_id(ops[1]).and_then(|id| overrides.get(&id)) {
+                    Some(words) => Value::Scalar(scalar.mask(match scalar_from_words(words) {
+                        Value::Scalar(v) => v,
+                        _ => 0
+                    })),
+                    None => scalar_from_words(&ops[2..])
+                };
+                self.emit_value(ops[0], ops[1], &value);
+                return;
+            }
+            OP_SPEC_CONSTANT_COMPOSITE => {
+                let value = Value::Composite(ops[2..].iter().map(|id| self.value(*id)).collect());
+                self.values.insert(ops[1], value);
+                self.emit(OP_CONSTANT_COMPOSITE, ops);
+                return;
+            }
+            OP_SPEC_CONSTANT_OP => {
+                let value = self.fold(ops[0], ops[2] as Op, &ops[3..]);
+                self.emit_value(ops[0], ops[1], &value);
+                return;
+            }
+            _ => ()
+        }
+
+        self.emit(inst.opcode, ops);
+    }
+
+    fn spec_id(&self, id: Id) -> Option<u32> {
+        self.module.decoration(id, DECORATION_SPEC_ID).map(|d| d[0])
+    }
+
+    fn value(&self, id: Id) -> Value {
+        self.values.get(&id).cloned().unwrap_or(Value::Scalar(0))
+    }
+
+    fn emit(&mut self, opcode: Op, operands: &[u32]) {
+        self.out.push((((operands.len() + 1) as u32) << 16) | opcode as u32);
+        self.out.extend(operands.iter().cloned());
+    }
+
+    /// Emits a constant instruction defining `id` as `value`, creating new
+    /// constants for the constituents of composites.
+    fn emit_value(&mut self, ty: Id, id: Id, value: &Value) {
+        self.values.insert(id, value.clone());
+
+        match *value {
+            Value::Bool(b) => self.emit(if b { OP_CONSTANT_TRUE } else { OP_CONSTANT_FALSE }, &[ty, id]),
+            Value::Scalar(v) => {
+                if self.scalar(ty).width > 32 {
+                    self.emit(OP_CONSTANT, &[ty, id, v as u32, (v >> 32) as u32]);
+                } else {
+                    self.emit(OP_CONSTANT, &[ty, id, v as u32]);
+                }
+            }
+            Value::Composite(ref elems) => {
+                let mut operands = vec![ty, id];
+                for (i, elem) in elems.iter().enumerate() {
+                    let elem_ty = self.element_type(ty, i);
+                    let elem_id = self.bound;
+                    self.bound += 1;
+                    self.emit_value(elem_ty, elem_id, elem);
+                    operands.push(elem_id);
+                }
+                self.emit(OP_CONSTANT_COMPOSITE, &operands);
+            }
+        }
+    }
+
+    fn element_type(&self, ty: Id, index: usize) -> Id {
+        match self.module.types.get(&ty) {
+            Some(&Type::Vector { component, .. }) => component,
+            Some(&Type::Matrix { column, .. }) => column,
+            Some(&Type::Array { element, .. }) => element,
+            Some(&Type::Struct { ref members }) => members[index],
+            _ => ty
+        }
+    }
+
+    fn scalar(&self, ty: Id) -> Scalar {
+        match self.module.types.get(&ty) {
+            Some(&Type::Int { width, .. }) => Scalar { width: width },
+            Some(&Type::Float { width }) => Scalar { width: width },
+            Some(&Type::Vector { component, .. }) => self.scalar(component),
+            _ => Scalar { width: 32 }
+        }
+    }
+
+    fn zero(&self, ty: Id) -> Value {
+        match self.module.types.get(&ty) {
+            Some(&Type::Bool) => Value::Bool(false),
+            Some(&Type::Vector { component, count }) =>
+                Value::Composite((0..count).map(|_| self.zero(component)).collect()),
+            Some(&Type::Matrix { column, columns }) =>
+                Value::Composite((0..columns).map(|_| self.zero(column)).collect()),
+            Some(&Type::Array { element, length }) =>
+                Value::Composite((0..self.module.array_length(length)).map(|_| self.zero(element)).collect()),
+            Some(&Type::Struct { ref members }) =>
+                Value::Composite(members.iter().map(|&m| self.zero(m)).collect()),
+            _ => Value::Scalar(0)
+        }
+    }
+
+    fn operand_scalar(&self, id: Id) -> Scalar {
+        match self.module.constants.get(&id) {
+            Some(c) => self.scalar(c.ty),
+            None => Scalar { width: 32 }
+        }
+    }
+
+    fn fold(&self, ty: Id, opcode: Op, ops: &[u32]) -> Value {
+        let rs = self.scalar(ty);
+
+        match opcode {
+            OP_VECTOR_SHUFFLE => {
+                let mut all = components(&self.value(ops[0]));
+                all.extend(components(&self.value(ops[1])));
+                Value::Composite(ops[2..].iter().map(|&c| {
+                    all.get(c as usize).cloned().unwrap_or(Value::Scalar(0))
+                }).collect())
+            }
+            OP_COMPOSITE_EXTRACT => {
+                let mut v = self.value(ops[0]);
+                for &index in &ops[1..] {
+                    v = components(&v).get(index as usize).cloned().unwrap_or(Value::Scalar(0));
+                }
+                v
+            }
+            OP_COMPOSITE_INSERT => {
+                let object = self.value(ops[0]);
+                let composite = self.value(ops[1]);
+                insert(composite, &ops[2..], object)
+            }
+            OP_SELECT => {
+                let cond = self.value(ops[0]);
+                let a = self.value(ops[1]);
+                let b = self.value(ops[2]);
+                match cond {
+                    Value::Bool(c) => if c { a } else { b },
+                    Value::Composite(ref conds) => Value::Composite(
+                        conds.iter().zip(components(&a).into_iter().zip(components(&b)))
+                            .map(|(c, (x, y))| if *c == Value::Bool(true) { x } else { y })
+                            .collect()),
+                    _ => a
+                }
+            }
+            OP_SCONVERT | OP_UCONVERT | OP_FCONVERT | OP_QUANTIZE_TO_F16 |
+            OP_SNEGATE | OP_NOT | OP_LOGICAL_NOT => {
+                let s = self.operand_scalar(ops[0]);
+                map1(&self.value(ops[0]), &|v| unary(opcode, s, rs, v))
+            }
+            _ => {
+                let s = self.operand_scalar(ops[0]);
+                map2(&self.value(ops[0]), &self.value(ops[1]), &|a, b| binary(opcode, s, rs, a, b))
+            }
+        }
+    }
+}
+
+fn scalar_from_words(words: &[u32]) -> Value {
+    let lo = words.get(0).cloned().unwrap_or(0) as u64;
+    let hi = words.get(1).cloned().unwrap_or(0) as u64;
+    Value::Scalar(lo | (hi << 32))
+}
+
+fn components(v: &Value) -> Vec<Value> {
+    match *v {
+        Value::Composite(ref elems) => elems.clone(),
+        ref scalar => vec![scalar.clone()]
+    }
+}
+
+fn insert(composite: Value, indices: &[u32], object: Value) -> Value {
+    if indices.is_empty() {
+        return object;
+    }
+
+    match composite {
+        Value::Composite(mut elems) => {
+            let i = indices[0] as usize;
+            if i < elems.len() {
+                let elem = elems[i].clone();
+                elems[i] = insert(elem, &indices[1..], object);
+            }
+            Value::Composite(elems)
+        }
+        other => other
+    }
+}
+
+fn map1(a: &Value, f: &Fn(&Value) -> Value) -> Value {
+    match *a {
+        Value::Composite(ref elems) => Value::Composite(elems.iter().map(|e| map1(e, f)).collect()),
+        ref scalar => f(scalar)
+    }
+}
+
+fn map2(a: &Value, b: &Value, f: &Fn(&Value, &Value) -> Value) -> Value {
+    match (a, b) {
+        (&Value::Composite(ref x), &Value::Composite(ref y)) =>
+            Value::Composite(x.iter().zip(y.iter()).map(|(x, y)| map2(x, y, f)).collect()),
+        (x, y) => f(x, y)
+    }
+}
+
+fn unary(opcode: Op, s: Scalar, rs: Scalar, v: &Value) -> Value {
+    let v = match *v {
+        Value::Bool(b) => return Value::Bool(if opcode == OP_LOGICAL_NOT { !b } else { b }),
+        Value::Scalar(v) => v,
+        Value::Composite(_) => 0,
+    };
+
+    Value::Scalar(rs.mask(match opcode {
+        OP_SCONVERT => s.sext(v) as u64,
+        OP_UCONVERT => s.mask(v),
+        OP_FCONVERT => {
+            let f = if s.width == 64 { bits_to_f64(v) } else { bits_to_f32(v as u32) as f64 };
+            if rs.width == 64 { f64_to_bits(f) } else { f32_to_bits(f as f32) as u64 }
+        }
+        OP_QUANTIZE_TO_F16 => {
+            let f = half::f16_to_f32(half::f32_to_f16(bits_to_f32(v as u32)));
+            f32_to_bits(f) as u64
+        }
+        OP_SNEGATE => (s.sext(v).wrapping_neg()) as u64,
+        OP_NOT => !v,
+        _ => v
+    }))
+}
+
+fn binary(opcode: Op, s: Scalar, rs: Scalar, a: &Value, b: &Value) -> Value {
+    let (a, b) = match (a, b) {
+        (&Value::Bool(x), &Value::Bool(y)) => return Value::Bool(match opcode {
+            OP_LOGICAL_OR => x || y,
+            OP_LOGICAL_AND => x && y,
+            OP_LOGICAL_EQUAL => x == y,
+            OP_LOGICAL_NOT_EQUAL => x != y,
+            _ => false
+        }),
+        (&Value::Scalar(x), &Value::Scalar(y)) => (s.mask(x), s.mask(y)),
+        _ => return Value::Scalar(0)
+    };
+    let (sa, sb) = (s.sext(a), s.sext(b));
+
+    let cmp = |r: bool| Value::Bool(r);
+
+    match opcode {
+        OP_IEQUAL => return cmp(a == b),
+        OP_INOT_EQUAL => return cmp(a != b),
+        OP_ULESS_THAN => return cmp(a < b),
+        OP_SLESS_THAN => return cmp(sa < sb),
+        OP_UGREATER_THAN => return cmp(a > b),
+        OP_SGREATER_THAN => return cmp(sa > sb),
+        OP_ULESS_THAN_EQUAL => return cmp(a <= b),
+        OP_SLESS_THAN_EQUAL => return cmp(sa <= sb),
+        OP_UGREATER_THAN_EQUAL => return cmp(a >= b),
+        OP_SGREATER_THAN_EQUAL => return cmp(sa >= sb),
+        _ => ()
+    }
+
+    let width = s.width as u64;
+    let r = match opcode {
+        OP_IADD => a.wrapping_add(b),
+        OP_ISUB => a.wrapping_sub(b),
+        OP_IMUL => a.wrapping_mul(b),
+        OP_UDIV => if b == 0 { 0 } else { a / b },
+        OP_UMOD => if b == 0 { 0 } else { a % b },
+        OP_SDIV => if sb == 0 { 0 } else { sa.wrapping_div(sb) as u64 },
+        OP_SREM => if sb == 0 { 0 } else { sa.wrapping_rem(sb) as u64 },
+        OP_SMOD => if sb == 0 {
+            0
+        } else {
+            let r = sa.wrapping_rem(sb);
+            (if r != 0 && (r < 0) != (sb < 0) { r + sb } else { r }) as u64
+        },
+        OP_SHIFT_LEFT_LOGICAL => if b >= width { 0 } else { a << b },
+        OP_SHIFT_RIGHT_LOGICAL => if b >= width { 0 } else { a >> b },
+        OP_SHIFT_RIGHT_ARITHMETIC => (sa >> ::std::cmp::min(b, 63)) as u64,
+        OP_BITWISE_OR => a | b,
+        OP_BITWISE_XOR => a ^ b,
+        OP_BITWISE_AND => a & b,
+        _ => {
+            warn!("specialize: cannot fold OpSpecConstantOp with opcode {}", opcode);
+            0
+        }
+    };
+
+    Value::Scalar(rs.mask(r))
+}
+
+fn bits_to_f64(bits: u64) -> f64 {
+    unsafe { ::std::mem::transmute(bits) }
+}
+
+fn f64_to_bits(value: f64) -> u64 {
+    unsafe { ::std::mem::transmute(value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use spirv::consts::*;
+    use spirv::module::*;
+    use super::specialize;
+
+    fn inst(opcode: Op, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode as u32];
+        words.extend(operands.iter().cloned());
+        words
+    }
+
+    /// A module with an int spec constant %3 = 10 (SpecId 0), %4 = -3
+    /// (SpecId 1), a bool spec constant %8 = true (SpecId 2) and spec
+    /// constant ops %5 = %3 + %4, %6 = %4 < %3, %7 = %3 / %4 and
+    /// %9 = %8 ? %3 : %4.
+    fn module() -> Module {
+        let mut words = vec![MAGIC_NUMBER, 0x10000, 0, 10, 0];
+        words.extend(inst(OP_DECORATE, &[3, DECORATION_SPEC_ID, 0]));
+        words.extend(inst(OP_DECORATE, &[4, DECORATION_SPEC_ID, 1]));
+        words.extend(inst(OP_DECORATE, &[8, DECORATION_SPEC_ID, 2]));
+        words.extend(inst(OP_TYPE_INT, &[1, 32, 1]));
+        words.extend(inst(OP_TYPE_BOOL, &[2]));
+        words.extend(inst(OP_SPEC_CONSTANT, &[1, 3, 10]));
+        words.extend(inst(OP_SPEC_CONSTANT, &[1, 4, -3i32 as u32]));
+        words.extend(inst(OP_SPEC_CONSTANT_TRUE, &[2, 8]));
+        words.extend(inst(OP_SPEC_CONSTANT_OP, &[1, 5, OP_IADD as u32, 3, 4]));
+        words.extend(inst(OP_SPEC_CONSTANT_OP, &[2, 6, OP_SLESS_THAN as u32, 4, 3]));
+        words.extend(inst(OP_SPEC_CONSTANT_OP, &[1, 7, OP_SDIV as u32, 3, 4]));
+        words.extend(inst(OP_SPEC_CONSTANT_OP, &[1, 9, OP_SELECT as u32, 8, 3, 4]));
+        Module::from_words(&words).unwrap()
+    }
+
+    fn value(module: &Module, id: Id) -> ConstantValue {
+        let constant = &module.constants[&id];
+        assert!(!constant.spec, "constant {} is still a spec constant", id);
+        constant.value.clone()
+    }
+
+    #[test]
+    fn defaults() {
+        let module = specialize(&module(), &HashMap::new()).unwrap();
+        assert_eq!(value(&module, 3), ConstantValue::Scalar(vec![10]));
+        assert_eq!(value(&module, 5), ConstantValue::Scalar(vec![7]));
+        assert_eq!(value(&module, 6), ConstantValue::Bool(true));
+        assert_eq!(value(&module, 7), ConstantValue::Scalar(vec![-3i32 as u32]));
+        assert_eq!(value(&module, 9), ConstantValue::Scalar(vec![10]));
+    }
+
+    #[test]
+    fn overrides() {
+        let mut values = HashMap::new();
+        values.insert(0, vec![-20i32 as u32]);
+        values.insert(2, vec![0]);
+        let module = specialize(&module(), &values).unwrap();
+        assert_eq!(value(&module, 3), ConstantValue::Scalar(vec![-20i32 as u32]));
+        assert_eq!(value(&module, 4), ConstantValue::Scalar(vec![-3i32 as u32]));
+        assert_eq!(value(&module, 5), ConstantValue::Scalar(vec![-23i32 as u32]));
+        assert_eq!(value(&module, 6), ConstantValue::Bool(false));
+        assert_eq!(value(&module, 7), ConstantValue::Scalar(vec![6]));
+        assert_eq!(value(&module, 8), ConstantValue::Bool(false));
+        assert_eq!(value(&module, 9), ConstantValue::Scalar(vec![-3i32 as u32]));
+    }
+
+    #[test]
+    fn division_by_zero_folds_to_zero() {
+        let mut values = HashMap::new();
+        values.insert(1, vec![0]);
+        let module = specialize(&module(), &values).unwrap();
+        assert_eq!(value(&module, 7), ConstantValue::Scalar(vec![0]));
+    }
+}
@@ -0,0 +1,441 @@
+use {std, vk, spirv, blend, depth, multisample, pool, vertex};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use assembly::Primitive;
+use command::VertexBufferBinding;
+use depth::DepthBias;
+use format::{self, Channel};
+use fragment::{self, Output};
+use image::ImageView;
+use pipeline::{GraphicsPipeline, DepthStencilState, Rect, Viewport};
+use raster::{Rasterizer, Fragment};
+use spirv::{bits_to_f32, f32_to_bits};
+use vertex::Vertex;
+
+/// Width and height of the screen tiles that primitives are binned into.
+pub const TILE_SIZE: u32 = 64;
+
+/// Number of vertices a worker thread shades at a time.
+const VERTEX_CHUNK: usize = 64;
+
+/// A draw waiting in the bins of a render pass instance: the state it was
+/// recorded with and the geometry of its instances. Objects are referenced
+/// by pointer, as in recorded commands.
+pub struct Draw {
+    pub pipeline: *const GraphicsPipeline,
+    pub resources: spirv::Resources,
+    pub vertex_buffers: Vec<Option<VertexBufferBinding>>,
+    /// Distinct vertex indices the draw uses, with the vertex offset added.
+    pub indices: Vec<u32>,
+    /// Primitives referring to vertices by their position in `indices`.
+    pub primitives: Vec<Primitive>,
+    pub first_instance: u32,
+    pub instance_count: u32,
+    pub viewport: Viewport,
+    /// Pixels the draw may write: the render area and scissor, clipped to
+    /// the framebuffer.
+    pub bounds: Rect,
+    pub line_width: f32,
+    pub depth_bias: Option<DepthBias>,
+    /// Depth and stencil state with the dynamic values at the time of the
+    /// draw.
+    pub depth_stencil: Option<DepthStencilState>,
+    pub blend_constants: [f32; 4],
+    /// Color attachments of the subpass by location.
+    pub color_attachments: Vec<Option<*const ImageView>>,
+    pub depth_stencil_attachment: Option<*const ImageView>,
+}
+
+impl Draw {
+    unsafe fn pipeline(&self) -> &GraphicsPipeline {
+        &*self.pipeline
+    }
+
+    /// Rasterizer for the primitives of the draw within `bounds`.
+    unsafe fn rasterizer(&self, bounds: Rect) -> Rasterizer {
+        let pipeline = self.pipeline();
+
+        Rasterizer {
+            state: &pipeline.rasterization,
+            viewport: self.viewport,
+            bounds: bounds,
+            line_width: self.line_width,
+            depth_bias: self.depth_bias,
+            depth_format: self.depth_stencil_attachment.map(|v| (*v).format).unwrap_or(vk::FORMAT_UNDEFINED),
+            samples: pipeline.multisample.samples,
+            per_sample: pipeline.stage(vk::SHADER_STAGE_FRAGMENT_BIT)
+                .map(|s| multisample::per_sample_shading(&pipeline.multisample, s))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Runs the vertex shader for the vertex at `slot` of `indices`.
+    unsafe fn shade(&self, slot: usize, instance: u32) -> Vertex {
+        let pipeline = self.pipeline();
+        let index = self.indices[slot];
+        let attributes = vertex::fetch(&pipeline.vertex_input, &self.vertex_buffers, index, instance);
+        vertex::shade(&pipeline.stages[0], &attributes, index, instance)
+    }
+
+    /// How far a primitive may reach beyond its vertices, for wide lines and
+    /// points.
+    unsafe fn radius(&self, vertices: &[&Vertex]) -> f32 {
+        let line = self.line_width * 0.5;
+        let point = vertices.iter().fold(0.0f32, |r, v| r.max(v.point_size * 0.5));
+
+        match vertices.len() {
+            1 => point,
+            2 => line,
+            _ => match self.pipeline().rasterization.polygon_mode {
+                vk::POLYGON_MODE_LINE => line,
+                vk::POLYGON_MODE_POINT => point,
+                _ => 0.0
+            }
+        }
+    }
+
+    /// Rasterizes a primitive within `bounds` and processes its fragments.
+    unsafe fn rasterize(&self, vertices: &[&Vertex], bounds: Rect) {
+        let rasterizer = self.rasterizer(bounds);
+        let emit = |fragment: &Fragment| self.fragment(vertices, fragment);
+
+        match vertices.len() {
+            1 => rasterizer.point(&vertices[0].position, vertices[0].point_size, emit),
+            2 => rasterizer.line(&[vertices[0].position, vertices[1].position], emit),
+            3 => rasterizer.triangle(&[vertices[0].position, vertices[1].position, vertices[2].position],
+                                     &[vertices[0].point_size, vertices[1].point_size, vertices[2].point_size],
+                                     emit),
+            _ => ()
+        }
+    }
+
+    /// Runs the depth bounds, stencil and depth tests for the samples in
+    /// `coverage` and returns the samples that passed. `frag_depth` replaces
+    /// the interpolated depth if the shader wrote one.
+    unsafe fn test(&self, fragment: &Fragment, coverage: u32, frag_depth: Option<f32>) -> u32 {
+        let (view, state) = match (self.depth_stencil_attachment, self.depth_stencil.as_ref()) {
+            (Some(view), Some(state)) => (&*view, state),
+            _ => return coverage
+        };
+        let has_depth = view.info.has_channel(Channel::D);
+        let has_stencil = view.info.has_channel(Channel::S);
+
+        let mut passed = 0;
+        for sample in 0..view.image().samples {
+            if coverage & 1 << sample == 0 {
+                continue;
+            }
+
+            let texel = std::slice::from_raw_parts_mut(view.texel(0, fragment.x, fragment.y, sample),
+                                                       view.info.size as usize);
+            let stored = format::decode(&view.info, texel);
+            let mut stored_depth = bits_to_f32(stored[0]);
+            let mut stored_stencil = stored[1] as u8;
+
+            let depth = frag_depth.unwrap_or(fragment.sample_depths[sample as usize]);
+            if depth::test(state, view.format, fragment.front_facing, depth,
+                           if has_depth { Some(&mut stored_depth) } else { None },
+                           if has_stencil { Some(&mut stored_stencil) } else { None }) {
+                passed |= 1 << sample;
+            }
+
+            let value = [f32_to_bits(stored_depth), stored_stencil as u32, 0, 0];
+            format::encode(&view.info, &value, 0x3, texel);
+        }
+
+        passed
+    }
+
+    /// Writes the color outputs of a fragment to the samples in `coverage`
+    /// of the color attachments. Locations the shader doesn't write are left
+    /// alone.
+    unsafe fn write_colors(&self, fragment: &Fragment, coverage: u32, output: &Output) {
+        let color_blend = match self.pipeline().color_blend {
+            Some(ref state) => state,
+            None => return
+        };
+        let logic_op = if color_blend.logic_op_enable { Some(color_blend.logic_op) } else { None };
+
+        for (location, view) in self.color_attachments.iter().enumerate() {
+            let (view, attachment, color) = match (*view, color_blend.attachments.get(location),
+                                                   output.colors.get(location)) {
+                (Some(view), Some(attachment), Some(color)) => (&*view, attachment, color),
+                _ => continue
+            };
+
+            for sample in 0..view.image().samples {
+                if coverage & 1 << sample != 0 {
+                    let texel = std::slice::from_raw_parts_mut(view.texel(0, fragment.x, fragment.y, sample),
+                                                               view.info.size as usize);
+                    blend::write(attachment, logic_op, &self.blend_constants, &view.info, color,
+                                 &output.secondary, texel);
+                }
+            }
+        }
+    }
+
+    /// Shades a fragment and merges the result into the attachments. With
+    /// early fragment tests the depth and stencil tests run before the
+    /// shader, and their updates stick even if the shader discards.
+    unsafe fn fragment(&self, vertices: &[&Vertex], fragment: &Fragment) {
+        let pipeline = self.pipeline();
+        let multisample = &pipeline.multisample;
+        let stage = pipeline.stage(vk::SHADER_STAGE_FRAGMENT_BIT);
+        let early = stage.map(fragment::early_fragment_tests).unwrap_or(false);
+
+        let mut coverage = fragment.coverage & multisample.sample_mask.get(0).cloned().unwrap_or(!0);
+        if early {
+            coverage = self.test(fragment, coverage, None);
+            if coverage == 0 {
+                return;
+            }
+        }
+
+        let mut output = match stage {
+            Some(stage) => match fragment::shade(stage, &self.resources, vertices, fragment) {
+                Some(output) => output,
+                None => return
+            },
+            None => Output {
+                colors: Vec::new(),
+                secondary: [0; 4],
+                depth: None,
+                sample_mask: None,
+            }
+        };
+
+        coverage = multisample::coverage(multisample, coverage, &output);
+        multisample::alpha_to_one(multisample, &mut output);
+        if !early {
+            coverage = self.test(fragment, coverage, output.depth);
+        }
+        if coverage != 0 {
+            self.write_colors(fragment, coverage, &output);
+        }
+    }
+}
+
+/// The draws of a render pass instance that haven't been rendered yet.
+///
+/// Draws are rendered together when something may depend on their results:
+/// their vertices are shaded on the worker threads, their primitives binned
+/// into screen tiles by their bounding boxes, and the workers then render
+/// whole tiles, each going through its primitives in submission order. That
+/// keeps depth testing and blending in primitive order at every pixel while
+/// tiles proceed in parallel, however small the individual draws are.
+pub struct Bins {
+    /// Area the tiles cover: the render area clipped to the framebuffer.
+    bounds: Rect,
+    draws: Vec<Draw>,
+}
+
+impl Bins {
+    pub fn new(bounds: Rect) -> Bins {
+        Bins {
+            bounds: bounds,
+            draws: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, draw: Draw) {
+        self.draws.push(draw);
+    }
+
+    /// Renders the waiting draws. They are finished when this returns.
+    pub unsafe fn flush(&mut self) {
+        if self.draws.is_empty() {
+            return;
+        }
+
+        let draws = std::mem::replace(&mut self.draws, Vec::new());
+        let vertices = shade(&draws);
+        render(self.bounds, &draws, &vertices);
+    }
+}
+
+/// Vertices of the instances of a list of draws, which worker threads shade
+/// in chunks taken in order until none are left.
+struct Shading<'a> {
+    draws: &'a [Draw],
+    /// Draw, instance and first vertex index of each chunk, and its part of
+    /// the vertices of the instance. Each chunk is taken by one worker only.
+    chunks: Vec<(usize, u32, usize, Mutex<&'a mut [Vertex]>)>,
+    next: AtomicUsize,
+}
+
+// Draws are only read while shading.
+unsafe impl<'a> Sync for Shading<'a> {}
+
+impl<'a> Shading<'a> {
+    fn run(&self) {
+        loop {
+            let chunk = self.next.fetch_add(1, Ordering::Relaxed);
+            if chunk >= self.chunks.len() {
+                break;
+            }
+
+            let (draw, instance, start, ref vertices) = self.chunks[chunk];
+            let mut vertices = match vertices.lock() {
+                Ok(vertices) => vertices,
+                Err(_) => panic!("Shading::run: poisoned chunk")
+            };
+            for (i, vertex) in vertices.iter_mut().enumerate() {
+                *vertex = unsafe { self.draws[draw].shade(start + i, instance) };
+            }
+        }
+    }
+}
+
+/// Shades the vertices of every instance of the draws, returning them in
+/// draw and instance order.
+fn shade(draws: &[Draw]) -> Vec<Vec<Vertex>> {
+    let mut runs = Vec::new();
+    let mut vertices = Vec::new();
+    for (index, draw) in draws.iter().enumerate() {
+        for i in 0..draw.instance_count {
+            runs.push((index, draw.first_instance.wrapping_add(i)));
+            vertices.push((0..draw.indices.len()).map(|_| Vertex {
+                position: [0.0; 4],
+                point_size: 0.0,
+                outputs: Vec::new(),
+            }).collect::<Vec<_>>());
+        }
+    }
+
+    {
+        let mut shading = Shading {
+            draws: draws,
+            chunks: Vec::new(),
+            next: AtomicUsize::new(0),
+        };
+        for (&(draw, instance), run) in runs.iter().zip(vertices.iter_mut()) {
+            for (c, chunk) in run.chunks_mut(VERTEX_CHUNK).enumerate() {
+                shading.chunks.push((draw, instance, c * VERTEX_CHUNK, Mutex::new(chunk)));
+            }
+        }
+
+        if shading.chunks.len() <= 1 {
+            shading.run();
+        } else {
+            pool::run(&|| shading.run());
+        }
+    }
+    vertices
+}
+
+/// Primitives of a list of draws binned into the tiles of a render pass
+/// instance, which worker threads take in order until none are left.
+struct Tiles<'a> {
+    draws: &'a [Draw],
+    bounds: Rect,
+    /// Draw and vertices of each primitive, in submission order.
+    primitives: Vec<(usize, Vec<&'a Vertex>)>,
+    /// Indices of the primitives that may cover each tile, in submission
+    /// order.
+    bins: Vec<Vec<usize>>,
+    columns: u32,
+    next: AtomicUsize,
+}
+
+// Tiles don't overlap, so workers never write the same attachment texels.
+// Other memory that shaders write is up to the application to synchronize.
+unsafe impl<'a> Sync for Tiles<'a> {}
+
+impl<'a> Tiles<'a> {
+    fn bounds(&self, tile: usize) -> Rect {
+        let x = (tile as u32 % self.columns) * TILE_SIZE;
+        let y = (tile as u32 / self.columns) * TILE_SIZE;
+
+        Rect {
+            x: self.bounds.x + x as i32,
+            y: self.bounds.y + y as i32,
+            width: std::cmp::min(TILE_SIZE, self.bounds.width - x),
+            height: std::cmp::min(TILE_SIZE, self.bounds.height - y),
+        }
+    }
+
+    fn run(&self) {
+        loop {
+            let tile = self.next.fetch_add(1, Ordering::Relaxed);
+            if tile >= self.bins.len() {
+                break;
+            }
+
+            let bounds = self.bounds(tile);
+            for &primitive in &self.bins[tile] {
+                let (draw, ref vertices) = self.primitives[primitive];
+                let draw = &self.draws[draw];
+                let bounds = bounds.intersection(&draw.bounds);
+                if bounds.width > 0 && bounds.height > 0 {
+                    unsafe { draw.rasterize(vertices, bounds) };
+                }
+            }
+        }
+    }
+}
+
+/// Bins the primitives of the draws and renders the tiles. `vertices` holds
+/// the shaded vertices of each instance of each draw.
+///
+/// Adjacent vertices are ignored without a geometry shader, and patches are
+/// skipped since tessellation is not supported.
+unsafe fn render(bounds: Rect, draws: &[Draw], vertices: &[Vec<Vertex>]) {
+    if bounds.width == 0 || bounds.height == 0 {
+        return;
+    }
+
+    let columns = (bounds.width + TILE_SIZE - 1) / TILE_SIZE;
+    let rows = (bounds.height + TILE_SIZE - 1) / TILE_SIZE;
+    let mut tiles = Tiles {
+        draws: draws,
+        bounds: bounds,
+        primitives: Vec::new(),
+        bins: vec![Vec::new(); (columns * rows) as usize],
+        columns: columns,
+        next: AtomicUsize::new(0),
+    };
+
+    let mut runs = vertices.iter();
+    for (index, draw) in draws.iter().enumerate() {
+        let instances: Vec<&Vec<Vertex>> = runs.by_ref().take(draw.instance_count as usize).collect();
+        if draw.pipeline().rasterization.rasterizer_discard || draw.bounds.width == 0 || draw.bounds.height == 0 {
+            continue;
+        }
+
+        let rasterizer = draw.rasterizer(draw.bounds);
+        for vertices in instances {
+            for primitive in &draw.primitives {
+                if let Primitive::Patch(_) = *primitive {
+                    continue;
+                }
+                let primitive: Vec<&Vertex> = primitive.vertices().iter().map(|&i| &vertices[i as usize]).collect();
+                let positions: Vec<[f32; 4]> = primitive.iter().map(|v| v.position).collect();
+
+                // The bounding box lies within the draw's bounds, which lie
+                // within the tiles.
+                let rect = match rasterizer.bounding_box(&positions, draw.radius(&primitive)) {
+                    Some(rect) => rect,
+                    None => continue
+                };
+                let x0 = (rect.x - bounds.x) as u32 / TILE_SIZE;
+                let y0 = (rect.y - bounds.y) as u32 / TILE_SIZE;
+                let x1 = (rect.x - bounds.x) as u32 + rect.width - 1;
+                let y1 = (rect.y - bounds.y) as u32 + rect.height - 1;
+                for y in y0..y1 / TILE_SIZE + 1 {
+                    for x in x0..x1 / TILE_SIZE + 1 {
+                        tiles.bins[(y * columns + x) as usize].push(tiles.primitives.len());
+                    }
+                }
+                tiles.primitives.push((index, primitive));
+            }
+        }
+    }
+
+    if tiles.bins.iter().filter(|b| !b.is_empty()).count() <= 1 {
+        tiles.run();
+    } else {
+        pool::run(&|| tiles.run());
+    }
+}
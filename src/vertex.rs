@@ -0,0 +1,119 @@
+use {vk, format, spirv};
+
+use command::VertexBufferBinding;
+use pipeline::{VertexInputState, ShaderStage};
+use spirv::f32_to_bits;
+
+/// Value of a vertex attribute as read by the vertex shader.
+#[derive(Clone, Copy, Debug)]
+pub struct Attribute {
+    pub location: u32,
+    pub value: [u32; 4],
+}
+
+/// Reads every attribute of one vertex from the bound vertex buffers.
+/// `instance_index` already includes the draw's first instance.
+/// Attributes whose binding has no buffer or which fall outside of the
+/// buffer read as (0, 0, 0, 1).
+pub unsafe fn fetch(input: &VertexInputState, buffers: &[Option<VertexBufferBinding>],
+                    vertex_index: u32, instance_index: u32) -> Vec<Attribute> {
+    let mut attributes = Vec::with_capacity(input.attributes.len());
+
+    for attribute in &input.attributes {
+        let info = match format::info(attribute.format) {
+            Some(info) => info,
+            None => continue
+        };
+        let default = if info.numeric.is_integer() { [0, 0, 0, 1] } else { [0, 0, 0, f32_to_bits(1.0)] };
+
+        let binding = input.binding(attribute.binding);
+        let bound = buffers.get(attribute.binding as usize).and_then(|b| *b);
+
+        let value = match (binding, bound) {
+            (Some(binding), Some(bound)) => {
+                let index = if binding.input_rate == vk::VERTEX_INPUT_RATE_INSTANCE {
+                    instance_index
+                } else {
+                    vertex_index
+                };
+
+                let data = (*bound.buffer).data();
+                let start = bound.offset as usize + index as usize * binding.stride as usize +
+                    attribute.offset as usize;
+                let end = start + info.size as usize;
+
+                if end <= data.len() {
+                    format::decode(&info, &data[start..end])
+                } else {
+                    default
+                }
+            }
+            _ => default
+        };
+
+        attributes.push(Attribute {
+            location: attribute.location,
+            value: value,
+        });
+    }
+
+    attributes
+}
+
+/// A vertex after vertex shading.
+#[derive(Clone, Debug)]
+pub struct Vertex {
+    /// Clip space position.
+    pub position: [f32; 4],
+    pub point_size: f32,
+    /// User defined outputs by location and component.
+    pub outputs: Vec<[u32; 4]>,
+}
+
+/// Runs the vertex shader for one vertex.
+pub fn shade(stage: &ShaderStage, attributes: &[Attribute], vertex_index: u32, instance_index: u32) -> Vertex {
+    let program = &stage.program;
+    let mut invocation = spirv::Invocation::new(program);
+
+    for slot in program.inputs.iter().filter(|s| s.builtin.is_none()) {
+        if let Some(a) = attributes.iter().find(|a| a.location == slot.location) {
+            invocation.write_slot(slot, &a.value[slot.component as usize..]);
+        }
+    }
+    invocation.set_builtin(spirv::BUILT_IN_VERTEX_INDEX, &[vertex_index]);
+    invocation.set_builtin(spirv::BUILT_IN_INSTANCE_INDEX, &[instance_index]);
+
+    invocation.run();
+
+    let mut position = [0.0; 4];
+    if let Some(words) = invocation.builtin_output(spirv::BUILT_IN_POSITION) {
+        for (p, &w) in position.iter_mut().zip(words) {
+            *p = spirv::bits_to_f32(w);
+        }
+    }
+
+    let point_size = invocation.builtin_output(spirv::BUILT_IN_POINT_SIZE)
+        .map(|w| spirv::bits_to_f32(w[0]))
+        .unwrap_or(1.0);
+
+    let mut outputs = Vec::new();
+    for slot in program.outputs.iter().filter(|s| s.builtin.is_none()) {
+        let location = slot.location as usize;
+        if outputs.len() <= location {
+            outputs.resize(location + 1, [0; 4]);
+        }
+
+        let words = invocation.read_slot(slot);
+        for (i, &w) in words.iter().enumerate() {
+            if let Some(c) = outputs[location].get_mut(slot.component as usize + i) {
+                *c = w;
+            }
+        }
+    }
+
+    Vertex {
+        position: position,
+        point_size: point_size,
+        outputs: outputs,
+    }
+}
@@ -74,12 +74,14 @@ pub const ERROR_FEATURE_NOT_PRESENT: u32 = -8i32 as u32;
 pub const ERROR_INCOMPATIBLE_DRIVER: u32 = -9i32 as u32;
 pub const ERROR_TOO_MANY_OBJECTS: u32 = -10i32 as u32;
 pub const ERROR_FORMAT_NOT_SUPPORTED: u32 = -11i32 as u32;
+pub const ERROR_FRAGMENTED_POOL: u32 = -12i32 as u32;
 pub const ERROR_SURFACE_LOST_KHR: u32 = -1000000000i32 as u32;
 pub const ERROR_NATIVE_WINDOW_IN_USE_KHR: u32 = -1000000001i32 as u32;
 pub const SUBOPTIMAL_KHR: u32 = 1000001003;
 pub const ERROR_OUT_OF_DATE_KHR: u32 = -1000001004i32 as u32;
 pub const ERROR_INCOMPATIBLE_DISPLAY_KHR: u32 = -1000003001i32 as u32;
 pub const ERROR_VALIDATION_FAILED_EXT: u32 = -1000011001i32 as u32;
+pub const ERROR_OUT_OF_POOL_MEMORY_KHR: u32 = -1000069000i32 as u32;
 
 pub type StructureType = u32;
 pub const STRUCTURE_TYPE_APPLICATION_INFO: u32 = 0;